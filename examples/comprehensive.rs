@@ -1,15 +1,13 @@
 // Comprehensive example showcasing all Oxidite features
 // This demonstrates: routing, middleware, auth, caching, queues, and database
 
-use oxidite_core::{Router, Server, OxiditeRequest, OxiditeResponse, Result as CoreResult, Path, Json};
+use oxidite_core::{BoxBody, Router, Server, OxiditeRequest, OxiditeResponse, Result as CoreResult, Path, Json};
 use oxidite_middleware::{ServiceBuilder, LoggerLayer};
 use oxidite_config::Config;
 use oxidite_auth::{hash_password, verify_password, create_token, JwtToken, Claims};
 use oxidite_cache::{Cache, MemoryCache};
 use oxidite_queue::{Queue, Job, JobWrapper, Worker};
 use serde::{Deserialize, Serialize};
-use http_body_util::Full;
-use bytes::Bytes;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -106,7 +104,7 @@ async fn index(_req: OxiditeRequest) -> CoreResult<OxiditeResponse> {
 
     Ok(hyper::Response::builder()
         .header("content-type", "application/json")
-        .body(Full::new(Bytes::from(json)))
+        .body(BoxBody::from(json))
         .unwrap())
 }
 
@@ -126,7 +124,7 @@ async fn health(_req: OxiditeRequest) -> CoreResult<OxiditeResponse> {
 
     Ok(hyper::Response::builder()
         .header("content-type", "application/json")
-        .body(Full::new(Bytes::from(json)))
+        .body(BoxBody::from(json))
         .unwrap())
 }
 
@@ -168,7 +166,7 @@ async fn register(_req: OxiditeRequest) -> CoreResult<OxiditeResponse> {
     Ok(hyper::Response::builder()
         .status(201)
         .header("content-type", "application/json")
-        .body(Full::new(Bytes::from(json)))
+        .body(BoxBody::from(json))
         .unwrap())
 }
 
@@ -192,7 +190,7 @@ async fn cache_demo(_req: OxiditeRequest) -> CoreResult<OxiditeResponse> {
 
     Ok(hyper::Response::builder()
         .header("content-type", "application/json")
-        .body(Full::new(Bytes::from(json)))
+        .body(BoxBody::from(json))
         .unwrap())
 }
 
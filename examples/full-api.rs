@@ -1,11 +1,9 @@
 // Example: Full-featured API with Oxidite
 // Demonstrates routing, extractors, middleware, and more
 
-use oxidite_core::{Router, Server, OxiditeRequest, OxiditeResponse, Result, Path, Query, Json};
+use oxidite_core::{BoxBody, Router, Server, OxiditeRequest, OxiditeResponse, Result, Path, Query, Json};
 use oxidite_middleware::{ServiceBuilder, LoggerLayer};
 use serde::{Deserialize, Serialize};
-use http_body_util::Full;
-use bytes::Bytes;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct User {
@@ -33,7 +31,7 @@ struct UserId {
 
 // GET / - Hello world
 async fn index(_req: OxiditeRequest) -> Result<OxiditeResponse> {
-    Ok(hyper::Response::new(Full::new(Bytes::from("Welcome to Oxidite API!"))))
+    Ok(hyper::Response::new(BoxBody::from("Welcome to Oxidite API!")))
 }
 
 // GET /users - List users with pagination
@@ -57,7 +55,7 @@ async fn list_users(req: OxiditeRequest) -> Result<OxiditeResponse> {
 
     Ok(hyper::Response::builder()
         .header("content-type", "application/json")
-        .body(Full::new(Bytes::from(body)))
+        .body(BoxBody::from(body))
         .unwrap())
 }
 
@@ -77,7 +75,7 @@ async fn get_user(req: OxiditeRequest) -> Result<OxiditeResponse> {
 
     Ok(hyper::Response::builder()
         .header("content-type", "application/json")
-        .body(Full::new(Bytes::from(body)))
+        .body(BoxBody::from(body))
         .unwrap())
 }
 
@@ -97,7 +95,7 @@ async fn create_user(req: OxiditeRequest) -> Result<OxiditeResponse> {
     Ok(hyper::Response::builder()
         .status(201)
         .header("content-type", "application/json")
-        .body(Full::new(Bytes::from(body)))
+        .body(BoxBody::from(body))
         .unwrap())
 }
 
@@ -114,7 +112,7 @@ async fn health(_req: OxiditeRequest) -> Result<OxiditeResponse> {
 
     Ok(hyper::Response::builder()
         .header("content-type", "application/json")
-        .body(Full::new(Bytes::from(body)))
+        .body(BoxBody::from(body))
         .unwrap())
 }
 
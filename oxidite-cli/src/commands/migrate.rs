@@ -14,88 +14,137 @@ pub fn create_migration(name: &str) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-pub async fn run_migrations() -> Result<(), Box<dyn std::error::Error>> {
-    use oxidite_db::{MigrationManager, DbPool, Database};
+async fn connect() -> Result<oxidite_db::DbPool, Box<dyn std::error::Error>> {
+    use oxidite_db::DbPool;
     use oxidite_config::Config;
-    
+
     // Load database URL from config
     let config = Config::load()?;
     let db_url = config.get::<String>("database.url")
         .unwrap_or("sqlite://data.db".to_string());
-    
-    let db = DbPool::connect(&db_url).await?;
+
+    Ok(DbPool::connect(&db_url).await?)
+}
+
+pub async fn run_migrations(to: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    use oxidite_db::MigrationManager;
+
+    let db = connect().await?;
     let manager = MigrationManager::new("migrations");
-    
-    // Get pending migrations
-    let pending = manager.get_pending_migrations(&db).await?;
-    
-    if pending.is_empty() {
+
+    // Each migration runs in its own transaction; a failure stops the run
+    let applied = match to {
+        Some(version) => manager.migrate_to(&db, &version).await?,
+        None => manager.migrate(&db).await?,
+    };
+
+    if applied.is_empty() {
         println!("✅ No pending migrations.");
         return Ok(());
     }
-    
-    println!("Running {} pending migrations...\n", pending.len());
-    
-    for migration in pending {
-        println!("⏫ Applying: {} - {}", migration.version, migration.name);
-        
-        if !migration.up_sql.is_empty() {
-            db.execute(&migration.up_sql).await?;
-            manager.mark_migration_applied(&db, &migration.version).await?;
-            println!("   ✅ Done");
-        } else {
-            println!("   ⚠️  Empty migration");
-        }
+
+    for version in &applied {
+        println!("⏫ Applied: {}", version);
     }
-    
-    println!("\n✅ All migrations run successfully!");
-    
+
+    println!("\n✅ {} migration(s) run successfully!", applied.len());
+
     Ok(())
 }
 
-pub async fn revert_migration() -> Result<(), Box<dyn std::error::Error>> {
-    use oxidite_db::{MigrationManager, DbPool, Database};
+pub async fn run_tenant_migrations() -> Result<(), Box<dyn std::error::Error>> {
+    use oxidite_db::{tenancy, DbPool, MigrationManager, PoolOptions};
     use oxidite_config::Config;
-    
-    // Load database URL from config
+
     let config = Config::load()?;
     let db_url = config.get::<String>("database.url")
         .unwrap_or("sqlite://data.db".to_string());
-    
-    let db = DbPool::connect(&db_url).await?;
+    let options = PoolOptions { tenant_schemas: true, ..PoolOptions::default() };
+    let db = DbPool::connect_with_options(&db_url, options).await?;
     let manager = MigrationManager::new("migrations");
-    
-    // Get applied migrations
-    let applied = manager.get_applied_migrations(&db).await?;
-    
-    if applied.is_empty() {
+
+    let tenants = tenancy::schema_tenants(&db).await?;
+    if tenants.is_empty() {
+        println!("No tenant schemas found.");
+        return Ok(());
+    }
+
+    for (tenant, applied) in manager.migrate_tenants(&db, &tenants).await? {
+        if applied.is_empty() {
+            println!("✅ {}: no pending migrations.", tenant);
+        } else {
+            println!("⏫ {}: applied {}", tenant, applied.join(", "));
+        }
+    }
+
+    println!("\n✅ Migrated {} tenant schema(s).", tenants.len());
+
+    Ok(())
+}
+
+pub async fn revert_migration(steps: usize) -> Result<(), Box<dyn std::error::Error>> {
+    use oxidite_db::MigrationManager;
+
+    let db = connect().await?;
+    let manager = MigrationManager::new("migrations");
+
+    let reverted = manager.rollback(&db, steps).await?;
+
+    if reverted.is_empty() {
         println!("No migrations to revert.");
         return Ok(());
     }
-    
-    // Get the last applied migration
-    let last_version = applied.last().unwrap();
-    
-    // Find the migration file
-    let all_migrations = manager.list_migrations()?;
-    let migration = all_migrations
-        .iter()
-        .find(|m| &m.version == last_version)
-        .ok_or("Migration file not found")?;
-    
-    println!("⏬ Reverting: {} - {}", migration.version, migration.name);
-    
-    if !migration.down_sql.is_empty() {
-        db.execute(&migration.down_sql).await?;
-        manager.mark_migration_reverted(&db, &migration.version).await?;
-        println!("   ✅ Done");
-    } else {
-        println!("   ⚠️  No down migration defined");
-        return Err("No down migration available".into());
+
+    for version in &reverted {
+        println!("⏬ Reverted: {}", version);
     }
-    
-    println!("\n✅ Migration reverted successfully!");
-    
+
+    println!("\n✅ {} migration(s) reverted successfully!", reverted.len());
+
+    Ok(())
+}
+
+pub async fn reset_migrations() -> Result<(), Box<dyn std::error::Error>> {
+    use oxidite_db::MigrationManager;
+
+    let db = connect().await?;
+    let manager = MigrationManager::new("migrations");
+
+    let reverted = manager.reset(&db).await?;
+    for version in &reverted {
+        println!("⏬ Reverted: {}", version);
+    }
+
+    println!("\n✅ Reverted {} migration(s).", reverted.len());
+
+    Ok(())
+}
+
+pub async fn refresh_migrations() -> Result<(), Box<dyn std::error::Error>> {
+    use oxidite_db::MigrationManager;
+
+    let db = connect().await?;
+    let manager = MigrationManager::new("migrations");
+
+    let applied = manager.refresh(&db).await?;
+    for version in &applied {
+        println!("⏫ Applied: {}", version);
+    }
+
+    println!("\n✅ Rebuilt schema from {} migration(s).", applied.len());
+
+    Ok(())
+}
+
+pub async fn unlock_migrations() -> Result<(), Box<dyn std::error::Error>> {
+    use oxidite_db::MigrationManager;
+
+    let db = connect().await?;
+    let manager = MigrationManager::new("migrations");
+
+    manager.force_unlock(&db).await?;
+    println!("🔓 Migration lock cleared.");
+
     Ok(())
 }
 
@@ -112,23 +161,28 @@ pub async fn migration_status() -> Result<(), Box<dyn std::error::Error>> {
     }
     
     // Try to connect to database to get applied migrations
-    let applied = if let Ok(config) = Config::load() {
+    let (applied, modified) = if let Ok(config) = Config::load() {
         if let Some(db_url) = config.get::<String>("database.url").map(String::from) {
             if let Ok(db) = DbPool::connect(&db_url).await {
-                manager.get_applied_migrations(&db).await.unwrap_or_default()
+                (
+                    manager.get_applied_migrations(&db).await.unwrap_or_default(),
+                    manager.modified_migrations(&db).await.unwrap_or_default(),
+                )
             } else {
-                Vec::new()
+                (Vec::new(), Vec::new())
             }
         } else {
-            Vec::new()
+            (Vec::new(), Vec::new())
         }
     } else {
-        Vec::new()
+        (Vec::new(), Vec::new())
     };
     
     println!("Migrations:\n");
     for migration in &migrations {
-        let status = if applied.contains(&migration.version) {
+        let status = if modified.contains(&migration.version) {
+            "⚠️  Modified"
+        } else if applied.contains(&migration.version) {
             "✅ Applied"
         } else {
             "⏳ Pending"
@@ -32,8 +32,58 @@ INSERT INTO users (username, email) VALUES ('admin', 'admin@example.com');
     Ok(())
 }
 
+/// Rust seeders live in a `seed` binary that registers them with `Seeders`
+const SEED_BIN: &str = "src/bin/seed.rs";
+
+pub fn create_rust_seeder(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let path = Path::new(SEED_BIN);
+    if path.exists() {
+        return Err(format!(
+            "{} already exists; add {} to it and register it with Seeders::seeder",
+            SEED_BIN, name
+        )
+        .into());
+    }
+    fs::create_dir_all("src/bin")?;
+
+    let template = format!(
+        r#"use oxidite_db::{{async_trait, DbPool, DbTransaction, Seeder, Seeders}};
+
+struct {name};
+
+#[async_trait]
+impl Seeder for {name} {{
+    async fn run(&self, _db: &DbTransaction) -> oxidite_db::Result<()> {{
+        // Create records here, e.g. UserFactory::builder().count(10).create(_db).await?;
+        Ok(())
+    }}
+}}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {{
+    let url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite://data.db".to_string());
+    let db = DbPool::connect(&url).await?;
+
+    // Seeders run in the order they are added; ones that already ran are skipped
+    for name in Seeders::new().seeder({name}).run(&db).await? {{
+        println!("   ✅ {{}}", name);
+    }}
+    Ok(())
+}}
+"#,
+        name = name
+    );
+
+    fs::write(path, template)?;
+
+    println!("✅ Created seeder: {}", path.display());
+    println!("\n`oxidite seed run` runs it after the SQL seeders.");
+
+    Ok(())
+}
+
 pub async fn run_seeders() -> Result<(), Box<dyn std::error::Error>> {
-    use oxidite_db::{DbPool, Database};
+    use oxidite_db::{seeding, DbPool, Database};
     use oxidite_config::Config;
     
     // Load database URL from config
@@ -45,51 +95,75 @@ pub async fn run_seeders() -> Result<(), Box<dyn std::error::Error>> {
     
     let seeds_dir = Path::new("seeds");
     
-    if !seeds_dir.exists() {
-        println!("No seeds directory found.");
-        return Ok(());
-    }
-    
     // Get all seed files
-    let mut seed_files: Vec<_> = fs::read_dir(seeds_dir)?
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| {
-            entry.path().extension()
-                .and_then(|s| s.to_str())
-                .map(|s| s == "sql")
-                .unwrap_or(false)
-        })
-        .collect();
+    let mut seed_files: Vec<_> = if seeds_dir.exists() {
+        fs::read_dir(seeds_dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry.path().extension()
+                    .and_then(|s| s.to_str())
+                    .map(|s| s == "sql")
+                    .unwrap_or(false)
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
     
     // Sort by filename (timestamp)
     seed_files.sort_by_key(|entry| entry.file_name());
-    
-    if seed_files.is_empty() {
-        println!("No seed files found.");
+
+    let rust_seeders = Path::new(SEED_BIN).exists();
+    if seed_files.is_empty() && !rust_seeders {
+        println!("No seeders found.");
         return Ok(());
     }
+
+    // Seed files that already ran are recorded in `_seeders` and skipped
+    let ran = seeding::ran(&db).await?;
+    let pending: Vec<_> = seed_files
+        .into_iter()
+        .filter(|entry| !ran.contains(&entry.file_name().to_string_lossy().into_owned()))
+        .collect();
+
+    if !pending.is_empty() {
+        println!("Running {} seeders...\n", pending.len());
+    }
     
-    println!("Running {} seeders...\n", seed_files.len());
-    
-    for entry in seed_files {
+    for entry in pending {
         let path = entry.path();
-        let filename = path.file_name().unwrap().to_string_lossy();
+        let filename = path.file_name().unwrap().to_string_lossy().into_owned();
         
         println!("🌱 Seeding: {}", filename);
         
         let sql = fs::read_to_string(&path)?;
-        
-        if !sql.trim().is_empty() {
+        if sql.trim().is_empty() {
+            println!("   ⚠️  Empty seeder");
+        }
+
+        // The statements and the record of the run commit together
+        db.transaction(|tx| async move {
             // Split by semicolons and execute each statement
             for statement in sql.split(';') {
                 let statement = statement.trim();
                 if !statement.is_empty() && !statement.starts_with("--") {
-                    db.execute(statement).await?;
+                    tx.execute(statement).await?;
                 }
             }
-            println!("   ✅ Done");
-        } else {
-            println!("   ⚠️  Empty seeder");
+            seeding::record(&tx, &filename).await
+        })
+        .await?;
+        println!("   ✅ Done");
+    }
+
+    if rust_seeders {
+        println!("🌱 Running Rust seeders ({})", SEED_BIN);
+        let status = std::process::Command::new("cargo")
+            .args(["run", "--quiet", "--bin", "seed"])
+            .env("DATABASE_URL", &db_url)
+            .status()?;
+        if !status.success() {
+            return Err("Rust seeders failed".into());
         }
     }
     
@@ -1,8 +1,6 @@
 use clap::{Parser, Subcommand};
-use oxidite_core::{Router, Server, OxiditeRequest, OxiditeResponse, Result};
+use oxidite_core::{BoxBody, Router, Server, OxiditeRequest, OxiditeResponse, Result};
 use oxidite_middleware::{ServiceBuilder, LoggerLayer};
-use http_body_util::Full;
-use bytes::Bytes;
 mod commands;
 
 #[derive(Parser)]
@@ -75,11 +73,28 @@ enum MigrateCommand {
     /// Create a new migration
     Create { name: String },
     /// Run pending migrations
-    Run,
-    /// Revert the last migration
-    Revert,
+    Run {
+        /// Stop after this migration version
+        #[arg(long)]
+        to: Option<String>,
+        /// Migrate every tenant schema instead of the default one (Postgres)
+        #[arg(long, conflicts_with = "to")]
+        tenants: bool,
+    },
+    /// Revert the most recent migrations
+    Revert {
+        /// Number of migrations to revert
+        #[arg(long, default_value_t = 1)]
+        steps: usize,
+    },
+    /// Revert all migrations
+    Reset,
+    /// Revert all migrations and run them again
+    Refresh,
     /// Show migration status
     Status,
+    /// Clear a migration lock left behind by a crashed run (SQLite)
+    Unlock,
 }
 
 #[derive(Subcommand)]
@@ -87,7 +102,12 @@ enum SeedCommand {
     /// Run database seeders
     Run,
     /// Create a new seeder
-    Create { name: String },
+    Create {
+        name: String,
+        /// Write a Rust seeder to src/bin/seed.rs instead of a SQL file
+        #[arg(long)]
+        rust: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -106,7 +126,7 @@ enum QueueCommand {
 }
 
 async fn hello(_req: OxiditeRequest) -> Result<OxiditeResponse> {
-    Ok(hyper::Response::new(Full::new(Bytes::from("Hello, Oxidite!"))))
+    Ok(hyper::Response::new(BoxBody::from("Hello, Oxidite!")))
 }
 
 #[tokio::main]
@@ -154,13 +174,28 @@ async fn main() -> Result<()> {
                     commands::migrate::create_migration(&name)
                         .map_err(|e| oxidite_core::Error::Server(e.to_string()))?;
                 }
-                MigrateCommand::Run => {
-                    commands::migrate::run_migrations()
+                MigrateCommand::Run { tenants: true, .. } => {
+                    commands::migrate::run_tenant_migrations()
+                        .await
+                        .map_err(|e| oxidite_core::Error::Server(e.to_string()))?;
+                }
+                MigrateCommand::Run { to, tenants: false } => {
+                    commands::migrate::run_migrations(to)
+                        .await
+                        .map_err(|e| oxidite_core::Error::Server(e.to_string()))?;
+                }
+                MigrateCommand::Revert { steps } => {
+                    commands::migrate::revert_migration(steps)
+                        .await
+                        .map_err(|e| oxidite_core::Error::Server(e.to_string()))?;
+                }
+                MigrateCommand::Reset => {
+                    commands::migrate::reset_migrations()
                         .await
                         .map_err(|e| oxidite_core::Error::Server(e.to_string()))?;
                 }
-                MigrateCommand::Revert => {
-                    commands::migrate::revert_migration()
+                MigrateCommand::Refresh => {
+                    commands::migrate::refresh_migrations()
                         .await
                         .map_err(|e| oxidite_core::Error::Server(e.to_string()))?;
                 }
@@ -169,6 +204,11 @@ async fn main() -> Result<()> {
                         .await
                         .map_err(|e| oxidite_core::Error::Server(e.to_string()))?;
                 }
+                MigrateCommand::Unlock => {
+                    commands::migrate::unlock_migrations()
+                        .await
+                        .map_err(|e| oxidite_core::Error::Server(e.to_string()))?;
+                }
             }
             Ok(())
         }
@@ -179,7 +219,11 @@ async fn main() -> Result<()> {
                         .await
                         .map_err(|e| oxidite_core::Error::Server(e.to_string()))?;
                 }
-                SeedCommand::Create { name } => {
+                SeedCommand::Create { name, rust: true } => {
+                    commands::seed::create_rust_seeder(&name)
+                        .map_err(|e| oxidite_core::Error::Server(e.to_string()))?;
+                }
+                SeedCommand::Create { name, rust: false } => {
                     commands::seed::create_seeder(&name)
                         .map_err(|e| oxidite_core::Error::Server(e.to_string()))?;
                 }
@@ -1,3 +1,6 @@
+use crate::types::OxiditeResponse;
+use crate::validation::ValidationErrors;
+use http::StatusCode;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,10 +13,52 @@ pub enum Error {
     BadRequest(String),
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Validation failed: {0}")]
+    Validation(ValidationErrors),
     #[error(transparent)]
     Hyper(#[from] hyper::Error),
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
 
+impl Error {
+    /// HTTP status code this error maps to
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Error::NotFound => StatusCode::NOT_FOUND,
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Error::Conflict(_) => StatusCode::CONFLICT,
+            Error::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::Server(_) | Error::Hyper(_) | Error::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Convert the error into a JSON error response
+    ///
+    /// Validation errors include the per-field messages under `errors`.
+    pub fn into_response(self) -> OxiditeResponse {
+        let status = self.status_code();
+        let body = match &self {
+            Error::Validation(errors) => serde_json::json!({
+                "error": "Validation failed",
+                "errors": errors,
+            }),
+            _ => serde_json::json!({ "error": self.to_string() }),
+        };
+
+        let mut response = crate::response::json(body);
+        *response.status_mut() = status;
+        response
+    }
+}
+
+impl From<ValidationErrors> for Error {
+    fn from(errors: ValidationErrors) -> Self {
+        Error::Validation(errors)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
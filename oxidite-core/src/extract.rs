@@ -1,5 +1,7 @@
 use crate::error::{Error, Result};
 use crate::types::OxiditeRequest;
+use crate::cookie::Form;
+use crate::validation::Validate;
 use serde::de::DeserializeOwned;
 use std::marker::PhantomData;
 
@@ -103,10 +105,47 @@ pub struct PathParams(pub serde_json::Value);
 
 // Helper to serialize responses as JSON
 impl<T: serde::Serialize> Json<T> {
-    pub fn into_response(self) -> Result<http_body_util::Full<bytes::Bytes>> {
+    pub fn into_response(self) -> Result<crate::types::BoxBody> {
         let body = serde_json::to_vec(&self.0)
             .map_err(|e| Error::Server(format!("Failed to serialize JSON: {}", e)))?;
-        Ok(http_body_util::Full::new(bytes::Bytes::from(body)))
+        Ok(body.into())
+    }
+}
+
+/// Run `Validate` rules on an extracted value
+///
+/// Rejects the request with a `422 Unprocessable Entity` carrying the
+/// per-field messages when any rule fails.
+///
+/// # Example
+/// ```ignore
+/// async fn create_user(Validated(Json(data)): Validated<Json<CreateUser>>) -> Result<OxiditeResponse> {
+///     // `data` passed every #[validate(...)] rule
+/// }
+/// ```
+pub struct Validated<T>(pub T);
+
+impl<T: DeserializeOwned + Validate> FromRequest for Validated<Json<T>> {
+    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
+        let Json(value) = Json::<T>::from_request(req).await?;
+        value.validate()?;
+        Ok(Validated(Json(value)))
+    }
+}
+
+impl<T: DeserializeOwned + Validate> FromRequest for Validated<Query<T>> {
+    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
+        let Query(value) = Query::<T>::from_request(req).await?;
+        value.validate()?;
+        Ok(Validated(Query(value)))
+    }
+}
+
+impl<T: DeserializeOwned + Validate> FromRequest for Validated<Form<T>> {
+    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
+        let Form(value) = Form::<T>::from_request(req).await?;
+        value.validate()?;
+        Ok(Validated(Form(value)))
     }
 }
 
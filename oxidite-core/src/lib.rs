@@ -8,11 +8,17 @@ pub mod tls;
 pub mod types;
 pub mod versioning;
 pub mod cookie;
+pub mod validation;
+pub mod pagination;
 
 pub use error::{Error, Result};
-pub use extract::{FromRequest, Json, Path, Query, State};
+pub use extract::{FromRequest, Json, Path, Query, State, Validated};
 pub use cookie::{Cookies, Form};
+pub use response::IntoResponse;
 pub use router::{Handler, Router};
 pub use server::Server;
-pub use types::{OxiditeRequest, OxiditeResponse};
+pub use types::{BoxBody, OxiditeRequest, OxiditeResponse};
 pub use versioning::{ApiVersion, VersionedRouter};
+pub use validation::{Validate, ValidationErrors};
+pub use pagination::{CursorPage, Page, Pagination};
+pub use oxidite_macros::Validate;
@@ -0,0 +1,305 @@
+//! Pagination parameters and response envelopes
+//!
+//! [`Pagination`] deserializes from the query string (`?page=2&per_page=20` or
+//! `?cursor=...`) and the page types serialize to a consistent envelope:
+//!
+//! ```json
+//! { "data": [...], "meta": { "total": 42, "per_page": 20, "current_page": 2, ... } }
+//! ```
+//!
+//! # Example
+//! ```ignore
+//! async fn list_users(req: OxiditeRequest) -> Result<OxiditeResponse> {
+//!     let uri = req.uri().clone();
+//!     let Query(params) = Query::<Pagination>::from_request(&mut req).await?;
+//!     let page = User::paginate(&db, params.page(), params.per_page()).await?;
+//!     Ok(page.into_response(&uri))
+//! }
+//! ```
+
+use crate::types::OxiditeResponse;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use http::header::{HeaderValue, LINK};
+use http::Uri;
+use serde::{Deserialize, Serialize};
+
+/// Page size used when the request doesn't ask for one
+pub const DEFAULT_PER_PAGE: u64 = 15;
+
+/// Largest page size a client can request
+pub const MAX_PER_PAGE: u64 = 100;
+
+/// Highest page number a client can request
+pub const MAX_PAGE: u64 = 1_000_000;
+
+/// Pagination query parameters
+///
+/// Use with `Query<Pagination>`; missing values fall back to page 1 and
+/// [`DEFAULT_PER_PAGE`]. Read the values through the accessors, which clamp
+/// them to sane bounds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pagination {
+    #[serde(default = "default_page")]
+    pub page: u64,
+    #[serde(default = "default_per_page")]
+    pub per_page: u64,
+    /// Opaque cursor for keyset pagination
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+fn default_page() -> u64 {
+    1
+}
+
+fn default_per_page() -> u64 {
+    DEFAULT_PER_PAGE
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self {
+            page: default_page(),
+            per_page: default_per_page(),
+            cursor: None,
+        }
+    }
+}
+
+impl Pagination {
+    /// 1-based page number, between 1 and [`MAX_PAGE`]
+    pub fn page(&self) -> u64 {
+        self.page.clamp(1, MAX_PAGE)
+    }
+
+    /// Page size, between 1 and [`MAX_PER_PAGE`]
+    pub fn per_page(&self) -> u64 {
+        self.per_page.clamp(1, MAX_PER_PAGE)
+    }
+
+    /// Rows to skip for offset pagination
+    pub fn offset(&self) -> u64 {
+        (self.page() - 1).saturating_mul(self.per_page())
+    }
+
+    pub fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref().filter(|c| !c.is_empty())
+    }
+}
+
+/// Page metadata for offset pagination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageMeta {
+    pub total: u64,
+    pub per_page: u64,
+    pub current_page: u64,
+    pub last_page: u64,
+    pub next_page: Option<u64>,
+    pub prev_page: Option<u64>,
+}
+
+/// One page of results with totals
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub meta: PageMeta,
+}
+
+impl<T> Page<T> {
+    pub fn new(data: Vec<T>, total: u64, page: u64, per_page: u64) -> Self {
+        let per_page = per_page.max(1);
+        let current_page = page.max(1);
+        let last_page = total.div_ceil(per_page).max(1);
+
+        Self {
+            data,
+            meta: PageMeta {
+                total,
+                per_page,
+                current_page,
+                last_page,
+                next_page: (current_page < last_page).then(|| current_page + 1),
+                prev_page: (current_page > 1).then(|| (current_page - 1).min(last_page)),
+            },
+        }
+    }
+
+    /// Convert the items, keeping the metadata
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            data: self.data.into_iter().map(f).collect(),
+            meta: self.meta,
+        }
+    }
+
+    /// `Link` header value with first, prev, next and last pages relative to `uri`
+    pub fn link_header(&self, uri: &Uri) -> String {
+        let page_link = |page: u64, rel: &str| {
+            format!(
+                "<{}>; rel=\"{}\"",
+                with_params(uri, &[("page", page.to_string()), ("per_page", self.meta.per_page.to_string())]),
+                rel
+            )
+        };
+
+        let mut links = vec![page_link(1, "first")];
+        if let Some(prev) = self.meta.prev_page {
+            links.push(page_link(prev, "prev"));
+        }
+        if let Some(next) = self.meta.next_page {
+            links.push(page_link(next, "next"));
+        }
+        links.push(page_link(self.meta.last_page, "last"));
+        links.join(", ")
+    }
+}
+
+impl<T: Serialize> Page<T> {
+    /// JSON envelope with a `Link` header built from the request URI
+    pub fn into_response(self, uri: &Uri) -> OxiditeResponse {
+        let link = self.link_header(uri);
+        with_link(crate::response::json(self), &link)
+    }
+}
+
+/// Page metadata for cursor pagination
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorMeta {
+    pub per_page: u64,
+    /// Pass as `?cursor=` to fetch the following page
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+/// One page of results from keyset pagination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CursorPage<T> {
+    pub data: Vec<T>,
+    pub meta: CursorMeta,
+}
+
+impl<T> CursorPage<T> {
+    pub fn new(data: Vec<T>, per_page: u64, next_cursor: Option<String>) -> Self {
+        Self {
+            data,
+            meta: CursorMeta {
+                per_page,
+                has_more: next_cursor.is_some(),
+                next_cursor,
+            },
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> CursorPage<U> {
+        CursorPage {
+            data: self.data.into_iter().map(f).collect(),
+            meta: self.meta,
+        }
+    }
+
+    /// `Link` header value with the first and next pages relative to `uri`
+    pub fn link_header(&self, uri: &Uri) -> String {
+        let per_page = ("per_page", self.meta.per_page.to_string());
+        let mut links = vec![format!("<{}>; rel=\"first\"", with_params(uri, std::slice::from_ref(&per_page)))];
+        if let Some(cursor) = &self.meta.next_cursor {
+            links.push(format!(
+                "<{}>; rel=\"next\"",
+                with_params(uri, &[("cursor", cursor.clone()), per_page])
+            ));
+        }
+        links.join(", ")
+    }
+}
+
+impl<T: Serialize> CursorPage<T> {
+    /// JSON envelope with a `Link` header built from the request URI
+    pub fn into_response(self, uri: &Uri) -> OxiditeResponse {
+        let link = self.link_header(uri);
+        with_link(crate::response::json(self), &link)
+    }
+}
+
+/// Encode the sort key of the last row as an opaque cursor
+pub fn encode_cursor(values: &[serde_json::Value]) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(values).unwrap_or_default())
+}
+
+/// Decode a cursor produced by [`encode_cursor`]
+pub fn decode_cursor(cursor: &str) -> Option<Vec<serde_json::Value>> {
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// The request path and query with pagination parameters replaced
+fn with_params(uri: &Uri, params: &[(&str, String)]) -> String {
+    let mut query: Vec<(String, String)> = uri
+        .query()
+        .and_then(|q| serde_urlencoded::from_str(q).ok())
+        .unwrap_or_default();
+    query.retain(|(key, _)| key != "page" && key != "per_page" && key != "cursor");
+    query.extend(params.iter().map(|(k, v)| (k.to_string(), v.clone())));
+
+    let encoded = serde_urlencoded::to_string(&query).unwrap_or_default();
+    format!("{}?{}", uri.path(), encoded)
+}
+
+fn with_link(mut response: OxiditeResponse, link: &str) -> OxiditeResponse {
+    if let Ok(value) = HeaderValue::from_str(link) {
+        response.headers_mut().insert(LINK, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_math() {
+        let page = Page::new(vec![1, 2, 3], 42, 2, 20);
+        assert_eq!(page.meta.last_page, 3);
+        assert_eq!(page.meta.next_page, Some(3));
+        assert_eq!(page.meta.prev_page, Some(1));
+
+        let empty: Page<i32> = Page::new(vec![], 0, 1, 20);
+        assert_eq!(empty.meta.last_page, 1);
+        assert_eq!(empty.meta.next_page, None);
+        assert_eq!(empty.meta.prev_page, None);
+    }
+
+    #[test]
+    fn test_query_defaults_and_clamping() {
+        let params: Pagination = serde_urlencoded::from_str("per_page=500").unwrap();
+        assert_eq!(params.page(), 1);
+        assert_eq!(params.per_page(), MAX_PER_PAGE);
+
+        let params: Pagination = serde_urlencoded::from_str("page=3&per_page=10&cursor=").unwrap();
+        assert_eq!(params.offset(), 20);
+        assert_eq!(params.cursor(), None);
+
+        let params: Pagination = serde_urlencoded::from_str("page=18446744073709551615&per_page=100").unwrap();
+        assert_eq!(params.page(), MAX_PAGE);
+        assert_eq!(params.offset(), (MAX_PAGE - 1) * MAX_PER_PAGE);
+    }
+
+    #[test]
+    fn test_link_header_keeps_other_params() {
+        let uri: Uri = "/users?status=active&page=2&per_page=10".parse().unwrap();
+        let page = Page::new(vec![(); 10], 35, 2, 10);
+        let links = page.link_header(&uri);
+
+        assert!(links.contains("</users?status=active&page=1&per_page=10>; rel=\"first\""));
+        assert!(links.contains("</users?status=active&page=3&per_page=10>; rel=\"next\""));
+        assert!(links.contains("</users?status=active&page=4&per_page=10>; rel=\"last\""));
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let values = vec![serde_json::json!(1700000000), serde_json::json!("abc")];
+        let cursor = encode_cursor(&values);
+        assert!(!cursor.contains('='));
+        assert_eq!(decode_cursor(&cursor), Some(values));
+        assert_eq!(decode_cursor("not a cursor"), None);
+    }
+}
@@ -1,10 +1,19 @@
-use crate::types::OxiditeResponse;
-use http_body_util::Full;
-use bytes::Bytes;
+use crate::types::{BoxBody, OxiditeResponse};
 use hyper::Response;
 use hyper::header::{HeaderValue, CONTENT_TYPE, SERVER};
 use http::StatusCode;
 
+/// A value a handler can return in place of an [`OxiditeResponse`]
+pub trait IntoResponse {
+    fn into_response(self) -> OxiditeResponse;
+}
+
+impl IntoResponse for OxiditeResponse {
+    fn into_response(self) -> OxiditeResponse {
+        self
+    }
+}
+
 /// Create a JSON response
 pub fn json<T: serde::Serialize>(data: T) -> OxiditeResponse {
     match serde_json::to_vec(&data) {
@@ -12,7 +21,7 @@ pub fn json<T: serde::Serialize>(data: T) -> OxiditeResponse {
             let mut response = Response::builder()
                 .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
                 .header(SERVER, HeaderValue::from_static("Oxidite/0.1.0"))
-                .body(Full::new(Bytes::from(json_bytes)))
+                .body(BoxBody::from(json_bytes))
                 .unwrap();
             response
         },
@@ -20,7 +29,7 @@ pub fn json<T: serde::Serialize>(data: T) -> OxiditeResponse {
             let mut response = Response::builder()
                 .status(StatusCode::INTERNAL_SERVER_ERROR)
                 .header(SERVER, HeaderValue::from_static("Oxidite/0.1.0"))
-                .body(Full::new(Bytes::from(format!("Internal Server Error: {}", e))))
+                .body(BoxBody::from(format!("Internal Server Error: {}", e)))
                 .unwrap();
             response
         },
@@ -32,7 +41,7 @@ pub fn html(body: impl Into<String>) -> OxiditeResponse {
     let mut response = Response::builder()
         .header(CONTENT_TYPE, HeaderValue::from_static("text/html"))
         .header(SERVER, HeaderValue::from_static("Oxidite/0.1.0"))
-        .body(Full::new(Bytes::from(body.into())))
+        .body(BoxBody::from(body.into()))
         .unwrap();
     response
 }
@@ -42,6 +51,6 @@ pub fn text(body: impl Into<String>) -> OxiditeResponse {
     Response::builder()
         .header(CONTENT_TYPE, HeaderValue::from_static("text/plain"))
         .header(SERVER, HeaderValue::from_static("Oxidite/0.1.0"))
-        .body(Full::new(Bytes::from(body.into())))
+        .body(BoxBody::from(body.into()))
         .unwrap()
 }
@@ -1,4 +1,5 @@
 use crate::error::{Error, Result};
+use crate::response::IntoResponse;
 use crate::types::{OxiditeRequest, OxiditeResponse};
 use hyper::Method;
 use std::collections::HashMap;
@@ -13,13 +14,15 @@ pub trait Handler: Send + Sync + 'static {
     fn call(&self, req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>>;
 }
 
-impl<F, Fut> Handler for F
+impl<F, Fut, R> Handler for F
 where
     F: Fn(OxiditeRequest) -> Fut + Send + Sync + 'static,
-    Fut: Future<Output = Result<OxiditeResponse>> + Send + 'static,
+    Fut: Future<Output = Result<R>> + Send + 'static,
+    R: IntoResponse,
 {
     fn call(&self, req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>> {
-        Box::pin(self(req))
+        let response = self(req);
+        Box::pin(async move { response.await.map(IntoResponse::into_response) })
     }
 }
 
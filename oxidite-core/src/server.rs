@@ -2,9 +2,9 @@ use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use hyper::server::conn::http1;
 use hyper_util::rt::TokioIo;
-use hyper_util::service::TowerToHyperService;
 use crate::error::{Error, Result};
 use crate::types::{OxiditeRequest, OxiditeResponse};
+use tower::ServiceExt;
 use tower_service::Service;
 use std::future::Future;
 
@@ -33,7 +33,17 @@ where
             let service = self.service.clone();
 
             tokio::task::spawn(async move {
-                let hyper_service = TowerToHyperService::new(service);
+                // Handler errors become error responses instead of dropping the connection
+                let hyper_service = hyper::service::service_fn(move |req: OxiditeRequest| {
+                    let service = service.clone();
+                    async move {
+                        let response = match service.oneshot(req).await {
+                            Ok(response) => response,
+                            Err(err) => err.into_response(),
+                        };
+                        Ok::<_, std::convert::Infallible>(response)
+                    }
+                });
                 
                 if let Err(err) = http1::Builder::new()
                     .serve_connection(io, hyper_service)
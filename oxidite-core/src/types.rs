@@ -1,7 +1,107 @@
-use http_body_util::Full;
 use bytes::Bytes;
-use hyper::{Request, Response, body::Incoming};
+use futures_util::{Stream, TryStreamExt};
+use http_body_util::{combinators, BodyExt, Full, StreamBody};
+use hyper::body::{Body, Frame, Incoming, SizeHint};
+use hyper::{Request, Response};
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Error a streamed response body can end with
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Body of an [`OxiditeResponse`], either complete or streamed
+///
+/// Strings and bytes convert into a complete body with `into()`;
+/// [`BoxBody::from_stream`] sends chunks as a stream produces them.
+pub struct BoxBody(combinators::BoxBody<Bytes, BoxError>);
+
+impl BoxBody {
+    /// Wrap any body with `Bytes` chunks
+    pub fn new<B>(body: B) -> Self
+    where
+        B: Body<Data = Bytes> + Send + Sync + 'static,
+        B::Error: Into<BoxError>,
+    {
+        Self(body.map_err(Into::into).boxed())
+    }
+
+    /// A body sent a chunk at a time as `stream` yields them
+    ///
+    /// An `Err` from the stream aborts the response after the chunks already sent.
+    pub fn from_stream<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = std::result::Result<Bytes, E>> + Send + Sync + 'static,
+        E: Into<BoxError> + 'static,
+    {
+        Self::new(StreamBody::new(stream.map_ok(Frame::data).map_err(Into::<BoxError>::into)))
+    }
+}
+
+impl Default for BoxBody {
+    fn default() -> Self {
+        Self::new(Full::<Bytes>::default())
+    }
+}
+
+impl fmt::Debug for BoxBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxBody").finish_non_exhaustive()
+    }
+}
+
+impl Body for BoxBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<std::result::Result<Frame<Bytes>, BoxError>>> {
+        Pin::new(&mut self.0).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.0.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.0.size_hint()
+    }
+}
+
+impl From<Full<Bytes>> for BoxBody {
+    fn from(body: Full<Bytes>) -> Self {
+        Self::new(body)
+    }
+}
+
+impl From<Bytes> for BoxBody {
+    fn from(bytes: Bytes) -> Self {
+        Self::new(Full::new(bytes))
+    }
+}
+
+impl From<Vec<u8>> for BoxBody {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytes::from(bytes).into()
+    }
+}
+
+impl From<&'static [u8]> for BoxBody {
+    fn from(bytes: &'static [u8]) -> Self {
+        Bytes::from_static(bytes).into()
+    }
+}
+
+impl From<String> for BoxBody {
+    fn from(text: String) -> Self {
+        Bytes::from(text).into()
+    }
+}
+
+impl From<&'static str> for BoxBody {
+    fn from(text: &'static str) -> Self {
+        Bytes::from_static(text.as_bytes()).into()
+    }
+}
 
-pub type BoxBody = Full<Bytes>;
 pub type OxiditeRequest = Request<Incoming>;
 pub type OxiditeResponse = Response<BoxBody>;
@@ -0,0 +1,408 @@
+//! Field validation shared by request DTOs and database models
+//!
+//! Types deriving `Validate` (or `Model` in oxidite-db) describe their rules with
+//! `#[validate(...)]` attributes. Failures are collected into a [`ValidationErrors`]
+//! map instead of stopping at the first one, and converting that map into an
+//! [`Error`](crate::Error) produces a `422 Unprocessable Entity` response.
+//!
+//! # Example
+//! ```ignore
+//! #[derive(Deserialize, Validate)]
+//! struct SignupForm {
+//!     #[validate(email)]
+//!     email: String,
+//!     #[validate(length(min = 8, max = 64))]
+//!     password: String,
+//!     #[validate(must_match = "password")]
+//!     password_confirmation: String,
+//! }
+//!
+//! async fn signup(Validated(Json(form)): Validated<Json<SignupForm>>) -> Result<OxiditeResponse> {
+//!     // `form` passed every rule
+//! }
+//! ```
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[doc(hidden)]
+pub use regex;
+
+/// Types that can check their own fields
+pub trait Validate {
+    /// Run every rule and collect the failures
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// Validation failures keyed by field name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct ValidationErrors {
+    errors: BTreeMap<String, Vec<String>>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a message against a field
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.errors.entry(field.into()).or_default().push(message.into());
+    }
+
+    /// Merge another set of errors into this one
+    pub fn merge(&mut self, other: ValidationErrors) {
+        for (field, messages) in other.errors {
+            self.errors.entry(field).or_default().extend(messages);
+        }
+    }
+
+    /// Messages recorded for a single field
+    pub fn get(&self, field: &str) -> Option<&[String]> {
+        self.errors.get(field).map(|m| m.as_slice())
+    }
+
+    /// Check whether a field has any errors
+    pub fn has(&self, field: &str) -> bool {
+        self.errors.contains_key(field)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Number of fields with errors
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.errors.iter().map(|(k, v)| (k.as_str(), v.as_slice()))
+    }
+
+    /// Return `Ok(())` when no errors were recorded
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (field, messages) in &self.errors {
+            for message in messages {
+                if !first {
+                    write!(f, "; ")?;
+                }
+                write!(f, "{}: {}", field, message)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+/// Rule helpers called by the code generated for `#[validate(...)]`
+///
+/// Each helper returns `None` when the value passes and `Some(message)` otherwise.
+/// Optional fields are skipped when `None`; use `required` to reject missing values.
+pub mod rules {
+    use std::sync::OnceLock;
+
+    /// Values that can be checked as text
+    pub trait AsText {
+        fn as_text(&self) -> Option<&str>;
+    }
+
+    impl AsText for str {
+        fn as_text(&self) -> Option<&str> {
+            Some(self)
+        }
+    }
+
+    impl AsText for String {
+        fn as_text(&self) -> Option<&str> {
+            Some(self.as_str())
+        }
+    }
+
+    impl<T: AsText + ?Sized> AsText for &T {
+        fn as_text(&self) -> Option<&str> {
+            (**self).as_text()
+        }
+    }
+
+    impl<T: AsText> AsText for Option<T> {
+        fn as_text(&self) -> Option<&str> {
+            self.as_ref().and_then(|v| v.as_text())
+        }
+    }
+
+    /// Values with a length (characters for text, items for collections)
+    pub trait HasLength {
+        fn length(&self) -> Option<usize>;
+    }
+
+    impl HasLength for str {
+        fn length(&self) -> Option<usize> {
+            Some(self.chars().count())
+        }
+    }
+
+    impl HasLength for String {
+        fn length(&self) -> Option<usize> {
+            Some(self.chars().count())
+        }
+    }
+
+    impl<T> HasLength for Vec<T> {
+        fn length(&self) -> Option<usize> {
+            Some(self.len())
+        }
+    }
+
+    impl<T: HasLength> HasLength for Option<T> {
+        fn length(&self) -> Option<usize> {
+            self.as_ref().and_then(|v| v.length())
+        }
+    }
+
+    /// Numeric values that can be range checked
+    pub trait AsNumber {
+        fn as_number(&self) -> Option<f64>;
+    }
+
+    macro_rules! impl_as_number {
+        ($($ty:ty),*) => {
+            $(
+                impl AsNumber for $ty {
+                    fn as_number(&self) -> Option<f64> {
+                        Some(*self as f64)
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_as_number!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+    impl<T: AsNumber> AsNumber for Option<T> {
+        fn as_number(&self) -> Option<f64> {
+            self.as_ref().and_then(|v| v.as_number())
+        }
+    }
+
+    /// Values that can be compared against a list of allowed literals
+    pub trait AsLiteral {
+        fn as_literal(&self) -> Option<String>;
+    }
+
+    impl AsLiteral for str {
+        fn as_literal(&self) -> Option<String> {
+            Some(self.to_string())
+        }
+    }
+
+    impl AsLiteral for String {
+        fn as_literal(&self) -> Option<String> {
+            Some(self.clone())
+        }
+    }
+
+    macro_rules! impl_as_literal {
+        ($($ty:ty),*) => {
+            $(
+                impl AsLiteral for $ty {
+                    fn as_literal(&self) -> Option<String> {
+                        Some(self.to_string())
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_as_literal!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, char, bool);
+
+    impl<T: AsLiteral> AsLiteral for Option<T> {
+        fn as_literal(&self) -> Option<String> {
+            self.as_ref().and_then(|v| v.as_literal())
+        }
+    }
+
+    /// Values that can be absent or blank
+    pub trait Presence {
+        fn is_present(&self) -> bool;
+    }
+
+    impl Presence for String {
+        fn is_present(&self) -> bool {
+            !self.trim().is_empty()
+        }
+    }
+
+    impl<T> Presence for Vec<T> {
+        fn is_present(&self) -> bool {
+            !self.is_empty()
+        }
+    }
+
+    macro_rules! impl_always_present {
+        ($($ty:ty),*) => {
+            $(
+                impl Presence for $ty {
+                    fn is_present(&self) -> bool {
+                        true
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_always_present!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64, char, bool);
+
+    impl<T: Presence> Presence for Option<T> {
+        fn is_present(&self) -> bool {
+            self.as_ref().map(|v| v.is_present()).unwrap_or(false)
+        }
+    }
+
+    fn email_regex() -> &'static regex::Regex {
+        static RE: OnceLock<regex::Regex> = OnceLock::new();
+        RE.get_or_init(|| regex::Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap())
+    }
+
+    fn url_regex() -> &'static regex::Regex {
+        static RE: OnceLock<regex::Regex> = OnceLock::new();
+        RE.get_or_init(|| {
+            regex::Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*://[^\s/?#]+[^\s]*$").unwrap()
+        })
+    }
+
+    pub fn email<T: AsText + ?Sized>(value: &T) -> Option<String> {
+        match value.as_text() {
+            Some(s) if !email_regex().is_match(s) => Some("Invalid email format".to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn url<T: AsText + ?Sized>(value: &T) -> Option<String> {
+        match value.as_text() {
+            Some(s) if !url_regex().is_match(s) => Some("Invalid URL format".to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn pattern<T: AsText + ?Sized>(value: &T, re: &regex::Regex) -> Option<String> {
+        match value.as_text() {
+            Some(s) if !re.is_match(s) => Some("Invalid format".to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn length<T: HasLength + ?Sized>(
+        value: &T,
+        min: Option<usize>,
+        max: Option<usize>,
+    ) -> Option<String> {
+        let len = value.length()?;
+        match (min, max) {
+            (Some(min), Some(max)) if len < min || len > max => {
+                if min == max {
+                    Some(format!("Length must be exactly {}", min))
+                } else {
+                    Some(format!("Length must be between {} and {}", min, max))
+                }
+            }
+            (Some(min), None) if len < min => Some(format!("Length must be at least {}", min)),
+            (None, Some(max)) if len > max => Some(format!("Length must be at most {}", max)),
+            _ => None,
+        }
+    }
+
+    pub fn range<T: AsNumber + ?Sized>(
+        value: &T,
+        min: Option<f64>,
+        max: Option<f64>,
+    ) -> Option<String> {
+        let n = value.as_number()?;
+        match (min, max) {
+            (Some(min), Some(max)) if n < min || n > max => {
+                Some(format!("Value must be between {} and {}", min, max))
+            }
+            (Some(min), None) if n < min => Some(format!("Value must be at least {}", min)),
+            (None, Some(max)) if n > max => Some(format!("Value must be at most {}", max)),
+            _ => None,
+        }
+    }
+
+    pub fn required<T: Presence + ?Sized>(value: &T) -> Option<String> {
+        if value.is_present() {
+            None
+        } else {
+            Some("This field is required".to_string())
+        }
+    }
+
+    pub fn one_of<T: AsLiteral + ?Sized>(value: &T, allowed: &[&str]) -> Option<String> {
+        match value.as_literal() {
+            Some(v) if !allowed.contains(&v.as_str()) => {
+                Some(format!("Value must be one of: {}", allowed.join(", ")))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn must_match<T: PartialEq + ?Sized>(value: &T, other: &T, other_name: &str) -> Option<String> {
+        if value == other {
+            None
+        } else {
+            Some(format!("Must match {}", other_name))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_are_grouped_by_field() {
+        let mut errors = ValidationErrors::new();
+        errors.add("email", "Invalid email format");
+        errors.add("name", "This field is required");
+        errors.add("email", "Length must be at most 5");
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors.get("email").unwrap().len(), 2);
+        assert!(errors.has("name"));
+        assert!(!errors.has("age"));
+
+        let json = serde_json::to_value(&errors).unwrap();
+        assert_eq!(json["name"][0], "This field is required");
+    }
+
+    #[test]
+    fn test_rules() {
+        assert!(rules::email("a@b.co").is_none());
+        assert!(rules::email("nope").is_some());
+        assert!(rules::email(&None::<String>).is_none());
+        assert!(rules::url("https://example.com/x").is_none());
+        assert!(rules::url("example").is_some());
+        assert!(rules::length("héllo", Some(5), Some(5)).is_none());
+        assert!(rules::length("hi", Some(3), None).is_some());
+        assert!(rules::range(&150_i64, Some(0.0), Some(100.0)).is_some());
+        assert!(rules::range(&42_u8, Some(0.0), Some(100.0)).is_none());
+        assert!(rules::required(&Some(String::new())).is_some());
+        assert!(rules::required(&Some("x".to_string())).is_none());
+        assert!(rules::one_of("admin", &["admin", "user"]).is_none());
+        assert!(rules::one_of(&3_i32, &["1", "2"]).is_some());
+    }
+}
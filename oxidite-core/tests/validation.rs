@@ -0,0 +1,43 @@
+use oxidite_core::{Error, Validate, ValidationErrors};
+use http::StatusCode;
+
+#[derive(Validate)]
+struct CreateUser {
+    #[validate(email)]
+    email: String,
+    #[validate(length(min = 2))]
+    name: String,
+    #[validate(range(min = 0.5, max = 10))]
+    rating: Option<f64>,
+}
+
+#[test]
+fn test_dto_validation() {
+    let dto = CreateUser {
+        email: "bob@example.com".to_string(),
+        name: "Bob".to_string(),
+        rating: None,
+    };
+    assert!(dto.validate().is_ok());
+
+    let dto = CreateUser {
+        email: "bob".to_string(),
+        name: "B".to_string(),
+        rating: Some(11.0),
+    };
+    let errors = dto.validate().unwrap_err();
+    assert_eq!(errors.len(), 3);
+}
+
+#[test]
+fn test_validation_error_is_422() {
+    let mut errors = ValidationErrors::new();
+    errors.add("email", "Invalid email format");
+
+    let err: Error = errors.into();
+    assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = err.into_response();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.headers()["content-type"], "application/json");
+}
@@ -0,0 +1,203 @@
+//! Audit trail
+//!
+//! Models marked `#[model(auditable)]` write an [`Audit`] row whenever the
+//! derived `create`, `update`, `delete` or `force_delete` changes them. The
+//! row holds the old and new values of the changed fields as JSON, the actor
+//! set for the current request and a timestamp:
+//!
+//! ```ignore
+//! // Once, alongside the app's migrations
+//! manager.register(audit::migration());
+//!
+//! // In auth middleware, inside a `DbContextLayer` scope
+//! context::set_actor(format!("user:{}", user.id));
+//!
+//! // Later
+//! for audit in Audit::for_record::<Post>(&db, post.id).await? {
+//!     println!("{} {} by {:?}: {}", audit.created_at, audit.event, audit.actor, audit.new_values);
+//! }
+//! ```
+//!
+//! Updates record only the fields that changed, so auditable models need
+//! change tracking (an `original` field, see [`crate::tracking`]).
+//! `created_at` and `updated_at` are left out of the diffs.
+
+use crate::schema::Schema;
+use crate::tracking::FieldChange;
+use crate::{context, Database, Migration, Model, Result};
+use serde_json::{Map, Value};
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use std::fmt;
+
+/// Table the audit trail is written to
+pub const AUDITS_TABLE: &str = "audits";
+
+/// What happened to the record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditEvent {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl AuditEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEvent::Created => "created",
+            AuditEvent::Updated => "updated",
+            AuditEvent::Deleted => "deleted",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "created" => Some(AuditEvent::Created),
+            "updated" => Some(AuditEvent::Updated),
+            "deleted" => Some(AuditEvent::Deleted),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AuditEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One entry in the audit trail
+#[derive(Debug, Clone, PartialEq)]
+pub struct Audit {
+    pub id: i64,
+    /// Table of the audited model
+    pub auditable_type: String,
+    pub auditable_id: String,
+    pub event: AuditEvent,
+    /// Changed fields before the change; an empty object for `Created`
+    pub old_values: Value,
+    /// Changed fields after the change; an empty object for `Deleted`
+    pub new_values: Value,
+    pub actor: Option<String>,
+    pub created_at: i64,
+}
+
+impl<'r> sqlx::FromRow<'r, AnyRow> for Audit {
+    fn from_row(row: &'r AnyRow) -> std::result::Result<Self, sqlx::Error> {
+        let json = |column: &str| -> std::result::Result<Value, sqlx::Error> {
+            let text: Option<String> = row.try_get(column)?;
+            Ok(text
+                .and_then(|text| serde_json::from_str(&text).ok())
+                .unwrap_or_else(|| Value::Object(Map::new())))
+        };
+        let event: String = row.try_get("event")?;
+
+        Ok(Self {
+            id: row.try_get("id")?,
+            auditable_type: row.try_get("auditable_type")?,
+            auditable_id: row.try_get("auditable_id")?,
+            event: AuditEvent::parse(&event).ok_or_else(|| sqlx::Error::ColumnDecode {
+                index: "event".to_string(),
+                source: format!("unknown audit event `{}`", event).into(),
+            })?,
+            old_values: json("old_values")?,
+            new_values: json("new_values")?,
+            actor: row.try_get("actor")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl Audit {
+    /// Audit entries for one record of `M`, oldest first
+    pub async fn for_record<M: Model>(db: &impl Database, id: impl ToString) -> Result<Vec<Audit>> {
+        let db_type = db.db_type();
+        let sql = format!(
+            "SELECT * FROM {} WHERE auditable_type = {} AND auditable_id = {} ORDER BY id",
+            AUDITS_TABLE,
+            db_type.placeholder(1),
+            db_type.placeholder(2)
+        );
+        let query = sqlx::query(&sql)
+            .bind(M::table_name().to_string())
+            .bind(id.to_string());
+
+        db.fetch_all(query)
+            .await?
+            .iter()
+            .map(sqlx::FromRow::from_row)
+            .collect()
+    }
+}
+
+/// Migration creating the `audits` table
+pub fn migration() -> Migration {
+    Migration::schema(
+        "00000000000000_create_audits_table",
+        vec![Schema::create(AUDITS_TABLE, |t| {
+            t.id();
+            t.string("auditable_type");
+            t.string("auditable_id");
+            t.string_with_length("event", 16);
+            t.text("old_values").nullable();
+            t.text("new_values").nullable();
+            t.string("actor").nullable();
+            t.big_integer("created_at");
+            t.index(&["auditable_type", "auditable_id"]);
+        })],
+        vec![Schema::drop(AUDITS_TABLE)],
+    )
+}
+
+/// Write an audit entry for `changes` to record `id` of `M`
+#[doc(hidden)]
+pub async fn record<M: Model>(
+    db: &impl Database,
+    event: AuditEvent,
+    id: impl ToString,
+    changes: Vec<FieldChange>,
+) -> Result<()> {
+    let mut old_values = Map::new();
+    let mut new_values = Map::new();
+    for change in changes {
+        if change.field == "created_at" || change.field == "updated_at" {
+            continue;
+        }
+        if let Some(old) = change.old {
+            old_values.insert(change.field.to_string(), old);
+        }
+        if event != AuditEvent::Deleted {
+            new_values.insert(change.field.to_string(), change.new);
+        }
+    }
+    if event == AuditEvent::Updated && new_values.is_empty() {
+        return Ok(());
+    }
+
+    let db_type = db.db_type();
+    let placeholders: Vec<String> = (1..=7).map(|i| db_type.placeholder(i)).collect();
+    let sql = format!(
+        "INSERT INTO {} (auditable_type, auditable_id, event, old_values, new_values, actor, created_at) VALUES ({})",
+        AUDITS_TABLE,
+        placeholders.join(", ")
+    );
+    let query = sqlx::query(&sql)
+        .bind(M::table_name().to_string())
+        .bind(id.to_string())
+        .bind(event.as_str())
+        .bind(Value::Object(old_values).to_string())
+        .bind(Value::Object(new_values).to_string())
+        .bind(context::actor())
+        .bind(chrono::Utc::now().timestamp());
+    db.execute_query(query).await?;
+    Ok(())
+}
+
+/// Every field as removed, for `Deleted` entries
+#[doc(hidden)]
+pub fn removed(values: Vec<(&'static str, Value)>) -> Vec<FieldChange> {
+    values
+        .into_iter()
+        .map(|(field, value)| FieldChange { field, old: Some(value), new: Value::Null })
+        .collect()
+}
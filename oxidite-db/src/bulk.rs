@@ -0,0 +1,392 @@
+//! Set-based writes: multi-row inserts, upserts and filtered updates/deletes
+//!
+//! These back `Model::insert_many`, `Model::upsert`, `Model::update_where` and
+//! `Model::delete_where`. They work on many rows per statement, so lifecycle
+//! hooks and observers don't run; timestamps and soft deletes are still applied.
+
+use crate::tenancy::{self, Tenant, TENANT_COLUMN};
+use crate::{json, Database, DatabaseType, Model, Result};
+use sqlx::any::AnyArguments;
+use sqlx::query::Query;
+
+/// A value bound into a set-based query
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    /// Values for an `IN` condition
+    List(Vec<SqlValue>),
+}
+
+impl From<bool> for SqlValue {
+    fn from(value: bool) -> Self {
+        SqlValue::Bool(value)
+    }
+}
+
+impl From<i32> for SqlValue {
+    fn from(value: i32) -> Self {
+        SqlValue::Int(value as i64)
+    }
+}
+
+impl From<i64> for SqlValue {
+    fn from(value: i64) -> Self {
+        SqlValue::Int(value)
+    }
+}
+
+impl From<f64> for SqlValue {
+    fn from(value: f64) -> Self {
+        SqlValue::Float(value)
+    }
+}
+
+impl From<&str> for SqlValue {
+    fn from(value: &str) -> Self {
+        SqlValue::Text(value.to_string())
+    }
+}
+
+impl From<String> for SqlValue {
+    fn from(value: String) -> Self {
+        SqlValue::Text(value)
+    }
+}
+
+impl<T: Into<SqlValue>> From<Vec<T>> for SqlValue {
+    fn from(values: Vec<T>) -> Self {
+        SqlValue::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<SqlValue>> From<Option<T>> for SqlValue {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(SqlValue::Null)
+    }
+}
+
+impl SqlValue {
+    pub(crate) fn bind<'q>(self, query: Query<'q, sqlx::Any, AnyArguments<'q>>) -> Query<'q, sqlx::Any, AnyArguments<'q>> {
+        match self {
+            // Rendered as a literal NULL instead, see `Params::push`
+            SqlValue::Null => query,
+            SqlValue::Bool(b) => query.bind(b),
+            SqlValue::Int(n) => query.bind(n),
+            SqlValue::Float(n) => query.bind(n),
+            SqlValue::Text(s) => query.bind(s),
+            // Flattened into one placeholder per item, see `Params::push`
+            SqlValue::List(items) => items.into_iter().fold(query, |query, item| item.bind(query)),
+        }
+    }
+}
+
+/// Collects bound values and hands out matching placeholders
+pub(crate) struct Params {
+    pub(crate) db_type: DatabaseType,
+    values: Vec<SqlValue>,
+}
+
+impl Params {
+    pub(crate) fn new(db_type: DatabaseType) -> Self {
+        Self { db_type, values: Vec::new() }
+    }
+
+    /// Placeholder for `value`; NULL is inlined because an untyped NULL
+    /// parameter is rejected by Postgres for non-text columns, and a list
+    /// becomes a parenthesized placeholder per item
+    pub(crate) fn push(&mut self, value: SqlValue) -> String {
+        match value {
+            SqlValue::Null => "NULL".to_string(),
+            // `IN (NULL)` matches nothing, which is what an empty list means
+            SqlValue::List(items) if items.is_empty() => "(NULL)".to_string(),
+            SqlValue::List(items) => {
+                let placeholders: Vec<String> = items.into_iter().map(|item| self.push(item)).collect();
+                format!("({})", placeholders.join(", "))
+            }
+            value => {
+                self.values.push(value);
+                self.db_type.placeholder(self.values.len())
+            }
+        }
+    }
+
+    /// `column op placeholder` for an already validated column and operator
+    pub(crate) fn condition(&mut self, column: &str, op: &str, value: SqlValue) -> String {
+        match (&value, op) {
+            (SqlValue::Null, "=") => format!("{} IS NULL", column),
+            (SqlValue::Null, "!=" | "<>") => format!("{} IS NOT NULL", column),
+            _ => format!("{} {} {}", column, op, self.push(value)),
+        }
+    }
+
+    pub(crate) fn into_values(self) -> Vec<SqlValue> {
+        self.values
+    }
+
+    pub(crate) fn bind_all<'q>(self, mut query: Query<'q, sqlx::Any, AnyArguments<'q>>) -> Query<'q, sqlx::Any, AnyArguments<'q>> {
+        for value in self.values {
+            query = value.bind(query);
+        }
+        query
+    }
+}
+
+/// `ON CONFLICT` behaviour for `insert`
+pub(crate) struct OnConflict<'a> {
+    pub conflict_columns: &'a [&'a str],
+    pub update_columns: &'a [&'a str],
+}
+
+// Parameter limits: SQLite's default SQLITE_MAX_VARIABLE_NUMBER on older builds,
+// and the 16-bit limit of the Postgres and MySQL wire protocols
+const SQLITE_MAX_PARAMS: usize = 999;
+const MAX_PARAMS: usize = 65_535;
+const MAX_ROWS_PER_STATEMENT: usize = 1_000;
+
+/// Insert `rows` in chunks inside one transaction, returning rows affected
+pub(crate) async fn insert<M: Model>(db: &impl Database, rows: &[M], on_conflict: Option<OnConflict<'_>>) -> Result<u64> {
+    let columns = M::insert_columns();
+    if rows.is_empty() {
+        return Ok(0);
+    }
+    if columns.is_empty() {
+        return Err(sqlx::Error::Protocol(format!(
+            "{} does not support bulk inserts; derive Model to generate them",
+            M::table_name()
+        )));
+    }
+    if let Some(conflict) = &on_conflict {
+        if conflict.conflict_columns.is_empty() && !conflict.update_columns.is_empty() {
+            return Err(sqlx::Error::Protocol(format!(
+                "upserting into {} needs conflict columns to know which rows to update",
+                M::table_name()
+            )));
+        }
+    }
+    // `bind_insert` fills in the current tenant
+    let tenant_scoped = tenancy::scope_for::<M>()?.is_some();
+
+    let db_type = db.db_type();
+    let max_params = match db_type {
+        DatabaseType::Sqlite => SQLITE_MAX_PARAMS,
+        _ => MAX_PARAMS,
+    };
+    let chunk_size = (max_params / columns.len()).clamp(1, MAX_ROWS_PER_STATEMENT);
+    let touch_updated_at = M::fields().contains(&"updated_at");
+    let now = chrono::Utc::now().timestamp();
+    let on_conflict = on_conflict.as_ref();
+
+    db.transaction(|tx| async move {
+        let mut affected = 0;
+        for chunk in rows.chunks(chunk_size) {
+            let sql = insert_sql::<M>(columns, chunk.len(), db_type, on_conflict, touch_updated_at, tenant_scoped);
+            let mut query = sqlx::query(&sql);
+            for row in chunk {
+                query = row.bind_insert(query, now);
+            }
+            affected += tx.execute_query(query).await?;
+        }
+        Ok(affected)
+    })
+    .await
+}
+
+fn insert_sql<M: Model>(
+    columns: &[&str],
+    row_count: usize,
+    db_type: DatabaseType,
+    on_conflict: Option<&OnConflict<'_>>,
+    touch_updated_at: bool,
+    tenant_scoped: bool,
+) -> String {
+    let table = M::table_name();
+    let rows: Vec<String> = (0..row_count)
+        .map(|row| {
+            let placeholders: Vec<String> = columns
+                .iter()
+                .enumerate()
+                .map(|(col, name)| {
+                    let cast = json::cast(db_type, M::json_columns().contains(name));
+                    format!("{}{}", db_type.placeholder(row * columns.len() + col + 1), cast)
+                })
+                .collect();
+            format!("({})", placeholders.join(", "))
+        })
+        .collect();
+
+    let mut sql = format!(
+        "INSERT INTO {} ({}) VALUES {}",
+        table,
+        columns.join(", "),
+        rows.join(", ")
+    );
+
+    let Some(conflict) = on_conflict else {
+        return sql;
+    };
+
+    let mut updates: Vec<&str> = conflict.update_columns.to_vec();
+    if touch_updated_at && !updates.is_empty() && !updates.contains(&"updated_at") {
+        updates.push("updated_at");
+    }
+
+    match db_type {
+        DatabaseType::MySql => {
+            let sets: Vec<String> = if updates.is_empty() {
+                // A no-op assignment skips duplicates like DO NOTHING; unlike
+                // INSERT IGNORE it doesn't also swallow other errors
+                let column = conflict.conflict_columns.first().unwrap_or(&columns[0]);
+                vec![format!("{} = {}", column, column)]
+            } else if tenant_scoped {
+                // MySQL has no WHERE on this clause, so each assignment keeps
+                // another tenant's row as it was
+                updates
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{} = IF({} = VALUES({}), VALUES({}), {})",
+                            c, TENANT_COLUMN, TENANT_COLUMN, c, c
+                        )
+                    })
+                    .collect()
+            } else {
+                updates.iter().map(|c| format!("{} = VALUES({})", c, c)).collect()
+            };
+            sql.push_str(&format!(" ON DUPLICATE KEY UPDATE {}", sets.join(", ")));
+        }
+        DatabaseType::Postgres | DatabaseType::Sqlite => {
+            sql.push_str(" ON CONFLICT");
+            if !conflict.conflict_columns.is_empty() {
+                sql.push_str(&format!(" ({})", conflict.conflict_columns.join(", ")));
+            }
+            if updates.is_empty() {
+                sql.push_str(" DO NOTHING");
+            } else {
+                let sets: Vec<String> = updates.iter().map(|c| format!("{} = excluded.{}", c, c)).collect();
+                sql.push_str(&format!(" DO UPDATE SET {}", sets.join(", ")));
+                // Never let a conflict on a shared key overwrite another tenant's row
+                if tenant_scoped {
+                    sql.push_str(&format!(" WHERE {}.{} = excluded.{}", table, TENANT_COLUMN, TENANT_COLUMN));
+                }
+            }
+        }
+    }
+
+    sql
+}
+
+/// Operators accepted in a condition's `"column op"` spec
+const OPERATORS: &[&str] = &["=", "!=", "<>", "<", "<=", ">", ">=", "LIKE", "IN"];
+
+fn invalid(message: String) -> sqlx::Error {
+    sqlx::Error::Protocol(message)
+}
+
+/// Checks that `column` is one of the model's fields
+fn check_column<M: Model>(column: &str) -> Result<()> {
+    if M::fields().contains(&column) {
+        Ok(())
+    } else {
+        Err(invalid(format!("{} has no column {:?}", M::table_name(), column)))
+    }
+}
+
+/// Splits a `"column"` (equality) or `"column op"` spec, checking both halves
+fn parse_condition<M: Model>(spec: &str) -> Result<(&str, &'static str)> {
+    let spec = spec.trim();
+    let (column, op) = spec.split_once(' ').unwrap_or((spec, "="));
+    check_column::<M>(column)?;
+    let op = op.trim();
+    let op = OPERATORS
+        .iter()
+        .find(|allowed| allowed.eq_ignore_ascii_case(op))
+        .ok_or_else(|| invalid(format!("unsupported operator {:?} in condition {:?}", op, spec)))?;
+    Ok((column, op))
+}
+
+/// `WHERE` clause for set-based updates and deletes, limited to `tenant` and
+/// optionally skipping soft-deleted rows
+///
+/// At least one condition is required, so a forgotten filter can't rewrite
+/// or delete the whole table.
+fn where_sql<M: Model>(
+    params: &mut Params,
+    conditions: &[(&str, SqlValue)],
+    tenant: Option<Tenant>,
+    skip_deleted: bool,
+) -> Result<String> {
+    if conditions.is_empty() {
+        return Err(invalid(format!(
+            "bulk update or delete on {} needs at least one condition",
+            M::table_name()
+        )));
+    }
+
+    let mut clauses = Vec::with_capacity(conditions.len() + 2);
+    for (spec, value) in conditions {
+        let (column, op) = parse_condition::<M>(spec)?;
+        if (op == "IN") != matches!(value, SqlValue::List(_)) {
+            return Err(invalid(format!("condition {:?} needs IN with a list of values", spec)));
+        }
+        clauses.push(params.condition(column, op, value.clone()));
+    }
+    if let Some(tenant) = tenant {
+        clauses.push(params.condition(TENANT_COLUMN, "=", tenant.id().into()));
+    }
+    if skip_deleted && M::has_soft_delete() {
+        clauses.push("deleted_at IS NULL".to_string());
+    }
+
+    Ok(format!(" WHERE {}", clauses.join(" AND ")))
+}
+
+pub(crate) async fn update_where<M: Model>(
+    db: &impl Database,
+    conditions: &[(&str, SqlValue)],
+    values: &[(&str, SqlValue)],
+) -> Result<u64> {
+    let tenant = tenancy::scope_for::<M>()?;
+    let mut params = Params::new(db.db_type());
+
+    if values.is_empty() {
+        return Err(invalid(format!("update_where on {} needs at least one value to set", M::table_name())));
+    }
+    let mut sets = Vec::with_capacity(values.len() + 2);
+    for (column, value) in values {
+        check_column::<M>(column)?;
+        if matches!(value, SqlValue::List(_)) {
+            return Err(invalid(format!("cannot set {} to a list of values", column)));
+        }
+        let cast = json::cast(params.db_type, M::json_columns().contains(column));
+        sets.push(format!("{} = {}{}", column, params.push(value.clone()), cast));
+    }
+    if M::fields().contains(&"updated_at") && !values.iter().any(|(c, _)| *c == "updated_at") {
+        sets.push(format!("updated_at = {}", params.push(chrono::Utc::now().timestamp().into())));
+    }
+    // Concurrent editors holding the old version must see their update as stale
+    if let Some(version) = M::version_column() {
+        sets.push(format!("{} = {} + 1", version, version));
+    }
+
+    let where_clause = where_sql::<M>(&mut params, conditions, tenant, true)?;
+    let sql = format!("UPDATE {} SET {}{}", M::table_name(), sets.join(", "), where_clause);
+    db.execute_query(params.bind_all(sqlx::query(&sql))).await
+}
+
+pub(crate) async fn delete_where<M: Model>(db: &impl Database, conditions: &[(&str, SqlValue)], force: bool) -> Result<u64> {
+    let tenant = tenancy::scope_for::<M>()?;
+    let mut params = Params::new(db.db_type());
+
+    let sql = if M::has_soft_delete() && !force {
+        let set = format!("deleted_at = {}", params.push(chrono::Utc::now().timestamp().into()));
+        format!("UPDATE {} SET {}{}", M::table_name(), set, where_sql::<M>(&mut params, conditions, tenant, true)?)
+    } else {
+        format!("DELETE FROM {}{}", M::table_name(), where_sql::<M>(&mut params, conditions, tenant, false)?)
+    };
+
+    db.execute_query(params.bind_all(sqlx::query(&sql))).await
+}
@@ -0,0 +1,187 @@
+//! Read replicas and named connections
+//!
+//! A [`DbPool`] can carry read replicas and other named pools alongside its
+//! primary:
+//!
+//! ```ignore
+//! let analytics = DbPool::connect("postgres://analytics-db/events").await?;
+//! let db = DbPool::connect_with_replicas(
+//!     "postgres://primary/app",
+//!     &["postgres://replica-1/app", "postgres://replica-2/app"],
+//!     PoolOptions::default(),
+//! )
+//! .await?
+//! .with_connection("analytics", analytics);
+//! ```
+//!
+//! Plain `SELECT`s sent through `query`, `query_one`, `fetch_all` and
+//! `fetch_one` go to the replicas round-robin; everything else, including
+//! transactions and `SELECT`s that lock rows, select `INTO` or call sequence
+//! and lock functions, goes to the primary. Inside a [`context::scope`](crate::context::scope), the
+//! first write pins the rest of the scope's reads to the primary so a request
+//! always sees its own changes.
+//!
+//! Bookkeeping that must not trust a lagging replica, like the migration
+//! runner and seeders reading what has already run, goes through
+//! [`on_primary`].
+//!
+//! Models marked `#[model(connection = "analytics")]` run on the pool
+//! registered under that name. When the handle they are given has no such
+//! pool (a `DbTransaction`, or a pool without that connection) they use the
+//! handle as-is.
+
+use crate::{Database, DatabaseType, DbPool, DbTransaction, Model, Result};
+use sqlx::any::{AnyArguments, AnyConnectOptions, AnyRow};
+use sqlx::query::Query;
+use std::sync::Arc;
+
+/// Fragments that make a `SELECT` lock rows, write, or depend on the session
+/// it runs in, so it has to run on the primary
+const PRIMARY_ONLY: &[&str] = &[
+    " INTO ",
+    " FOR UPDATE",
+    " FOR NO KEY UPDATE",
+    " FOR SHARE",
+    " FOR KEY SHARE",
+    " LOCK IN SHARE MODE",
+    "NEXTVAL(",
+    "SETVAL(",
+    "CURRVAL(",
+    "LASTVAL(",
+    "ADVISORY_",
+    "GET_LOCK(",
+    "RELEASE_LOCK(",
+    "SET_CONFIG(",
+    "PG_NOTIFY(",
+    "LAST_INSERT_ID(",
+    "LAST_INSERT_ROWID(",
+];
+
+/// Whether a statement only reads and may be served by a replica
+///
+/// Only plain `SELECT`s qualify. Calls to user-defined functions with side
+/// effects can't be told apart from reads; send those through [`on_primary`].
+pub(crate) fn is_read_only(sql: &str) -> bool {
+    let sql = sql.split_whitespace().collect::<Vec<_>>().join(" ").to_ascii_uppercase();
+    // Padded so a fragment at the very end still matches, e.g. "... INTO x"
+    let sql = format!("{} ", sql.replace(" (", "("));
+    sql.starts_with("SELECT ") && !PRIMARY_ONLY.iter().any(|fragment| sql.contains(fragment))
+}
+
+/// The handle a model should run on: its named connection if `db` has one
+pub fn route<M: Model, D: Database>(db: &D) -> Routed<'_, D> {
+    match M::connection_name().and_then(|name| db.named_connection(name)) {
+        Some(pool) => Routed::Named(pool),
+        None => Routed::Given(db),
+    }
+}
+
+/// `db` with its reads sent to the primary rather than a replica
+pub fn on_primary<D: Database>(db: &D) -> Routed<'_, D> {
+    match db.primary_pool() {
+        Some(pool) => Routed::Named(pool),
+        None => Routed::Given(db),
+    }
+}
+
+/// A database handle chosen by [`route`] or [`on_primary`]
+#[derive(Debug)]
+pub enum Routed<'a, D> {
+    Given(&'a D),
+    Named(DbPool),
+}
+
+#[async_trait::async_trait]
+impl<D: Database> Database for Routed<'_, D> {
+    fn db_type(&self) -> DatabaseType {
+        match self {
+            Routed::Given(db) => db.db_type(),
+            Routed::Named(pool) => pool.db_type(),
+        }
+    }
+
+    fn named_connection(&self, name: &str) -> Option<DbPool> {
+        match self {
+            Routed::Given(db) => db.named_connection(name),
+            Routed::Named(pool) => pool.named_connection(name),
+        }
+    }
+
+    fn primary_pool(&self) -> Option<DbPool> {
+        match self {
+            Routed::Given(db) => db.primary_pool(),
+            Routed::Named(pool) => pool.primary_pool(),
+        }
+    }
+
+    fn connect_options(&self) -> Option<Arc<AnyConnectOptions>> {
+        match self {
+            Routed::Given(db) => db.connect_options(),
+            Routed::Named(pool) => pool.connect_options(),
+        }
+    }
+
+    async fn execute(&self, query: &str) -> Result<u64> {
+        match self {
+            Routed::Given(db) => db.execute(query).await,
+            Routed::Named(pool) => pool.execute(query).await,
+        }
+    }
+
+    async fn query(&self, query: &str) -> Result<Vec<AnyRow>> {
+        match self {
+            Routed::Given(db) => db.query(query).await,
+            Routed::Named(pool) => pool.query(query).await,
+        }
+    }
+
+    async fn query_one(&self, query: &str) -> Result<Option<AnyRow>> {
+        match self {
+            Routed::Given(db) => db.query_one(query).await,
+            Routed::Named(pool) => pool.query_one(query).await,
+        }
+    }
+
+    async fn ping(&self) -> Result<()> {
+        match self {
+            Routed::Given(db) => db.ping().await,
+            Routed::Named(pool) => pool.ping().await,
+        }
+    }
+
+    async fn begin_transaction(&self) -> Result<DbTransaction> {
+        match self {
+            Routed::Given(db) => db.begin_transaction().await,
+            Routed::Named(pool) => pool.begin_transaction().await,
+        }
+    }
+
+    async fn execute_query<'q>(&self, query: Query<'q, sqlx::Any, AnyArguments<'q>>) -> Result<u64> {
+        match self {
+            Routed::Given(db) => db.execute_query(query).await,
+            Routed::Named(pool) => pool.execute_query(query).await,
+        }
+    }
+
+    async fn execute_insert<'q>(&self, query: Query<'q, sqlx::Any, AnyArguments<'q>>) -> Result<Option<i64>> {
+        match self {
+            Routed::Given(db) => db.execute_insert(query).await,
+            Routed::Named(pool) => pool.execute_insert(query).await,
+        }
+    }
+
+    async fn fetch_all<'q>(&self, query: Query<'q, sqlx::Any, AnyArguments<'q>>) -> Result<Vec<AnyRow>> {
+        match self {
+            Routed::Given(db) => db.fetch_all(query).await,
+            Routed::Named(pool) => pool.fetch_all(query).await,
+        }
+    }
+
+    async fn fetch_one<'q>(&self, query: Query<'q, sqlx::Any, AnyArguments<'q>>) -> Result<Option<AnyRow>> {
+        match self {
+            Routed::Given(db) => db.fetch_one(query).await,
+            Routed::Named(pool) => pool.fetch_one(query).await,
+        }
+    }
+}
+
@@ -0,0 +1,191 @@
+//! Per-request database state
+//!
+//! Work running inside [`scope`] shares a [`RequestContext`] that the database
+//! layer uses to remember what the request has done so far, and counts the
+//! request's queries. Wrap the router in [`DbContextLayer`] to give every
+//! request its own scope and log its query totals:
+//!
+//! ```ignore
+//! let app = ServiceBuilder::new()
+//!     .layer(DbContextLayer::new())
+//!     .service(router);
+//! ```
+//!
+//! Outside a scope (background jobs, tests, spawned tasks) each query stands
+//! on its own.
+
+use crate::instrument::QueryMetrics;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::{Layer, Service};
+
+/// Executions of one statement within a request that get it flagged as a likely N+1
+pub const DEFAULT_N_PLUS_ONE_THRESHOLD: usize = 10;
+
+tokio::task_local! {
+    static CONTEXT: Arc<RequestContext>;
+}
+
+/// State shared by the queries of one request
+#[derive(Debug, Default)]
+pub struct RequestContext {
+    wrote: AtomicBool,
+    metrics: Mutex<QueryMetrics>,
+    actor: Mutex<Option<String>>,
+}
+
+impl RequestContext {
+    /// Whether the request has written to a primary, so reads should stay there
+    pub fn has_written(&self) -> bool {
+        self.wrote.load(Ordering::Relaxed)
+    }
+
+    /// Queries run so far in this scope
+    pub fn metrics(&self) -> QueryMetrics {
+        self.metrics.lock().map(|m| m.clone()).unwrap_or_default()
+    }
+}
+
+/// Run `f` with a fresh request context
+pub async fn scope<F: Future>(f: F) -> F::Output {
+    CONTEXT.scope(Arc::new(RequestContext::default()), f).await
+}
+
+/// The context of the enclosing [`scope`], if any
+pub fn current() -> Option<Arc<RequestContext>> {
+    CONTEXT.try_with(Arc::clone).ok()
+}
+
+/// Record who is making this request's changes, for the audit trail
+///
+/// Does nothing outside a [`scope`].
+pub fn set_actor(actor: impl Into<String>) {
+    let actor = actor.into();
+    let _ = CONTEXT.try_with(|ctx| {
+        if let Ok(mut current) = ctx.actor.lock() {
+            *current = Some(actor);
+        }
+    });
+}
+
+/// The actor set with [`set_actor`] in the enclosing scope
+pub fn actor() -> Option<String> {
+    CONTEXT
+        .try_with(|ctx| ctx.actor.lock().ok().and_then(|actor| actor.clone()))
+        .ok()
+        .flatten()
+}
+
+pub(crate) fn mark_write() {
+    let _ = CONTEXT.try_with(|ctx| ctx.wrote.store(true, Ordering::Relaxed));
+}
+
+pub(crate) fn has_written() -> bool {
+    CONTEXT.try_with(|ctx| ctx.has_written()).unwrap_or(false)
+}
+
+pub(crate) fn record_query(sql: &str, duration: Duration) {
+    let _ = CONTEXT.try_with(|ctx| {
+        if let Ok(mut metrics) = ctx.metrics.lock() {
+            metrics.record(sql, duration);
+        }
+    });
+}
+
+/// Log the current scope's query count and time, and statements repeated `n_plus_one_threshold` times
+fn report(n_plus_one_threshold: usize) {
+    let Some(metrics) = current().map(|ctx| ctx.metrics()) else {
+        return;
+    };
+    if metrics.count == 0 {
+        return;
+    }
+
+    tracing::debug!(
+        target: "oxidite_db::request",
+        queries = metrics.count,
+        db_time_ms = metrics.total_time.as_secs_f64() * 1000.0,
+        "request database usage"
+    );
+    for (statement, count) in metrics.repeated(n_plus_one_threshold) {
+        tracing::warn!(
+            target: "oxidite_db::n_plus_one",
+            count,
+            statement,
+            "statement ran {} times in one request; possible N+1 query",
+            count
+        );
+    }
+}
+
+/// Runs each request in its own [`scope`] and reports its queries when it finishes
+#[derive(Clone, Debug)]
+pub struct DbContextService<S> {
+    inner: S,
+    n_plus_one_threshold: usize,
+}
+
+impl<S, Req> Service<Req> for DbContextService<S>
+where
+    S: Service<Req>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let response = self.inner.call(req);
+        let n_plus_one_threshold = self.n_plus_one_threshold;
+        Box::pin(scope(async move {
+            let response = response.await;
+            report(n_plus_one_threshold);
+            response
+        }))
+    }
+}
+
+/// Layer for [`DbContextService`]
+#[derive(Clone, Debug)]
+pub struct DbContextLayer {
+    n_plus_one_threshold: usize,
+}
+
+impl Default for DbContextLayer {
+    fn default() -> Self {
+        Self {
+            n_plus_one_threshold: DEFAULT_N_PLUS_ONE_THRESHOLD,
+        }
+    }
+}
+
+impl DbContextLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flag statements run at least this many times in one request
+    pub fn n_plus_one_threshold(mut self, threshold: usize) -> Self {
+        self.n_plus_one_threshold = threshold.max(2);
+        self
+    }
+}
+
+impl<S> Layer<S> for DbContextLayer {
+    type Service = DbContextService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DbContextService {
+            inner,
+            n_plus_one_threshold: self.n_plus_one_threshold,
+        }
+    }
+}
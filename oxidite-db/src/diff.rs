@@ -0,0 +1,520 @@
+//! Diffing model definitions against the live schema
+//!
+//! Compares the fields recorded by `#[derive(Model)]` with what
+//! [`introspect`](crate::introspect) finds in the database and produces the
+//! schema changes needed to bring the database in line:
+//!
+//! ```ignore
+//! let manager = MigrationManager::new("migrations");
+//! let models = [
+//!     ModelSchema::of::<User>().unique(&["email"]),
+//!     ModelSchema::of::<Post>().index(&["user_id"]),
+//! ];
+//!
+//! if let Some(path) = manager.generate_migration(&db, "sync_models", &models, DiffOptions::default()).await? {
+//!     println!("Review {} before running it", path.display());
+//! }
+//! ```
+//!
+//! New tables, columns and indexes are created, and columns whose type or
+//! nullability changed are altered (SQLite rebuilds the table). Columns
+//! missing from the model are only dropped with [`DiffOptions::drop_columns`],
+//! since that loses their data; otherwise they, and indexes the model doesn't
+//! declare, are reported as notes at the top of the generated file. Notes
+//! alone don't produce a file.
+
+use crate::introspect::{self, ColumnInfo, TableInfo};
+use crate::schema::{ColumnDefault, ColumnType, IndexDefinition, Schema, SchemaError};
+use crate::{Database, DatabaseType, Migration, Model, Result};
+use sqlx::Row;
+
+/// A model field as seen by the derive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldMeta {
+    pub name: &'static str,
+    /// Rust type without the `Option`, e.g. `i64` or `chrono::NaiveDate`
+    pub rust_type: &'static str,
+    /// Whether the field is an `Option`
+    pub nullable: bool,
+}
+
+impl FieldMeta {
+    /// Column type used when creating this field
+    pub fn column_type(&self) -> ColumnType {
+        let (path, generics) = match self.rust_type.split_once('<') {
+            Some((path, rest)) => (path, rest.trim_end_matches('>')),
+            None => (self.rust_type, ""),
+        };
+        let base = path.rsplit("::").next().unwrap_or(path);
+
+        match base {
+            "i8" | "i16" | "u8" => ColumnType::SmallInteger,
+            "i32" | "u16" => ColumnType::Integer,
+            "i64" | "u32" | "u64" | "isize" | "usize" => ColumnType::BigInteger,
+            "f32" => ColumnType::Float,
+            "f64" => ColumnType::Double,
+            "bool" => ColumnType::Boolean,
+            "char" => ColumnType::Char(1),
+            "String" | "&str" => ColumnType::String(255),
+            "NaiveDate" => ColumnType::Date,
+            "NaiveTime" => ColumnType::Time,
+            "NaiveDateTime" => ColumnType::DateTime,
+            "DateTime" => ColumnType::Timestamp,
+            "Uuid" => ColumnType::Uuid,
+            "Decimal" | "BigDecimal" => ColumnType::Decimal { precision: 18, scale: 4 },
+            "Vec" if generics == "u8" => ColumnType::Binary,
+            "Vec" | "HashMap" | "BTreeMap" | "Value" | "Json" => ColumnType::Json,
+            _ => ColumnType::Text,
+        }
+    }
+}
+
+/// An index a model expects, declared with [`ModelSchema::index`] or [`ModelSchema::unique`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexMeta {
+    pub columns: Vec<String>,
+    pub unique: bool,
+}
+
+/// Table name, fields and indexes of a model, for diffing
+#[derive(Debug, Clone)]
+pub struct ModelSchema {
+    pub table: &'static str,
+    pub fields: Vec<FieldMeta>,
+    pub indexes: Vec<IndexMeta>,
+}
+
+impl ModelSchema {
+    pub fn of<M: Model>() -> Self {
+        let fields = if M::field_meta().is_empty() {
+            // Hand-written `Model` impls don't record types
+            M::fields()
+                .iter()
+                .map(|name| FieldMeta { name, rust_type: "", nullable: true })
+                .collect()
+        } else {
+            M::field_meta().to_vec()
+        };
+
+        Self {
+            table: M::table_name(),
+            fields,
+            indexes: Vec::new(),
+        }
+    }
+
+    /// Expect an index over `columns`
+    pub fn index(mut self, columns: &[&str]) -> Self {
+        self.indexes.push(IndexMeta { columns: columns.iter().map(|c| c.to_string()).collect(), unique: false });
+        self
+    }
+
+    /// Expect a unique index over `columns`
+    pub fn unique(mut self, columns: &[&str]) -> Self {
+        self.indexes.push(IndexMeta { columns: columns.iter().map(|c| c.to_string()).collect(), unique: true });
+        self
+    }
+}
+
+/// Changes [`diff_models`] may make that lose data, all off by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Drop columns that are no longer on the model instead of only noting them
+    pub drop_columns: bool,
+}
+
+/// Changes needed to bring the database in line with the models
+#[derive(Debug, Clone, Default)]
+pub struct SchemaDiff {
+    pub up: Vec<Schema>,
+    /// Reverses `up`, newest change first
+    pub down: Vec<Schema>,
+    /// Differences that need a manual decision
+    pub notes: Vec<String>,
+}
+
+impl SchemaDiff {
+    /// Whether there is nothing to migrate; notes alone need a manual change, not a migration
+    pub fn is_empty(&self) -> bool {
+        self.up.is_empty()
+    }
+
+    /// Render as a SQL migration for `db_type`
+    pub fn to_migration(&self, name: &str, db_type: DatabaseType) -> std::result::Result<Migration, SchemaError> {
+        let render = |changes: &[Schema]| -> std::result::Result<String, SchemaError> {
+            let mut statements = Vec::new();
+            for change in changes {
+                statements.extend(change.to_sql(db_type)?.into_iter().map(|statement| format!("{};", statement)));
+            }
+            Ok(statements.join("\n"))
+        };
+
+        let notes: String = self.notes.iter().map(|note| format!("-- NOTE: {}\n", note)).collect();
+
+        let mut migration = Migration::new(name);
+        migration.up_sql = format!("{}{}", notes, render(&self.up)?);
+        migration.down_sql = render(&self.down)?;
+        Ok(migration)
+    }
+}
+
+/// A column whose type or nullability differs from its field
+struct Drift<'a> {
+    column: &'a ColumnInfo,
+    /// SQL type the model expects, if the type family differs
+    data_type: Option<String>,
+    /// Nullability the model expects, if it differs
+    nullable: Option<bool>,
+}
+
+/// Compare models with the live schema
+pub async fn diff_models(db: &impl Database, models: &[ModelSchema], options: DiffOptions) -> Result<SchemaDiff> {
+    let db_type = db.db_type();
+    let mut diff = SchemaDiff::default();
+
+    for model in models {
+        let table = model.table;
+
+        let Some(info) = introspect::table_info(db, table).await? else {
+            let fields = model.fields.clone();
+            let indexes = model.indexes.clone();
+            diff.up.push(Schema::create(table, |t| {
+                for field in &fields {
+                    let column_type = field.column_type();
+                    if field.name == "id" && matches!(column_type, ColumnType::BigInteger | ColumnType::Integer) {
+                        t.id();
+                        continue;
+                    }
+                    let column = t.column(field.name, column_type);
+                    if field.nullable {
+                        column.nullable();
+                    }
+                }
+                for index in &indexes {
+                    let columns: Vec<&str> = index.columns.iter().map(String::as_str).collect();
+                    if index.unique {
+                        t.unique(&columns);
+                    } else {
+                        t.index(&columns);
+                    }
+                }
+            }));
+            diff.down.insert(0, Schema::drop(table));
+            continue;
+        };
+
+        // Changes to this table in the order they run, and their reversal in the order it runs
+        let mut up = Vec::new();
+        let mut down = Vec::new();
+
+        let mut added = Vec::new();
+        let mut drift = Vec::new();
+
+        for field in &model.fields {
+            let column_type = field.column_type();
+            match info.column(field.name) {
+                None => {
+                    // Existing rows need a value for a new NOT NULL column
+                    let default = (!field.nullable).then(|| zero_value(&column_type)).flatten();
+                    if !field.nullable && default.is_none() {
+                        diff.notes.push(format!(
+                            "{}.{} is added as NOT NULL without a default; existing rows need a value",
+                            table, field.name
+                        ));
+                    }
+                    added.push((field.name, column_type, field.nullable, default));
+                }
+                // Hand-written `Model` impls don't record types
+                Some(_) if field.rust_type.is_empty() => {}
+                Some(column) => {
+                    let expected = column_type.to_sql(db_type);
+                    let data_type = (type_family(&expected) != type_family(&column.data_type)).then_some(expected);
+                    let nullable = (!column.primary_key && column.nullable != field.nullable).then_some(field.nullable);
+                    if nullable == Some(false) {
+                        diff.notes.push(format!(
+                            "{}.{} becomes NOT NULL; existing NULLs must be filled in first",
+                            table, field.name
+                        ));
+                    }
+                    if data_type.is_some() || nullable.is_some() {
+                        drift.push(Drift { column, data_type, nullable });
+                    }
+                }
+            }
+        }
+
+        if !drift.is_empty() {
+            let (forward, backward) = match db_type {
+                DatabaseType::Postgres => postgres_alter_columns(table, &drift),
+                DatabaseType::MySql => {
+                    for change in drift.iter().filter(|change| change.column.default.is_some()) {
+                        diff.notes.push(format!(
+                            "{}.{} loses its default to MODIFY COLUMN; add it back if it is still needed",
+                            table, change.column.name
+                        ));
+                    }
+                    mysql_alter_columns(table, &drift)
+                }
+                DatabaseType::Sqlite => {
+                    diff.notes.push(format!(
+                        "{} is rebuilt to change column types or nullability; its foreign keys, checks and triggers are not carried over",
+                        table
+                    ));
+                    let autoincrement = sqlite_autoincrement(db, table).await?;
+                    let current: Vec<ColumnInfo> = info.columns.clone();
+                    let target: Vec<ColumnInfo> = info
+                        .columns
+                        .iter()
+                        .map(|column| match drift.iter().find(|change| change.column.name == column.name) {
+                            Some(change) => ColumnInfo {
+                                data_type: change.data_type.clone().unwrap_or_else(|| column.data_type.clone()),
+                                nullable: change.nullable.unwrap_or(column.nullable),
+                                ..column.clone()
+                            },
+                            None => column.clone(),
+                        })
+                        .collect();
+                    (
+                        sqlite_rebuild(&info, &target, autoincrement),
+                        sqlite_rebuild(&info, &current, autoincrement),
+                    )
+                }
+            };
+            up.extend(forward.iter().map(|sql| Schema::raw(sql)));
+            down.splice(0..0, backward.iter().map(|sql| Schema::raw(sql)));
+        }
+
+        let removed: Vec<&ColumnInfo> = info
+            .columns
+            .iter()
+            .filter(|column| !model.fields.iter().any(|f| f.name == column.name))
+            .collect();
+        for column in &removed {
+            diff.notes.push(if options.drop_columns {
+                format!("{}.{} is not on the model and will be dropped", table, column.name)
+            } else {
+                format!(
+                    "{}.{} is not on the model; drop it by hand or with `DiffOptions {{ drop_columns: true }}`",
+                    table, column.name
+                )
+            });
+        }
+        let removed: Vec<&ColumnInfo> = if options.drop_columns { removed } else { Vec::new() };
+
+        if !added.is_empty() || !removed.is_empty() {
+            let removed_names: Vec<String> = removed.iter().map(|c| c.name.clone()).collect();
+            let added_names: Vec<&str> = added.iter().map(|(name, ..)| *name).collect();
+            up.push(Schema::alter(table, |t| {
+                for name in &removed_names {
+                    t.drop_column(name);
+                }
+                for (name, column_type, nullable, default) in added {
+                    let column = t.column(name, column_type);
+                    if nullable {
+                        column.nullable();
+                    }
+                    if let Some(default) = default {
+                        column.default(default);
+                    }
+                }
+            }));
+
+            let mut reverse = Vec::new();
+            if !added_names.is_empty() {
+                reverse.push(Schema::alter(table, |t| {
+                    for name in added_names {
+                        t.drop_column(name);
+                    }
+                }));
+            }
+            // Dropped columns come back with the type the database reported
+            reverse.extend(removed.iter().map(|column| Schema::raw(&readd_column_sql(table, column))));
+            down.splice(0..0, reverse);
+        }
+
+        for index in &model.indexes {
+            let exists = info.indexes.iter().any(|existing| existing.columns == index.columns && existing.unique == index.unique);
+            if exists {
+                continue;
+            }
+            let columns: Vec<&str> = index.columns.iter().map(String::as_str).collect();
+            let name = IndexDefinition::new(table, &columns, index.unique).name;
+            up.push(Schema::alter(table, |t| {
+                if index.unique {
+                    t.unique(&columns);
+                } else {
+                    t.index(&columns);
+                }
+            }));
+            down.insert(0, Schema::alter(table, |t| t.drop_index(&name)));
+        }
+        for existing in &info.indexes {
+            let declared = model.indexes.iter().any(|index| index.columns == existing.columns && index.unique == existing.unique);
+            if !declared {
+                diff.notes.push(format!(
+                    "{} has {}index {} on ({}) that the model doesn't declare",
+                    table,
+                    if existing.unique { "unique " } else { "" },
+                    existing.name,
+                    existing.columns.join(", ")
+                ));
+            }
+        }
+
+        diff.up.extend(up);
+        diff.down.splice(0..0, down);
+    }
+
+    Ok(diff)
+}
+
+/// `ALTER COLUMN` statements applying each change, and the ones undoing them
+fn postgres_alter_columns(table: &str, drift: &[Drift<'_>]) -> (Vec<String>, Vec<String>) {
+    let mut forward = Vec::new();
+    let mut backward = Vec::new();
+    for change in drift {
+        let name = &change.column.name;
+        let alter = format!("ALTER TABLE {} ALTER COLUMN {}", table, name);
+        if let Some(data_type) = &change.data_type {
+            forward.push(format!("{} TYPE {} USING {}::{}", alter, data_type, name, data_type));
+            backward.push(format!(
+                "{} TYPE {} USING {}::{}",
+                alter, change.column.data_type, name, change.column.data_type
+            ));
+        }
+        if let Some(nullable) = change.nullable {
+            let set = |nullable: bool| if nullable { "DROP NOT NULL" } else { "SET NOT NULL" };
+            forward.push(format!("{} {}", alter, set(nullable)));
+            backward.push(format!("{} {}", alter, set(!nullable)));
+        }
+    }
+    backward.reverse();
+    (forward, backward)
+}
+
+/// `MODIFY COLUMN` statements applying each change, and the ones undoing them
+fn mysql_alter_columns(table: &str, drift: &[Drift<'_>]) -> (Vec<String>, Vec<String>) {
+    let modify = |name: &str, data_type: &str, nullable: bool| {
+        let null = if nullable { "NULL" } else { "NOT NULL" };
+        format!("ALTER TABLE {} MODIFY COLUMN {} {} {}", table, name, data_type, null)
+    };
+    let mut forward = Vec::new();
+    let mut backward = Vec::new();
+    for change in drift {
+        let column = change.column;
+        let data_type = change.data_type.as_deref().unwrap_or(&column.data_type);
+        forward.push(modify(&column.name, data_type, change.nullable.unwrap_or(column.nullable)));
+        backward.push(modify(&column.name, &column.data_type, column.nullable));
+    }
+    backward.reverse();
+    (forward, backward)
+}
+
+/// Whether the SQLite table was created with `AUTOINCREMENT`, which the
+/// column list doesn't report
+async fn sqlite_autoincrement(db: &impl Database, table: &str) -> Result<bool> {
+    let query = sqlx::query("SELECT sql FROM sqlite_master WHERE type = 'table' AND name = $1").bind(table.to_string());
+    let sql = db
+        .fetch_one(query)
+        .await?
+        .and_then(|row| row.try_get::<Option<String>, _>("sql").ok().flatten())
+        .unwrap_or_default();
+    Ok(sql.to_ascii_uppercase().contains("AUTOINCREMENT"))
+}
+
+/// Recreate a SQLite table with `columns`, which can't be changed in place,
+/// copying its rows and indexes across
+fn sqlite_rebuild(info: &TableInfo, columns: &[ColumnInfo], autoincrement: bool) -> Vec<String> {
+    let table = &info.name;
+    let temporary = format!("_oxidite_rebuild_{}", table);
+    let primary: Vec<&str> = columns.iter().filter(|c| c.primary_key).map(|c| c.name.as_str()).collect();
+
+    let mut definitions: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let mut sql = format!("{} {}", column.name, column.data_type);
+            if column.primary_key && primary.len() == 1 {
+                sql.push_str(" PRIMARY KEY");
+                if autoincrement {
+                    sql.push_str(" AUTOINCREMENT");
+                }
+            } else if !column.nullable {
+                sql.push_str(" NOT NULL");
+            }
+            if let Some(default) = &column.default {
+                sql.push_str(&format!(" DEFAULT {}", default));
+            }
+            sql
+        })
+        .collect();
+    if primary.len() > 1 {
+        definitions.push(format!("PRIMARY KEY ({})", primary.join(", ")));
+    }
+
+    let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+    let mut statements = vec![
+        format!("CREATE TABLE {} (\n    {}\n)", temporary, definitions.join(",\n    ")),
+        format!("INSERT INTO {} ({}) SELECT {} FROM {}", temporary, names.join(", "), names.join(", "), table),
+        format!("DROP TABLE {}", table),
+        format!("ALTER TABLE {} RENAME TO {}", temporary, table),
+    ];
+    for index in &info.indexes {
+        // Indexes SQLite made for UNIQUE constraints come back as plain unique indexes
+        let name = if index.name.starts_with("sqlite_autoindex_") {
+            let columns: Vec<&str> = index.columns.iter().map(String::as_str).collect();
+            IndexDefinition::new(table, &columns, index.unique).name
+        } else {
+            index.name.clone()
+        };
+        let unique = if index.unique { "UNIQUE " } else { "" };
+        statements.push(format!("CREATE {}INDEX {} ON {} ({})", unique, name, table, index.columns.join(", ")));
+    }
+    statements
+}
+
+fn zero_value(column_type: &ColumnType) -> Option<ColumnDefault> {
+    match column_type {
+        ColumnType::SmallInteger | ColumnType::Integer | ColumnType::BigInteger => Some(ColumnDefault::Integer(0)),
+        ColumnType::Float | ColumnType::Double | ColumnType::Decimal { .. } => Some(ColumnDefault::Float(0.0)),
+        ColumnType::Boolean => Some(ColumnDefault::Boolean(false)),
+        ColumnType::Char(_) | ColumnType::String(_) | ColumnType::Text => Some(ColumnDefault::String(String::new())),
+        _ => None,
+    }
+}
+
+fn readd_column_sql(table: &str, column: &ColumnInfo) -> String {
+    let mut sql = format!("ALTER TABLE {} ADD COLUMN {} {}", table, column.name, column.data_type);
+    if !column.nullable {
+        sql.push_str(" NOT NULL");
+    }
+    if let Some(default) = &column.default {
+        sql.push_str(&format!(" DEFAULT {}", default));
+    }
+    sql
+}
+
+/// Coarse type family, so `BIGINT`, `bigint` and `INTEGER` compare equal
+fn type_family(sql_type: &str) -> &'static str {
+    let t = sql_type.to_ascii_lowercase();
+    if t.starts_with("tinyint(1)") || t.contains("bool") {
+        "boolean"
+    } else if t.contains("int") || t.contains("serial") {
+        "integer"
+    } else if t.contains("char") || t.contains("text") || t.contains("clob") {
+        "text"
+    } else if t.contains("real") || t.contains("float") || t.contains("double") {
+        "float"
+    } else if t.contains("numeric") || t.contains("decimal") {
+        "decimal"
+    } else if t.contains("date") || t.contains("time") {
+        "temporal"
+    } else if t.contains("json") {
+        "json"
+    } else if t.contains("uuid") {
+        "uuid"
+    } else if t.contains("blob") || t.contains("bytea") || t.contains("binary") {
+        "binary"
+    } else {
+        "other"
+    }
+}
@@ -0,0 +1,287 @@
+//! Model factories
+//!
+//! A [`Factory`] describes a valid record filled with fake data. Its builder
+//! applies states, sets how many records to build and links related records:
+//!
+//! ```ignore
+//! #[derive(Default)]
+//! struct UserFactory;
+//!
+//! impl Factory<User> for UserFactory {
+//!     fn definition(&self, faker: &mut Faker, sequence: u64) -> User {
+//!         User {
+//!             id: 0,
+//!             name: faker.name(),
+//!             email: format!("user{}@example.com", sequence),
+//!             role: "member".into(),
+//!         }
+//!     }
+//! }
+//!
+//! // Named states live on the builder
+//! trait UserStates {
+//!     fn admin(self) -> Self;
+//! }
+//!
+//! impl<R: Related<User>> UserStates for FactoryBuilder<User, R> {
+//!     fn admin(self) -> Self {
+//!         self.state(|user, _| user.role = "admin".into())
+//!     }
+//! }
+//!
+//! let admin = UserFactory::builder().admin().create_one(&db).await?;
+//!
+//! // Three users, each with two posts
+//! let users = UserFactory::builder()
+//!     .count(3)
+//!     .has(PostFactory::builder().count(2), User::posts)
+//!     .create(&db)
+//!     .await?;
+//! let posts = users[0].posts().get(&db).await?;
+//!
+//! // A post with a fresh author
+//! let post = PostFactory::builder()
+//!     .for_parent(UserFactory::builder(), User::posts)
+//!     .create_one(&db)
+//!     .await?;
+//! ```
+//!
+//! Sequence numbers count up from 1 per model for the life of the process;
+//! [`reset_sequences`] starts them over.
+
+use crate::fake::Faker;
+use crate::{Database, ForeignKey, Model, Result};
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+static SEQUENCES: Lazy<Mutex<HashMap<TypeId, u64>>> = Lazy::new(Default::default);
+
+/// Builds records of `M` filled with fake data
+pub trait Factory<M: Model + 'static>: Send + Sync + 'static {
+    /// A valid, unsaved record; `sequence` is unique per model
+    fn definition(&self, faker: &mut Faker, sequence: u64) -> M;
+
+    /// A builder for this factory
+    fn builder() -> FactoryBuilder<M>
+    where
+        Self: Default + Sized,
+    {
+        FactoryBuilder::new(Self::default())
+    }
+}
+
+/// Start every model's sequence at 1 again
+pub fn reset_sequences() {
+    SEQUENCES.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+fn next_sequence<M: 'static>() -> u64 {
+    let mut sequences = SEQUENCES.lock().unwrap_or_else(|e| e.into_inner());
+    let sequence = sequences.entry(TypeId::of::<M>()).or_insert(0);
+    *sequence += 1;
+    *sequence
+}
+
+type State<M> = Arc<dyn Fn(&mut M, &mut Faker) + Send + Sync>;
+
+type Link<C, P> = Box<dyn Fn(&mut C, &P) -> Result<()> + Send + Sync>;
+
+/// Points a child at its parent by setting the foreign key `relation` names
+fn link<P: Model, C: Model, Rel: ForeignKey<P, C>>(relation: impl Fn(&P) -> Rel + Send + Sync + 'static) -> Link<C, P> {
+    Box::new(move |child, parent| {
+        let relation = relation(parent);
+        if child.set_foreign_key(relation.foreign_key(), relation.parent_id()) {
+            Ok(())
+        } else {
+            Err(sqlx::Error::Protocol(format!(
+                "{} has no integer column {} to link to {}",
+                C::table_name(),
+                relation.foreign_key(),
+                P::table_name()
+            )))
+        }
+    })
+}
+
+/// Records created along with each record of `M`
+///
+/// Implemented by the links [`FactoryBuilder::has`] and
+/// [`FactoryBuilder::for_parent`] add.
+#[async_trait]
+pub trait Related<M: Model>: Send + Sync {
+    /// Called with the records about to be inserted
+    async fn before_create<D: Database>(&self, _db: &D, _models: &mut [M]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after each record is inserted
+    async fn after_create<D: Database>(&self, _db: &D, _model: &M) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<M: Model> Related<M> for () {}
+
+#[async_trait]
+impl<M: Model, A: Related<M>, B: Related<M>> Related<M> for (A, B) {
+    async fn before_create<D: Database>(&self, db: &D, models: &mut [M]) -> Result<()> {
+        self.0.before_create(db, models).await?;
+        self.1.before_create(db, models).await
+    }
+
+    async fn after_create<D: Database>(&self, db: &D, model: &M) -> Result<()> {
+        self.0.after_create(db, model).await?;
+        self.1.after_create(db, model).await
+    }
+}
+
+/// Children created for each parent, see [`FactoryBuilder::has`]
+pub struct Children<P, C: Model + 'static, R> {
+    factory: FactoryBuilder<C, R>,
+    link: Link<C, P>,
+}
+
+#[async_trait]
+impl<P: Model, C: Model + 'static, R: Related<C>> Related<P> for Children<P, C, R> {
+    async fn after_create<D: Database>(&self, db: &D, parent: &P) -> Result<()> {
+        let mut children = self.factory.make();
+        for child in &mut children {
+            (self.link)(child, parent)?;
+        }
+        self.factory.insert(db, children).await?;
+        Ok(())
+    }
+}
+
+/// A parent created for the records, see [`FactoryBuilder::for_parent`]
+pub struct Parent<C, P: Model + 'static, R> {
+    factory: FactoryBuilder<P, R>,
+    link: Link<C, P>,
+}
+
+#[async_trait]
+impl<C: Model, P: Model + 'static, R: Related<P>> Related<C> for Parent<C, P, R> {
+    async fn before_create<D: Database>(&self, db: &D, models: &mut [C]) -> Result<()> {
+        let parent = self.factory.create_one(db).await?;
+        for model in models {
+            (self.link)(model, &parent)?;
+        }
+        Ok(())
+    }
+}
+
+/// Configures and runs a [`Factory`]
+pub struct FactoryBuilder<M: Model + 'static, R = ()> {
+    factory: Arc<dyn Factory<M>>,
+    states: Vec<State<M>>,
+    count: usize,
+    faker: Mutex<Faker>,
+    related: R,
+}
+
+impl<M: Model + 'static> FactoryBuilder<M> {
+    pub fn new(factory: impl Factory<M>) -> Self {
+        Self {
+            factory: Arc::new(factory),
+            states: Vec::new(),
+            count: 1,
+            faker: Mutex::new(Faker::new()),
+            related: (),
+        }
+    }
+}
+
+impl<M: Model + 'static, R: Related<M>> FactoryBuilder<M, R> {
+    /// Adjust each record after its definition is built; states apply in order
+    pub fn state(mut self, state: impl Fn(&mut M, &mut Faker) + Send + Sync + 'static) -> Self {
+        self.states.push(Arc::new(state));
+        self
+    }
+
+    /// How many records `make` and `create` build (default 1)
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Use a seeded [`Faker`] for repeatable data
+    pub fn seed(self, seed: u64) -> Self {
+        Self { faker: Mutex::new(Faker::seeded(seed)), ..self }
+    }
+
+    /// Create records of `children` for each record, after it is inserted
+    ///
+    /// `relation` is the parent's relation to them, e.g. `User::posts`; its
+    /// foreign key is set on each child.
+    pub fn has<C: Model + 'static, CR: Related<C>, Rel: ForeignKey<M, C>>(
+        self,
+        children: FactoryBuilder<C, CR>,
+        relation: impl Fn(&M) -> Rel + Send + Sync + 'static,
+    ) -> FactoryBuilder<M, (R, Children<M, C, CR>)> {
+        self.relate(Children { factory: children, link: link(relation) })
+    }
+
+    /// Create one record of `parent` and point every record at it
+    ///
+    /// `relation` is the parent's relation to these records, e.g. `User::posts`.
+    pub fn for_parent<P: Model + 'static, PR: Related<P>, Rel: ForeignKey<P, M>>(
+        self,
+        parent: FactoryBuilder<P, PR>,
+        relation: impl Fn(&P) -> Rel + Send + Sync + 'static,
+    ) -> FactoryBuilder<M, (R, Parent<M, P, PR>)> {
+        self.relate(Parent { factory: parent, link: link(relation) })
+    }
+
+    fn relate<T: Related<M>>(self, related: T) -> FactoryBuilder<M, (R, T)> {
+        FactoryBuilder {
+            factory: self.factory,
+            states: self.states,
+            count: self.count,
+            faker: self.faker,
+            related: (self.related, related),
+        }
+    }
+
+    /// Build the records without saving them or their relations
+    pub fn make(&self) -> Vec<M> {
+        let mut faker = self.faker.lock().unwrap_or_else(|e| e.into_inner());
+        (0..self.count).map(|_| self.build(&mut faker)).collect()
+    }
+
+    /// Build one record without saving it
+    pub fn make_one(&self) -> M {
+        let mut faker = self.faker.lock().unwrap_or_else(|e| e.into_inner());
+        self.build(&mut faker)
+    }
+
+    fn build(&self, faker: &mut Faker) -> M {
+        let mut model = self.factory.definition(faker, next_sequence::<M>());
+        for state in &self.states {
+            state(&mut model, faker);
+        }
+        model
+    }
+
+    /// Build, insert and return the records along with their relations
+    pub async fn create(&self, db: &impl Database) -> Result<Vec<M>> {
+        self.insert(db, self.make()).await
+    }
+
+    /// Build, insert and return one record along with its relations
+    pub async fn create_one(&self, db: &impl Database) -> Result<M> {
+        let mut models = self.insert(db, vec![self.make_one()]).await?;
+        Ok(models.remove(0))
+    }
+
+    async fn insert<D: Database>(&self, db: &D, mut models: Vec<M>) -> Result<Vec<M>> {
+        self.related.before_create(db, &mut models).await?;
+        for model in &mut models {
+            model.create(db).await?;
+            self.related.after_create(db, model).await?;
+        }
+        Ok(models)
+    }
+}
@@ -0,0 +1,149 @@
+//! Fake data for factories and seeders
+//!
+//! ```ignore
+//! let mut faker = Faker::seeded(42);
+//! let name = faker.name();            // "Grace Hopper"
+//! let email = faker.email();          // "grace.hopper17@example.com"
+//! let joined = faker.past_timestamp(365);
+//! ```
+//!
+//! A seeded `Faker` produces the same values on every run.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::ops::RangeInclusive;
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada", "Alan", "Barbara", "Brian", "Claude", "Dennis", "Donald", "Edsger", "Frances", "Grace",
+    "Guido", "Hedy", "John", "Ken", "Linus", "Margaret", "Niklaus", "Radia", "Robin", "Sophie",
+    "Tim", "Yukihiro",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Allen", "Backus", "Berners-Lee", "Dijkstra", "Hamilton", "Hopper", "Kernighan", "Knuth",
+    "Lamarr", "Liskov", "Lovelace", "Matsumoto", "McCarthy", "Milner", "Perlman", "Ritchie",
+    "Rossum", "Shannon", "Thompson", "Torvalds", "Turing", "Wilson", "Wirth",
+];
+
+const WORDS: &[&str] = &[
+    "alias", "anvil", "beacon", "binary", "cache", "canvas", "cipher", "comet", "delta", "echo",
+    "ember", "fable", "falcon", "garnet", "harbor", "helix", "island", "jasper", "kernel", "lantern",
+    "lumen", "meadow", "nebula", "orbit", "pixel", "quartz", "raven", "signal", "summit", "tundra",
+    "vector", "willow", "zenith",
+];
+
+const DOMAINS: &[&str] = &["example.com", "example.org", "example.net"];
+
+/// Random values that look like real data
+#[derive(Debug, Clone)]
+pub struct Faker {
+    rng: StdRng,
+}
+
+impl Faker {
+    /// A faker seeded from system entropy
+    pub fn new() -> Self {
+        Self { rng: StdRng::from_entropy() }
+    }
+
+    /// A faker that yields the same sequence of values for the same seed
+    pub fn seeded(seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed) }
+    }
+
+    pub fn first_name(&mut self) -> String {
+        self.pick(FIRST_NAMES).to_string()
+    }
+
+    pub fn last_name(&mut self) -> String {
+        self.pick(LAST_NAMES).to_string()
+    }
+
+    pub fn name(&mut self) -> String {
+        format!("{} {}", self.first_name(), self.last_name())
+    }
+
+    /// Lowercase `first.last` with a number, e.g. `ada.lovelace42`
+    pub fn username(&mut self) -> String {
+        let first = self.first_name().to_lowercase();
+        let last = self.last_name().to_lowercase().replace('-', "");
+        format!("{}.{}{}", first, last, self.number(1..=99))
+    }
+
+    /// An address at one of the reserved example domains
+    pub fn email(&mut self) -> String {
+        let domain = self.pick(DOMAINS);
+        format!("{}@{}", self.username(), domain)
+    }
+
+    pub fn word(&mut self) -> String {
+        self.pick(WORDS).to_string()
+    }
+
+    pub fn words(&mut self, count: usize) -> Vec<String> {
+        (0..count).map(|_| self.word()).collect()
+    }
+
+    /// A capitalised sentence of 4 to 12 words, ending in a period
+    pub fn sentence(&mut self) -> String {
+        let count = self.number(4..=12);
+        let mut sentence = self.words(count).join(" ");
+        sentence[..1].make_ascii_uppercase();
+        sentence.push('.');
+        sentence
+    }
+
+    /// 3 to 6 sentences
+    pub fn paragraph(&mut self) -> String {
+        let count = self.number(3..=6);
+        (0..count).map(|_| self.sentence()).collect::<Vec<_>>().join(" ")
+    }
+
+    pub fn number<T>(&mut self, range: RangeInclusive<T>) -> T
+    where
+        T: rand::distributions::uniform::SampleUniform + PartialOrd,
+    {
+        self.rng.gen_range(range)
+    }
+
+    pub fn boolean(&mut self) -> bool {
+        self.rng.gen()
+    }
+
+    /// One of `items`, which must not be empty
+    pub fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        *items.choose(&mut self.rng).expect("Faker::pick needs at least one item")
+    }
+
+    /// A moment in `[from, to]`
+    pub fn date_time_between(&mut self, from: DateTime<Utc>, to: DateTime<Utc>) -> DateTime<Utc> {
+        let (from, to) = (from.timestamp(), to.timestamp());
+        DateTime::from_timestamp(self.number(from.min(to)..=to.max(from)), 0).unwrap_or_default()
+    }
+
+    /// A day in `[from, to]`
+    pub fn date_between(&mut self, from: NaiveDate, to: NaiveDate) -> NaiveDate {
+        let days = (to - from).num_days();
+        from + Duration::days(self.number(days.min(0)..=days.max(0)))
+    }
+
+    /// Unix timestamp within the last `days` days
+    pub fn past_timestamp(&mut self, days: i64) -> i64 {
+        let now = Utc::now();
+        self.date_time_between(now - Duration::days(days), now).timestamp()
+    }
+
+    /// Unix timestamp within the next `days` days
+    pub fn future_timestamp(&mut self, days: i64) -> i64 {
+        let now = Utc::now();
+        self.date_time_between(now, now + Duration::days(days)).timestamp()
+    }
+}
+
+impl Default for Faker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,239 @@
+//! Query instrumentation
+//!
+//! Every statement run through a `DbPool` or `DbTransaction` is wrapped in a
+//! `db.query` tracing span carrying the SQL, bound parameter count, rows and
+//! duration. Statements slower than the pool's threshold are also logged at
+//! `WARN` under the `oxidite_db::slow_query` target, and listeners registered
+//! with `DbPool::on_query` see each one:
+//!
+//! ```ignore
+//! let db = DbPool::connect(&url)
+//!     .await?
+//!     .with_slow_query_threshold(Duration::from_millis(200))
+//!     .on_query(|event| metrics::histogram!("db.query", event.duration));
+//! ```
+//!
+//! Inside a [`context::scope`](crate::context::scope) the statements are also
+//! counted per request, see [`QueryMetrics`].
+
+use crate::{context, DatabaseType, Result};
+use sqlx::any::{AnyArguments, AnyQueryResult, AnyRow};
+use sqlx::query::Query;
+use sqlx::{Arguments, Execute};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::field::Empty;
+use tracing::Instrument;
+
+/// Queries taking longer than this are logged as slow unless the pool sets its own threshold
+pub const DEFAULT_SLOW_QUERY_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// A finished statement, as passed to `DbPool::on_query` listeners
+#[derive(Debug)]
+pub struct QueryEvent<'a> {
+    pub sql: &'a str,
+    /// Number of bound parameters
+    pub params: usize,
+    pub duration: Duration,
+    /// Rows affected by a write, or rows returned by a read
+    pub rows: u64,
+    /// Whether the statement crossed the slow query threshold
+    pub slow: bool,
+    pub error: Option<&'a sqlx::Error>,
+}
+
+pub(crate) type QueryListener = Arc<dyn Fn(&QueryEvent<'_>) + Send + Sync>;
+
+/// Slow query threshold and listeners shared by a pool and its transactions
+#[derive(Clone)]
+pub(crate) struct Instrumentation {
+    pub(crate) slow_threshold: Option<Duration>,
+    pub(crate) listeners: Vec<QueryListener>,
+}
+
+impl Default for Instrumentation {
+    fn default() -> Self {
+        Self {
+            slow_threshold: Some(DEFAULT_SLOW_QUERY_THRESHOLD),
+            listeners: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Debug for Instrumentation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instrumentation")
+            .field("slow_threshold", &self.slow_threshold)
+            .field("listeners", &self.listeners.len())
+            .finish()
+    }
+}
+
+/// Result types that report a row count
+pub(crate) trait RowCount {
+    fn row_count(&self) -> u64;
+}
+
+impl RowCount for u64 {
+    fn row_count(&self) -> u64 {
+        *self
+    }
+}
+
+impl RowCount for Vec<AnyRow> {
+    fn row_count(&self) -> u64 {
+        self.len() as u64
+    }
+}
+
+impl RowCount for AnyQueryResult {
+    fn row_count(&self) -> u64 {
+        self.rows_affected()
+    }
+}
+
+impl RowCount for Option<AnyRow> {
+    fn row_count(&self) -> u64 {
+        self.is_some() as u64
+    }
+}
+
+/// SQL text and parameter count of a query, returning an equivalent query to run
+pub(crate) fn inspect<'q>(
+    mut query: Query<'q, sqlx::Any, AnyArguments<'q>>,
+) -> Result<(&'q str, usize, Query<'q, sqlx::Any, AnyArguments<'q>>)> {
+    let sql = query.sql();
+    let arguments = query.take_arguments().map_err(sqlx::Error::Encode)?;
+    Ok(match arguments {
+        Some(arguments) => (sql, arguments.len(), sqlx::query_with(sql, arguments)),
+        None => (sql, 0, sqlx::query(sql)),
+    })
+}
+
+/// Run `statement`, recording it as `sql` with `params` bound values
+pub(crate) async fn observe<T: RowCount>(
+    instrumentation: &Instrumentation,
+    db_type: DatabaseType,
+    sql: &str,
+    params: usize,
+    statement: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let span = tracing::debug_span!(
+        "db.query",
+        db.system = ?db_type,
+        db.statement = sql,
+        db.params = params,
+        db.rows = Empty,
+        db.duration_ms = Empty,
+    );
+
+    let started = Instant::now();
+    let result = statement.instrument(span.clone()).await;
+    let duration = started.elapsed();
+    let rows = result.as_ref().map_or(0, RowCount::row_count);
+    let duration_ms = duration.as_secs_f64() * 1000.0;
+    let slow = instrumentation.slow_threshold.is_some_and(|threshold| duration >= threshold);
+
+    span.record("db.rows", rows);
+    span.record("db.duration_ms", duration_ms);
+    span.in_scope(|| match &result {
+        Ok(_) => tracing::debug!(rows, duration_ms, "query"),
+        Err(error) => tracing::debug!(%error, duration_ms, "query failed"),
+    });
+    if slow {
+        tracing::warn!(target: "oxidite_db::slow_query", duration_ms, params, sql, "slow query");
+    }
+
+    context::record_query(sql, duration);
+
+    let event = QueryEvent {
+        sql,
+        params,
+        duration,
+        rows,
+        slow,
+        error: result.as_ref().err(),
+    };
+    for listener in &instrumentation.listeners {
+        listener(&event);
+    }
+
+    result
+}
+
+/// Queries run during one request
+#[derive(Debug, Clone, Default)]
+pub struct QueryMetrics {
+    pub count: usize,
+    pub total_time: Duration,
+    /// Executions per statement fingerprint
+    pub statements: HashMap<String, usize>,
+}
+
+impl QueryMetrics {
+    pub(crate) fn record(&mut self, sql: &str, duration: Duration) {
+        self.count += 1;
+        self.total_time += duration;
+        *self.statements.entry(fingerprint(sql)).or_default() += 1;
+    }
+
+    /// Statements run at least `min` times, most frequent first; a likely N+1
+    pub fn repeated(&self, min: usize) -> Vec<(&str, usize)> {
+        let mut repeated: Vec<(&str, usize)> = self
+            .statements
+            .iter()
+            .filter(|(_, count)| **count >= min)
+            .map(|(sql, count)| (sql.as_str(), *count))
+            .collect();
+        repeated.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        repeated
+    }
+}
+
+/// `sql` with literals replaced by `?` and whitespace collapsed, so
+/// `WHERE id = 1` and `WHERE id = 2` count as the same statement
+pub fn fingerprint(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    // Whether the previous character can continue an identifier or `$n` placeholder
+    let mut in_word = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                while let Some(c) = chars.next() {
+                    if c == '\'' {
+                        if chars.peek() == Some(&'\'') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                out.push('?');
+                in_word = false;
+            }
+            c if c.is_ascii_digit() && !in_word => {
+                while chars.peek().is_some_and(|c| c.is_ascii_digit() || *c == '.') {
+                    chars.next();
+                }
+                out.push('?');
+            }
+            c if c.is_whitespace() => {
+                if !out.is_empty() && !out.ends_with(' ') {
+                    out.push(' ');
+                }
+                in_word = false;
+            }
+            c => {
+                out.push(c);
+                in_word = c.is_alphanumeric() || c == '_' || c == '$';
+            }
+        }
+    }
+
+    out.trim_end().to_string()
+}
@@ -0,0 +1,269 @@
+//! Live schema introspection
+//!
+//! Reads tables, columns and indexes from the connected database so they can
+//! be compared with model definitions (see [`crate::diff`]).
+
+use crate::{Database, DatabaseType, Result};
+use sqlx::any::AnyRow;
+use sqlx::Row;
+
+/// A table as it exists in the database
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableInfo {
+    pub name: String,
+    pub columns: Vec<ColumnInfo>,
+    pub indexes: Vec<IndexInfo>,
+}
+
+impl TableInfo {
+    pub fn column(&self, name: &str) -> Option<&ColumnInfo> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+}
+
+/// A column as reported by the database
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnInfo {
+    pub name: String,
+    /// Type as the database spells it, e.g. `VARCHAR(255)`, `character varying(255)` or `bigint`
+    pub data_type: String,
+    pub nullable: bool,
+    /// Default expression, unparsed
+    pub default: Option<String>,
+    pub primary_key: bool,
+}
+
+/// A secondary index (primary keys are reported on the columns)
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexInfo {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+}
+
+/// Names of all user tables in the current database/schema
+pub async fn list_tables(db: &impl Database) -> Result<Vec<String>> {
+    let sql = match db.db_type() {
+        DatabaseType::Sqlite => {
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+        }
+        DatabaseType::Postgres => {
+            "SELECT table_name::text AS name FROM information_schema.tables \
+             WHERE table_schema = current_schema() AND table_type = 'BASE TABLE' ORDER BY table_name"
+        }
+        DatabaseType::MySql => {
+            "SELECT CAST(TABLE_NAME AS CHAR) AS name FROM information_schema.TABLES \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_TYPE = 'BASE TABLE' ORDER BY TABLE_NAME"
+        }
+    };
+
+    let rows = db.query(sql).await?;
+    Ok(rows.iter().filter_map(|row| row.try_get::<String, _>("name").ok()).collect())
+}
+
+/// Columns and indexes of a table, or `None` if it doesn't exist
+pub async fn table_info(db: &impl Database, table: &str) -> Result<Option<TableInfo>> {
+    let (columns, indexes) = match db.db_type() {
+        DatabaseType::Sqlite => (sqlite_columns(db, table).await?, sqlite_indexes(db, table).await?),
+        DatabaseType::Postgres => postgres_table(db, table).await?,
+        DatabaseType::MySql => (mysql_columns(db, table).await?, mysql_indexes(db, table).await?),
+    };
+
+    if columns.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(TableInfo {
+        name: table.to_string(),
+        columns,
+        indexes,
+    }))
+}
+
+/// Boolean columns come back as `BOOLEAN` on Postgres and as integers elsewhere
+fn flag(row: &AnyRow, column: &str) -> bool {
+    row.try_get::<bool, _>(column)
+        .or_else(|_| row.try_get::<i64, _>(column).map(|n| n != 0))
+        .or_else(|_| row.try_get::<i32, _>(column).map(|n| n != 0))
+        .unwrap_or(false)
+}
+
+fn text(row: &AnyRow, column: &str) -> Option<String> {
+    row.try_get::<Option<String>, _>(column).ok().flatten()
+}
+
+/// Fold `(index, unique, column)` rows, ordered by index, into `IndexInfo`s
+fn group_indexes(rows: impl IntoIterator<Item = (String, bool, String)>) -> Vec<IndexInfo> {
+    let mut indexes: Vec<IndexInfo> = Vec::new();
+    for (name, unique, column) in rows {
+        match indexes.last_mut() {
+            Some(index) if index.name == name => index.columns.push(column),
+            _ => indexes.push(IndexInfo { name, columns: vec![column], unique }),
+        }
+    }
+    indexes
+}
+
+async fn fetch(db: &impl Database, sql: &str, table: &str) -> Result<Vec<AnyRow>> {
+    db.fetch_all(sqlx::query(sql).bind(table.to_string())).await
+}
+
+async fn sqlite_columns(db: &impl Database, table: &str) -> Result<Vec<ColumnInfo>> {
+    let rows = fetch(
+        db,
+        "SELECT name, type, \"notnull\" AS not_null, dflt_value, pk FROM pragma_table_info($1) ORDER BY cid",
+        table,
+    )
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let primary_key = row.try_get::<i64, _>("pk").unwrap_or(0) > 0;
+            ColumnInfo {
+                name: text(row, "name").unwrap_or_default(),
+                data_type: text(row, "type").unwrap_or_default(),
+                // SQLite reports INTEGER PRIMARY KEY columns as nullable
+                nullable: !flag(row, "not_null") && !primary_key,
+                default: text(row, "dflt_value"),
+                primary_key,
+            }
+        })
+        .collect())
+}
+
+async fn sqlite_indexes(db: &impl Database, table: &str) -> Result<Vec<IndexInfo>> {
+    let rows = fetch(
+        db,
+        "SELECT name, \"unique\" AS is_unique, origin FROM pragma_index_list($1) ORDER BY name",
+        table,
+    )
+    .await?;
+
+    let mut indexes = Vec::new();
+    for row in rows {
+        if text(&row, "origin").as_deref() == Some("pk") {
+            continue;
+        }
+        let name = text(&row, "name").unwrap_or_default();
+        let columns = fetch(db, "SELECT name FROM pragma_index_info($1) ORDER BY seqno", &name)
+            .await?
+            .iter()
+            .filter_map(|r| text(r, "name"))
+            .collect();
+        indexes.push(IndexInfo {
+            name,
+            columns,
+            unique: flag(&row, "is_unique"),
+        });
+    }
+    Ok(indexes)
+}
+
+async fn postgres_table(db: &impl Database, table: &str) -> Result<(Vec<ColumnInfo>, Vec<IndexInfo>)> {
+    let rows = fetch(
+        db,
+        "SELECT a.attname::text AS name, format_type(a.atttypid, a.atttypmod) AS data_type, \
+                NOT a.attnotnull AS nullable, pg_get_expr(d.adbin, d.adrelid) AS default_value \
+         FROM pg_attribute a \
+         JOIN pg_class c ON c.oid = a.attrelid \
+         JOIN pg_namespace n ON n.oid = c.relnamespace \
+         LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum \
+         WHERE c.relname = $1 AND c.relkind = 'r' AND n.nspname = current_schema() \
+           AND a.attnum > 0 AND NOT a.attisdropped \
+         ORDER BY a.attnum",
+        table,
+    )
+    .await?;
+
+    let index_rows = fetch(
+        db,
+        "SELECT i.relname::text AS index_name, ix.indisunique AS is_unique, ix.indisprimary AS is_primary, \
+                a.attname::text AS column_name \
+         FROM pg_index ix \
+         JOIN pg_class t ON t.oid = ix.indrelid \
+         JOIN pg_class i ON i.oid = ix.indexrelid \
+         JOIN pg_namespace n ON n.oid = t.relnamespace \
+         JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = ANY(ix.indkey) \
+         WHERE t.relname = $1 AND n.nspname = current_schema() \
+         ORDER BY i.relname, array_position(ix.indkey::int2[], a.attnum)",
+        table,
+    )
+    .await?;
+
+    let primary: Vec<String> = index_rows
+        .iter()
+        .filter(|row| flag(row, "is_primary"))
+        .filter_map(|row| text(row, "column_name"))
+        .collect();
+
+    let columns = rows
+        .iter()
+        .map(|row| {
+            let name = text(row, "name").unwrap_or_default();
+            ColumnInfo {
+                primary_key: primary.contains(&name),
+                name,
+                data_type: text(row, "data_type").unwrap_or_default(),
+                nullable: flag(row, "nullable"),
+                default: text(row, "default_value"),
+            }
+        })
+        .collect();
+
+    let indexes = group_indexes(index_rows.iter().filter(|row| !flag(row, "is_primary")).map(|row| {
+        (
+            text(row, "index_name").unwrap_or_default(),
+            flag(row, "is_unique"),
+            text(row, "column_name").unwrap_or_default(),
+        )
+    }));
+
+    Ok((columns, indexes))
+}
+
+async fn mysql_columns(db: &impl Database, table: &str) -> Result<Vec<ColumnInfo>> {
+    let rows = fetch(
+        db,
+        "SELECT CAST(COLUMN_NAME AS CHAR) AS name, CAST(COLUMN_TYPE AS CHAR) AS data_type, \
+                IS_NULLABLE = 'YES' AS nullable, CAST(COLUMN_DEFAULT AS CHAR) AS default_value, \
+                COLUMN_KEY = 'PRI' AS primary_key \
+         FROM information_schema.COLUMNS \
+         WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+         ORDER BY ORDINAL_POSITION",
+        table,
+    )
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| ColumnInfo {
+            name: text(row, "name").unwrap_or_default(),
+            data_type: text(row, "data_type").unwrap_or_default(),
+            nullable: flag(row, "nullable"),
+            default: text(row, "default_value"),
+            primary_key: flag(row, "primary_key"),
+        })
+        .collect())
+}
+
+async fn mysql_indexes(db: &impl Database, table: &str) -> Result<Vec<IndexInfo>> {
+    let rows = fetch(
+        db,
+        "SELECT CAST(INDEX_NAME AS CHAR) AS index_name, NON_UNIQUE = 0 AS is_unique, \
+                CAST(COLUMN_NAME AS CHAR) AS column_name \
+         FROM information_schema.STATISTICS \
+         WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND INDEX_NAME <> 'PRIMARY' \
+         ORDER BY INDEX_NAME, SEQ_IN_INDEX",
+        table,
+    )
+    .await?;
+
+    Ok(group_indexes(rows.iter().map(|row| {
+        (
+            text(row, "index_name").unwrap_or_default(),
+            flag(row, "is_unique"),
+            text(row, "column_name").unwrap_or_default(),
+        )
+    })))
+}
@@ -0,0 +1,47 @@
+//! Validation failures from `Model::save`
+//!
+//! ```ignore
+//! if let Err(err) = user.save(&db).await {
+//!     // A 422 with the per-field messages, or a 500 for a database failure
+//!     return Err(oxidite_db::http_error(err));
+//! }
+//! ```
+
+use crate::validation::ValidationErrors;
+use std::fmt;
+
+/// The model failed its `#[validate(...)]` rules, so nothing was written
+///
+/// Returned inside `sqlx::Error::Database`; use [`InvalidModel::find`] to detect it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidModel {
+    pub table: &'static str,
+    pub errors: ValidationErrors,
+    message: String,
+}
+
+impl InvalidModel {
+    pub fn new(table: &'static str, errors: ValidationErrors) -> Self {
+        let message = format!("{} is invalid: {}", table, errors);
+        Self { table, errors, message }
+    }
+
+    /// The `InvalidModel` carried by `err`, if it is one
+    pub fn find(err: &sqlx::Error) -> Option<&InvalidModel> {
+        err.as_database_error()?.try_downcast_ref::<InvalidModel>()
+    }
+}
+
+impl fmt::Display for InvalidModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+database_error!(InvalidModel);
+
+impl From<InvalidModel> for oxidite_core::Error {
+    fn from(err: InvalidModel) -> Self {
+        oxidite_core::Error::Validation(err.errors)
+    }
+}
@@ -0,0 +1,208 @@
+//! JSON columns
+//!
+//! Wrap a field in [`Json`] to store any serde type as JSON: `TEXT` on SQLite,
+//! `JSONB` on Postgres and `JSON` on MySQL (`t.json("settings")` in a schema
+//! migration creates the right one):
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize, Default)]
+//! pub struct Settings {
+//!     pub theme: String,
+//!     pub notifications: bool,
+//! }
+//!
+//! #[derive(Model, sqlx::FromRow)]
+//! pub struct User {
+//!     pub id: i64,
+//!     pub settings: Json<Settings>,
+//! }
+//!
+//! user.settings.theme = "dark".into();
+//! user.update(&db).await?;
+//!
+//! let dark = User::query().where_json("settings->theme", "dark").get::<User>(&db).await?;
+//! ```
+//!
+//! The sqlx `Any` driver can't decode Postgres `JSONB` or MySQL `JSON`
+//! values, so the derived reads select JSON columns as text there, and
+//! Postgres writes cast the bound text to `JSONB`.
+
+use crate::bulk::{Params, SqlValue};
+use crate::DatabaseType;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::any::{AnyTypeInfo, AnyValueRef};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::{Any, Decode, Encode, Type};
+use std::ops::{Deref, DerefMut};
+
+/// A column holding `T` serialized as JSON
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Json<T>(pub T);
+
+impl<T> Json<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Json<T> {
+    fn from(value: T) -> Self {
+        Json(value)
+    }
+}
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> Type<Any> for Json<T> {
+    fn type_info() -> AnyTypeInfo {
+        <String as Type<Any>>::type_info()
+    }
+
+    fn compatible(ty: &AnyTypeInfo) -> bool {
+        <String as Type<Any>>::compatible(ty)
+    }
+}
+
+impl<'q, T: Serialize> Encode<'q, Any> for Json<T> {
+    fn encode_by_ref(&self, buf: &mut <Any as sqlx::Database>::ArgumentBuffer<'q>) -> Result<IsNull, BoxDynError> {
+        <String as Encode<'q, Any>>::encode(serde_json::to_string(&self.0)?, buf)
+    }
+}
+
+impl<'r, T: DeserializeOwned> Decode<'r, Any> for Json<T> {
+    fn decode(value: AnyValueRef<'r>) -> Result<Self, BoxDynError> {
+        let text = <String as Decode<'r, Any>>::decode(value)?;
+        Ok(Json(serde_json::from_str(&text)?))
+    }
+}
+
+impl<T: Serialize> From<Json<T>> for SqlValue {
+    fn from(value: Json<T>) -> Self {
+        match serde_json::to_string(&value.0) {
+            Ok(text) => SqlValue::Text(text),
+            Err(_) => SqlValue::Null,
+        }
+    }
+}
+
+/// Cast appended to the placeholder bound into a JSON column
+#[doc(hidden)]
+pub fn cast(db_type: DatabaseType, is_json: bool) -> &'static str {
+    match db_type {
+        DatabaseType::Postgres if is_json => "::jsonb",
+        _ => "",
+    }
+}
+
+/// `column` in a select list, read as text if it holds JSON the driver can't decode
+pub(crate) fn select_column(db_type: DatabaseType, column: &str, is_json: bool) -> String {
+    match db_type {
+        DatabaseType::Postgres if is_json => format!("{}::text AS {}", column, column),
+        DatabaseType::MySql if is_json => format!("CAST({} AS CHAR) AS {}", column, column),
+        _ => column.to_string(),
+    }
+}
+
+/// Select list for `fields`, or `*` when no column needs reading as text
+pub(crate) fn select_list(db_type: DatabaseType, fields: &[&str], json_columns: &[&str]) -> String {
+    if json_columns.is_empty() || db_type == DatabaseType::Sqlite {
+        return "*".to_string();
+    }
+    fields
+        .iter()
+        .map(|field| select_column(db_type, field, json_columns.contains(field)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// One step of a JSON path: an object key or an array index
+enum Step<'a> {
+    Key(&'a str),
+    Index(&'a str),
+}
+
+/// Split `column->key->0` into the column and its steps
+fn parse_path(path: &str) -> Option<(&str, Vec<Step<'_>>)> {
+    let mut parts = path.split("->").map(str::trim);
+    let column = parts.next()?;
+    let steps = parts
+        .map(|part| {
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-') {
+                None
+            } else if part.bytes().all(|b| b.is_ascii_digit()) {
+                Some(Step::Index(part))
+            } else {
+                Some(Step::Key(part))
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let valid_column = !column.is_empty() && column.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'.');
+    (valid_column && !steps.is_empty()).then_some((column, steps))
+}
+
+/// `path = value` for [`QueryBuilder::where_json`](crate::QueryBuilder::where_json),
+/// with the value bound through `params`
+///
+/// An invalid path, or a list value, gives a condition that matches no rows.
+pub(crate) fn path_condition(params: &mut Params, path: &str, value: &SqlValue) -> String {
+    let Some((column, steps)) = parse_path(path).filter(|_| !matches!(value, SqlValue::List(_))) else {
+        return "1 = 0".to_string();
+    };
+    let db_type = params.db_type;
+
+    if db_type == DatabaseType::Postgres {
+        let keys: Vec<&str> = steps
+            .iter()
+            .map(|step| match step {
+                Step::Key(key) | Step::Index(key) => *key,
+            })
+            .collect();
+        let path = format!("'{{{}}}'", keys.join(","));
+        return match value {
+            SqlValue::Null => format!("{} #>> {} IS NULL", column, path),
+            SqlValue::Bool(b) => format!("{} #> {} = {}::jsonb", column, path, params.push(b.to_string().into())),
+            SqlValue::Int(_) | SqlValue::Float(_) => {
+                format!("({} #>> {})::numeric = {}", column, path, params.push(value.clone()))
+            }
+            SqlValue::Text(_) | SqlValue::List(_) => format!("{} #>> {} = {}", column, path, params.push(value.clone())),
+        };
+    }
+
+    let path: String = steps
+        .iter()
+        .map(|step| match step {
+            Step::Key(key) => format!(".\"{}\"", key),
+            Step::Index(index) => format!("[{}]", index),
+        })
+        .collect();
+    let extract = format!("JSON_EXTRACT({}, '${}')", column, path);
+
+    match (db_type, value) {
+        (DatabaseType::MySql, SqlValue::Null) => format!("COALESCE(JSON_TYPE({}), 'NULL') = 'NULL'", extract),
+        (DatabaseType::MySql, SqlValue::Bool(b)) => {
+            format!("{} = CAST({} AS JSON)", extract, params.push(b.to_string().into()))
+        }
+        (DatabaseType::MySql, SqlValue::Text(_)) => format!("JSON_UNQUOTE({}) = {}", extract, params.push(value.clone())),
+        (_, SqlValue::Null) => format!("{} IS NULL", extract),
+        // SQLite extracts JSON booleans as 1 and 0
+        (_, SqlValue::Bool(b)) => format!("{} = {}", extract, params.push((*b as i64).into())),
+        (_, SqlValue::Int(_) | SqlValue::Float(_) | SqlValue::Text(_) | SqlValue::List(_)) => {
+            format!("{} = {}", extract, params.push(value.clone()))
+        }
+    }
+}
@@ -1,19 +1,126 @@
-use sqlx::{any::{AnyPoolOptions, AnyRow}, AnyPool, Transaction};
+use sqlx::{any::{AnyConnectOptions, AnyPoolOptions, AnyRow}, AnyPool, Row, Transaction};
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub use sqlx;
 
+/// Implement `DatabaseError` for an error type of this crate with a
+/// `message` field, so it can be returned inside `sqlx::Error::Database`
+/// and found again with `try_downcast_ref`
+macro_rules! database_error {
+    ($error:ty) => {
+        impl std::error::Error for $error {}
+
+        impl sqlx::error::DatabaseError for $error {
+            fn message(&self) -> &str {
+                &self.message
+            }
+
+            fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+                self
+            }
+
+            fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+                self
+            }
+
+            fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+                self
+            }
+
+            // Raised before or instead of a write, never by the database itself
+            fn kind(&self) -> sqlx::error::ErrorKind {
+                sqlx::error::ErrorKind::Other
+            }
+        }
+    };
+}
+
 pub mod migrations;
-pub use migrations::{Migration, MigrationManager};
+pub use migrations::{Migration, MigrationError, MigrationManager};
+
+pub mod schema;
+pub use schema::{Blueprint, Schema, SchemaError};
+
+pub mod introspect;
+pub mod diff;
+pub use diff::{DiffOptions, FieldMeta, ModelSchema, SchemaDiff};
 
 pub mod relations;
-pub use relations::{HasMany, HasOne, BelongsTo};
+pub use relations::{HasMany, HasOne, BelongsTo, ForeignKey};
+
+pub mod observers;
+pub use observers::Observer;
+
+pub mod transaction;
+pub use transaction::RetryPolicy;
+
+pub mod bulk;
+pub use bulk::SqlValue;
+
+pub mod context;
+pub use context::DbContextLayer;
+
+pub mod connections;
+
+pub mod instrument;
+pub use instrument::{QueryEvent, QueryMetrics};
+use instrument::Instrumentation;
+
+pub mod locking;
+pub use locking::StaleObject;
+
+pub mod invalid;
+pub use invalid::InvalidModel;
+
+pub mod tracking;
+pub use tracking::{FieldChange, Original};
+
+pub mod audit;
+pub use audit::Audit;
+
+pub mod tenancy;
+pub use tenancy::{Tenant, TenantLayer, TenantResolver};
+
+pub mod fake;
+pub use fake::Faker;
+
+pub mod factory;
+pub use factory::{Factory, FactoryBuilder};
+
+pub mod seeding;
+pub use seeding::{Seeder, Seeders};
+
+pub mod json;
+pub use json::Json;
 
 pub type Result<T> = std::result::Result<T, sqlx::Error>;
 
+/// Convert a database error for a handler: `StaleObject` becomes 409 Conflict,
+/// `InvalidModel` 422 Unprocessable Entity, a missing row 404 Not Found,
+/// anything else a 500
+pub fn http_error(err: sqlx::Error) -> oxidite_core::Error {
+    if let Some(stale) = StaleObject::find(&err) {
+        return stale.clone().into();
+    }
+    if let Some(invalid) = InvalidModel::find(&err) {
+        return invalid.clone().into();
+    }
+    match err {
+        sqlx::Error::RowNotFound => oxidite_core::Error::NotFound,
+        err => oxidite_core::Error::Server(err.to_string()),
+    }
+}
+
 pub use oxidite_macros::Model;
+pub use oxidite_core::validation;
+pub use validation::ValidationErrors;
+pub use oxidite_core::pagination;
+pub use pagination::{CursorPage, Page, Pagination};
 pub use async_trait::async_trait;
 pub use chrono;
+pub use serde_json;
 pub use regex;
 pub use once_cell;
 
@@ -25,6 +132,28 @@ pub enum DatabaseType {
     Sqlite,
 }
 
+impl DatabaseType {
+    /// Bind parameter placeholder for the 1-based `index` (`$1` or `?`)
+    pub fn placeholder(&self, index: usize) -> String {
+        match self {
+            DatabaseType::MySql => "?".to_string(),
+            DatabaseType::Postgres | DatabaseType::Sqlite => format!("${}", index),
+        }
+    }
+
+    /// Whether DDL statements can be rolled back inside a transaction
+    pub fn supports_transactional_ddl(&self) -> bool {
+        !matches!(self, DatabaseType::MySql)
+    }
+
+    /// Whether `INSERT ... RETURNING` is available
+    ///
+    /// MySQL reports generated ids on the query result instead.
+    pub fn supports_returning(&self) -> bool {
+        !matches!(self, DatabaseType::MySql)
+    }
+}
+
 /// Connection pool configuration
 #[derive(Debug, Clone)]
 pub struct PoolOptions {
@@ -32,6 +161,8 @@ pub struct PoolOptions {
     pub min_connections: u32,
     pub connect_timeout: std::time::Duration,
     pub idle_timeout: Option<std::time::Duration>,
+    /// Switch Postgres `search_path` to the current tenant's schema on each checkout
+    pub tenant_schemas: bool,
 }
 
 impl Default for PoolOptions {
@@ -41,6 +172,7 @@ impl Default for PoolOptions {
             min_connections: 0,
             connect_timeout: std::time::Duration::from_secs(30),
             idle_timeout: Some(std::time::Duration::from_secs(600)), // 10 minutes
+            tenant_schemas: false,
         }
     }
 }
@@ -74,13 +206,93 @@ pub trait Database: Send + Sync + Debug {
 
     /// Fetch one from a sqlx Query
     async fn fetch_one<'q>(&self, query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<Option<AnyRow>>;
+
+    /// Pool registered under `name` with `DbPool::with_connection`, if any
+    fn named_connection(&self, _name: &str) -> Option<DbPool> {
+        None
+    }
+
+    /// This handle's primary without replicas, if it would send reads to a replica
+    fn primary_pool(&self) -> Option<DbPool> {
+        None
+    }
+
+    /// Options for opening a connection to the primary outside any pool, for
+    /// session state that must outlive one statement, such as the migration lock
+    fn connect_options(&self) -> Option<Arc<AnyConnectOptions>> {
+        None
+    }
+
+    /// Run an `INSERT`, returning the id of the new row where the driver reports it
+    ///
+    /// Where [`DatabaseType::supports_returning`] holds the statement must end in
+    /// `RETURNING id`; MySQL reports the id without it.
+    async fn execute_insert<'q>(&self, query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<Option<i64>> {
+        self.execute_query(query).await?;
+        Ok(None)
+    }
+
+    /// Run `f` in a transaction, committing on `Ok` and rolling back on `Err` or panic
+    ///
+    /// On a `DbTransaction` this nests the work in a savepoint.
+    ///
+    /// ```ignore
+    /// let user = db.transaction(|tx| async move {
+    ///     user.create(&tx).await?;
+    ///     profile.create(&tx).await?;
+    ///     Ok::<_, sqlx::Error>(user)
+    /// }).await?;
+    /// ```
+    async fn transaction<F, Fut, T, E>(&self, f: F) -> std::result::Result<T, E>
+    where
+        Self: Sized,
+        F: FnOnce(DbTransaction) -> Fut + Send,
+        Fut: std::future::Future<Output = std::result::Result<T, E>> + Send,
+        T: Send,
+        E: From<sqlx::Error> + Send,
+    {
+        let tx = self.begin_transaction().await?;
+        transaction::run(tx, f).await
+    }
+
+    /// Like `transaction`, but retries the whole closure on serialization failures
+    /// and deadlocks (Postgres and MySQL, top-level transactions only)
+    async fn transaction_with_retry<F, Fut, T>(&self, policy: RetryPolicy, mut f: F) -> Result<T>
+    where
+        Self: Sized,
+        F: FnMut(DbTransaction) -> Fut + Send,
+        Fut: std::future::Future<Output = Result<T>> + Send,
+        T: Send,
+    {
+        let mut attempt = 1;
+        loop {
+            let tx = self.begin_transaction().await?;
+            let retryable = !tx.is_nested() && attempt < policy.max_attempts;
+
+            match transaction::run(tx, &mut f).await {
+                Err(err) if retryable && transaction::is_retryable(&err, self.db_type()) => {
+                    tokio::time::sleep(policy.delay_for(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
 }
 
 /// Database connection pool wrapper
+///
+/// Optionally routes reads to replicas and carries other named pools, see
+/// [`connections`].
 #[derive(Clone, Debug)]
 pub struct DbPool {
     pool: AnyPool,
     db_type: DatabaseType,
+    replicas: Arc<Vec<AnyPool>>,
+    next_replica: Arc<AtomicUsize>,
+    connections: Arc<HashMap<String, DbPool>>,
+    instrumentation: Arc<Instrumentation>,
+    tenant_schemas: bool,
 }
 
 impl DbPool {
@@ -101,8 +313,6 @@ impl DbPool {
             pool_options = pool_options.idle_timeout(idle_timeout);
         }
         
-        let pool = pool_options.connect(url).await?;
-        
         let db_type = if url.starts_with("postgres://") || url.starts_with("postgresql://") {
             DatabaseType::Postgres
         } else if url.starts_with("mysql://") {
@@ -118,7 +328,93 @@ impl DbPool {
             DatabaseType::Sqlite 
         };
 
-        Ok(Self { pool, db_type })
+        let tenant_schemas = options.tenant_schemas && db_type == DatabaseType::Postgres;
+        if tenant_schemas {
+            pool_options = pool_options
+                .after_connect(|conn, _| Box::pin(tenancy::set_search_path(conn)))
+                .before_acquire(|conn, _| Box::pin(async move { tenancy::set_search_path(conn).await.map(|_| true) }));
+        }
+
+        let pool = pool_options.connect(url).await?;
+
+        Ok(Self {
+            pool,
+            db_type,
+            replicas: Arc::default(),
+            next_replica: Arc::default(),
+            connections: Arc::default(),
+            instrumentation: Arc::default(),
+            tenant_schemas,
+        })
+    }
+
+    /// Connect to a primary and its read replicas with the same options
+    pub async fn connect_with_replicas(primary_url: &str, replica_urls: &[&str], options: PoolOptions) -> Result<Self> {
+        let mut db = Self::connect_with_options(primary_url, options.clone()).await?;
+        for url in replica_urls {
+            db = db.with_replica(Self::connect_with_options(url, options.clone()).await?);
+        }
+        Ok(db)
+    }
+
+    /// Add a read replica; read-only statements are spread across replicas round-robin
+    pub fn with_replica(mut self, replica: DbPool) -> Self {
+        Arc::make_mut(&mut self.replicas).push(replica.pool);
+        self
+    }
+
+    /// Register another pool under `name`, for models with `#[model(connection = "...")]`
+    pub fn with_connection(mut self, name: impl Into<String>, mut pool: DbPool) -> Self {
+        // Named pools don't resolve further names, so routing always ends
+        pool.connections = Arc::default();
+        Arc::make_mut(&mut self.connections).insert(name.into(), pool);
+        self
+    }
+
+    /// The pool registered under `name`
+    pub fn connection(&self, name: &str) -> Option<&DbPool> {
+        self.connections.get(name)
+    }
+
+    /// Log statements taking at least `threshold` as slow (`None` turns this off)
+    ///
+    /// Defaults to [`instrument::DEFAULT_SLOW_QUERY_THRESHOLD`]. Applies to
+    /// transactions begun after the call.
+    pub fn with_slow_query_threshold(mut self, threshold: impl Into<Option<std::time::Duration>>) -> Self {
+        Arc::make_mut(&mut self.instrumentation).slow_threshold = threshold.into();
+        self
+    }
+
+    /// Call `listener` after every statement run on this pool or its transactions
+    pub fn on_query(mut self, listener: impl Fn(&QueryEvent<'_>) + Send + Sync + 'static) -> Self {
+        Arc::make_mut(&mut self.instrumentation).listeners.push(Arc::new(listener));
+        self
+    }
+
+    /// Whether checkouts switch to the current tenant's schema, see [`PoolOptions::tenant_schemas`]
+    pub fn uses_tenant_schemas(&self) -> bool {
+        self.tenant_schemas
+    }
+
+    /// This pool without its replicas, for reads that must see the latest writes
+    pub fn primary(&self) -> DbPool {
+        Self {
+            replicas: Arc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Pool to run `sql` on, noting writes in the request context
+    fn pool_for(&self, sql: &str) -> &AnyPool {
+        if !connections::is_read_only(sql) {
+            context::mark_write();
+            return &self.pool;
+        }
+        if self.replicas.is_empty() || context::has_written() {
+            return &self.pool;
+        }
+        let index = self.next_replica.fetch_add(1, Ordering::Relaxed) % self.replicas.len();
+        &self.replicas[index]
     }
 }
 
@@ -128,19 +424,35 @@ impl Database for DbPool {
         self.db_type
     }
 
+    fn named_connection(&self, name: &str) -> Option<DbPool> {
+        self.connections.get(name).cloned()
+    }
+
+    fn primary_pool(&self) -> Option<DbPool> {
+        (!self.replicas.is_empty()).then(|| self.primary())
+    }
+
+    fn connect_options(&self) -> Option<Arc<AnyConnectOptions>> {
+        Some(self.pool.connect_options())
+    }
+
     async fn execute(&self, query: &str) -> Result<u64> {
-        let result = sqlx::query(query).execute(&self.pool).await?;
-        Ok(result.rows_affected())
+        context::mark_write();
+        self.observe(query, 0, async {
+            let result = sqlx::query(query).execute(&self.pool).await?;
+            Ok(result.rows_affected())
+        })
+        .await
     }
 
     async fn query(&self, query: &str) -> Result<Vec<AnyRow>> {
-        let rows = sqlx::query(query).fetch_all(&self.pool).await?;
-        Ok(rows)
+        let pool = self.pool_for(query);
+        self.observe(query, 0, sqlx::query(query).fetch_all(pool)).await
     }
 
     async fn query_one(&self, query: &str) -> Result<Option<AnyRow>> {
-        let row = sqlx::query(query).fetch_optional(&self.pool).await?;
-        Ok(row)
+        let pool = self.pool_for(query);
+        self.observe(query, 0, sqlx::query(query).fetch_optional(pool)).await
     }
 
     async fn ping(&self) -> Result<()> {
@@ -149,23 +461,59 @@ impl Database for DbPool {
     }
     
     async fn begin_transaction(&self) -> Result<DbTransaction> {
+        context::mark_write();
         let tx = self.pool.begin().await?;
-        Ok(DbTransaction { tx: Arc::new(Mutex::new(Some(tx))) })
+        Ok(DbTransaction {
+            tx: Arc::new(Mutex::new(Some(tx))),
+            db_type: self.db_type,
+            depth: 0,
+            instrumentation: self.instrumentation.clone(),
+            connect_options: self.pool.connect_options(),
+        })
     }
 
     async fn execute_query<'q>(&self, query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<u64> {
-        let result = query.execute(&self.pool).await?;
-        Ok(result.rows_affected())
+        context::mark_write();
+        let (sql, params, query) = instrument::inspect(query)?;
+        self.observe(sql, params, async {
+            let result = query.execute(&self.pool).await?;
+            Ok(result.rows_affected())
+        })
+        .await
+    }
+
+    async fn execute_insert<'q>(&self, query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<Option<i64>> {
+        context::mark_write();
+        let (sql, params, query) = instrument::inspect(query)?;
+        if self.db_type.supports_returning() {
+            let row = self.observe(sql, params, query.fetch_optional(&self.pool)).await?;
+            return Ok(row.as_ref().and_then(inserted_id));
+        }
+        let result = self.observe(sql, params, query.execute(&self.pool)).await?;
+        Ok(result.last_insert_id())
     }
 
     async fn fetch_all<'q>(&self, query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<Vec<AnyRow>> {
-        let rows = query.fetch_all(&self.pool).await?;
-        Ok(rows)
+        let (sql, params, query) = instrument::inspect(query)?;
+        let pool = self.pool_for(sql);
+        self.observe(sql, params, query.fetch_all(pool)).await
     }
 
     async fn fetch_one<'q>(&self, query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<Option<AnyRow>> {
-        let row = query.fetch_optional(&self.pool).await?;
-        Ok(row)
+        let (sql, params, query) = instrument::inspect(query)?;
+        let pool = self.pool_for(sql);
+        self.observe(sql, params, query.fetch_optional(pool)).await
+    }
+}
+
+impl DbPool {
+    async fn observe<T: instrument::RowCount>(
+        &self,
+        sql: &str,
+        params: usize,
+        statement: impl std::future::Future<Output = std::result::Result<T, sqlx::Error>>,
+    ) -> Result<T> {
+        instrument::observe(&self.instrumentation, self.db_type, sql, params, statement).await
     }
 }
 
@@ -173,47 +521,59 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 /// Database transaction
+///
+/// Nested transactions share the parent's connection and are implemented
+/// with savepoints; `depth` is 0 for the outermost transaction.
 #[derive(Clone, Debug)]
 pub struct DbTransaction {
     tx: Arc<Mutex<Option<Transaction<'static, sqlx::Any>>>>,
+    db_type: DatabaseType,
+    depth: usize,
+    instrumentation: Arc<Instrumentation>,
+    connect_options: Arc<AnyConnectOptions>,
 }
 
 impl DbTransaction {
+    /// Whether this is a savepoint inside another transaction
+    pub fn is_nested(&self) -> bool {
+        self.depth > 0
+    }
+
+    fn savepoint_name(&self) -> String {
+        format!("oxidite_sp_{}", self.depth)
+    }
+
     /// Execute a query within the transaction
     pub async fn execute(&self, query: &str) -> Result<u64> {
-        let mut lock = self.tx.lock().await;
-        if let Some(ref mut tx) = *lock {
-            let result = sqlx::query(query).execute(&mut **tx).await?;
-            Ok(result.rows_affected())
-        } else {
-            Err(sqlx::Error::PoolClosed)
-        }
+        self.execute_query(sqlx::query(query)).await
     }
 
     /// Query multiple rows within the transaction
     pub async fn query(&self, query: &str) -> Result<Vec<AnyRow>> {
-        let mut lock = self.tx.lock().await;
-        if let Some(ref mut tx) = *lock {
-            let rows = sqlx::query(query).fetch_all(&mut **tx).await?;
-            Ok(rows)
-        } else {
-            Err(sqlx::Error::PoolClosed)
-        }
+        self.fetch_all(sqlx::query(query)).await
     }
 
     /// Query one row within the transaction
     pub async fn query_one(&self, query: &str) -> Result<Option<AnyRow>> {
-        let mut lock = self.tx.lock().await;
-        if let Some(ref mut tx) = *lock {
-            let row = sqlx::query(query).fetch_optional(&mut **tx).await?;
-            Ok(row)
-        } else {
-            Err(sqlx::Error::PoolClosed)
-        }
+        self.fetch_one(sqlx::query(query)).await
     }
 
-    /// Commit the transaction
+    async fn observe<T: instrument::RowCount>(
+        &self,
+        sql: &str,
+        params: usize,
+        statement: impl std::future::Future<Output = std::result::Result<T, sqlx::Error>>,
+    ) -> Result<T> {
+        instrument::observe(&self.instrumentation, self.db_type, sql, params, statement).await
+    }
+
+    /// Commit the transaction, or release the savepoint when nested
     pub async fn commit(self) -> Result<()> {
+        if self.is_nested() {
+            self.execute(&format!("RELEASE SAVEPOINT {}", self.savepoint_name())).await?;
+            return Ok(());
+        }
+
         let mut lock = self.tx.lock().await;
         if let Some(tx) = lock.take() {
             tx.commit().await?;
@@ -221,8 +581,13 @@ impl DbTransaction {
         Ok(())
     }
 
-    /// Rollback the transaction
+    /// Rollback the transaction, or roll back to the savepoint when nested
     pub async fn rollback(self) -> Result<()> {
+        if self.is_nested() {
+            self.execute(&format!("ROLLBACK TO SAVEPOINT {}", self.savepoint_name())).await?;
+            return Ok(());
+        }
+
         let mut lock = self.tx.lock().await;
         if let Some(tx) = lock.take() {
             tx.rollback().await?;
@@ -234,8 +599,11 @@ impl DbTransaction {
 #[async_trait]
 impl Database for DbTransaction {
     fn db_type(&self) -> DatabaseType {
-        // Still a placeholder, but consistent
-        DatabaseType::Postgres 
+        self.db_type
+    }
+
+    fn connect_options(&self) -> Option<Arc<AnyConnectOptions>> {
+        Some(self.connect_options.clone())
     }
 
     async fn execute(&self, query: &str) -> Result<u64> {
@@ -256,50 +624,90 @@ impl Database for DbTransaction {
     }
     
     async fn begin_transaction(&self) -> Result<DbTransaction> {
-        // Nested transactions not supported by this simple wrapper yet
-        // Could use savepoints if needed.
-        Err(sqlx::Error::Configuration("Nested transactions not supported".into()))
+        let nested = DbTransaction {
+            tx: self.tx.clone(),
+            db_type: self.db_type,
+            depth: self.depth + 1,
+            instrumentation: self.instrumentation.clone(),
+            connect_options: self.connect_options.clone(),
+        };
+        self.execute(&format!("SAVEPOINT {}", nested.savepoint_name())).await?;
+        Ok(nested)
     }
 
     async fn execute_query<'q>(&self, query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<u64> {
+        let (sql, params, query) = instrument::inspect(query)?;
         let mut lock = self.tx.lock().await;
         if let Some(ref mut tx) = *lock {
-            let result = query.execute(&mut **tx).await?;
-            Ok(result.rows_affected())
+            self.observe(sql, params, async {
+                let result = query.execute(&mut **tx).await?;
+                Ok(result.rows_affected())
+            })
+            .await
         } else {
             Err(sqlx::Error::PoolClosed)
         }
     }
 
+    async fn execute_insert<'q>(&self, query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<Option<i64>> {
+        let (sql, params, query) = instrument::inspect(query)?;
+        let mut lock = self.tx.lock().await;
+        let Some(ref mut tx) = *lock else {
+            return Err(sqlx::Error::PoolClosed);
+        };
+        if self.db_type.supports_returning() {
+            let row = self.observe(sql, params, query.fetch_optional(&mut **tx)).await?;
+            return Ok(row.as_ref().and_then(inserted_id));
+        }
+        let result = self.observe(sql, params, query.execute(&mut **tx)).await?;
+        Ok(result.last_insert_id())
+    }
+
     async fn fetch_all<'q>(&self, query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<Vec<AnyRow>> {
+        let (sql, params, query) = instrument::inspect(query)?;
         let mut lock = self.tx.lock().await;
         if let Some(ref mut tx) = *lock {
-            let rows = query.fetch_all(&mut **tx).await?;
-            Ok(rows)
+            self.observe(sql, params, query.fetch_all(&mut **tx)).await
         } else {
             Err(sqlx::Error::PoolClosed)
         }
     }
 
     async fn fetch_one<'q>(&self, query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<Option<AnyRow>> {
+        let (sql, params, query) = instrument::inspect(query)?;
         let mut lock = self.tx.lock().await;
         if let Some(ref mut tx) = *lock {
-            let row = query.fetch_optional(&mut **tx).await?;
-            Ok(row)
+            self.observe(sql, params, query.fetch_optional(&mut **tx)).await
         } else {
             Err(sqlx::Error::PoolClosed)
         }
     }
 }
 
+/// The `id` returned by `INSERT ... RETURNING id`, whether the column is 32 or 64 bits
+fn inserted_id(row: &AnyRow) -> Option<i64> {
+    row.try_get::<i64, _>("id")
+        .or_else(|_| row.try_get::<i32, _>("id").map(i64::from))
+        .ok()
+}
+
 /// Query builder (simplified for now)
+///
+/// JSON filters and JSON columns render for the builder's dialect, SQLite
+/// unless set with `dialect`; `get` and the pagination methods set it from
+/// the database they run on. JSON filter values are bound, not written into
+/// the SQL: run `build()` with the values from `bindings()`.
 pub struct QueryBuilder {
     table: String,
     select_fields: Vec<String>,
     where_clauses: Vec<String>,
+    json_filters: Vec<(String, SqlValue)>,
     order_by: Vec<String>,
     limit: Option<usize>,
     offset: Option<usize>,
+    dialect: Option<DatabaseType>,
+    model_fields: &'static [&'static str],
+    json_columns: &'static [&'static str],
 }
 
 impl QueryBuilder {
@@ -308,12 +716,31 @@ impl QueryBuilder {
             table: table.to_string(),
             select_fields: vec!["*".to_string()],
             where_clauses: Vec::new(),
+            json_filters: Vec::new(),
             order_by: Vec::new(),
             limit: None,
             offset: None,
+            dialect: None,
+            model_fields: &[],
+            json_columns: &[],
+        }
+    }
+
+    /// A builder for `M`'s table that knows which of its columns hold JSON
+    pub fn for_model<M: Model>() -> Self {
+        Self {
+            model_fields: M::fields(),
+            json_columns: M::json_columns(),
+            ..Self::new(M::table_name())
         }
     }
 
+    /// Render SQL for `db_type`
+    pub fn dialect(mut self, db_type: DatabaseType) -> Self {
+        self.dialect = Some(db_type);
+        self
+    }
+
     pub fn select(mut self, fields: &[&str]) -> Self {
         self.select_fields = fields.iter().map(|s| s.to_string()).collect();
         self
@@ -324,6 +751,20 @@ impl QueryBuilder {
         self
     }
 
+    pub fn where_null(mut self, column: &str) -> Self {
+        self.where_clauses.push(format!("{} IS NULL", column));
+        self
+    }
+
+    /// Match a value inside a JSON column, e.g. `where_json("settings->theme", "dark")`
+    ///
+    /// Steps are object keys or array indexes (`tags->0`). A path with
+    /// characters other than letters, digits, `_` and `-` matches no rows.
+    pub fn where_json(mut self, path: &str, value: impl Into<SqlValue>) -> Self {
+        self.json_filters.push((path.to_string(), value.into()));
+        self
+    }
+
     pub fn order_by(mut self, column: &str, direction: &str) -> Self {
         self.order_by.push(format!("{} {}", column, direction));
         self
@@ -339,11 +780,48 @@ impl QueryBuilder {
         self
     }
 
+    fn db_type(&self) -> DatabaseType {
+        self.dialect.unwrap_or(DatabaseType::Sqlite)
+    }
+
+    fn conditions(&self, params: &mut bulk::Params) -> Vec<String> {
+        let json: Vec<String> = self
+            .json_filters
+            .iter()
+            .map(|(path, value)| json::path_condition(params, path, value))
+            .collect();
+        self.where_clauses.iter().cloned().chain(json).collect()
+    }
+
+    fn select_sql(&self) -> String {
+        if self.select_fields == ["*"] {
+            return json::select_list(self.db_type(), self.model_fields, self.json_columns);
+        }
+        self.select_fields
+            .iter()
+            .map(|field| json::select_column(self.db_type(), field, self.json_columns.contains(&field.as_str())))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     pub fn build(&self) -> String {
-        let mut query = format!("SELECT {} FROM {}", self.select_fields.join(", "), self.table);
+        self.render(&mut bulk::Params::new(self.db_type()))
+    }
 
-        if !self.where_clauses.is_empty() {
-            query.push_str(&format!(" WHERE {}", self.where_clauses.join(" AND ")));
+    /// Values for the placeholders in `build()` and `build_count()`, in order
+    pub fn bindings(&self) -> Vec<SqlValue> {
+        let mut params = bulk::Params::new(self.db_type());
+        self.render(&mut params);
+        params.into_values()
+    }
+
+    /// The `SELECT`, with placeholders numbered after those already in `params`
+    fn render(&self, params: &mut bulk::Params) -> String {
+        let mut query = format!("SELECT {} FROM {}", self.select_sql(), self.table);
+
+        let conditions = self.conditions(params);
+        if !conditions.is_empty() {
+            query.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
         }
 
         if !self.order_by.is_empty() {
@@ -360,7 +838,189 @@ impl QueryBuilder {
 
         query
     }
+
+    /// `SELECT COUNT(*)` with the same filters, ignoring order and limits
+    pub fn build_count(&self) -> String {
+        self.render_count(&mut bulk::Params::new(self.db_type()))
+    }
+
+    fn render_count(&self, params: &mut bulk::Params) -> String {
+        let mut query = format!("SELECT COUNT(*) AS count FROM {}", self.table);
+
+        let conditions = self.conditions(params);
+        if !conditions.is_empty() {
+            query.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+
+        query
+    }
+
+    /// Fetch the rows `render` selects
+    async fn fetch(&self, db: &impl Database, mut params: bulk::Params) -> Result<Vec<AnyRow>> {
+        let sql = self.render(&mut params);
+        db.fetch_all(params.bind_all(sqlx::query(&sql))).await
+    }
+
+    /// Fetch the matching records
+    pub async fn get<M: Model>(self, db: &impl Database) -> Result<Vec<M>> {
+        let query = self.dialect(db.db_type());
+        let rows = query.fetch(db, bulk::Params::new(query.db_type())).await?;
+        rows.iter().map(M::hydrate).collect()
+    }
+
+    /// Fetch one page of results plus the total row count
+    pub async fn paginate<M: Model>(self, db: &impl Database, page: u64, per_page: u64) -> Result<Page<M>> {
+        let page = page.max(1);
+        let query = self.dialect(db.db_type());
+        // Databases take LIMIT and OFFSET as signed 64-bit integers
+        let per_page = per_page.clamp(1, i64::MAX as u64);
+
+        let mut params = bulk::Params::new(query.db_type());
+        let count = query.render_count(&mut params);
+        let total = match db.fetch_one(params.bind_all(sqlx::query(&count))).await? {
+            Some(row) => row.try_get::<i64, _>("count")?.max(0) as u64,
+            None => 0,
+        };
+
+        let offset = (page - 1).saturating_mul(per_page).min(i64::MAX as u64);
+        let query = query
+            .limit(usize::try_from(per_page).unwrap_or(usize::MAX))
+            .offset(usize::try_from(offset).unwrap_or(usize::MAX));
+        let rows = query.fetch(db, bulk::Params::new(query.db_type())).await?;
+        let items = rows.iter().map(M::hydrate).collect::<Result<Vec<_>>>()?;
+
+        Ok(Page::new(items, total, page, per_page))
+    }
+
+    /// Keyset pagination ordered by `order_columns`
+    ///
+    /// Each column is `"name"` or `"name DESC"`; end the list with a unique
+    /// column such as `id` so the order is total. `after` is the `next_cursor`
+    /// of the previous page. Any `order_by` set on the builder is replaced.
+    pub async fn cursor_paginate<M: Model>(
+        mut self,
+        db: &impl Database,
+        order_columns: &[&str],
+        after: Option<&str>,
+        per_page: u64,
+    ) -> Result<CursorPage<M>> {
+        let per_page = per_page.max(1);
+        let keys: Vec<(&str, bool)> = order_columns
+            .iter()
+            .map(|spec| match spec.split_once(' ') {
+                Some((column, dir)) => (column, dir.trim().eq_ignore_ascii_case("desc")),
+                None => (*spec, false),
+            })
+            .collect();
+
+        self.order_by = keys
+            .iter()
+            .map(|(column, desc)| format!("{} {}", column, if *desc { "DESC" } else { "ASC" }))
+            .collect();
+        self.limit = Some(per_page as usize + 1);
+        self.offset = None;
+
+        let db_type = db.db_type();
+        self.dialect = Some(db_type);
+        let mut params = bulk::Params::new(db_type);
+        if let Some(cursor) = after {
+            let values = pagination::decode_cursor(cursor)
+                .filter(|values| values.len() == keys.len())
+                .ok_or_else(|| sqlx::Error::Protocol("Invalid pagination cursor".to_string()))?;
+
+            // (a > x) OR (a = x AND b > y) OR ... with per-column direction
+            let mut alternatives = Vec::new();
+            for i in 0..keys.len() {
+                let mut terms = Vec::new();
+                for (j, (column, desc)) in keys.iter().enumerate().take(i + 1) {
+                    let op = if j < i { "=" } else if *desc { "<" } else { ">" };
+                    terms.push(format!("{} {} {}", column, op, params.push(cursor_bind(&values[j]))));
+                }
+                alternatives.push(format!("({})", terms.join(" AND ")));
+            }
+            self.where_clauses.push(format!("({})", alternatives.join(" OR ")));
+        }
+
+        let mut rows = self.fetch(db, params).await?;
+        let has_more = rows.len() > per_page as usize;
+        rows.truncate(per_page as usize);
+
+        let next_cursor = match rows.last() {
+            Some(last) if has_more => {
+                let values: Vec<_> = keys.iter().map(|(column, _)| cursor_value(last, column)).collect();
+                Some(pagination::encode_cursor(&values))
+            }
+            _ => None,
+        };
+
+        let items = rows.iter().map(M::hydrate).collect::<Result<Vec<_>>>()?;
+        Ok(CursorPage::new(items, per_page, next_cursor))
+    }
+}
+
+/// A cursor value as a bound parameter
+fn cursor_bind(value: &serde_json::Value) -> SqlValue {
+    match value {
+        serde_json::Value::Bool(b) => SqlValue::Bool(*b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(n) => SqlValue::Int(n),
+            None => n.as_f64().map_or(SqlValue::Null, SqlValue::Float),
+        },
+        serde_json::Value::String(s) => SqlValue::Text(s.clone()),
+        _ => SqlValue::Null,
+    }
+}
+
+/// Read a sort key column as JSON for the cursor
+fn cursor_value(row: &AnyRow, column: &str) -> serde_json::Value {
+    if let Ok(v) = row.try_get::<i64, _>(column) {
+        return v.into();
+    }
+    if let Ok(v) = row.try_get::<i32, _>(column) {
+        return v.into();
+    }
+    if let Ok(v) = row.try_get::<f64, _>(column) {
+        return v.into();
+    }
+    if let Ok(v) = row.try_get::<String, _>(column) {
+        return v.into();
+    }
+    if let Ok(v) = row.try_get::<bool, _>(column) {
+        return v.into();
+    }
+    serde_json::Value::Null
+}
+/// Lifecycle hooks for models using `#[derive(Model)]`
+///
+/// Add `#[model(hooks)]` to the struct and implement this trait; the derived
+/// `Model` impl forwards its hook methods here.
+#[async_trait]
+pub trait ModelHooks: Send + Sync {
+    async fn before_create(&mut self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
+
+    async fn after_create(&mut self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
+
+    async fn before_update(&mut self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
+
+    async fn after_update(&mut self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
+
+    async fn before_delete(&self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
+
+    async fn after_delete(&self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
 }
+
 /// Model trait for database entities
 #[async_trait]
 pub trait Model: Sized + Send + Sync + Unpin + for<'r> sqlx::FromRow<'r, AnyRow> {
@@ -370,44 +1030,272 @@ pub trait Model: Sized + Send + Sync + Unpin + for<'r> sqlx::FromRow<'r, AnyRow>
     /// Get the list of fields (columns)
     fn fields() -> &'static [&'static str];
 
+    /// Column metadata recorded by `#[derive(Model)]`, used to diff models against the live schema
+    fn field_meta() -> &'static [FieldMeta] {
+        &[]
+    }
+
     /// Check if the model supports soft deletes
     fn has_soft_delete() -> bool {
         false
     }
 
+    /// Column checked and incremented by `update`, set with `#[model(version_column = "...")]`
+    fn version_column() -> Option<&'static str> {
+        None
+    }
+
+    /// Whether `#[model(auditable)]` is set
+    fn is_auditable() -> bool {
+        false
+    }
+
+    /// Whether `#[model(tenant_scoped)]` is set, see [`tenancy`]
+    fn is_tenant_scoped() -> bool {
+        false
+    }
+
+    /// Named connection set with `#[model(connection = "...")]`
+    fn connection_name() -> Option<&'static str> {
+        None
+    }
+
+    /// Build a record from a row, remembering its values for change tracking
+    #[doc(hidden)]
+    fn hydrate(row: &AnyRow) -> Result<Self> {
+        let mut model = Self::from_row(row)?;
+        model.sync_original();
+        Ok(model)
+    }
+
+    /// Current column values other than `id`, generated for models with an `original` field
+    #[doc(hidden)]
+    fn attribute_values(&self) -> Vec<(&'static str, serde_json::Value)> {
+        Vec::new()
+    }
+
+    /// Set the integer column `column` to `id`, returning false when the model has no such column
+    ///
+    /// Lets factories point a record at its parent through a relation's foreign key.
+    #[doc(hidden)]
+    fn set_foreign_key(&mut self, _column: &str, _id: i64) -> bool {
+        false
+    }
+
+    /// The values recorded when the record was loaded, for models with an `original` field
+    fn original(&self) -> Option<&Original> {
+        None
+    }
+
+    #[doc(hidden)]
+    fn original_mut(&mut self) -> Option<&mut Original> {
+        None
+    }
+
+    /// Remember the current values as the saved state
+    fn sync_original(&mut self) {
+        let values = self.attribute_values();
+        if let Some(original) = self.original_mut() {
+            original.set(values);
+        }
+    }
+
+    /// Fields that differ from the saved state, with old and new values
+    ///
+    /// Records that weren't loaded from the database report every field.
+    /// Models without an `original` field can't track changes and report no values.
+    fn changes(&self) -> Vec<FieldChange> {
+        let original = self.original().filter(|original| original.is_loaded());
+        self.attribute_values()
+            .into_iter()
+            .filter_map(|(field, new)| {
+                let old = original.and_then(|original| original.get(field)).cloned();
+                match (original, &old) {
+                    (Some(_), Some(old)) if *old == new => None,
+                    _ => Some(FieldChange { field, old, new }),
+                }
+            })
+            .collect()
+    }
+
+    /// Names of the fields that differ from the saved state
+    ///
+    /// Without change tracking every column but `id` is reported.
+    fn changed_fields(&self) -> Vec<&'static str> {
+        if self.original().is_none() {
+            return Self::fields().iter().copied().filter(|field| *field != "id").collect();
+        }
+        self.changes().into_iter().map(|change| change.field).collect()
+    }
+
+    /// Whether any field differs from the saved state
+    fn is_dirty(&self) -> bool {
+        !self.changed_fields().is_empty()
+    }
+
     /// Find a record by ID
     async fn find(db: &impl Database, id: i64) -> Result<Option<Self>> {
-        let mut query = format!("SELECT * FROM {} WHERE id = {}", Self::table_name(), id);
+        let db = &connections::route::<Self, _>(db);
+        let columns = json::select_list(db.db_type(), Self::fields(), Self::json_columns());
+        let mut query = format!("SELECT {} FROM {} WHERE id = {}", columns, Self::table_name(), id);
         if Self::has_soft_delete() {
             query.push_str(" AND deleted_at IS NULL");
         }
+        if let Some(tenant) = tenancy::condition::<Self>()? {
+            query.push_str(&format!(" AND {}", tenant));
+        }
         let row = db.query_one(&query).await?;
         
         match row {
-            Some(row) => Ok(Some(Self::from_row(&row)?)),
+            Some(row) => Ok(Some(Self::hydrate(&row)?)),
             None => Ok(None),
         }
     }
 
     /// Find all records
     async fn all(db: &impl Database) -> Result<Vec<Self>> {
-        let mut query = format!("SELECT * FROM {}", Self::table_name());
+        let db = &connections::route::<Self, _>(db);
+        let mut conditions = Vec::new();
         if Self::has_soft_delete() {
-            query.push_str(" WHERE deleted_at IS NULL");
+            conditions.push("deleted_at IS NULL".to_string());
+        }
+        conditions.extend(tenancy::condition::<Self>()?);
+        let columns = json::select_list(db.db_type(), Self::fields(), Self::json_columns());
+        let mut query = format!("SELECT {} FROM {}", columns, Self::table_name());
+        if !conditions.is_empty() {
+            query.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
         }
         let rows = db.query(&query).await?;
         
         let mut models = Vec::new();
         for row in rows {
-            models.push(Self::from_row(&row)?);
+            models.push(Self::hydrate(&row)?);
         }
         Ok(models)
     }
     
+    /// Columns holding [`Json`] values
+    #[doc(hidden)]
+    fn json_columns() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Columns written by `insert_many` and `upsert`, in bind order
+    #[doc(hidden)]
+    fn insert_columns() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Bind this record's values for `insert_columns`, using `now` for timestamps
+    #[doc(hidden)]
+    fn bind_insert<'q>(
+        &'q self,
+        query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
+        _now: i64,
+    ) -> sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>> {
+        query
+    }
+
+    /// Insert many records with multi-row `INSERT`s in one transaction
+    ///
+    /// Sets `created_at`/`updated_at` like `create`, but skips hooks and observers.
+    /// Returns the number of rows inserted.
+    async fn insert_many(db: &impl Database, rows: &[Self]) -> Result<u64> {
+        let db = &connections::route::<Self, _>(db);
+        bulk::insert(db, rows, None).await
+    }
+
+    /// Insert records, updating `update_columns` where `conflict_columns` already match
+    ///
+    /// Uses `ON CONFLICT ... DO UPDATE` on Postgres and SQLite and `ON DUPLICATE KEY UPDATE`
+    /// on MySQL (which matches on any unique key). With no update columns, conflicting
+    /// rows are left untouched, and `conflict_columns` may be empty to skip a conflict
+    /// on any unique key; updating needs conflict columns. `updated_at` is refreshed on
+    /// updated rows.
+    async fn upsert(
+        db: &impl Database,
+        rows: &[Self],
+        conflict_columns: &[&str],
+        update_columns: &[&str],
+    ) -> Result<u64> {
+        let on_conflict = bulk::OnConflict { conflict_columns, update_columns };
+        let db = &connections::route::<Self, _>(db);
+        bulk::insert(db, rows, Some(on_conflict)).await
+    }
+
+    /// Update every matching record in one statement, returning rows affected
+    ///
+    /// Conditions are `("column", value)` for equality or `("column op", value)`,
+    /// e.g. `("score <", 10.into())`, where `op` is one of `= != <> < <= > >= LIKE IN`
+    /// and `IN` takes a list (`vec![1, 2].into()`). Columns must be model fields, and
+    /// both `conditions` and `values` must be non-empty. Soft-deleted rows are skipped,
+    /// `updated_at` is set and the version column, if any, is incremented.
+    async fn update_where(
+        db: &impl Database,
+        conditions: &[(&str, SqlValue)],
+        values: &[(&str, SqlValue)],
+    ) -> Result<u64> {
+        let db = &connections::route::<Self, _>(db);
+        bulk::update_where::<Self>(db, conditions, values).await
+    }
+
+    /// Delete every matching record, soft deleting when the model supports it
+    ///
+    /// Conditions are checked as for [`update_where`](Model::update_where).
+    async fn delete_where(db: &impl Database, conditions: &[(&str, SqlValue)]) -> Result<u64> {
+        let db = &connections::route::<Self, _>(db);
+        bulk::delete_where::<Self>(db, conditions, false).await
+    }
+
+    /// Permanently delete every matching record
+    async fn force_delete_where(db: &impl Database, conditions: &[(&str, SqlValue)]) -> Result<u64> {
+        let db = &connections::route::<Self, _>(db);
+        bulk::delete_where::<Self>(db, conditions, true).await
+    }
+
+    /// One page of records ordered by `id`, with totals
+    async fn paginate(db: &impl Database, page: u64, per_page: u64) -> Result<Page<Self>> {
+        let db = &connections::route::<Self, _>(db);
+        tenancy::scope_for::<Self>()?;
+        Self::query().order_by("id", "ASC").paginate(db, page, per_page).await
+    }
+
+    /// Keyset pagination, e.g. `cursor_paginate(&db, &["created_at DESC", "id DESC"], cursor, 20)`
+    async fn cursor_paginate(
+        db: &impl Database,
+        order_columns: &[&str],
+        after: Option<&str>,
+        per_page: u64,
+    ) -> Result<CursorPage<Self>> {
+        let db = &connections::route::<Self, _>(db);
+        tenancy::scope_for::<Self>()?;
+        Self::query().cursor_paginate(db, order_columns, after, per_page).await
+    }
+
+    /// Query builder for this model's table, excluding soft-deleted rows
+    ///
+    /// For tenant-scoped models it is limited to the current tenant, and
+    /// matches nothing when no tenant is set.
+    fn query() -> QueryBuilder {
+        let mut query = QueryBuilder::for_model::<Self>();
+        if Self::has_soft_delete() {
+            query = query.where_null("deleted_at");
+        }
+        match tenancy::condition::<Self>() {
+            Ok(Some(tenant)) => query.where_clauses.push(tenant),
+            Ok(None) => {}
+            Err(_) => query.where_clauses.push("1 = 0".to_string()),
+        }
+        query
+    }
+
     /// Create a new record
     async fn create(&mut self, db: &impl Database) -> Result<()>;
 
     /// Update an existing record
+    ///
+    /// With change tracking (see [`tracking`]) only changed columns are written,
+    /// and nothing at all when no column changed.
     async fn update(&mut self, db: &impl Database) -> Result<()>;
 
     /// Delete the record (soft delete if supported, otherwise hard delete)
@@ -416,15 +1304,48 @@ pub trait Model: Sized + Send + Sync + Unpin + for<'r> sqlx::FromRow<'r, AnyRow>
     /// Force delete the record (hard delete)
     async fn force_delete(&self, db: &impl Database) -> Result<()>;
     
-    /// Validate the model fields
-    fn validate(&self) -> std::result::Result<(), String> {
+    /// Called by `create` before the row is inserted; an error aborts the insert
+    ///
+    /// Hooks receive the same `db` handle as the operation, so when that is a
+    /// `DbTransaction` they run inside it.
+    async fn before_create(&mut self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called by `create` after the row is inserted
+    async fn after_create(&mut self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called by `update` before the row is written; an error aborts the update
+    async fn before_update(&mut self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called by `update` after the row is written
+    async fn after_update(&mut self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called by `delete` and `force_delete` before the row is removed; an error aborts the delete
+    async fn before_delete(&self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called by `delete` and `force_delete` after the row is removed
+    async fn after_delete(&self, _db: &impl Database) -> Result<()> {
+        Ok(())
+    }
+
+    /// Validate the model fields, collecting every failing rule
+    fn validate(&self) -> std::result::Result<(), ValidationErrors> {
         Ok(())
     }
 
-    /// Save (create or update)
+    /// Save (create or update); failing validation is an [`InvalidModel`] error
     async fn save(&mut self, db: &impl Database) -> Result<()> {
-        if let Err(e) = self.validate() {
-            return Err(sqlx::Error::Protocol(e.into()));
+        if let Err(errors) = self.validate() {
+            return Err(InvalidModel::new(Self::table_name(), errors).into());
         }
         // This default impl is tricky without knowing if it's new.
         // For now, let's leave it to the user or macro to decide.
@@ -0,0 +1,60 @@
+//! Optimistic locking
+//!
+//! With `#[model(version_column = "lock_version")]` the derived `update`
+//! only writes the row if its version still matches the one that was loaded,
+//! and bumps it on success:
+//!
+//! ```ignore
+//! match post.update(&db).await {
+//!     Ok(()) => {}
+//!     Err(err) if StaleObject::find(&err).is_some() => {
+//!         // Someone else saved the post first; reload and retry, or report a 409
+//!         return Err(oxidite_db::http_error(err));
+//!     }
+//!     Err(err) => return Err(oxidite_db::http_error(err)),
+//! }
+//! ```
+
+use std::fmt;
+
+/// The record was changed or deleted since it was loaded
+///
+/// Returned inside `sqlx::Error::Database`; use [`StaleObject::find`] to detect it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleObject {
+    pub table: &'static str,
+    pub id: String,
+    /// The version the update expected to find
+    pub version: i64,
+    message: String,
+}
+
+impl StaleObject {
+    pub fn new(table: &'static str, id: impl ToString, version: i64) -> Self {
+        let id = id.to_string();
+        let message = format!(
+            "{} {} was modified or deleted since version {} was loaded",
+            table, id, version
+        );
+        Self { table, id, version, message }
+    }
+
+    /// The `StaleObject` carried by `err`, if it is one
+    pub fn find(err: &sqlx::Error) -> Option<&StaleObject> {
+        err.as_database_error()?.try_downcast_ref::<StaleObject>()
+    }
+}
+
+impl fmt::Display for StaleObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+database_error!(StaleObject);
+
+impl From<StaleObject> for oxidite_core::Error {
+    fn from(err: StaleObject) -> Self {
+        oxidite_core::Error::Conflict(err.to_string())
+    }
+}
@@ -3,185 +3,826 @@
 use std::path::{Path, PathBuf};
 use std::fs;
 use chrono::Utc;
-use sqlx::Row;
+use sha2::{Digest, Sha256};
+use sqlx::error::ErrorKind;
+use sqlx::{AnyConnection, ConnectOptions, Connection, Row};
+use std::time::Duration;
 
-/// Migration file
-#[derive(Debug)]
+use crate::diff::{diff_models, DiffOptions, ModelSchema};
+use crate::schema::{Schema, SchemaError};
+use crate::tenancy::{self, Tenant};
+use crate::{connections, Database, DatabaseType, DbPool};
+
+/// Migration loaded from a `.sql` file or defined in code with the schema builder
+#[derive(Debug, Clone, Default)]
 pub struct Migration {
     pub version: String,
     pub name: String,
     pub up_sql: String,
     pub down_sql: String,
+    /// Schema builder changes, rendered for the target database after `up_sql`
+    pub up_schema: Vec<Schema>,
+    /// Schema builder changes, rendered for the target database after `down_sql`
+    pub down_schema: Vec<Schema>,
+    /// Run the statements outside a transaction, for those such as `CREATE
+    /// INDEX CONCURRENTLY` that refuse to run in one; a `.sql` file sets it
+    /// with `-- migrate:up transaction:false`
+    pub no_transaction: bool,
 }
 
 impl Migration {
     pub fn new(name: &str) -> Self {
         let timestamp = Utc::now().format("%Y%m%d%H%M%S").to_string();
         let version = format!("{}_{}", timestamp, name);
-        
+
         Self {
             version,
             name: name.to_string(),
-            up_sql: String::new(),
-            down_sql: String::new(),
+            ..Default::default()
+        }
+    }
+
+    /// Migration written with the schema builder, registered with `MigrationManager::register`
+    ///
+    /// `version` follows the file naming scheme, e.g. `20240101120000_create_users`.
+    pub fn schema(version: &str, up: Vec<Schema>, down: Vec<Schema>) -> Self {
+        let name = version.split_once('_').map(|(_, name)| name).unwrap_or(version);
+
+        Self {
+            version: version.to_string(),
+            name: name.to_string(),
+            up_schema: up,
+            down_schema: down,
+            ..Default::default()
+        }
+    }
+
+    /// Statements to apply this migration on `db_type`
+    pub fn up_statements(&self, db_type: DatabaseType) -> Result<Vec<String>, SchemaError> {
+        let mut statements = split_statements(&self.up_sql);
+        for schema in &self.up_schema {
+            statements.extend(schema.to_sql(db_type)?);
+        }
+        Ok(statements)
+    }
+
+    /// Statements to revert this migration on `db_type`
+    pub fn down_statements(&self, db_type: DatabaseType) -> Result<Vec<String>, SchemaError> {
+        let mut statements = split_statements(&self.down_sql);
+        for schema in &self.down_schema {
+            statements.extend(schema.to_sql(db_type)?);
         }
+        Ok(statements)
     }
-    
+
     pub fn from_file(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
         let path = path.as_ref();
         let content = fs::read_to_string(path)?;
-        
+
         let filename = path.file_stem()
             .and_then(|s| s.to_str())
             .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid filename"))?;
-        
+
         // Parse filename: 20240101120000_create_users
         let parts: Vec<&str> = filename.splitn(2, '_').collect();
         if parts.len() != 2 {
             return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid migration filename"));
         }
-        
+
         let version = filename.to_string();
         let name = parts[1].to_string();
-        
+
         // Split content into up/down SQL
         let sections: Vec<&str> = content.split("-- migrate:down").collect();
-        let up_sql = sections.get(0)
-            .unwrap_or(&"")
-            .replace("-- migrate:up", "")
+        let up = sections.first().unwrap_or(&"");
+        let is_up_header = |line: &&str| line.trim_start().starts_with("-- migrate:up");
+        let no_transaction = up.lines().filter(is_up_header).any(|line| line.contains("transaction:false"));
+        let up_sql = up
+            .lines()
+            .filter(|line| !is_up_header(line))
+            .collect::<Vec<_>>()
+            .join("\n")
             .trim()
             .to_string();
         let down_sql = sections.get(1)
             .unwrap_or(&"")
             .trim()
             .to_string();
-        
+
         Ok(Self {
             version,
             name,
             up_sql,
             down_sql,
+            no_transaction,
+            ..Default::default()
         })
     }
-    
+
     pub fn save(&self, migrations_dir: impl AsRef<Path>) -> Result<PathBuf, std::io::Error> {
         let migrations_dir = migrations_dir.as_ref();
         fs::create_dir_all(migrations_dir)?;
-        
+
         let filename = format!("{}.sql", self.version);
         let path = migrations_dir.join(filename);
-        
+
         let content = format!(
-            "-- migrate:up\n{}\n\n-- migrate:down\n{}\n",
+            "-- migrate:up{}\n{}\n\n-- migrate:down\n{}\n",
+            if self.no_transaction { " transaction:false" } else { "" },
             self.up_sql,
             self.down_sql
         );
-        
+
         fs::write(&path, content)?;
         Ok(path)
     }
+
+    /// SHA-256 of the up migration, used to detect edits after it was applied
+    pub fn checksum(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.up_sql.trim().as_bytes());
+        // Schema changes are hashed in one fixed dialect so the checksum doesn't
+        // depend on which database the migration ran against; Postgres accepts
+        // every change the builder can describe
+        for statement in self.up_schema.iter().flat_map(|s| s.to_sql(DatabaseType::Postgres).unwrap_or_default()) {
+            hasher.update(b"\n");
+            hasher.update(statement.as_bytes());
+        }
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Split a SQL script into individual statements
+///
+/// Semicolons inside quoted strings, identifiers, comments, Postgres
+/// dollar-quoted bodies and `BEGIN ... END` or `CASE ... END` blocks (as in
+/// a `CREATE TRIGGER`) do not end a statement.
+pub fn split_statements(sql: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let chars: Vec<char> = sql.chars().collect();
+    let mut i = 0;
+    // Open `BEGIN`/`CASE` blocks in the current statement
+    let mut depth = 0usize;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\'' | '"' | '`' => {
+                // Quoted string or identifier; doubled quotes are escapes
+                current.push(c);
+                i += 1;
+                while i < chars.len() {
+                    current.push(chars[i]);
+                    if chars[i] == c {
+                        if i + 1 < chars.len() && chars[i + 1] == c {
+                            current.push(chars[i + 1]);
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+                continue;
+            }
+            '$' => {
+                // Dollar quoting: $$ ... $$ or $tag$ ... $tag$
+                let tag_end = chars[i + 1..]
+                    .iter()
+                    .position(|ch| !(ch.is_alphanumeric() || *ch == '_'))
+                    .map(|p| i + 1 + p);
+                match tag_end {
+                    Some(end) if chars[end] == '$' => {
+                        let tag: String = chars[i..=end].iter().collect();
+                        current.push_str(&tag);
+                        i = end + 1;
+                        let rest: String = chars[i..].iter().collect();
+                        match rest.find(&tag) {
+                            Some(pos) => {
+                                current.push_str(&rest[..pos + tag.len()]);
+                                i += rest[..pos + tag.len()].chars().count();
+                            }
+                            None => {
+                                current.push_str(&rest);
+                                i = chars.len();
+                            }
+                        }
+                        continue;
+                    }
+                    _ => current.push(c),
+                }
+            }
+            ';' if depth == 0 => {
+                let statement = current.trim();
+                if !statement.is_empty() {
+                    statements.push(statement.to_string());
+                }
+                current.clear();
+            }
+            c if is_word_char(c) && !chars[..i].last().copied().is_some_and(is_word_char) => {
+                let first_word = current.trim().is_empty();
+                let word = read_word(&chars, i);
+                current.push_str(&word);
+                i += word.chars().count();
+                match word.to_ascii_uppercase().as_str() {
+                    // A `BEGIN` opening the statement starts a transaction, not a block
+                    "BEGIN" if !first_word => depth += 1,
+                    "CASE" => depth += 1,
+                    "END" => {
+                        // `END IF` and `END LOOP` close blocks that weren't counted
+                        let next = chars[i..].iter().position(|ch| !ch.is_whitespace()).map(|skip| read_word(&chars, i + skip));
+                        let closes_uncounted = next.is_some_and(|next| {
+                            ["IF", "LOOP", "WHILE", "REPEAT"].contains(&next.to_ascii_uppercase().as_str())
+                        });
+                        if !closes_uncounted {
+                            depth = depth.saturating_sub(1);
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            _ => current.push(c),
+        }
+        i += 1;
+    }
+
+    let statement = current.trim();
+    if !statement.is_empty() {
+        statements.push(statement.to_string());
+    }
+
+    statements
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The identifier or keyword starting at `start`
+fn read_word(chars: &[char], start: usize) -> String {
+    chars[start..].iter().take_while(|c| is_word_char(**c)).collect()
+}
+
+/// Migration runner errors
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Schema(#[from] SchemaError),
+
+    #[error("Migration {0} was modified after it was applied (checksum mismatch)")]
+    ChecksumMismatch(String),
+
+    #[error("Migration {0} is recorded as applied but its file was not found")]
+    MissingMigration(String),
+
+    #[error("Migration {0} has no down migration")]
+    NoDownMigration(String),
+
+    #[error("Unknown migration version: {0}")]
+    UnknownVersion(String),
+
+    #[error("Another process is running migrations")]
+    Locked,
+
+    #[error("Tenant migrations need a Postgres pool connected with `tenant_schemas` enabled")]
+    TenantSchemasDisabled,
+}
+
+/// A row from the `_migrations` table
+#[derive(Debug, Clone)]
+pub struct AppliedMigration {
+    pub version: String,
+    pub checksum: Option<String>,
+    pub applied_at: i64,
 }
 
+/// Lock held while migrations run
+enum MigrationLock {
+    /// Postgres advisory lock or MySQL named lock, held by a connection of its own
+    Session(AnyConnection),
+    /// Row in the `_migrations_lock` table
+    Table,
+}
+
+// Arbitrary key shared by every app instance using the same database
+const ADVISORY_LOCK_KEY: i64 = 0x006f_7869_6469_7465;
+const MYSQL_LOCK_NAME: &str = "oxidite_migrations";
+const MYSQL_LOCK_TIMEOUT_SECS: u32 = 60;
+
+/// How long a `_migrations_lock` row holds before it is taken to be left
+/// behind by a crashed run
+pub const DEFAULT_STALE_LOCK_AFTER: Duration = Duration::from_secs(15 * 60);
+
 /// Migration manager
 pub struct MigrationManager {
     migrations_dir: PathBuf,
+    registered: Vec<Migration>,
+    stale_lock_after: Duration,
 }
 
 impl MigrationManager {
     pub fn new(migrations_dir: impl AsRef<Path>) -> Self {
         Self {
             migrations_dir: migrations_dir.as_ref().to_path_buf(),
+            registered: Vec::new(),
+            stale_lock_after: DEFAULT_STALE_LOCK_AFTER,
         }
     }
-    
+
+    /// How long a SQLite `_migrations_lock` row holds before another run may
+    /// clear it; longer than the slowest migration
+    pub fn with_stale_lock_after(mut self, after: Duration) -> Self {
+        self.stale_lock_after = after;
+        self
+    }
+
+    /// Add a migration defined in code; it runs in version order alongside the `.sql` files
+    pub fn register(&mut self, migration: Migration) -> &mut Self {
+        self.registered.push(migration);
+        self
+    }
+
     pub fn list_migrations(&self) -> Result<Vec<Migration>, std::io::Error> {
-        let mut migrations = Vec::new();
-        
+        let mut migrations = self.registered.clone();
+
         if !self.migrations_dir.exists() {
+            migrations.sort_by(|a, b| a.version.cmp(&b.version));
             return Ok(migrations);
         }
-        
+
         for entry in fs::read_dir(&self.migrations_dir)? {
             let entry = entry?;
             let path = entry.path();
-            
+
             if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("sql") {
                 if let Ok(migration) = Migration::from_file(&path) {
                     migrations.push(migration);
                 }
             }
         }
-        
+
         // Sort by version
         migrations.sort_by(|a, b| a.version.cmp(&b.version));
-        
+
         Ok(migrations)
     }
-    
+
     pub fn create_migration(&self, name: &str) -> Result<PathBuf, std::io::Error> {
-        let migration = Migration::new(name);
+        self.create_migration_with_sql(name, "", "")
+    }
+
+    /// Create a migration file with its up and down SQL filled in
+    pub fn create_migration_with_sql(&self, name: &str, up_sql: &str, down_sql: &str) -> Result<PathBuf, std::io::Error> {
+        let mut migration = Migration::new(name);
+        migration.up_sql = up_sql.to_string();
+        migration.down_sql = down_sql.to_string();
         migration.save(&self.migrations_dir)
     }
-    
+
+    /// Diff `models` against the live schema and write a migration for the differences
+    ///
+    /// Returns `None` when the schema already matches. The generated SQL targets
+    /// the connected database; review it before running, especially altered
+    /// column types and, with `options.drop_columns`, dropped columns.
+    pub async fn generate_migration(
+        &self,
+        db: &impl Database,
+        name: &str,
+        models: &[ModelSchema],
+        options: DiffOptions,
+    ) -> Result<Option<PathBuf>, MigrationError> {
+        let diff = diff_models(db, models, options).await?;
+        if diff.is_empty() {
+            return Ok(None);
+        }
+
+        let migration = diff.to_migration(name, db.db_type())?;
+        Ok(Some(self.create_migration_with_sql(name, &migration.up_sql, &migration.down_sql)?))
+    }
+
     /// Ensure migrations table exists
-    pub async fn ensure_migrations_table(&self, db: &impl crate::Database) -> crate::Result<()> {
-        let sql = r#"
-            CREATE TABLE IF NOT EXISTS _migrations (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                version TEXT NOT NULL UNIQUE,
-                applied_at INTEGER NOT NULL
-            )
-        "#;
+    pub async fn ensure_migrations_table(&self, db: &impl Database) -> crate::Result<()> {
+        let db = &connections::on_primary(db);
+        let sql = match db.db_type() {
+            DatabaseType::Postgres => r#"
+                CREATE TABLE IF NOT EXISTS _migrations (
+                    id BIGSERIAL PRIMARY KEY,
+                    version VARCHAR(255) NOT NULL UNIQUE,
+                    checksum VARCHAR(64),
+                    applied_at BIGINT NOT NULL
+                )
+            "#,
+            DatabaseType::MySql => r#"
+                CREATE TABLE IF NOT EXISTS _migrations (
+                    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+                    version VARCHAR(255) NOT NULL UNIQUE,
+                    checksum VARCHAR(64),
+                    applied_at BIGINT NOT NULL
+                )
+            "#,
+            DatabaseType::Sqlite => r#"
+                CREATE TABLE IF NOT EXISTS _migrations (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    version VARCHAR(255) NOT NULL UNIQUE,
+                    checksum VARCHAR(64),
+                    applied_at INTEGER NOT NULL
+                )
+            "#,
+        };
         db.execute(sql).await?;
+
+        // Tables created before checksums were tracked lack the column
+        if db.query_one("SELECT checksum FROM _migrations WHERE 1 = 0").await.is_err() {
+            db.execute("ALTER TABLE _migrations ADD COLUMN checksum VARCHAR(64)").await?;
+        }
         Ok(())
     }
-    
+
     /// Get list of applied migrations
-    pub async fn get_applied_migrations(&self, db: &impl crate::Database) -> crate::Result<Vec<String>> {
+    pub async fn get_applied_migrations(&self, db: &impl Database) -> crate::Result<Vec<String>> {
+        Ok(self
+            .applied_migrations(db)
+            .await?
+            .into_iter()
+            .map(|m| m.version)
+            .collect())
+    }
+
+    /// Get applied migrations with their stored checksums, oldest first
+    pub async fn applied_migrations(&self, db: &impl Database) -> crate::Result<Vec<AppliedMigration>> {
+        // A lagging replica would report applied migrations as pending
+        let db = &connections::on_primary(db);
         self.ensure_migrations_table(db).await?;
-        
-        let rows = db.query("SELECT version FROM _migrations ORDER BY version").await?;
-        let mut versions = Vec::new();
-        
+
+        let rows = db.query("SELECT version, checksum, applied_at FROM _migrations ORDER BY version").await?;
+        let mut applied = Vec::new();
+
         for row in rows {
             if let Ok(version) = row.try_get::<String, _>("version") {
-                versions.push(version);
+                applied.push(AppliedMigration {
+                    version,
+                    checksum: row.try_get::<Option<String>, _>("checksum").ok().flatten(),
+                    applied_at: row.try_get::<i64, _>("applied_at").unwrap_or_default(),
+                });
             }
         }
-        
-        Ok(versions)
+
+        Ok(applied)
     }
-    
+
     /// Mark migration as applied
-    pub async fn mark_migration_applied(&self, db: &impl crate::Database, version: &str) -> crate::Result<()> {
+    pub async fn mark_migration_applied(&self, db: &impl Database, version: &str) -> crate::Result<()> {
         self.ensure_migrations_table(db).await?;
-        
-        let timestamp = chrono::Utc::now().timestamp();
+
+        let checksum = self
+            .list_migrations()
+            .ok()
+            .and_then(|all| all.into_iter().find(|m| m.version == version))
+            .map(|m| m.checksum());
+        self.record_applied(db, version, checksum).await
+    }
+
+    async fn record_applied(&self, db: &impl Database, version: &str, checksum: Option<String>) -> crate::Result<()> {
+        let db_type = db.db_type();
         let sql = format!(
-            "INSERT INTO _migrations (version, applied_at) VALUES ('{}', {})",
-            version, timestamp
+            "INSERT INTO _migrations (version, checksum, applied_at) VALUES ({}, {}, {})",
+            db_type.placeholder(1),
+            db_type.placeholder(2),
+            db_type.placeholder(3),
         );
-        db.execute(&sql).await?;
+        let query = sqlx::query(&sql)
+            .bind(version.to_string())
+            .bind(checksum)
+            .bind(Utc::now().timestamp());
+        db.execute_query(query).await?;
         Ok(())
     }
-    
+
     /// Remove migration record (for rollback)
-    pub async fn mark_migration_reverted(&self, db: &impl crate::Database, version: &str) -> crate::Result<()> {
-        let sql = format!("DELETE FROM _migrations WHERE version = '{}'", version);
-        db.execute(&sql).await?;
+    pub async fn mark_migration_reverted(&self, db: &impl Database, version: &str) -> crate::Result<()> {
+        let sql = format!("DELETE FROM _migrations WHERE version = {}", db.db_type().placeholder(1));
+        db.execute_query(sqlx::query(&sql).bind(version.to_string())).await?;
         Ok(())
     }
-    
+
     /// Get pending migrations
-    pub async fn get_pending_migrations(&self, db: &impl crate::Database) -> Result<Vec<Migration>, Box<dyn std::error::Error>> {
+    pub async fn get_pending_migrations(&self, db: &impl Database) -> Result<Vec<Migration>, Box<dyn std::error::Error>> {
         let all_migrations = self.list_migrations()?;
         let applied = self.get_applied_migrations(db).await?;
-        
+
         let pending: Vec<Migration> = all_migrations
             .into_iter()
             .filter(|m| !applied.contains(&m.version))
             .collect();
-        
+
         Ok(pending)
     }
+
+    /// Applied migrations whose file changed since they ran
+    pub async fn modified_migrations(&self, db: &impl Database) -> Result<Vec<String>, MigrationError> {
+        let migrations = self.list_migrations()?;
+        let applied = self.applied_migrations(db).await?;
+
+        Ok(applied
+            .iter()
+            .filter_map(|record| {
+                let stored = record.checksum.as_ref()?;
+                let migration = migrations.iter().find(|m| m.version == record.version)?;
+                (&migration.checksum() != stored).then(|| record.version.clone())
+            })
+            .collect())
+    }
+
+    /// Apply all pending migrations, returning the versions that ran
+    ///
+    /// Refuses to run if an applied migration was edited afterwards.
+    pub async fn migrate(&self, db: &impl Database) -> Result<Vec<String>, MigrationError> {
+        self.locked(db, self.run_migrate(db, None)).await
+    }
+
+    /// Apply pending migrations in each tenant's schema, creating missing schemas
+    ///
+    /// Each schema keeps its own `_migrations` table. Returns the versions run
+    /// per tenant and stops at the first failure.
+    pub async fn migrate_tenants(
+        &self,
+        db: &DbPool,
+        tenants: &[Tenant],
+    ) -> Result<Vec<(Tenant, Vec<String>)>, MigrationError> {
+        if !db.uses_tenant_schemas() {
+            return Err(MigrationError::TenantSchemasDisabled);
+        }
+
+        let mut results = Vec::new();
+        for tenant in tenants {
+            db.execute(&format!("CREATE SCHEMA IF NOT EXISTS \"{}\"", tenant.schema())).await?;
+            let ran = tenancy::with_tenant(tenant.clone(), self.migrate(db)).await?;
+            results.push((tenant.clone(), ran));
+        }
+        Ok(results)
+    }
+
+    /// Revert the last `steps` applied migrations, newest first
+    pub async fn rollback(&self, db: &impl Database, steps: usize) -> Result<Vec<String>, MigrationError> {
+        self.locked(db, self.run_rollback(db, Some(steps), None)).await
+    }
+
+    /// Revert every applied migration
+    pub async fn reset(&self, db: &impl Database) -> Result<Vec<String>, MigrationError> {
+        self.locked(db, self.run_rollback(db, None, None)).await
+    }
+
+    /// Revert every applied migration, then apply them all again
+    pub async fn refresh(&self, db: &impl Database) -> Result<Vec<String>, MigrationError> {
+        self.locked(db, async {
+            self.run_rollback(db, None, None).await?;
+            self.run_migrate(db, None).await
+        })
+        .await
+    }
+
+    /// Migrate up or down so that `version` is the latest applied migration
+    pub async fn migrate_to(&self, db: &impl Database, version: &str) -> Result<Vec<String>, MigrationError> {
+        if !self.list_migrations()?.iter().any(|m| m.version == version) {
+            return Err(MigrationError::UnknownVersion(version.to_string()));
+        }
+
+        self.locked(db, async {
+            let mut changed = self.run_rollback(db, None, Some(version)).await?;
+            changed.extend(self.run_migrate(db, Some(version)).await?);
+            Ok(changed)
+        })
+        .await
+    }
+
+    async fn run_migrate(&self, db: &impl Database, up_to: Option<&str>) -> Result<Vec<String>, MigrationError> {
+        if let Some(version) = self.modified_migrations(db).await?.into_iter().next() {
+            return Err(MigrationError::ChecksumMismatch(version));
+        }
+
+        let applied = self.get_applied_migrations(db).await?;
+        let mut ran = Vec::new();
+
+        for migration in self.list_migrations()? {
+            if applied.contains(&migration.version) {
+                continue;
+            }
+            if up_to.is_some_and(|target| migration.version.as_str() > target) {
+                break;
+            }
+
+            self.apply(db, &migration).await?;
+            ran.push(migration.version);
+        }
+
+        Ok(ran)
+    }
+
+    /// Roll back `steps` migrations, or everything newer than `down_to`, or everything
+    async fn run_rollback(
+        &self,
+        db: &impl Database,
+        steps: Option<usize>,
+        down_to: Option<&str>,
+    ) -> Result<Vec<String>, MigrationError> {
+        let migrations = self.list_migrations()?;
+        let mut applied = self.get_applied_migrations(db).await?;
+        applied.reverse();
+
+        if let Some(target) = down_to {
+            applied.retain(|version| version.as_str() > target);
+        }
+        if let Some(steps) = steps {
+            applied.truncate(steps);
+        }
+
+        let mut reverted = Vec::new();
+        for version in applied {
+            let migration = migrations
+                .iter()
+                .find(|m| m.version == version)
+                .ok_or_else(|| MigrationError::MissingMigration(version.clone()))?;
+
+            self.revert(db, migration).await?;
+            reverted.push(version);
+        }
+
+        Ok(reverted)
+    }
+
+    /// Run a migration's up SQL and record it, atomically where the dialect allows
+    async fn apply(&self, db: &impl Database, migration: &Migration) -> Result<(), MigrationError> {
+        let statements = migration.up_statements(db.db_type())?;
+
+        if db.db_type().supports_transactional_ddl() && !migration.no_transaction {
+            db.transaction(|tx| async move {
+                for statement in &statements {
+                    tx.execute(statement).await?;
+                }
+                self.record_applied(&tx, &migration.version, Some(migration.checksum())).await
+            })
+            .await?;
+        } else {
+            // MySQL commits implicitly after each DDL statement, and
+            // `no_transaction` statements can't run in one
+            for statement in &statements {
+                db.execute(statement).await?;
+            }
+            self.record_applied(db, &migration.version, Some(migration.checksum())).await?;
+        }
+        Ok(())
+    }
+
+    async fn revert(&self, db: &impl Database, migration: &Migration) -> Result<(), MigrationError> {
+        let statements = migration.down_statements(db.db_type())?;
+        if statements.is_empty() {
+            return Err(MigrationError::NoDownMigration(migration.version.clone()));
+        }
+
+        if db.db_type().supports_transactional_ddl() && !migration.no_transaction {
+            db.transaction(|tx| async move {
+                for statement in &statements {
+                    tx.execute(statement).await?;
+                }
+                self.mark_migration_reverted(&tx, &migration.version).await
+            })
+            .await?;
+        } else {
+            for statement in &statements {
+                db.execute(statement).await?;
+            }
+            self.mark_migration_reverted(db, &migration.version).await?;
+        }
+        Ok(())
+    }
+
+    /// Run `work` while holding the migration lock, releasing it afterwards
+    /// whether or not `work` succeeded
+    ///
+    /// `work` runs on `db` itself, so each migration gets a transaction of its own.
+    async fn locked<T>(
+        &self,
+        db: &impl Database,
+        work: impl std::future::Future<Output = Result<T, MigrationError>>,
+    ) -> Result<T, MigrationError> {
+        self.ensure_migrations_table(db).await?;
+        let lock = self.acquire_lock(db).await?;
+        let result = work.await;
+        let released = self.release_lock(db, lock).await;
+        let value = result?;
+        released?;
+        Ok(value)
+    }
+
+    async fn acquire_lock(&self, db: &impl Database) -> Result<MigrationLock, MigrationError> {
+        let options = match db.db_type() {
+            DatabaseType::Postgres | DatabaseType::MySql => db.connect_options(),
+            // SQLite has no advisory locks, and a new connection wouldn't see an in-memory database
+            DatabaseType::Sqlite => None,
+        };
+        let Some(options) = options else {
+            return self.lock_table(db).await;
+        };
+
+        // A connection outside the pool holds the lock, so the migrations
+        // never wait for it to come back
+        let mut conn = options.connect().await?;
+        let acquired = match db.db_type() {
+            // Waits for other instances to finish
+            DatabaseType::Postgres => sqlx::query(&format!("SELECT pg_advisory_lock({})", ADVISORY_LOCK_KEY))
+                .execute(&mut conn)
+                .await
+                .map(|_| true),
+            _ => sqlx::query(&format!("SELECT GET_LOCK('{}', {}) AS acquired", MYSQL_LOCK_NAME, MYSQL_LOCK_TIMEOUT_SECS))
+                .fetch_one(&mut conn)
+                .await
+                .map(|row| row.try_get::<i64, _>("acquired").unwrap_or(0) == 1),
+        };
+        match acquired {
+            Ok(true) => Ok(MigrationLock::Session(conn)),
+            Ok(false) => {
+                let _ = conn.close().await;
+                Err(MigrationError::Locked)
+            }
+            Err(err) => {
+                let _ = conn.close().await;
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Take the `_migrations_lock` row, first clearing one older than the stale-lock timeout
+    async fn lock_table(&self, db: &impl Database) -> Result<MigrationLock, MigrationError> {
+        ensure_lock_table(db).await?;
+
+        let now = Utc::now().timestamp();
+        let stale_before = now.saturating_sub(i64::try_from(self.stale_lock_after.as_secs()).unwrap_or(i64::MAX));
+        db.execute(&format!("DELETE FROM _migrations_lock WHERE id = 1 AND locked_at < {}", stale_before)).await?;
+
+        match db.execute(&format!("INSERT INTO _migrations_lock (id, locked_at) VALUES (1, {})", now)).await {
+            Ok(_) => Ok(MigrationLock::Table),
+            Err(err) if err.as_database_error().is_some_and(|e| e.kind() == ErrorKind::UniqueViolation) => {
+                Err(MigrationError::Locked)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn release_lock(&self, db: &impl Database, lock: MigrationLock) -> Result<(), MigrationError> {
+        match lock {
+            MigrationLock::Session(mut conn) => {
+                let sql = match db.db_type() {
+                    DatabaseType::MySql => format!("SELECT RELEASE_LOCK('{}')", MYSQL_LOCK_NAME),
+                    _ => format!("SELECT pg_advisory_unlock({})", ADVISORY_LOCK_KEY),
+                };
+                let unlocked = sqlx::query(&sql).execute(&mut conn).await;
+                // Ending the session releases the lock even if unlocking failed
+                conn.close().await?;
+                unlocked?;
+            }
+            MigrationLock::Table => {
+                db.execute("DELETE FROM _migrations_lock WHERE id = 1").await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Clear a `_migrations_lock` row left behind by a crashed process
+    ///
+    /// Rows older than the stale-lock timeout are cleared on the next run
+    /// anyway. Postgres and MySQL locks end with the session that took them,
+    /// so there is nothing to clear for those.
+    pub async fn force_unlock(&self, db: &impl Database) -> crate::Result<()> {
+        ensure_lock_table(db).await?;
+        db.execute("DELETE FROM _migrations_lock WHERE id = 1").await?;
+        Ok(())
+    }
 }
+
+async fn ensure_lock_table(db: &impl Database) -> crate::Result<()> {
+    db.execute("CREATE TABLE IF NOT EXISTS _migrations_lock (id INTEGER PRIMARY KEY, locked_at BIGINT NOT NULL)")
+        .await?;
+    Ok(())
+}
+
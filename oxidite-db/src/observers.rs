@@ -0,0 +1,94 @@
+//! Model observers
+//!
+//! Observers subscribe to a model's lifecycle events from outside the model type,
+//! e.g. to bust cache keys or publish realtime events. They run after the model's
+//! own hooks, and an error from `creating`, `updating` or `deleting` aborts the
+//! operation just like a `before_*` hook.
+//!
+//! ```ignore
+//! struct UserCacheBuster { cache: Arc<MemoryCache> }
+//!
+//! #[async_trait]
+//! impl Observer<User> for UserCacheBuster {
+//!     async fn updated(&self, user: &User) -> Result<()> {
+//!         let _ = self.cache.delete(&format!("user:{}", user.id)).await;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! observers::observe::<User>(UserCacheBuster { cache });
+//! ```
+
+use crate::{Model, Result};
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Receives lifecycle events for a model type
+#[async_trait]
+pub trait Observer<M: Model>: Send + Sync + 'static {
+    async fn creating(&self, _model: &mut M) -> Result<()> {
+        Ok(())
+    }
+
+    async fn created(&self, _model: &M) -> Result<()> {
+        Ok(())
+    }
+
+    async fn updating(&self, _model: &mut M) -> Result<()> {
+        Ok(())
+    }
+
+    async fn updated(&self, _model: &M) -> Result<()> {
+        Ok(())
+    }
+
+    async fn deleting(&self, _model: &M) -> Result<()> {
+        Ok(())
+    }
+
+    async fn deleted(&self, _model: &M) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Each entry holds an `Arc<dyn Observer<M>>` for the model type keyed by `TypeId::of::<M>()`
+type Registry = HashMap<TypeId, Vec<Box<dyn Any + Send + Sync>>>;
+
+static OBSERVERS: Lazy<RwLock<Registry>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Register an observer for a model type
+pub fn observe<M: Model + 'static>(observer: impl Observer<M>) {
+    let observer: Arc<dyn Observer<M>> = Arc::new(observer);
+    OBSERVERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(TypeId::of::<M>())
+        .or_default()
+        .push(Box::new(observer));
+}
+
+/// Observers registered for a model type, in registration order
+pub fn observers_for<M: Model + 'static>() -> Vec<Arc<dyn Observer<M>>> {
+    OBSERVERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&TypeId::of::<M>())
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| entry.downcast_ref::<Arc<dyn Observer<M>>>().cloned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Remove all observers for a model type
+pub fn clear<M: Model + 'static>() {
+    OBSERVERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&TypeId::of::<M>());
+}
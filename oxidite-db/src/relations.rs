@@ -1,6 +1,19 @@
-use crate::{Database, Model, Result};
+use crate::{connections, json, tenancy, Database, Model, Result};
 use std::marker::PhantomData;
 
+/// A relation whose child records point at the parent through a foreign key
+///
+/// Lets [`FactoryBuilder::has`](crate::FactoryBuilder::has) and
+/// [`FactoryBuilder::for_parent`](crate::FactoryBuilder::for_parent) link
+/// records through a relation such as `User::posts`.
+pub trait ForeignKey<P, C> {
+    /// The column on the child that holds the parent's id
+    fn foreign_key(&self) -> &str;
+
+    /// The parent's id
+    fn parent_id(&self) -> i64;
+}
+
 /// Represents a one-to-many relationship
 pub struct HasMany<P, C> {
     parent_id: i64,
@@ -23,22 +36,37 @@ where
 
     /// Fetch all related records
     pub async fn get(&self, db: &impl Database) -> Result<Vec<C>> {
-        let query = format!(
-            "SELECT * FROM {} WHERE {} = {}",
+        let db = &connections::route::<C, _>(db);
+        let mut query = format!(
+            "SELECT {} FROM {} WHERE {} = {}",
+            json::select_list(db.db_type(), C::fields(), C::json_columns()),
             C::table_name(),
             self.foreign_key,
             self.parent_id
         );
+        if let Some(tenant) = tenancy::condition::<C>()? {
+            query.push_str(&format!(" AND {}", tenant));
+        }
         let rows = db.query(&query).await?;
         
         let mut models = Vec::new();
         for row in rows {
-            models.push(C::from_row(&row)?);
+            models.push(C::hydrate(&row)?);
         }
         Ok(models)
     }
 }
 
+impl<P, C> ForeignKey<P, C> for HasMany<P, C> {
+    fn foreign_key(&self) -> &str {
+        &self.foreign_key
+    }
+
+    fn parent_id(&self) -> i64 {
+        self.parent_id
+    }
+}
+
 /// Represents a one-to-one relationship (owned)
 pub struct HasOne<P, C> {
     parent_id: i64,
@@ -61,21 +89,36 @@ where
 
     /// Fetch the related record
     pub async fn get(&self, db: &impl Database) -> Result<Option<C>> {
-        let query = format!(
-            "SELECT * FROM {} WHERE {} = {}",
+        let db = &connections::route::<C, _>(db);
+        let mut query = format!(
+            "SELECT {} FROM {} WHERE {} = {}",
+            json::select_list(db.db_type(), C::fields(), C::json_columns()),
             C::table_name(),
             self.foreign_key,
             self.parent_id
         );
+        if let Some(tenant) = tenancy::condition::<C>()? {
+            query.push_str(&format!(" AND {}", tenant));
+        }
         let row = db.query_one(&query).await?;
         
         match row {
-            Some(row) => Ok(Some(C::from_row(&row)?)),
+            Some(row) => Ok(Some(C::hydrate(&row)?)),
             None => Ok(None),
         }
     }
 }
 
+impl<P, C> ForeignKey<P, C> for HasOne<P, C> {
+    fn foreign_key(&self) -> &str {
+        &self.foreign_key
+    }
+
+    fn parent_id(&self) -> i64 {
+        self.parent_id
+    }
+}
+
 /// Represents a belongs-to relationship (inverse of HasMany/HasOne)
 pub struct BelongsTo<C, P> {
     foreign_key_value: i64,
@@ -96,6 +139,7 @@ where
 
     /// Fetch the parent record
     pub async fn get(&self, db: &impl Database) -> Result<Option<P>> {
+        let db = &connections::route::<P, _>(db);
         P::find(db, self.foreign_key_value).await
     }
 }
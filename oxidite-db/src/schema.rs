@@ -0,0 +1,734 @@
+//! Dialect-neutral schema builder for migrations
+//!
+//! Describes tables in Rust and renders the DDL for whichever backend the
+//! migration runs against, so the same migration works on SQLite in tests and
+//! Postgres or MySQL in production.
+//!
+//! ```ignore
+//! let up = vec![
+//!     Schema::create("users", |t| {
+//!         t.id();
+//!         t.string("email").unique();
+//!         t.string("name").nullable();
+//!         t.timestamps();
+//!         t.soft_deletes();
+//!     }),
+//!     Schema::create("posts", |t| {
+//!         t.id();
+//!         t.foreign_id("user_id").references("users", "id").on_delete(ReferentialAction::Cascade);
+//!         t.string("title");
+//!         t.boolean("published").default(false);
+//!         t.index(&["user_id", "published"]);
+//!     }),
+//! ];
+//! let down = vec![Schema::drop("posts"), Schema::drop("users")];
+//!
+//! manager.register(Migration::schema("20240101120000_create_users", up, down));
+//! ```
+
+use crate::DatabaseType;
+
+/// A single schema change
+#[derive(Debug, Clone)]
+pub struct Schema {
+    op: SchemaOp,
+}
+
+#[derive(Debug, Clone)]
+enum SchemaOp {
+    Create(Blueprint),
+    Alter(Blueprint),
+    Drop { table: String, if_exists: bool },
+    Rename { from: String, to: String },
+    Raw(String),
+}
+
+impl Schema {
+    /// Create a new table
+    pub fn create(table: &str, f: impl FnOnce(&mut Blueprint)) -> Self {
+        let mut blueprint = Blueprint::new(table);
+        f(&mut blueprint);
+        Self { op: SchemaOp::Create(blueprint) }
+    }
+
+    /// Add, drop or rename columns and indexes on an existing table
+    pub fn alter(table: &str, f: impl FnOnce(&mut Blueprint)) -> Self {
+        let mut blueprint = Blueprint::new(table);
+        f(&mut blueprint);
+        Self { op: SchemaOp::Alter(blueprint) }
+    }
+
+    pub fn drop(table: &str) -> Self {
+        Self { op: SchemaOp::Drop { table: table.to_string(), if_exists: false } }
+    }
+
+    pub fn drop_if_exists(table: &str) -> Self {
+        Self { op: SchemaOp::Drop { table: table.to_string(), if_exists: true } }
+    }
+
+    pub fn rename(from: &str, to: &str) -> Self {
+        Self { op: SchemaOp::Rename { from: from.to_string(), to: to.to_string() } }
+    }
+
+    /// Raw SQL passed through unchanged, for anything the builder doesn't cover
+    pub fn raw(sql: &str) -> Self {
+        Self { op: SchemaOp::Raw(sql.to_string()) }
+    }
+
+    /// Render the DDL statements for a backend, or why the backend can't make the change
+    pub fn to_sql(&self, db_type: DatabaseType) -> Result<Vec<String>, SchemaError> {
+        Ok(match &self.op {
+            SchemaOp::Create(blueprint) => blueprint.create_sql(db_type),
+            SchemaOp::Alter(blueprint) => blueprint.alter_sql(db_type)?,
+            SchemaOp::Drop { table, if_exists } => {
+                let if_exists = if *if_exists { "IF EXISTS " } else { "" };
+                vec![format!("DROP TABLE {}{}", if_exists, table)]
+            }
+            SchemaOp::Rename { from, to } => vec![format!("ALTER TABLE {} RENAME TO {}", from, to)],
+            SchemaOp::Raw(sql) => vec![sql.clone()],
+        })
+    }
+}
+
+/// A change the target database can't make
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum SchemaError {
+    #[error(
+        "SQLite cannot add a foreign key to existing column {table}.{column}; \
+         declare it with `references` on a new column instead"
+    )]
+    SqliteForeignKey { table: String, column: String },
+}
+
+/// Column types understood by the schema builder
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnType {
+    /// Auto-incrementing 32-bit primary key
+    Increments,
+    /// Auto-incrementing 64-bit primary key
+    BigIncrements,
+    SmallInteger,
+    Integer,
+    BigInteger,
+    Float,
+    Double,
+    Decimal { precision: u8, scale: u8 },
+    Boolean,
+    Char(u32),
+    String(u32),
+    Text,
+    Date,
+    Time,
+    DateTime,
+    Timestamp,
+    Json,
+    Uuid,
+    Binary,
+}
+
+impl ColumnType {
+    fn is_auto_increment(&self) -> bool {
+        matches!(self, ColumnType::Increments | ColumnType::BigIncrements)
+    }
+
+    /// SQL type name for a backend
+    pub fn to_sql(&self, db_type: DatabaseType) -> String {
+        use DatabaseType::*;
+
+        match (self, db_type) {
+            (ColumnType::Increments, Postgres) => "SERIAL".into(),
+            (ColumnType::Increments, MySql) => "INT AUTO_INCREMENT".into(),
+            (ColumnType::BigIncrements, Postgres) => "BIGSERIAL".into(),
+            (ColumnType::BigIncrements, MySql) => "BIGINT AUTO_INCREMENT".into(),
+            // SQLite only auto-increments an INTEGER PRIMARY KEY
+            (ColumnType::Increments | ColumnType::BigIncrements, Sqlite) => "INTEGER".into(),
+            (ColumnType::SmallInteger, Sqlite) => "INTEGER".into(),
+            (ColumnType::SmallInteger, _) => "SMALLINT".into(),
+            (ColumnType::Integer, MySql) => "INT".into(),
+            (ColumnType::Integer, _) => "INTEGER".into(),
+            (ColumnType::BigInteger, Sqlite) => "INTEGER".into(),
+            (ColumnType::BigInteger, _) => "BIGINT".into(),
+            (ColumnType::Float, MySql) => "FLOAT".into(),
+            (ColumnType::Float, _) => "REAL".into(),
+            (ColumnType::Double, Postgres) => "DOUBLE PRECISION".into(),
+            (ColumnType::Double, MySql) => "DOUBLE".into(),
+            (ColumnType::Double, Sqlite) => "REAL".into(),
+            (ColumnType::Decimal { precision, scale }, MySql) => format!("DECIMAL({}, {})", precision, scale),
+            (ColumnType::Decimal { precision, scale }, _) => format!("NUMERIC({}, {})", precision, scale),
+            (ColumnType::Boolean, _) => "BOOLEAN".into(),
+            (ColumnType::Char(len), _) => format!("CHAR({})", len),
+            (ColumnType::String(len), _) => format!("VARCHAR({})", len),
+            (ColumnType::Text, _) => "TEXT".into(),
+            (ColumnType::Date, Sqlite) => "TEXT".into(),
+            (ColumnType::Date, _) => "DATE".into(),
+            (ColumnType::Time, Sqlite) => "TEXT".into(),
+            (ColumnType::Time, _) => "TIME".into(),
+            (ColumnType::DateTime | ColumnType::Timestamp, Sqlite) => "TEXT".into(),
+            (ColumnType::DateTime, MySql) => "DATETIME".into(),
+            (ColumnType::DateTime, Postgres) => "TIMESTAMP".into(),
+            (ColumnType::Timestamp, MySql) => "TIMESTAMP".into(),
+            (ColumnType::Timestamp, Postgres) => "TIMESTAMPTZ".into(),
+            (ColumnType::Json, Postgres) => "JSONB".into(),
+            (ColumnType::Json, MySql) => "JSON".into(),
+            (ColumnType::Json, Sqlite) => "TEXT".into(),
+            (ColumnType::Uuid, Postgres) => "UUID".into(),
+            (ColumnType::Uuid, MySql) => "CHAR(36)".into(),
+            (ColumnType::Uuid, Sqlite) => "TEXT".into(),
+            (ColumnType::Binary, Postgres) => "BYTEA".into(),
+            (ColumnType::Binary, _) => "BLOB".into(),
+        }
+    }
+}
+
+/// Default value for a column
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnDefault {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    /// SQL expression such as `CURRENT_TIMESTAMP`, inserted verbatim
+    Raw(String),
+}
+
+impl ColumnDefault {
+    fn to_sql(&self, db_type: DatabaseType) -> String {
+        match self {
+            ColumnDefault::String(s) => format!("'{}'", s.replace('\'', "''")),
+            ColumnDefault::Integer(n) => n.to_string(),
+            ColumnDefault::Float(n) => n.to_string(),
+            ColumnDefault::Boolean(b) => match (db_type, b) {
+                (DatabaseType::Postgres, true) => "TRUE".into(),
+                (DatabaseType::Postgres, false) => "FALSE".into(),
+                (_, true) => "1".into(),
+                (_, false) => "0".into(),
+            },
+            ColumnDefault::Raw(expr) => expr.clone(),
+        }
+    }
+}
+
+impl From<&str> for ColumnDefault {
+    fn from(value: &str) -> Self {
+        ColumnDefault::String(value.to_string())
+    }
+}
+
+impl From<String> for ColumnDefault {
+    fn from(value: String) -> Self {
+        ColumnDefault::String(value)
+    }
+}
+
+impl From<i32> for ColumnDefault {
+    fn from(value: i32) -> Self {
+        ColumnDefault::Integer(value as i64)
+    }
+}
+
+impl From<i64> for ColumnDefault {
+    fn from(value: i64) -> Self {
+        ColumnDefault::Integer(value)
+    }
+}
+
+impl From<f64> for ColumnDefault {
+    fn from(value: f64) -> Self {
+        ColumnDefault::Float(value)
+    }
+}
+
+impl From<bool> for ColumnDefault {
+    fn from(value: bool) -> Self {
+        ColumnDefault::Boolean(value)
+    }
+}
+
+/// What happens to referencing rows when the referenced row changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferentialAction {
+    Cascade,
+    SetNull,
+    Restrict,
+    NoAction,
+}
+
+impl ReferentialAction {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            ReferentialAction::Cascade => "CASCADE",
+            ReferentialAction::SetNull => "SET NULL",
+            ReferentialAction::Restrict => "RESTRICT",
+            ReferentialAction::NoAction => "NO ACTION",
+        }
+    }
+}
+
+/// A column in a `create` or `alter` blueprint
+#[derive(Debug, Clone)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub column_type: ColumnType,
+    pub nullable: bool,
+    pub default: Option<ColumnDefault>,
+    pub primary: bool,
+    pub unique: bool,
+    pub index: bool,
+    pub references: Option<ForeignKeyDefinition>,
+}
+
+impl ColumnDefinition {
+    fn new(name: &str, column_type: ColumnType) -> Self {
+        Self {
+            name: name.to_string(),
+            primary: column_type.is_auto_increment(),
+            column_type,
+            nullable: false,
+            default: None,
+            unique: false,
+            index: false,
+            references: None,
+        }
+    }
+
+    /// Allow NULL (columns are NOT NULL by default)
+    pub fn nullable(&mut self) -> &mut Self {
+        self.nullable = true;
+        self
+    }
+
+    pub fn default(&mut self, value: impl Into<ColumnDefault>) -> &mut Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    /// Default to a raw SQL expression
+    pub fn default_raw(&mut self, expr: &str) -> &mut Self {
+        self.default = Some(ColumnDefault::Raw(expr.to_string()));
+        self
+    }
+
+    pub fn primary(&mut self) -> &mut Self {
+        self.primary = true;
+        self
+    }
+
+    /// Add a unique index on this column
+    pub fn unique(&mut self) -> &mut Self {
+        self.unique = true;
+        self
+    }
+
+    /// Add a plain index on this column
+    pub fn index(&mut self) -> &mut Self {
+        self.index = true;
+        self
+    }
+
+    /// Add a foreign key constraint to `table(column)`
+    pub fn references(&mut self, table: &str, column: &str) -> &mut Self {
+        self.references = Some(ForeignKeyDefinition::new(&self.name, table, column));
+        self
+    }
+
+    /// Set the `ON DELETE` action of this column's foreign key
+    pub fn on_delete(&mut self, action: ReferentialAction) -> &mut Self {
+        if let Some(fk) = self.references.as_mut() {
+            fk.on_delete = Some(action);
+        }
+        self
+    }
+
+    /// Set the `ON UPDATE` action of this column's foreign key
+    pub fn on_update(&mut self, action: ReferentialAction) -> &mut Self {
+        if let Some(fk) = self.references.as_mut() {
+            fk.on_update = Some(action);
+        }
+        self
+    }
+
+    fn to_sql(&self, db_type: DatabaseType, inline_primary: bool) -> String {
+        let mut sql = format!("{} {}", self.name, self.column_type.to_sql(db_type));
+
+        if self.column_type.is_auto_increment() {
+            return match db_type {
+                DatabaseType::Sqlite => format!("{} PRIMARY KEY AUTOINCREMENT", sql),
+                _ => format!("{} PRIMARY KEY", sql),
+            };
+        }
+
+        if !self.nullable {
+            sql.push_str(" NOT NULL");
+        }
+        if let Some(default) = &self.default {
+            sql.push_str(&format!(" DEFAULT {}", default.to_sql(db_type)));
+        }
+        if self.primary && inline_primary {
+            sql.push_str(" PRIMARY KEY");
+        }
+        sql
+    }
+}
+
+/// A foreign key constraint
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignKeyDefinition {
+    pub column: String,
+    pub foreign_table: String,
+    pub foreign_column: String,
+    pub on_delete: Option<ReferentialAction>,
+    pub on_update: Option<ReferentialAction>,
+}
+
+impl ForeignKeyDefinition {
+    fn new(column: &str, foreign_table: &str, foreign_column: &str) -> Self {
+        Self {
+            column: column.to_string(),
+            foreign_table: foreign_table.to_string(),
+            foreign_column: foreign_column.to_string(),
+            on_delete: None,
+            on_update: None,
+        }
+    }
+
+    pub fn on_delete(&mut self, action: ReferentialAction) -> &mut Self {
+        self.on_delete = Some(action);
+        self
+    }
+
+    pub fn on_update(&mut self, action: ReferentialAction) -> &mut Self {
+        self.on_update = Some(action);
+        self
+    }
+
+    fn name(&self, table: &str) -> String {
+        format!("{}_{}_foreign", table, self.column)
+    }
+
+    /// `REFERENCES table (column) [ON DELETE ..] [ON UPDATE ..]`
+    fn references_sql(&self) -> String {
+        let mut sql = format!("REFERENCES {} ({})", self.foreign_table, self.foreign_column);
+        if let Some(action) = self.on_delete {
+            sql.push_str(&format!(" ON DELETE {}", action.as_sql()));
+        }
+        if let Some(action) = self.on_update {
+            sql.push_str(&format!(" ON UPDATE {}", action.as_sql()));
+        }
+        sql
+    }
+
+    fn constraint_sql(&self, table: &str) -> String {
+        format!(
+            "CONSTRAINT {} FOREIGN KEY ({}) {}",
+            self.name(table),
+            self.column,
+            self.references_sql()
+        )
+    }
+}
+
+/// An index over one or more columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexDefinition {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+}
+
+impl IndexDefinition {
+    pub(crate) fn new(table: &str, columns: &[&str], unique: bool) -> Self {
+        let suffix = if unique { "unique" } else { "index" };
+        Self {
+            name: format!("{}_{}_{}", table, columns.join("_"), suffix),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            unique,
+        }
+    }
+
+    fn create_sql(&self, table: &str) -> String {
+        let unique = if self.unique { "UNIQUE " } else { "" };
+        format!("CREATE {}INDEX {} ON {} ({})", unique, self.name, table, self.columns.join(", "))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum AlterCommand {
+    DropColumn(String),
+    RenameColumn { from: String, to: String },
+    DropIndex(String),
+}
+
+/// Columns, indexes and constraints of a table being created or altered
+#[derive(Debug, Clone)]
+pub struct Blueprint {
+    table: String,
+    columns: Vec<ColumnDefinition>,
+    indexes: Vec<IndexDefinition>,
+    foreign_keys: Vec<ForeignKeyDefinition>,
+    primary_key: Option<Vec<String>>,
+    commands: Vec<AlterCommand>,
+}
+
+impl Blueprint {
+    fn new(table: &str) -> Self {
+        Self {
+            table: table.to_string(),
+            columns: Vec::new(),
+            indexes: Vec::new(),
+            foreign_keys: Vec::new(),
+            primary_key: None,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// Add a column of any type
+    pub fn column(&mut self, name: &str, column_type: ColumnType) -> &mut ColumnDefinition {
+        self.columns.push(ColumnDefinition::new(name, column_type));
+        self.columns.last_mut().unwrap()
+    }
+
+    /// Auto-incrementing `id BIGINT` primary key, matching `Model::find(id: i64)`
+    pub fn id(&mut self) -> &mut ColumnDefinition {
+        self.big_increments("id")
+    }
+
+    pub fn increments(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Increments)
+    }
+
+    pub fn big_increments(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::BigIncrements)
+    }
+
+    pub fn small_integer(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::SmallInteger)
+    }
+
+    pub fn integer(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Integer)
+    }
+
+    pub fn big_integer(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::BigInteger)
+    }
+
+    /// `BIGINT` column for referencing another table's `id`
+    pub fn foreign_id(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.big_integer(name)
+    }
+
+    pub fn float(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Float)
+    }
+
+    pub fn double(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Double)
+    }
+
+    pub fn decimal(&mut self, name: &str, precision: u8, scale: u8) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Decimal { precision, scale })
+    }
+
+    pub fn boolean(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Boolean)
+    }
+
+    pub fn char(&mut self, name: &str, length: u32) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Char(length))
+    }
+
+    /// `VARCHAR(255)` column
+    pub fn string(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.string_with_length(name, 255)
+    }
+
+    pub fn string_with_length(&mut self, name: &str, length: u32) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::String(length))
+    }
+
+    pub fn text(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Text)
+    }
+
+    pub fn date(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Date)
+    }
+
+    pub fn time(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Time)
+    }
+
+    pub fn datetime(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::DateTime)
+    }
+
+    pub fn timestamp(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Timestamp)
+    }
+
+    pub fn json(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Json)
+    }
+
+    pub fn uuid(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Uuid)
+    }
+
+    pub fn binary(&mut self, name: &str) -> &mut ColumnDefinition {
+        self.column(name, ColumnType::Binary)
+    }
+
+    /// `created_at` and `updated_at` as Unix timestamps, as set by `Model::create`/`update`
+    pub fn timestamps(&mut self) {
+        self.big_integer("created_at").default(0);
+        self.big_integer("updated_at").default(0);
+    }
+
+    /// Nullable `deleted_at` Unix timestamp used by soft deletes
+    pub fn soft_deletes(&mut self) {
+        self.big_integer("deleted_at").nullable();
+    }
+
+    /// Composite primary key
+    pub fn primary(&mut self, columns: &[&str]) {
+        self.primary_key = Some(columns.iter().map(|c| c.to_string()).collect());
+    }
+
+    /// Index over one or more columns
+    pub fn index(&mut self, columns: &[&str]) -> &mut IndexDefinition {
+        self.indexes.push(IndexDefinition::new(&self.table, columns, false));
+        self.indexes.last_mut().unwrap()
+    }
+
+    /// Unique index over one or more columns
+    pub fn unique(&mut self, columns: &[&str]) -> &mut IndexDefinition {
+        self.indexes.push(IndexDefinition::new(&self.table, columns, true));
+        self.indexes.last_mut().unwrap()
+    }
+
+    /// Foreign key on an existing column
+    pub fn foreign(&mut self, column: &str, foreign_table: &str, foreign_column: &str) -> &mut ForeignKeyDefinition {
+        self.foreign_keys.push(ForeignKeyDefinition::new(column, foreign_table, foreign_column));
+        self.foreign_keys.last_mut().unwrap()
+    }
+
+    pub fn drop_column(&mut self, name: &str) {
+        self.commands.push(AlterCommand::DropColumn(name.to_string()));
+    }
+
+    pub fn rename_column(&mut self, from: &str, to: &str) {
+        self.commands.push(AlterCommand::RenameColumn { from: from.to_string(), to: to.to_string() });
+    }
+
+    /// Drop an index by name (see `IndexDefinition::name` for the generated names)
+    pub fn drop_index(&mut self, name: &str) {
+        self.commands.push(AlterCommand::DropIndex(name.to_string()));
+    }
+
+    /// Indexes from `index()`/`unique()` plus those declared on columns
+    fn all_indexes(&self) -> Vec<IndexDefinition> {
+        let column_indexes = self.columns.iter().flat_map(|col| {
+            let unique = col.unique.then(|| IndexDefinition::new(&self.table, &[&col.name], true));
+            let plain = col.index.then(|| IndexDefinition::new(&self.table, &[&col.name], false));
+            unique.into_iter().chain(plain)
+        });
+        column_indexes.chain(self.indexes.iter().cloned()).collect()
+    }
+
+    fn create_sql(&self, db_type: DatabaseType) -> Vec<String> {
+        let composite_primary = self.primary_key.is_some()
+            || self.columns.iter().filter(|c| c.primary).count() > 1;
+
+        let mut definitions: Vec<String> = self
+            .columns
+            .iter()
+            .map(|col| col.to_sql(db_type, !composite_primary))
+            .collect();
+
+        if composite_primary {
+            let columns = self.primary_key.clone().unwrap_or_else(|| {
+                self.columns.iter().filter(|c| c.primary).map(|c| c.name.clone()).collect()
+            });
+            definitions.push(format!("PRIMARY KEY ({})", columns.join(", ")));
+        }
+
+        let foreign_keys = self
+            .columns
+            .iter()
+            .filter_map(|col| col.references.as_ref())
+            .chain(self.foreign_keys.iter());
+        for fk in foreign_keys {
+            definitions.push(fk.constraint_sql(&self.table));
+        }
+
+        let mut statements = vec![format!(
+            "CREATE TABLE {} (\n    {}\n)",
+            self.table,
+            definitions.join(",\n    ")
+        )];
+        statements.extend(self.all_indexes().iter().map(|idx| idx.create_sql(&self.table)));
+        statements
+    }
+
+    fn alter_sql(&self, db_type: DatabaseType) -> Result<Vec<String>, SchemaError> {
+        let mut statements = Vec::new();
+
+        // Drop indexes before the columns they cover
+        for command in &self.commands {
+            if let AlterCommand::DropIndex(name) = command {
+                statements.push(match db_type {
+                    DatabaseType::MySql => format!("DROP INDEX {} ON {}", name, self.table),
+                    _ => format!("DROP INDEX {}", name),
+                });
+            }
+        }
+
+        for command in &self.commands {
+            match command {
+                AlterCommand::DropColumn(name) => {
+                    statements.push(format!("ALTER TABLE {} DROP COLUMN {}", self.table, name));
+                }
+                AlterCommand::RenameColumn { from, to } => {
+                    statements.push(format!("ALTER TABLE {} RENAME COLUMN {} TO {}", self.table, from, to));
+                }
+                AlterCommand::DropIndex(_) => {}
+            }
+        }
+
+        // One column per statement; SQLite can't add several at once
+        for col in &self.columns {
+            let mut sql = format!("ALTER TABLE {} ADD COLUMN {}", self.table, col.to_sql(db_type, true));
+            // SQLite can only add a foreign key inline with the new column
+            if let (DatabaseType::Sqlite, Some(fk)) = (db_type, &col.references) {
+                sql.push(' ');
+                sql.push_str(&fk.references_sql());
+            }
+            statements.push(sql);
+        }
+
+        statements.extend(self.all_indexes().iter().map(|idx| idx.create_sql(&self.table)));
+
+        let column_fks = self.columns.iter().filter_map(|col| col.references.as_ref());
+        match db_type {
+            DatabaseType::Sqlite => {
+                if let Some(fk) = self.foreign_keys.first() {
+                    return Err(SchemaError::SqliteForeignKey {
+                        table: self.table.clone(),
+                        column: fk.column.clone(),
+                    });
+                }
+            }
+            _ => {
+                for fk in column_fks.chain(self.foreign_keys.iter()) {
+                    statements.push(format!("ALTER TABLE {} ADD {}", self.table, fk.constraint_sql(&self.table)));
+                }
+            }
+        }
+
+        Ok(statements)
+    }
+}
@@ -0,0 +1,115 @@
+//! Seeders
+//!
+//! A [`Seeder`] fills the database, usually through factories. [`Seeders`]
+//! runs them in the order they were added, each in a transaction, and records
+//! every one that succeeds in the `_seeders` table so later runs skip it:
+//!
+//! ```ignore
+//! struct AdminSeeder;
+//!
+//! #[async_trait]
+//! impl Seeder for AdminSeeder {
+//!     async fn run(&self, db: &DbTransaction) -> oxidite_db::Result<()> {
+//!         UserFactory::builder().admin().create_one(db).await?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! // src/bin/seed.rs, run by `oxidite seed run`
+//! let ran = Seeders::new().seeder(AdminSeeder).seeder(DemoPostsSeeder).run(&db).await?;
+//! ```
+//!
+//! A seeder is identified by [`Seeder::name`]; renaming one makes it run again.
+
+use crate::{connections, Database, DbTransaction, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::Row;
+
+/// Table recording the seeders that have run
+pub const SEEDERS_TABLE: &str = "_seeders";
+
+/// Fills the database with data
+#[async_trait]
+pub trait Seeder: Send + Sync {
+    /// Name recorded once the seeder has run; the type name by default
+    fn name(&self) -> String {
+        let path = std::any::type_name::<Self>();
+        path.rsplit("::").next().unwrap_or(path).to_string()
+    }
+
+    async fn run(&self, db: &DbTransaction) -> Result<()>;
+}
+
+/// Seeders run in the order they were added
+#[derive(Default)]
+pub struct Seeders {
+    seeders: Vec<Box<dyn Seeder>>,
+}
+
+impl Seeders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `seeder` after the ones already added
+    pub fn seeder(mut self, seeder: impl Seeder + 'static) -> Self {
+        self.seeders.push(Box::new(seeder));
+        self
+    }
+
+    /// Run the seeders that haven't run yet, returning their names
+    ///
+    /// Stops at the first failure; that seeder's writes are rolled back and it
+    /// runs again next time.
+    pub async fn run(&self, db: &impl Database) -> Result<Vec<String>> {
+        let done = ran(db).await?;
+        let mut names = Vec::new();
+        for seeder in &self.seeders {
+            let name = seeder.name();
+            if done.contains(&name) {
+                continue;
+            }
+            db.transaction(|tx| async move {
+                seeder.run(&tx).await?;
+                record(&tx, &name).await?;
+                Ok::<_, sqlx::Error>(name)
+            })
+            .await
+            .map(|name| names.push(name))?;
+        }
+        Ok(names)
+    }
+}
+
+/// Create the `_seeders` table if it doesn't exist
+pub async fn ensure_table(db: &impl Database) -> Result<()> {
+    db.execute(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (name VARCHAR(255) PRIMARY KEY, ran_at BIGINT NOT NULL)",
+        SEEDERS_TABLE
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Names of the seeders that have run, in name order
+pub async fn ran(db: &impl Database) -> Result<Vec<String>> {
+    // A lagging replica would report seeders as not run, and they'd run again
+    let db = &connections::on_primary(db);
+    ensure_table(db).await?;
+    let rows = db.query(&format!("SELECT name FROM {} ORDER BY name", SEEDERS_TABLE)).await?;
+    rows.iter().map(|row| row.try_get("name")).collect()
+}
+
+/// Record that the seeder `name` has run
+pub async fn record(db: &impl Database, name: &str) -> Result<()> {
+    let db_type = db.db_type();
+    let sql = format!(
+        "INSERT INTO {} (name, ran_at) VALUES ({}, {})",
+        SEEDERS_TABLE,
+        db_type.placeholder(1),
+        db_type.placeholder(2)
+    );
+    db.execute_query(sqlx::query(&sql).bind(name.to_string()).bind(Utc::now().timestamp())).await?;
+    Ok(())
+}
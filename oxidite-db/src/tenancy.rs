@@ -0,0 +1,282 @@
+//! Multi-tenancy
+//!
+//! Work running inside [`with_tenant`] belongs to one [`Tenant`]. Wrap the
+//! router in [`TenantLayer`] to resolve the tenant of each request from its
+//! subdomain, a header or a JWT claim:
+//!
+//! ```ignore
+//! let app = ServiceBuilder::new()
+//!     .layer(TenantLayer::new(TenantResolver::subdomain("example.com"))
+//!         .or(TenantResolver::header("X-Tenant")))
+//!     .service(router);
+//! ```
+//!
+//! The ORM isolates tenants in one of two ways:
+//!
+//! - Row level: models marked `#[model(tenant_scoped)]` have a `tenant_id`
+//!   column. The derived methods fill it in on insert and add
+//!   `tenant_id = <current tenant>` to every read, update and delete. Using
+//!   such a model with no tenant set is an error rather than a query across
+//!   all tenants.
+//! - Schema per tenant (Postgres): with [`PoolOptions::tenant_schemas`] set,
+//!   each connection checkout sets `search_path` to the tenant's schema
+//!   (`tenant_<id>`) followed by `public`, or resets it when no tenant is set.
+//!   [`MigrationManager::migrate_tenants`] applies migrations in every schema.
+//!
+//! [`PoolOptions::tenant_schemas`]: crate::PoolOptions::tenant_schemas
+//! [`MigrationManager::migrate_tenants`]: crate::MigrationManager::migrate_tenants
+
+use crate::{Database, Model, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+use sqlx::{AnyConnection, Executor, Row};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// Column holding the tenant of row-level scoped models
+pub const TENANT_COLUMN: &str = "tenant_id";
+
+/// Prefix of per-tenant Postgres schemas
+pub const SCHEMA_PREFIX: &str = "tenant_";
+
+// Leaves room for the schema prefix in Postgres' 63-byte identifiers
+const MAX_TENANT_ID_LEN: usize = 48;
+
+tokio::task_local! {
+    static TENANT: Tenant;
+}
+
+/// A tenant id: ASCII letters, digits, `_` and `-`
+///
+/// The restricted alphabet makes ids safe to use in schema names and SQL literals.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tenant {
+    id: String,
+}
+
+impl Tenant {
+    /// `None` if `id` is empty, too long or has other characters
+    pub fn new(id: impl Into<String>) -> Option<Self> {
+        let id = id.into();
+        let valid = !id.is_empty()
+            && id.len() <= MAX_TENANT_ID_LEN
+            && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+        valid.then_some(Self { id })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Name of the tenant's Postgres schema
+    pub fn schema(&self) -> String {
+        format!("{}{}", SCHEMA_PREFIX, self.id)
+    }
+}
+
+impl fmt::Display for Tenant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.id)
+    }
+}
+
+/// Run `f` as `tenant`
+pub async fn with_tenant<F: Future>(tenant: Tenant, f: F) -> F::Output {
+    TENANT.scope(tenant, f).await
+}
+
+/// The tenant of the enclosing [`with_tenant`], if any
+pub fn current() -> Option<Tenant> {
+    TENANT.try_with(Clone::clone).ok()
+}
+
+/// The current tenant, or an error if `M` is used with none set
+pub fn require<M: Model>() -> Result<Tenant> {
+    current().ok_or_else(|| {
+        sqlx::Error::Protocol(format!("{} is tenant scoped but no tenant is set", M::table_name()))
+    })
+}
+
+/// The current tenant if `M` is tenant scoped
+pub(crate) fn scope_for<M: Model>() -> Result<Option<Tenant>> {
+    if M::is_tenant_scoped() {
+        require::<M>().map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// `tenant_id = '<id>'` if `M` is tenant scoped
+pub(crate) fn condition<M: Model>() -> Result<Option<String>> {
+    Ok(scope_for::<M>()?.map(|tenant| format!("{} = '{}'", TENANT_COLUMN, tenant.id)))
+}
+
+/// Point a freshly checked out connection at the current tenant's schema
+pub(crate) async fn set_search_path(conn: &mut AnyConnection) -> Result<()> {
+    let sql = match current() {
+        Some(tenant) => format!("SET search_path TO \"{}\", public", tenant.schema()),
+        None => "RESET search_path".to_string(),
+    };
+    conn.execute(sql.as_str()).await?;
+    Ok(())
+}
+
+/// Tenants with a schema in the database, in name order
+pub async fn schema_tenants(db: &impl Database) -> Result<Vec<Tenant>> {
+    let sql = format!(
+        "SELECT schema_name FROM information_schema.schemata WHERE schema_name LIKE '{}%' ORDER BY schema_name",
+        SCHEMA_PREFIX.replace('_', "\\_")
+    );
+    let mut tenants = Vec::new();
+    for row in db.query(&sql).await? {
+        let schema: String = row.try_get("schema_name")?;
+        if let Some(tenant) = schema.strip_prefix(SCHEMA_PREFIX).and_then(Tenant::new) {
+            tenants.push(tenant);
+        }
+    }
+    Ok(tenants)
+}
+
+/// Where to find the tenant of a request
+#[derive(Debug, Clone)]
+pub enum TenantResolver {
+    /// The first label of the host, e.g. `acme` for `acme.example.com`
+    Subdomain { base_domain: String },
+    /// The value of a request header
+    Header(String),
+    /// A claim of an HS256 bearer token, verified with `secret`
+    JwtClaim { claim: String, secret: String },
+}
+
+impl TenantResolver {
+    pub fn subdomain(base_domain: impl Into<String>) -> Self {
+        TenantResolver::Subdomain { base_domain: base_domain.into() }
+    }
+
+    pub fn header(name: impl Into<String>) -> Self {
+        TenantResolver::Header(name.into())
+    }
+
+    pub fn jwt_claim(claim: impl Into<String>, secret: impl Into<String>) -> Self {
+        TenantResolver::JwtClaim { claim: claim.into(), secret: secret.into() }
+    }
+
+    /// The tenant named by `req`, if it names a valid one
+    pub fn resolve<B>(&self, req: &http::Request<B>) -> Option<Tenant> {
+        let header = |name: &str| req.headers().get(name).and_then(|value| value.to_str().ok());
+        match self {
+            TenantResolver::Subdomain { base_domain } => {
+                let host = header("host").or_else(|| req.uri().host())?;
+                let host = host.split(':').next()?.to_ascii_lowercase();
+                let label = host.strip_suffix(base_domain.as_str())?.strip_suffix('.')?;
+                if label.contains('.') {
+                    return None;
+                }
+                Tenant::new(label)
+            }
+            TenantResolver::Header(name) => Tenant::new(header(name)?.trim()),
+            TenantResolver::JwtClaim { claim, secret } => {
+                let token = header("authorization")?.strip_prefix("Bearer ")?;
+                Tenant::new(verified_claim(token.trim(), claim, secret)?)
+            }
+        }
+    }
+}
+
+/// `claim` from an unexpired HS256 token signed with `secret`
+fn verified_claim(token: &str, claim: &str, secret: &str) -> Option<String> {
+    let decode = |part: &str| -> Option<Value> { serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part).ok()?).ok() };
+
+    let mut parts = token.split('.');
+    let (header, payload, signature) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || decode(header)?.get("alg")?.as_str()? != "HS256" {
+        return None;
+    }
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).ok()?;
+    mac.update(header.as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&URL_SAFE_NO_PAD.decode(signature).ok()?).ok()?;
+
+    let claims = decode(payload)?;
+    let now = chrono::Utc::now().timestamp();
+    if claims.get("exp").and_then(Value::as_i64).is_some_and(|exp| exp <= now)
+        || claims.get("nbf").and_then(Value::as_i64).is_some_and(|nbf| nbf > now)
+    {
+        return None;
+    }
+    match claims.get(claim)? {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Runs each request as the tenant its resolvers find
+///
+/// Requests naming no valid tenant run with none set, so tenant-scoped models
+/// refuse to work for them.
+#[derive(Clone, Debug)]
+pub struct TenantService<S> {
+    inner: S,
+    resolvers: Vec<TenantResolver>,
+}
+
+impl<S, B> Service<http::Request<B>> for TenantService<S>
+where
+    S: Service<http::Request<B>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = std::result::Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let tenant = self.resolvers.iter().find_map(|resolver| resolver.resolve(&req));
+        let response = self.inner.call(req);
+        match tenant {
+            Some(tenant) => Box::pin(with_tenant(tenant, response)),
+            None => Box::pin(response),
+        }
+    }
+}
+
+/// Layer for [`TenantService`]
+#[derive(Clone, Debug)]
+pub struct TenantLayer {
+    resolvers: Vec<TenantResolver>,
+}
+
+impl TenantLayer {
+    pub fn new(resolver: TenantResolver) -> Self {
+        Self { resolvers: vec![resolver] }
+    }
+
+    /// Try `resolver` when the earlier ones find no tenant
+    pub fn or(mut self, resolver: TenantResolver) -> Self {
+        self.resolvers.push(resolver);
+        self
+    }
+}
+
+impl<S> Layer<S> for TenantLayer {
+    type Service = TenantService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TenantService {
+            inner,
+            resolvers: self.resolvers.clone(),
+        }
+    }
+}
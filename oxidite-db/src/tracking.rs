@@ -0,0 +1,57 @@
+//! Change tracking
+//!
+//! A model opts in by adding a field of type [`Original`], under any name,
+//! skipped by `FromRow` and serde:
+//!
+//! ```ignore
+//! #[derive(Model, sqlx::FromRow, Serialize)]
+//! pub struct Post {
+//!     pub id: i64,
+//!     pub title: String,
+//!     pub body: String,
+//!     #[sqlx(skip)]
+//!     #[serde(skip)]
+//!     pub original: Original,
+//! }
+//!
+//! let mut post = Post::find(&db, 1).await?.unwrap();
+//! post.title = "New title".into();
+//! assert_eq!(post.changed_fields(), vec!["title"]);
+//! post.update(&db).await?; // UPDATE posts SET title = $1 WHERE id = $2
+//! ```
+//!
+//! Records loaded through the model (`find`, `all`, pagination, relations)
+//! remember their column values; `create` and `update` refresh them.
+//! `update` on a record with no changes runs no statement and leaves
+//! `updated_at` as it was; its hooks and observers still run.
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Column values a record had when it was loaded or last saved
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Original(Option<HashMap<&'static str, Value>>);
+
+impl Original {
+    /// Whether values have been recorded, i.e. the record came from the database
+    pub fn is_loaded(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// The recorded value of `field`
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.0.as_ref()?.get(field)
+    }
+
+    pub(crate) fn set(&mut self, values: Vec<(&'static str, Value)>) {
+        self.0 = Some(values.into_iter().collect());
+    }
+}
+
+/// One changed field: its recorded value (`None` for new records) and current value
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: Option<Value>,
+    pub new: Value,
+}
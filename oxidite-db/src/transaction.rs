@@ -0,0 +1,134 @@
+//! Closure-based transactions
+//!
+//! `Database::transaction` commits when the closure returns `Ok` and rolls back
+//! when it returns `Err` or panics. Calling it on a `DbTransaction` nests the
+//! work in a savepoint instead of a new transaction.
+
+use crate::{DatabaseType, DbTransaction};
+use sqlx::mysql::MySqlDatabaseError;
+use std::future::Future;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Retry policy for transactions that fail on serialization conflicts or deadlocks
+///
+/// Only applies to top-level transactions on Postgres and MySQL; a savepoint
+/// cannot be retried on its own because the enclosing transaction is aborted.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the second attempt; doubled for each further attempt
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..Self::default()
+        }
+    }
+
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub(crate) fn delay_for(&self, attempt: u32) -> Duration {
+        self.backoff * 2u32.saturating_pow(attempt.saturating_sub(1))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Duration::from_millis(50),
+        }
+    }
+}
+
+/// Check whether an error is a serialization failure or deadlock worth retrying
+pub fn is_retryable(error: &sqlx::Error, db_type: DatabaseType) -> bool {
+    let sqlx::Error::Database(db_error) = error else {
+        return false;
+    };
+    let code = db_error.code();
+
+    match db_type {
+        // serialization_failure, deadlock_detected
+        DatabaseType::Postgres => matches!(code.as_deref(), Some("40001") | Some("40P01")),
+        // ER_LOCK_DEADLOCK (1213) reports SQLSTATE 40001; ER_LOCK_WAIT_TIMEOUT
+        // (1205) only the generic HY000, so it is told apart by its error number
+        DatabaseType::MySql => {
+            let number = db_error.try_downcast_ref::<MySqlDatabaseError>().map(MySqlDatabaseError::number);
+            code.as_deref() == Some("40001") || matches!(number, Some(1213 | 1205))
+        }
+        DatabaseType::Sqlite => false,
+    }
+}
+
+/// Run `f` inside `tx`, committing on `Ok` and rolling back on `Err` or panic
+pub(crate) async fn run<F, Fut, T, E>(tx: DbTransaction, f: F) -> std::result::Result<T, E>
+where
+    F: FnOnce(DbTransaction) -> Fut,
+    Fut: Future<Output = std::result::Result<T, E>>,
+    E: From<sqlx::Error>,
+{
+    finish(tx.clone(), f(tx)).await
+}
+
+/// Await `work`, which runs against `tx`, then commit on `Ok` and roll back
+/// on `Err` or panic
+///
+/// The closure-free form of `Database::transaction`, for work that borrows
+/// from its surroundings; the model derive uses it to write a record and its
+/// audit entry together.
+pub async fn finish<Fut, T, E>(tx: DbTransaction, work: Fut) -> std::result::Result<T, E>
+where
+    Fut: Future<Output = std::result::Result<T, E>>,
+    E: From<sqlx::Error>,
+{
+    match CatchUnwind::new(work).await {
+        Ok(Ok(value)) => {
+            tx.commit().await?;
+            Ok(value)
+        }
+        Ok(Err(err)) => {
+            // The work's error is more useful than a failed rollback
+            let _ = tx.rollback().await;
+            Err(err)
+        }
+        Err(panic) => {
+            let _ = tx.rollback().await;
+            resume_unwind(panic)
+        }
+    }
+}
+
+/// Future wrapper that turns a panic while polling into an `Err`
+struct CatchUnwind<F> {
+    inner: Pin<Box<F>>,
+}
+
+impl<F> CatchUnwind<F> {
+    fn new(inner: F) -> Self {
+        Self { inner: Box::pin(inner) }
+    }
+}
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = self.inner.as_mut();
+        match catch_unwind(AssertUnwindSafe(|| inner.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(value)) => Poll::Ready(Ok(value)),
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    }
+}
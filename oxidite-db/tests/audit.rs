@@ -0,0 +1,198 @@
+use oxidite_db::audit::{self, AuditEvent};
+use oxidite_db::serde_json::json;
+use oxidite_db::{async_trait, context, observers, sqlx, Audit, Database, DatabaseType, DbPool, Model, Observer, Original};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Model, sqlx::FromRow, Debug, Clone)]
+#[model(auditable)]
+struct Post {
+    id: i64,
+    title: String,
+    body: String,
+    updated_at: i64,
+    #[sqlx(skip)]
+    original: Original,
+}
+
+/// Tracks changes in a field with another name; `original` here is a column
+#[derive(Model, sqlx::FromRow, Debug, Clone)]
+struct Revision {
+    id: i64,
+    original: String,
+    updated_at: i64,
+    #[sqlx(skip)]
+    loaded: Original,
+}
+
+struct UpdatedObserver {
+    updated: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Observer<Revision> for UpdatedObserver {
+    async fn updated(&self, _revision: &Revision) -> oxidite_db::Result<()> {
+        self.updated.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+type Log = Arc<Mutex<Vec<String>>>;
+
+async fn setup() -> (DbPool, Log) {
+    let log: Log = Arc::default();
+    let sink = log.clone();
+    let db = DbPool::connect("sqlite::memory:")
+        .await
+        .unwrap()
+        .on_query(move |event| sink.lock().unwrap().push(event.sql.to_string()));
+    db.execute(
+        "CREATE TABLE posts (id INTEGER PRIMARY KEY, title TEXT NOT NULL, body TEXT NOT NULL, \
+         updated_at INTEGER NOT NULL DEFAULT 0)",
+    )
+    .await
+    .unwrap();
+    for statement in audit::migration().up_statements(DatabaseType::Sqlite).unwrap() {
+        db.execute(&statement).await.unwrap();
+    }
+    db.execute("INSERT INTO posts (id, title, body) VALUES (1, 'Hello', 'First post')").await.unwrap();
+    log.lock().unwrap().clear();
+    (db, log)
+}
+
+fn updates(log: &Log) -> Vec<String> {
+    log.lock().unwrap().iter().filter(|sql| sql.starts_with("UPDATE")).cloned().collect()
+}
+
+#[tokio::test]
+async fn test_loaded_records_track_changes() {
+    let (db, _) = setup().await;
+
+    let mut post = Post::find(&db, 1).await.unwrap().unwrap();
+    assert!(post.original.is_loaded());
+    assert!(!post.is_dirty());
+
+    post.title = "Hello again".to_string();
+    assert!(post.is_dirty());
+    assert_eq!(post.changed_fields(), vec!["title"]);
+    let changes = post.changes();
+    assert_eq!(changes.len(), 1);
+    assert_eq!((changes[0].old.clone(), changes[0].new.clone()), (Some(json!("Hello")), json!("Hello again")));
+
+    post.title = "Hello".to_string();
+    assert!(!post.is_dirty(), "restoring the value clears the change");
+}
+
+#[tokio::test]
+async fn test_update_writes_only_changed_columns() {
+    let (db, log) = setup().await;
+
+    let mut post = Post::find(&db, 1).await.unwrap().unwrap();
+    post.update(&db).await.unwrap();
+    assert!(updates(&log).is_empty(), "a clean record is not written");
+
+    post.body = "Edited".to_string();
+    post.update(&db).await.unwrap();
+    assert_eq!(updates(&log), vec!["UPDATE posts SET body = $1, updated_at = $2 WHERE id = $3"]);
+    assert!(!post.is_dirty(), "a saved record is clean again");
+
+    let stored = Post::find(&db, 1).await.unwrap().unwrap();
+    assert_eq!((stored.title.as_str(), stored.body.as_str()), ("Hello", "Edited"));
+}
+
+#[tokio::test]
+async fn test_tracking_is_keyed_on_the_original_type() {
+    let (db, log) = setup().await;
+    db.execute("CREATE TABLE revisions (id INTEGER PRIMARY KEY, original TEXT NOT NULL, updated_at INTEGER NOT NULL)")
+        .await
+        .unwrap();
+    db.execute("INSERT INTO revisions (id, original, updated_at) VALUES (1, 'v1', 5)").await.unwrap();
+    let updated = Arc::new(AtomicUsize::new(0));
+    observers::observe::<Revision>(UpdatedObserver { updated: updated.clone() });
+
+    let mut revision = Revision::find(&db, 1).await.unwrap().unwrap();
+    assert!(revision.loaded.is_loaded());
+    assert_eq!(Revision::fields(), &["id", "original", "updated_at"]);
+
+    // A clean update writes nothing and keeps `updated_at`, but still reports the update
+    revision.update(&db).await.unwrap();
+    assert!(updates(&log).is_empty());
+    assert_eq!((revision.updated_at, updated.load(Ordering::SeqCst)), (5, 1));
+
+    revision.original = "v2".to_string();
+    revision.update(&db).await.unwrap();
+    assert_eq!(updates(&log), vec!["UPDATE revisions SET original = $1, updated_at = $2 WHERE id = $3"]);
+    assert_eq!(updated.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_audit_trail_records_diffs_and_actor() {
+    let (db, _) = setup().await;
+
+    context::scope(async {
+        context::set_actor("user:7");
+
+        let mut post = Post {
+            id: 0,
+            title: "Draft".to_string(),
+            body: "Body".to_string(),
+            updated_at: 0,
+            original: Original::default(),
+        };
+        post.create(&db).await.unwrap();
+        assert_eq!(post.id, 2, "the generated id is read back");
+
+        post.title = "Published".to_string();
+        post.update(&db).await.unwrap();
+        post.update(&db).await.unwrap();
+        post.delete(&db).await.unwrap();
+    })
+    .await;
+
+    let audits = Audit::for_record::<Post>(&db, 2).await.unwrap();
+    let events: Vec<_> = audits.iter().map(|a| a.event).collect();
+    assert_eq!(events, vec![AuditEvent::Created, AuditEvent::Updated, AuditEvent::Deleted]);
+    assert!(audits.iter().all(|a| a.auditable_type == "posts" && a.actor.as_deref() == Some("user:7")));
+
+    assert_eq!(audits[0].old_values, json!({}));
+    assert_eq!(audits[0].new_values, json!({ "title": "Draft", "body": "Body" }));
+    assert_eq!(audits[1].old_values, json!({ "title": "Draft" }));
+    assert_eq!(audits[1].new_values, json!({ "title": "Published" }));
+    assert_eq!(audits[2].old_values, json!({ "title": "Published", "body": "Body" }));
+    assert_eq!(audits[2].new_values, json!({}));
+
+    assert!(Audit::for_record::<Post>(&db, 1).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_failed_audit_undoes_the_write() {
+    let (db, _) = setup().await;
+    db.execute("CREATE TRIGGER audits_fail BEFORE INSERT ON audits BEGIN SELECT RAISE(ABORT, 'audit failed'); END")
+        .await
+        .unwrap();
+
+    let mut post = Post {
+        id: 0,
+        title: "Draft".to_string(),
+        body: "Body".to_string(),
+        updated_at: 0,
+        original: Original::default(),
+    };
+    assert!(post.create(&db).await.is_err());
+    assert_eq!(post.id, 0);
+
+    let mut post = Post::find(&db, 1).await.unwrap().unwrap();
+    post.title = "Changed".to_string();
+    assert!(post.update(&db).await.is_err());
+    assert!(post.delete(&db).await.is_err());
+
+    // Inside a transaction the write is undone through a savepoint
+    let tx = db.begin_transaction().await.unwrap();
+    assert!(post.update(&tx).await.is_err());
+    tx.execute("UPDATE posts SET body = 'Kept'").await.unwrap();
+    tx.commit().await.unwrap();
+
+    let stored = Post::all(&db).await.unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!((stored[0].title.as_str(), stored[0].body.as_str()), ("Hello", "Kept"));
+}
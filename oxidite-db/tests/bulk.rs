@@ -0,0 +1,144 @@
+use oxidite_db::{sqlx, Database, DbPool, Model, SqlValue};
+use sqlx::Row;
+
+#[derive(Model, sqlx::FromRow, Clone)]
+struct Product {
+    id: i64,
+    sku: String,
+    price: i64,
+    note: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+    deleted_at: Option<i64>,
+}
+
+fn product(sku: &str, price: i64) -> Product {
+    Product {
+        id: 0,
+        sku: sku.to_string(),
+        price,
+        note: None,
+        created_at: 0,
+        updated_at: 0,
+        deleted_at: None,
+    }
+}
+
+async fn setup() -> DbPool {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    db.execute(
+        "CREATE TABLE products (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sku TEXT NOT NULL UNIQUE,
+            price INTEGER NOT NULL,
+            note TEXT,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            deleted_at INTEGER
+        )",
+    )
+    .await
+    .unwrap();
+    db
+}
+
+async fn count(db: &DbPool, sql: &str) -> i64 {
+    db.query_one(sql).await.unwrap().unwrap().get::<i64, _>(0)
+}
+
+#[tokio::test]
+async fn test_insert_many_chunks_and_sets_timestamps() {
+    let db = setup().await;
+    // 7 columns per row, so this spans several statements on SQLite
+    let rows: Vec<Product> = (0..500).map(|i| product(&format!("sku-{}", i), i)).collect();
+
+    assert_eq!(Product::insert_many(&db, &rows).await.unwrap(), 500);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM products").await, 500);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM products WHERE created_at = 0 OR updated_at = 0").await, 0);
+    assert_eq!(Product::insert_many(&db, &[]).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_insert_many_is_atomic() {
+    let db = setup().await;
+    let rows = vec![product("a", 1), product("b", 2), product("a", 3)];
+
+    assert!(Product::insert_many(&db, &rows).await.is_err());
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM products").await, 0);
+}
+
+#[tokio::test]
+async fn test_upsert() {
+    let db = setup().await;
+    Product::insert_many(&db, &[product("a", 1), product("b", 2)]).await.unwrap();
+    db.execute("UPDATE products SET updated_at = 1").await.unwrap();
+
+    let rows = vec![product("a", 10), product("c", 30)];
+    Product::upsert(&db, &rows, &["sku"], &["price"]).await.unwrap();
+
+    assert_eq!(count(&db, "SELECT price FROM products WHERE sku = 'a'").await, 10);
+    assert!(count(&db, "SELECT updated_at FROM products WHERE sku = 'a'").await > 1);
+    assert_eq!(count(&db, "SELECT updated_at FROM products WHERE sku = 'b'").await, 1);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM products").await, 3);
+
+    // No update columns: existing rows are kept as they are
+    Product::upsert(&db, &[product("a", 99)], &["sku"], &[]).await.unwrap();
+    assert_eq!(count(&db, "SELECT price FROM products WHERE sku = 'a'").await, 10);
+
+    // No conflict columns: a conflict on any unique key is skipped, but updating needs them
+    Product::upsert(&db, &[product("a", 98), product("d", 40)], &[], &[]).await.unwrap();
+    assert_eq!(count(&db, "SELECT price FROM products WHERE sku = 'a'").await, 10);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM products").await, 4);
+    assert!(Product::upsert(&db, &[product("a", 97)], &[], &["price"]).await.is_err());
+}
+
+#[tokio::test]
+async fn test_update_and_delete_where_respect_soft_deletes() {
+    let db = setup().await;
+    let rows: Vec<Product> = (1..=6).map(|i| product(&format!("p{}", i), i)).collect();
+    Product::insert_many(&db, &rows).await.unwrap();
+    db.execute("UPDATE products SET deleted_at = 1 WHERE sku = 'p1'").await.unwrap();
+
+    let updated = Product::update_where(&db, &[("price <", 4.into())], &[("note", "cheap".into())])
+        .await
+        .unwrap();
+    assert_eq!(updated, 2);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM products WHERE note = 'cheap'").await, 2);
+
+    let cleared = Product::update_where(&db, &[("note", "cheap".into())], &[("note", SqlValue::Null)])
+        .await
+        .unwrap();
+    assert_eq!(cleared, 2);
+
+    assert_eq!(Product::delete_where(&db, &[("price >=", 5.into())]).await.unwrap(), 2);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM products WHERE deleted_at IS NULL").await, 3);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM products").await, 6);
+    assert!(Product::all(&db).await.unwrap().iter().all(|p| p.deleted_at.is_none()));
+
+    assert_eq!(Product::force_delete_where(&db, &[("deleted_at", SqlValue::Null)]).await.unwrap(), 3);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM products").await, 3);
+}
+
+#[tokio::test]
+async fn test_bulk_conditions_are_checked() {
+    let db = setup().await;
+    let rows: Vec<Product> = (1..=4).map(|i| product(&format!("p{}", i), i)).collect();
+    Product::insert_many(&db, &rows).await.unwrap();
+
+    assert!(Product::update_where(&db, &[], &[("note", "all".into())]).await.is_err());
+    assert!(Product::update_where(&db, &[("price", 1.into())], &[]).await.is_err());
+    assert!(Product::delete_where(&db, &[]).await.is_err());
+    assert!(Product::force_delete_where(&db, &[]).await.is_err());
+    assert!(Product::delete_where(&db, &[("price = 1 OR 1 =", 1.into())]).await.is_err());
+    assert!(Product::delete_where(&db, &[("1 = 1 OR price", 1.into())]).await.is_err());
+    assert!(Product::update_where(&db, &[("price", 1.into())], &[("note = 'x', sku", "y".into())]).await.is_err());
+    assert!(Product::delete_where(&db, &[("price IN", 1.into())]).await.is_err());
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM products WHERE deleted_at IS NULL AND note IS NULL").await, 4);
+
+    let updated = Product::update_where(&db, &[("sku in", vec!["p1", "p3"].into())], &[("note", "picked".into())])
+        .await
+        .unwrap();
+    assert_eq!(updated, 2);
+    assert_eq!(Product::delete_where(&db, &[("sku LIKE", "p%".into()), ("price IN", Vec::<i64>::new().into())]).await.unwrap(), 0);
+    assert_eq!(Product::delete_where(&db, &[("note !=", SqlValue::Null)]).await.unwrap(), 2);
+}
@@ -0,0 +1,141 @@
+use oxidite_db::{context, sqlx, sqlx::Row, BelongsTo, Database, DbPool, HasMany, HasOne, Model};
+
+#[derive(Model, sqlx::FromRow, Debug)]
+#[model(connection = "analytics")]
+struct Event {
+    id: i64,
+    name: String,
+}
+
+#[derive(Model, sqlx::FromRow, Debug)]
+#[model(connection = "analytics")]
+struct Visit {
+    id: i64,
+    account_id: i64,
+    event_id: i64,
+}
+
+#[derive(Model, sqlx::FromRow, Debug)]
+struct Account {
+    id: i64,
+    name: String,
+}
+
+/// A separate in-memory database whose `nodes` table names it
+async fn node(name: &str) -> DbPool {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    db.execute("CREATE TABLE nodes (name TEXT NOT NULL)").await.unwrap();
+    db.execute(&format!("INSERT INTO nodes (name) VALUES ('{}')", name)).await.unwrap();
+    db
+}
+
+async fn served_by(db: &impl Database) -> String {
+    let row = db.query_one("SELECT name FROM nodes").await.unwrap().unwrap();
+    row.try_get("name").unwrap()
+}
+
+async fn replicated() -> DbPool {
+    node("primary")
+        .await
+        .with_replica(node("replica-1").await)
+        .with_replica(node("replica-2").await)
+}
+
+#[tokio::test]
+async fn test_reads_round_robin_across_replicas() {
+    let db = replicated().await;
+
+    let mut seen = vec![served_by(&db).await, served_by(&db).await, served_by(&db).await];
+    assert_eq!(seen[0], seen[2]);
+    seen.sort();
+    seen.dedup();
+    assert_eq!(seen, vec!["replica-1", "replica-2"]);
+
+    // Reads with side effects or session state are not plain reads
+    let row = db.query_one("SELECT name, last_insert_rowid() AS id FROM nodes").await.unwrap().unwrap();
+    assert_eq!(row.try_get::<String, _>("name").unwrap(), "primary");
+    let row = db.query_one("select name,\n  LAST_INSERT_ROWID (\n) from nodes").await.unwrap().unwrap();
+    assert_eq!(row.try_get::<String, _>("name").unwrap(), "primary");
+
+    // Writes and transactions use the primary, even when they return rows
+    let row = db.query_one("UPDATE nodes SET name = 'primary!' RETURNING name").await.unwrap().unwrap();
+    assert_eq!(row.try_get::<String, _>("name").unwrap(), "primary!");
+    assert_eq!(served_by(&db.primary()).await, "primary!");
+    let tx = db.begin_transaction().await.unwrap();
+    assert_eq!(served_by(&tx).await, "primary!");
+    tx.rollback().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_reads_stick_to_primary_after_a_write_in_scope() {
+    let db = replicated().await;
+
+    context::scope(async {
+        assert!(served_by(&db).await.starts_with("replica"));
+        db.execute("UPDATE nodes SET name = 'written'").await.unwrap();
+        assert_eq!(served_by(&db).await, "written");
+        assert_eq!(served_by(&db).await, "written");
+    })
+    .await;
+
+    // A new scope, or no scope at all, starts on the replicas again
+    context::scope(async {
+        assert!(served_by(&db).await.starts_with("replica"));
+    })
+    .await;
+    db.execute("UPDATE nodes SET name = 'again'").await.unwrap();
+    assert!(served_by(&db).await.starts_with("replica"));
+}
+
+#[tokio::test]
+async fn test_model_uses_named_connection() {
+    let analytics = DbPool::connect("sqlite::memory:").await.unwrap();
+    analytics
+        .execute("CREATE TABLE events (id INTEGER PRIMARY KEY, name TEXT NOT NULL)")
+        .await
+        .unwrap();
+    let db = node("primary").await.with_connection("analytics", analytics);
+
+    let mut event = Event { id: 0, name: "signup".to_string() };
+    event.create(&db).await.unwrap();
+
+    let events = Event::all(&db).await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].name, "signup");
+    assert!(db.query("SELECT * FROM events").await.is_err(), "the default pool has no events table");
+
+    let direct = db.connection("analytics").unwrap();
+    assert_eq!(Event::find(direct, events[0].id).await.unwrap().unwrap().name, "signup");
+    assert!(db.connection("missing").is_none());
+}
+
+#[tokio::test]
+async fn test_relations_use_the_related_models_connection() {
+    let analytics = DbPool::connect("sqlite::memory:").await.unwrap();
+    analytics
+        .execute("CREATE TABLE events (id INTEGER PRIMARY KEY, name TEXT NOT NULL)")
+        .await
+        .unwrap();
+    analytics
+        .execute("CREATE TABLE visits (id INTEGER PRIMARY KEY, account_id INTEGER NOT NULL, event_id INTEGER NOT NULL)")
+        .await
+        .unwrap();
+    let db = node("primary").await.with_connection("analytics", analytics);
+    db.execute("CREATE TABLE accounts (id INTEGER PRIMARY KEY, name TEXT NOT NULL)").await.unwrap();
+
+    let mut account = Account { id: 0, name: "acme".to_string() };
+    account.create(&db).await.unwrap();
+    let mut event = Event { id: 0, name: "signup".to_string() };
+    event.create(&db).await.unwrap();
+    let mut visit = Visit { id: 0, account_id: account.id, event_id: event.id };
+    visit.create(&db).await.unwrap();
+
+    let visits = HasMany::<Account, Visit>::new(account.id, "account_id").get(&db).await.unwrap();
+    assert_eq!(visits.len(), 1);
+    let first = HasOne::<Account, Visit>::new(account.id, "account_id").get(&db).await.unwrap();
+    assert_eq!(first.unwrap().event_id, event.id);
+    let owner = BelongsTo::<Visit, Account>::new(visit.account_id).get(&db).await.unwrap();
+    assert_eq!(owner.unwrap().name, "acme");
+    let parent = BelongsTo::<Visit, Event>::new(visit.event_id).get(&db).await.unwrap();
+    assert_eq!(parent.unwrap().name, "signup");
+}
@@ -0,0 +1,168 @@
+use oxidite_db::{
+    async_trait, seeding, sqlx, Database, DbPool, DbTransaction, Factory, FactoryBuilder, Faker, HasMany, Model, Seeder,
+    Seeders,
+};
+
+#[derive(Model, sqlx::FromRow, Debug, Clone, PartialEq)]
+struct User {
+    id: i64,
+    name: String,
+    email: String,
+    role: String,
+}
+
+impl User {
+    fn posts(&self) -> HasMany<User, Post> {
+        HasMany::new(self.id, "user_id")
+    }
+}
+
+#[derive(Model, sqlx::FromRow, Debug, Clone, PartialEq)]
+struct Post {
+    id: i64,
+    user_id: i64,
+    title: String,
+}
+
+#[derive(Default)]
+struct UserFactory;
+
+impl Factory<User> for UserFactory {
+    fn definition(&self, faker: &mut Faker, sequence: u64) -> User {
+        User {
+            id: 0,
+            name: faker.name(),
+            email: format!("user{}@example.com", sequence),
+            role: "member".to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct PostFactory;
+
+impl Factory<Post> for PostFactory {
+    fn definition(&self, faker: &mut Faker, _sequence: u64) -> Post {
+        Post { id: 0, user_id: 0, title: faker.sentence() }
+    }
+}
+
+trait UserStates {
+    fn admin(self) -> Self;
+}
+
+impl<R: oxidite_db::factory::Related<User>> UserStates for FactoryBuilder<User, R> {
+    fn admin(self) -> Self {
+        self.state(|user, _| user.role = "admin".to_string())
+    }
+}
+
+async fn setup() -> DbPool {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    db.execute("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL, email TEXT NOT NULL, role TEXT NOT NULL)")
+        .await
+        .unwrap();
+    db.execute("CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER NOT NULL, title TEXT NOT NULL)")
+        .await
+        .unwrap();
+    db
+}
+
+#[test]
+fn test_states_sequences_and_seeds() {
+    let users = UserFactory::builder().count(3).admin().make();
+    assert!(users.iter().all(|u| u.role == "admin" && u.id == 0));
+    let sequences: Vec<u64> = users
+        .iter()
+        .map(|u| u.email.trim_start_matches("user").trim_end_matches("@example.com").parse().unwrap())
+        .collect();
+    assert!(sequences.windows(2).all(|pair| pair[0] < pair[1]), "sequences count up: {:?}", sequences);
+
+    let names = |seed| UserFactory::builder().seed(seed).count(5).make().into_iter().map(|u| u.name).collect::<Vec<_>>();
+    assert_eq!(names(7), names(7), "a seeded factory repeats its data");
+
+    let mut faker = Faker::seeded(1);
+    assert!(faker.email().contains("@example."));
+    assert!(faker.sentence().ends_with('.'));
+    assert!((1..=6).contains(&faker.number(1..=6)));
+}
+
+#[tokio::test]
+async fn test_related_records() {
+    let db = setup().await;
+
+    let users = UserFactory::builder()
+        .count(2)
+        .has(PostFactory::builder().count(3), User::posts)
+        .create(&db)
+        .await
+        .unwrap();
+    assert!(users[0].id > 0 && users[1].id > users[0].id, "create fills in the ids");
+    for user in &users {
+        let posts = user.posts().get(&db).await.unwrap();
+        assert_eq!(posts.len(), 3);
+        assert!(posts.iter().all(|p| p.user_id == user.id));
+    }
+
+    let posts = PostFactory::builder()
+        .count(2)
+        .for_parent(UserFactory::builder().admin(), User::posts)
+        .create(&db)
+        .await
+        .unwrap();
+    assert_eq!(posts[0].user_id, posts[1].user_id, "one parent is shared");
+    let author = User::find(&db, posts[0].user_id).await.unwrap().unwrap();
+    assert_eq!(author.role, "admin");
+    assert_eq!(User::all(&db).await.unwrap().len(), 3);
+
+    // A relation whose foreign key the child doesn't have can't link them
+    let unlinked = UserFactory::builder()
+        .has(PostFactory::builder(), |user: &User| HasMany::<User, Post>::new(user.id, "author_id"))
+        .create(&db)
+        .await;
+    assert!(unlinked.is_err());
+}
+
+struct AdminSeeder;
+
+#[async_trait]
+impl Seeder for AdminSeeder {
+    async fn run(&self, db: &DbTransaction) -> oxidite_db::Result<()> {
+        UserFactory::builder().admin().create_one(db).await?;
+        Ok(())
+    }
+}
+
+struct BrokenSeeder;
+
+#[async_trait]
+impl Seeder for BrokenSeeder {
+    async fn run(&self, db: &DbTransaction) -> oxidite_db::Result<()> {
+        UserFactory::builder().create_one(db).await?;
+        db.execute("INSERT INTO missing_table (id) VALUES (1)").await?;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_seeders_run_once_in_order() {
+    let db = setup().await;
+
+    let ran = Seeders::new().seeder(AdminSeeder).run(&db).await.unwrap();
+    assert_eq!(ran, vec!["AdminSeeder"]);
+
+    let result = Seeders::new().seeder(AdminSeeder).seeder(BrokenSeeder).run(&db).await;
+    assert!(result.is_err());
+    assert_eq!(User::all(&db).await.unwrap().len(), 1, "the failed seeder is rolled back");
+    assert_eq!(seeding::ran(&db).await.unwrap(), vec!["AdminSeeder"]);
+}
+
+#[tokio::test]
+async fn test_seeders_check_the_primary() {
+    let replica = DbPool::connect("sqlite::memory:").await.unwrap();
+    let db = setup().await.with_replica(replica);
+
+    assert_eq!(Seeders::new().seeder(AdminSeeder).run(&db).await.unwrap(), vec!["AdminSeeder"]);
+    assert!(Seeders::new().seeder(AdminSeeder).run(&db).await.unwrap().is_empty());
+    assert_eq!(seeding::ran(&db).await.unwrap(), vec!["AdminSeeder"]);
+}
@@ -0,0 +1,120 @@
+use oxidite_db::{Model, ModelHooks, Observer, observers, sqlx, Database, DatabaseType, DbTransaction, Result};
+use async_trait::async_trait;
+use sqlx::any::AnyRow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Model, sqlx::FromRow, Clone)]
+#[model(hooks)]
+struct Member {
+    id: i64,
+    email: String,
+    password: String,
+}
+
+#[async_trait]
+impl ModelHooks for Member {
+    async fn before_create(&mut self, _db: &impl Database) -> Result<()> {
+        if self.email.is_empty() {
+            return Err(sqlx::Error::Protocol("email is required".into()));
+        }
+        self.password = format!("hashed:{}", self.password);
+        Ok(())
+    }
+
+    async fn before_delete(&self, _db: &impl Database) -> Result<()> {
+        Err(sqlx::Error::Protocol("members cannot be deleted".into()))
+    }
+}
+
+#[derive(Model, sqlx::FromRow, Clone)]
+struct Note {
+    id: i64,
+    body: String,
+}
+
+struct CountingObserver {
+    events: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Observer<Note> for CountingObserver {
+    async fn created(&self, _note: &Note) -> Result<()> {
+        self.events.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn updating(&self, note: &mut Note) -> Result<()> {
+        if note.body.is_empty() {
+            return Err(sqlx::Error::Protocol("empty note".into()));
+        }
+        Ok(())
+    }
+
+    async fn deleted(&self, _note: &Note) -> Result<()> {
+        self.events.fetch_add(10, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockDb {
+    executed: AtomicUsize,
+}
+
+#[async_trait]
+impl Database for MockDb {
+    fn db_type(&self) -> DatabaseType { DatabaseType::Sqlite }
+    async fn execute(&self, _query: &str) -> Result<u64> { Ok(1) }
+    async fn query(&self, _query: &str) -> Result<Vec<AnyRow>> { Ok(vec![]) }
+    async fn query_one(&self, _query: &str) -> Result<Option<AnyRow>> { Ok(None) }
+    async fn ping(&self) -> Result<()> { Ok(()) }
+    async fn begin_transaction(&self) -> Result<DbTransaction> { unimplemented!() }
+    async fn execute_query<'q>(&self, _query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<u64> {
+        self.executed.fetch_add(1, Ordering::SeqCst);
+        Ok(1)
+    }
+    async fn fetch_all<'q>(&self, _query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<Vec<AnyRow>> { Ok(vec![]) }
+    async fn fetch_one<'q>(&self, _query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>) -> Result<Option<AnyRow>> { Ok(None) }
+}
+
+#[tokio::test]
+async fn test_before_create_hook_mutates_model() {
+    let db = MockDb::default();
+    let mut member = Member { id: 0, email: "a@b.co".to_string(), password: "secret".to_string() };
+
+    member.create(&db).await.unwrap();
+    assert_eq!(member.password, "hashed:secret");
+    assert_eq!(db.executed.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_before_hooks_abort_operation() {
+    let db = MockDb::default();
+    let mut member = Member { id: 0, email: String::new(), password: "secret".to_string() };
+
+    assert!(member.create(&db).await.is_err());
+    assert!(member.delete(&db).await.is_err());
+    assert!(member.force_delete(&db).await.is_err());
+    assert_eq!(db.executed.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_observers_receive_events() {
+    let db = MockDb::default();
+    let events = Arc::new(AtomicUsize::new(0));
+    observers::observe::<Note>(CountingObserver { events: events.clone() });
+
+    let mut note = Note { id: 1, body: "hello".to_string() };
+    note.create(&db).await.unwrap();
+    note.delete(&db).await.unwrap();
+    assert_eq!(events.load(Ordering::SeqCst), 11);
+
+    note.body.clear();
+    assert!(note.update(&db).await.is_err());
+    assert_eq!(db.executed.load(Ordering::SeqCst), 2);
+
+    observers::clear::<Note>();
+    note.create(&db).await.unwrap();
+    assert_eq!(events.load(Ordering::SeqCst), 11);
+}
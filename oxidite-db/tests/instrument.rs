@@ -0,0 +1,112 @@
+use oxidite_db::instrument::fingerprint;
+use oxidite_db::{context, sqlx, Database, DbContextLayer, DbPool, Model};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tower::{Layer, ServiceExt};
+
+#[derive(Model, sqlx::FromRow, Debug)]
+struct Widget {
+    id: i64,
+    name: String,
+}
+
+/// (sql, params, rows, slow) for each statement
+type Log = Arc<Mutex<Vec<(String, usize, u64, bool)>>>;
+
+async fn setup(slow_threshold: Option<Duration>) -> (DbPool, Log) {
+    let log: Log = Arc::default();
+    let sink = log.clone();
+    let db = DbPool::connect("sqlite::memory:")
+        .await
+        .unwrap()
+        .with_slow_query_threshold(slow_threshold)
+        .on_query(move |event| {
+            sink.lock().unwrap().push((event.sql.to_string(), event.params, event.rows, event.slow));
+        });
+    db.execute("CREATE TABLE widgets (id INTEGER PRIMARY KEY, name TEXT NOT NULL)").await.unwrap();
+    log.lock().unwrap().clear();
+    (db, log)
+}
+
+#[tokio::test]
+async fn test_listeners_see_sql_params_and_rows() {
+    let (db, log) = setup(None).await;
+
+    let mut widget = Widget { id: 0, name: "bolt".to_string() };
+    widget.create(&db).await.unwrap();
+    Widget::insert_many(&db, &[Widget { id: 0, name: "nut".into() }, Widget { id: 0, name: "gear".into() }])
+        .await
+        .unwrap();
+    assert_eq!(Widget::all(&db).await.unwrap().len(), 3);
+
+    let log = log.lock().unwrap();
+    let (sql, params, rows, slow) = &log[0];
+    assert!(sql.starts_with("INSERT INTO widgets"));
+    assert_eq!((*params, *rows, *slow), (1, 1, false));
+
+    // The bulk insert runs inside a transaction, which reports through the same listeners
+    let bulk = log.iter().find(|(sql, ..)| sql.contains("VALUES ($1), ($2)")).unwrap();
+    assert_eq!((bulk.1, bulk.2), (2, 2));
+
+    let (sql, params, rows, _) = log.last().unwrap();
+    assert_eq!(sql, "SELECT * FROM widgets");
+    assert_eq!((*params, *rows), (0, 3));
+}
+
+#[tokio::test]
+async fn test_slow_query_threshold() {
+    let (db, log) = setup(Some(Duration::ZERO)).await;
+    db.query("SELECT 1").await.unwrap();
+    assert!(log.lock().unwrap()[0].3);
+
+    let failed = db.query("SELECT * FROM missing").await;
+    assert!(failed.is_err());
+    assert_eq!(log.lock().unwrap().len(), 2, "failed statements are reported too");
+}
+
+#[tokio::test]
+async fn test_request_metrics_and_repeated_statements() {
+    let (db, _) = setup(None).await;
+
+    let metrics = context::scope(async {
+        for id in 1..=6 {
+            Widget::find(&db, id).await.unwrap();
+        }
+        Widget::all(&db).await.unwrap();
+        context::current().unwrap().metrics()
+    })
+    .await;
+
+    assert_eq!(metrics.count, 7);
+    assert!(metrics.total_time > Duration::ZERO);
+    assert_eq!(metrics.repeated(5), vec![("SELECT * FROM widgets WHERE id = ?", 6)]);
+    assert!(metrics.repeated(7).is_empty());
+
+    // Nothing is collected outside a scope
+    db.query("SELECT 1").await.unwrap();
+    assert!(context::current().is_none());
+}
+
+#[tokio::test]
+async fn test_layer_scopes_each_request() {
+    let (db, _) = setup(None).await;
+    let service = DbContextLayer::new().n_plus_one_threshold(3).layer(tower::service_fn(move |id: i64| {
+        let db = db.clone();
+        async move {
+            Widget::find(&db, id).await?;
+            Ok::<_, sqlx::Error>(context::current().unwrap().metrics().count)
+        }
+    }));
+
+    assert_eq!(service.clone().oneshot(1).await.unwrap(), 1);
+    assert_eq!(service.oneshot(2).await.unwrap(), 1);
+}
+
+#[test]
+fn test_fingerprint() {
+    assert_eq!(
+        fingerprint("SELECT * FROM t1  WHERE id = 42 AND name = 'o''brien'\n  AND score > 1.5"),
+        "SELECT * FROM t1 WHERE id = ? AND name = ? AND score > ?"
+    );
+    assert_eq!(fingerprint("UPDATE t SET a = $1 WHERE id = $2"), "UPDATE t SET a = $1 WHERE id = $2");
+}
@@ -0,0 +1,180 @@
+use oxidite_db::introspect::{list_tables, table_info};
+use oxidite_db::diff::diff_models;
+use oxidite_db::{sqlx, Database, DbPool, DiffOptions, FieldMeta, MigrationManager, Model, ModelSchema};
+
+#[derive(Model, sqlx::FromRow)]
+struct Widget {
+    id: i64,
+    name: String,
+    price: f64,
+    sku: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+#[test]
+fn test_derive_records_field_types() {
+    let meta = Widget::field_meta();
+    assert_eq!(meta.len(), 6);
+    assert_eq!(meta[1], FieldMeta { name: "name", rust_type: "String", nullable: false });
+    assert_eq!(meta[3], FieldMeta { name: "sku", rust_type: "String", nullable: true });
+}
+
+#[tokio::test]
+async fn test_sqlite_introspection() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    db.execute("CREATE TABLE things (id INTEGER PRIMARY KEY AUTOINCREMENT, name VARCHAR(50) NOT NULL, note TEXT DEFAULT 'x')")
+        .await
+        .unwrap();
+    db.execute("CREATE UNIQUE INDEX things_name_note_unique ON things (name, note)").await.unwrap();
+
+    assert!(list_tables(&db).await.unwrap().contains(&"things".to_string()));
+    assert!(table_info(&db, "missing").await.unwrap().is_none());
+
+    let info = table_info(&db, "things").await.unwrap().unwrap();
+    assert_eq!(info.columns.len(), 3);
+    assert!(info.column("id").unwrap().primary_key);
+    assert!(!info.column("id").unwrap().nullable);
+    assert_eq!(info.column("name").unwrap().data_type, "VARCHAR(50)");
+    assert!(!info.column("name").unwrap().nullable);
+    assert!(info.column("note").unwrap().nullable);
+    assert_eq!(info.column("note").unwrap().default.as_deref(), Some("'x'"));
+
+    assert_eq!(info.indexes.len(), 1);
+    assert_eq!(info.indexes[0].columns, vec!["name", "note"]);
+    assert!(info.indexes[0].unique);
+}
+
+#[tokio::test]
+async fn test_generate_migration_from_models() {
+    let dir = std::env::temp_dir().join(format!("oxidite_autogen_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let manager = MigrationManager::new(&dir);
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let options = DiffOptions::default();
+
+    // Missing table is created
+    let path = manager
+        .generate_migration(&db, "create_widgets", &[ModelSchema::of::<Widget>()], options)
+        .await
+        .unwrap()
+        .unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("CREATE TABLE widgets"));
+    assert!(content.contains("DROP TABLE widgets;"));
+
+    manager.migrate(&db).await.unwrap();
+    let info = table_info(&db, "widgets").await.unwrap().unwrap();
+    assert!(info.column("sku").unwrap().nullable);
+    assert!(!info.column("price").unwrap().nullable);
+
+    // In sync now
+    assert!(manager
+        .generate_migration(&db, "noop", &[ModelSchema::of::<Widget>()], options)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Model gained `stock` and lost `sku`; the column is only dropped when asked
+    let mut changed = ModelSchema::of::<Widget>();
+    changed.fields.retain(|f| f.name != "sku");
+    changed.fields.push(FieldMeta { name: "stock", rust_type: "i32", nullable: false });
+
+    let kept = diff_models(&db, &[changed.clone()], options).await.unwrap().to_migration("keep", db.db_type()).unwrap();
+    assert!(kept.up_sql.contains("-- NOTE: widgets.sku is not on the model; drop it by hand"));
+    assert!(!kept.up_sql.contains("DROP COLUMN sku"));
+
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    let drop = DiffOptions { drop_columns: true };
+    let path = manager.generate_migration(&db, "sync_widgets", &[changed], drop).await.unwrap().unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("-- NOTE: widgets.sku is not on the model and will be dropped"));
+    assert!(content.contains("ALTER TABLE widgets ADD COLUMN stock INTEGER NOT NULL DEFAULT 0;"));
+    assert!(content.contains("ALTER TABLE widgets DROP COLUMN sku;"));
+
+    manager.migrate(&db).await.unwrap();
+    let info = table_info(&db, "widgets").await.unwrap().unwrap();
+    assert!(info.column("sku").is_none());
+    assert!(info.column("stock").is_some());
+
+    // Down migration restores the dropped column
+    manager.rollback(&db, 1).await.unwrap();
+    let info = table_info(&db, "widgets").await.unwrap().unwrap();
+    assert!(info.column("sku").is_some());
+    assert!(info.column("stock").is_none());
+}
+
+#[tokio::test]
+async fn test_type_and_nullability_changes_rebuild_sqlite_tables() {
+    let dir = std::env::temp_dir().join(format!("oxidite_autogen_retype_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let manager = MigrationManager::new(&dir);
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    db.execute(
+        "CREATE TABLE widgets (id INTEGER PRIMARY KEY AUTOINCREMENT, name VARCHAR(255) NOT NULL, price REAL NOT NULL, \
+         sku VARCHAR(255), created_at INTEGER NOT NULL, updated_at INTEGER NOT NULL)",
+    )
+    .await
+    .unwrap();
+    db.execute("CREATE INDEX widgets_created_at_index ON widgets (created_at)").await.unwrap();
+    db.execute("INSERT INTO widgets (name, price, sku, created_at, updated_at) VALUES ('bolt', 1.5, 'B-1', 0, 0)")
+        .await
+        .unwrap();
+
+    // `price` became a String and `sku` is required now
+    let mut retyped = ModelSchema::of::<Widget>().index(&["created_at"]);
+    for field in &mut retyped.fields {
+        match field.name {
+            "price" => field.rust_type = "String",
+            "sku" => field.nullable = false,
+            _ => {}
+        }
+    }
+
+    let path = manager
+        .generate_migration(&db, "retype_widgets", &[retyped], DiffOptions::default())
+        .await
+        .unwrap()
+        .unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("-- NOTE: widgets.sku becomes NOT NULL"));
+    assert!(content.contains("CREATE TABLE _oxidite_rebuild_widgets"));
+    assert!(content.contains("id INTEGER PRIMARY KEY AUTOINCREMENT"));
+    assert!(content.contains("price VARCHAR(255) NOT NULL"));
+
+    manager.migrate(&db).await.unwrap();
+    let info = table_info(&db, "widgets").await.unwrap().unwrap();
+    assert_eq!(info.column("price").unwrap().data_type, "VARCHAR(255)");
+    assert!(!info.column("sku").unwrap().nullable);
+    assert_eq!(info.indexes[0].name, "widgets_created_at_index");
+    let row = db.query_one("SELECT sku FROM widgets").await.unwrap().unwrap();
+    assert_eq!(sqlx::Row::get::<String, _>(&row, "sku"), "B-1");
+
+    manager.rollback(&db, 1).await.unwrap();
+    let info = table_info(&db, "widgets").await.unwrap().unwrap();
+    assert_eq!(info.column("price").unwrap().data_type, "REAL");
+    assert!(info.column("sku").unwrap().nullable);
+    assert_eq!(db.query("SELECT id FROM widgets").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_indexes_are_compared() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    db.execute(
+        "CREATE TABLE widgets (id INTEGER PRIMARY KEY, name VARCHAR(255) NOT NULL, price REAL NOT NULL, \
+         sku VARCHAR(255), created_at INTEGER NOT NULL, updated_at INTEGER NOT NULL)",
+    )
+    .await
+    .unwrap();
+    db.execute("CREATE INDEX widgets_created_at_index ON widgets (created_at)").await.unwrap();
+
+    let model = ModelSchema::of::<Widget>().unique(&["sku"]);
+    let diff = diff_models(&db, &[model], DiffOptions::default()).await.unwrap();
+    let migration = diff.to_migration("widget_indexes", db.db_type()).unwrap();
+    assert!(migration.up_sql.contains("-- NOTE: widgets has index widgets_created_at_index on (created_at) that the model doesn't declare"));
+    assert!(migration.up_sql.contains("CREATE UNIQUE INDEX widgets_sku_unique ON widgets (sku);"));
+    assert_eq!(migration.down_sql, "DROP INDEX widgets_sku_unique;");
+
+    let model = ModelSchema::of::<Widget>().index(&["created_at"]);
+    assert!(diff_models(&db, &[model], DiffOptions::default()).await.unwrap().is_empty());
+}
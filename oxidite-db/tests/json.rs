@@ -0,0 +1,120 @@
+use oxidite_db::{sqlx, Database, DatabaseType, DbPool, Json, Model, Original, SqlValue};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+struct Settings {
+    theme: String,
+    font_size: i64,
+    beta: bool,
+}
+
+#[derive(Model, sqlx::FromRow, Debug, Clone)]
+struct Account {
+    id: i64,
+    name: String,
+    settings: Json<Settings>,
+    tags: Option<Json<Vec<String>>>,
+    #[sqlx(skip)]
+    original: Original,
+}
+
+fn account(name: &str, theme: &str, beta: bool) -> Account {
+    Account {
+        id: 0,
+        name: name.to_string(),
+        settings: Json(Settings { theme: theme.to_string(), font_size: 14, beta }),
+        tags: Some(Json(vec!["new".to_string()])),
+        original: Original::default(),
+    }
+}
+
+async fn setup() -> DbPool {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    db.execute("CREATE TABLE accounts (id INTEGER PRIMARY KEY, name TEXT NOT NULL, settings TEXT NOT NULL, tags TEXT)")
+        .await
+        .unwrap();
+    db
+}
+
+#[tokio::test]
+async fn test_json_columns_round_trip() {
+    let db = setup().await;
+
+    let mut ada = account("Ada", "dark", true);
+    ada.create(&db).await.unwrap();
+    Account::insert_many(&db, &[account("Alan", "light", false)]).await.unwrap();
+
+    let mut loaded = Account::find(&db, ada.id).await.unwrap().unwrap();
+    assert_eq!(loaded.settings, ada.settings);
+    assert_eq!(loaded.tags.as_deref().map(Vec::len), Some(1));
+
+    loaded.settings.font_size = 18;
+    loaded.tags = None;
+    assert_eq!(loaded.changed_fields(), vec!["settings", "tags"]);
+    loaded.update(&db).await.unwrap();
+
+    let stored = Account::find(&db, ada.id).await.unwrap().unwrap();
+    assert_eq!(stored.settings.font_size, 18);
+    assert!(stored.tags.is_none());
+}
+
+#[tokio::test]
+async fn test_where_json() {
+    let db = setup().await;
+    for mut account in [account("Ada", "dark", true), account("Alan", "light", false), account("Grace", "dark", false)] {
+        account.create(&db).await.unwrap();
+    }
+
+    let names = |accounts: Vec<Account>| accounts.into_iter().map(|a| a.name).collect::<Vec<_>>();
+    let dark = Account::query().where_json("settings->theme", "dark").get::<Account>(&db).await.unwrap();
+    assert_eq!(names(dark), vec!["Ada", "Grace"]);
+    let beta = Account::query().where_json("settings->beta", true).get::<Account>(&db).await.unwrap();
+    assert_eq!(names(beta), vec!["Ada"]);
+    let sized = Account::query().where_json("settings->font_size", 14).get::<Account>(&db).await.unwrap();
+    assert_eq!(sized.len(), 3);
+    let tagged = Account::query().where_json("tags->0", "new").get::<Account>(&db).await.unwrap();
+    assert_eq!(tagged.len(), 3);
+    let invalid = Account::query().where_json("settings->the'me", "dark").get::<Account>(&db).await.unwrap();
+    assert!(invalid.is_empty());
+}
+
+#[test]
+fn test_json_sql_per_dialect() {
+    let query = Account::query().where_json("settings->theme", "it's").where_json("settings->beta", true);
+
+    assert_eq!(
+        query.build(),
+        "SELECT * FROM accounts WHERE JSON_EXTRACT(settings, '$.\"theme\"') = $1 \
+         AND JSON_EXTRACT(settings, '$.\"beta\"') = $2"
+    );
+    assert_eq!(query.bindings(), vec![SqlValue::from("it's"), SqlValue::Int(1)]);
+
+    let query = query.dialect(DatabaseType::Postgres);
+    assert_eq!(
+        query.build(),
+        "SELECT id, name, settings::text AS settings, tags::text AS tags FROM accounts \
+         WHERE settings #>> '{theme}' = $1 AND settings #> '{beta}' = $2::jsonb"
+    );
+    assert_eq!(query.bindings(), vec![SqlValue::from("it's"), SqlValue::from("true")]);
+
+    let query = query.dialect(DatabaseType::MySql);
+    assert_eq!(
+        query.build(),
+        "SELECT id, name, CAST(settings AS CHAR) AS settings, CAST(tags AS CHAR) AS tags FROM accounts \
+         WHERE JSON_UNQUOTE(JSON_EXTRACT(settings, '$.\"theme\"')) = ? \
+         AND JSON_EXTRACT(settings, '$.\"beta\"') = CAST(? AS JSON)"
+    );
+    assert_eq!(query.bindings(), vec![SqlValue::from("it's"), SqlValue::from("true")]);
+}
+
+#[test]
+fn test_json_values_never_reach_the_sql() {
+    // MySQL reads `\'` as an escaped quote, so an inlined value would run the rest as SQL
+    let value = "\\' OR 1=1 -- ";
+    let query = Account::query().where_json("settings->theme", value).dialect(DatabaseType::MySql);
+
+    let sql = query.build();
+    assert!(!sql.contains("OR 1=1"), "{}", sql);
+    assert!(sql.ends_with("JSON_UNQUOTE(JSON_EXTRACT(settings, '$.\"theme\"')) = ?"), "{}", sql);
+    assert_eq!(query.bindings(), vec![SqlValue::from(value)]);
+}
@@ -0,0 +1,74 @@
+use oxidite_db::{http_error, sqlx, Database, DbPool, Model, SqlValue, StaleObject};
+
+#[derive(Model, sqlx::FromRow, Debug, Clone)]
+#[model(version_column = "lock_version")]
+struct Document {
+    id: i64,
+    title: String,
+    lock_version: i64,
+    updated_at: i64,
+}
+
+async fn setup() -> DbPool {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    db.execute(
+        "CREATE TABLE documents (id INTEGER PRIMARY KEY, title TEXT NOT NULL, \
+         lock_version INTEGER NOT NULL DEFAULT 0, updated_at INTEGER NOT NULL DEFAULT 0)",
+    )
+    .await
+    .unwrap();
+    db.execute("INSERT INTO documents (id, title) VALUES (1, 'draft')").await.unwrap();
+    db
+}
+
+#[tokio::test]
+async fn test_update_increments_version() {
+    let db = setup().await;
+
+    let mut doc = Document::find(&db, 1).await.unwrap().unwrap();
+    doc.title = "first".to_string();
+    doc.update(&db).await.unwrap();
+    doc.title = "second".to_string();
+    doc.update(&db).await.unwrap();
+
+    assert_eq!(doc.lock_version, 2);
+    let stored = Document::find(&db, 1).await.unwrap().unwrap();
+    assert_eq!((stored.title.as_str(), stored.lock_version), ("second", 2));
+}
+
+#[tokio::test]
+async fn test_concurrent_edit_is_stale() {
+    let db = setup().await;
+
+    let mut first = Document::find(&db, 1).await.unwrap().unwrap();
+    let mut second = first.clone();
+
+    first.title = "from first admin".to_string();
+    first.update(&db).await.unwrap();
+
+    second.title = "from second admin".to_string();
+    let err = second.update(&db).await.unwrap_err();
+    let stale = StaleObject::find(&err).expect("a stale object error");
+    assert_eq!((stale.table, stale.id.as_str(), stale.version), ("documents", "1", 0));
+    assert_eq!(second.lock_version, 0, "a failed update leaves the version alone");
+
+    let stored = Document::find(&db, 1).await.unwrap().unwrap();
+    assert_eq!(stored.title, "from first admin");
+
+    assert_eq!(http_error(err).status_code(), 409);
+    assert!(StaleObject::find(&sqlx::Error::RowNotFound).is_none());
+}
+
+#[tokio::test]
+async fn test_set_based_update_bumps_version() {
+    let db = setup().await;
+
+    let mut doc = Document::find(&db, 1).await.unwrap().unwrap();
+    Document::update_where(&db, &[("id", 1.into())], &[("title", SqlValue::from("bulk"))])
+        .await
+        .unwrap();
+
+    doc.title = "stale".to_string();
+    assert!(doc.update(&db).await.is_err());
+    assert_eq!(Document::find(&db, 1).await.unwrap().unwrap().lock_version, 1);
+}
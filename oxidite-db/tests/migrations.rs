@@ -0,0 +1,295 @@
+use oxidite_db::migrations::split_statements;
+use oxidite_db::{sqlx, Database, DbPool, Migration, MigrationError, MigrationManager, PoolOptions};
+use std::path::PathBuf;
+
+fn migrations_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("oxidite_migrations_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_migration(dir: &PathBuf, version: &str, up: &str, down: &str) {
+    Migration {
+        version: version.to_string(),
+        name: version.split_once('_').unwrap().1.to_string(),
+        up_sql: up.to_string(),
+        down_sql: down.to_string(),
+        ..Default::default()
+    }
+    .save(dir)
+    .unwrap();
+}
+
+fn with_two_migrations(name: &str) -> MigrationManager {
+    let dir = migrations_dir(name);
+    write_migration(
+        &dir,
+        "20240101000000_create_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);\nINSERT INTO users (name) VALUES ('a;b');",
+        "DROP TABLE users;",
+    );
+    write_migration(
+        &dir,
+        "20240102000000_create_posts",
+        "CREATE TABLE posts (id INTEGER PRIMARY KEY, title TEXT);",
+        "DROP TABLE posts;",
+    );
+    MigrationManager::new(dir)
+}
+
+async fn table_exists(db: &DbPool, table: &str) -> bool {
+    db.query_one(&format!("SELECT 1 FROM {} LIMIT 1", table)).await.is_ok()
+}
+
+#[tokio::test]
+async fn test_migrate_and_rollback() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let manager = with_two_migrations("migrate_and_rollback");
+
+    let ran = manager.migrate(&db).await.unwrap();
+    assert_eq!(ran.len(), 2);
+    assert!(table_exists(&db, "users").await);
+    assert!(table_exists(&db, "posts").await);
+
+    // Nothing left to run
+    assert!(manager.migrate(&db).await.unwrap().is_empty());
+
+    let reverted = manager.rollback(&db, 1).await.unwrap();
+    assert_eq!(reverted, vec!["20240102000000_create_posts"]);
+    assert!(!table_exists(&db, "posts").await);
+    assert!(table_exists(&db, "users").await);
+
+    let reverted = manager.reset(&db).await.unwrap();
+    assert_eq!(reverted, vec!["20240101000000_create_users"]);
+    assert!(manager.get_applied_migrations(&db).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_failed_migration_is_rolled_back() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let dir = migrations_dir("failed");
+    write_migration(
+        &dir,
+        "20240101000000_broken",
+        "CREATE TABLE things (id INTEGER PRIMARY KEY);\nINSERT INTO missing_table VALUES (1);",
+        "DROP TABLE things;",
+    );
+    let manager = MigrationManager::new(&dir);
+
+    assert!(matches!(manager.migrate(&db).await, Err(MigrationError::Database(_))));
+    assert!(!table_exists(&db, "things").await);
+    assert!(manager.get_applied_migrations(&db).await.unwrap().is_empty());
+
+    // The lock is released after a failure
+    write_migration(&dir, "20240101000000_broken", "CREATE TABLE things (id INTEGER PRIMARY KEY);", "DROP TABLE things;");
+    assert_eq!(manager.migrate(&db).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_checksum_mismatch_refuses_to_run() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let dir = migrations_dir("checksum");
+    write_migration(&dir, "20240101000000_create_users", "CREATE TABLE users (id INTEGER PRIMARY KEY);", "DROP TABLE users;");
+    let manager = MigrationManager::new(&dir);
+    manager.migrate(&db).await.unwrap();
+
+    write_migration(&dir, "20240101000000_create_users", "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);", "DROP TABLE users;");
+    write_migration(&dir, "20240102000000_create_posts", "CREATE TABLE posts (id INTEGER PRIMARY KEY);", "DROP TABLE posts;");
+
+    assert_eq!(manager.modified_migrations(&db).await.unwrap(), vec!["20240101000000_create_users"]);
+    match manager.migrate(&db).await {
+        Err(MigrationError::ChecksumMismatch(version)) => assert_eq!(version, "20240101000000_create_users"),
+        other => panic!("expected checksum mismatch, got {:?}", other),
+    }
+    assert!(!table_exists(&db, "posts").await);
+}
+
+#[tokio::test]
+async fn test_migrate_to_and_refresh() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let manager = with_two_migrations("migrate_to");
+
+    let ran = manager.migrate_to(&db, "20240101000000_create_users").await.unwrap();
+    assert_eq!(ran, vec!["20240101000000_create_users"]);
+    assert!(!table_exists(&db, "posts").await);
+
+    manager.migrate(&db).await.unwrap();
+    let changed = manager.migrate_to(&db, "20240101000000_create_users").await.unwrap();
+    assert_eq!(changed, vec!["20240102000000_create_posts"]);
+
+    assert!(matches!(
+        manager.migrate_to(&db, "20990101000000_nope").await,
+        Err(MigrationError::UnknownVersion(_))
+    ));
+
+    let ran = manager.refresh(&db).await.unwrap();
+    assert_eq!(ran.len(), 2);
+    assert_eq!(manager.get_applied_migrations(&db).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_concurrent_run_is_locked() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let manager = with_two_migrations("locked");
+    manager.ensure_migrations_table(&db).await.unwrap();
+
+    // Simulate another process holding the lock
+    let now = chrono::Utc::now().timestamp();
+    db.execute("CREATE TABLE _migrations_lock (id INTEGER PRIMARY KEY, locked_at INTEGER NOT NULL)").await.unwrap();
+    db.execute(&format!("INSERT INTO _migrations_lock (id, locked_at) VALUES (1, {})", now)).await.unwrap();
+    assert!(matches!(manager.migrate(&db).await, Err(MigrationError::Locked)));
+
+    manager.force_unlock(&db).await.unwrap();
+    assert_eq!(manager.migrate(&db).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_stale_lock_is_cleared() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let manager = with_two_migrations("stale_lock").with_stale_lock_after(std::time::Duration::from_secs(60));
+    manager.ensure_migrations_table(&db).await.unwrap();
+
+    // Left behind by a run that crashed two minutes ago
+    let then = chrono::Utc::now().timestamp() - 120;
+    db.execute("CREATE TABLE _migrations_lock (id INTEGER PRIMARY KEY, locked_at INTEGER NOT NULL)").await.unwrap();
+    db.execute(&format!("INSERT INTO _migrations_lock (id, locked_at) VALUES (1, {})", then)).await.unwrap();
+    assert_eq!(manager.migrate(&db).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_only_a_held_lock_reports_locked() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let manager = with_two_migrations("lock_error");
+    manager.ensure_migrations_table(&db).await.unwrap();
+
+    // Taking the lock fails for a reason other than another run holding it
+    db.execute("CREATE TABLE _migrations_lock (id INTEGER PRIMARY KEY, locked_at INTEGER NOT NULL, holder TEXT NOT NULL)")
+        .await
+        .unwrap();
+    assert!(matches!(manager.migrate(&db).await, Err(MigrationError::Database(_))));
+}
+
+#[tokio::test]
+async fn test_migration_outside_a_transaction() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let dir = migrations_dir("no_transaction");
+    std::fs::write(
+        dir.join("20240101000000_vacuum.sql"),
+        "-- migrate:up transaction:false\nVACUUM;\n\n-- migrate:down\nVACUUM;\n",
+    )
+    .unwrap();
+    let manager = MigrationManager::new(&dir);
+
+    let migration = &manager.list_migrations().unwrap()[0];
+    assert!(migration.no_transaction);
+    assert_eq!(migration.up_sql, "VACUUM;");
+
+    // VACUUM refuses to run inside a transaction
+    assert_eq!(manager.migrate(&db).await.unwrap().len(), 1);
+    assert_eq!(manager.rollback(&db, 1).await.unwrap().len(), 1);
+
+    let path = migration.save(&dir).unwrap();
+    assert!(std::fs::read_to_string(path).unwrap().starts_with("-- migrate:up transaction:false\n"));
+}
+
+#[tokio::test]
+async fn test_migrate_with_a_single_connection() {
+    let options = PoolOptions { max_connections: 1, ..Default::default() };
+    let db = DbPool::connect_with_options("sqlite::memory:", options).await.unwrap();
+    let manager = with_two_migrations("single_connection");
+
+    // Holding the lock doesn't take the only connection away from the migrations
+    let ran = tokio::time::timeout(std::time::Duration::from_secs(10), manager.migrate(&db)).await;
+    assert_eq!(ran.expect("migrate deadlocked").unwrap().len(), 2);
+    assert!(table_exists(&db, "posts").await);
+    assert_eq!(manager.rollback(&db, 2).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_bookkeeping_reads_use_the_primary() {
+    // The replica is a separate, empty database: as far behind as a replica gets
+    let replica = DbPool::connect("sqlite::memory:").await.unwrap();
+    let db = DbPool::connect("sqlite::memory:").await.unwrap().with_replica(replica);
+    let manager = with_two_migrations("primary_reads");
+
+    assert_eq!(manager.migrate(&db).await.unwrap().len(), 2);
+    assert_eq!(manager.get_applied_migrations(&db).await.unwrap().len(), 2);
+    assert!(manager.migrate(&db).await.unwrap().is_empty());
+}
+
+#[test]
+fn test_split_statements() {
+    let sql = r#"
+        -- comment; with semicolon
+        CREATE TABLE a (id INT);
+        INSERT INTO a VALUES ('x;y', "q;uote");
+        /* block; comment */
+        CREATE FUNCTION f() RETURNS trigger AS $$
+        BEGIN
+            NEW.updated_at = now();
+            RETURN NEW;
+        END;
+        $$ LANGUAGE plpgsql;
+        SELECT 'it''s; fine'
+    "#;
+
+    let statements = split_statements(sql);
+    assert_eq!(statements.len(), 4);
+    assert_eq!(statements[1], r#"INSERT INTO a VALUES ('x;y', "q;uote")"#);
+    assert!(statements[2].ends_with("$$ LANGUAGE plpgsql"));
+    assert_eq!(statements[3], "SELECT 'it''s; fine'");
+}
+
+#[test]
+fn test_split_statements_keeps_trigger_bodies() {
+    let sql = "
+        BEGIN;
+        CREATE TRIGGER touch AFTER UPDATE ON posts
+        BEGIN
+            UPDATE posts SET flag = CASE WHEN NEW.n > 0 THEN 1 ELSE 0 END WHERE id = NEW.id;
+            UPDATE posts SET updated = 1 WHERE id = NEW.id;
+        END;
+        CREATE PROCEDURE p() BEGIN IF 1 THEN SELECT 1; END IF; SELECT 2; END;
+        COMMIT;
+    ";
+
+    let statements = split_statements(sql);
+    assert_eq!(statements.len(), 4, "{:#?}", statements);
+    assert_eq!(statements[0], "BEGIN");
+    assert!(statements[1].starts_with("CREATE TRIGGER") && statements[1].ends_with("END"));
+    assert!(statements[2].ends_with("SELECT 2; END"));
+    assert_eq!(statements[3], "COMMIT");
+}
+
+#[tokio::test]
+async fn test_trigger_migration() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let dir = migrations_dir("trigger");
+    write_migration(
+        &dir,
+        "20240101000000_create_posts",
+        "CREATE TABLE posts (id INTEGER PRIMARY KEY, title TEXT, edits INTEGER NOT NULL DEFAULT 0);
+        CREATE TRIGGER count_edits AFTER UPDATE OF title ON posts
+        BEGIN
+            UPDATE posts SET edits = edits + 1 WHERE id = NEW.id;
+        END;",
+        "DROP TABLE posts;",
+    );
+
+    MigrationManager::new(dir).migrate(&db).await.unwrap();
+    db.execute("INSERT INTO posts (id, title) VALUES (1, 'a')").await.unwrap();
+    db.execute("UPDATE posts SET title = 'b' WHERE id = 1").await.unwrap();
+    let row = db.query_one("SELECT edits FROM posts WHERE id = 1").await.unwrap().unwrap();
+    assert_eq!(sqlx::Row::get::<i64, _>(&row, "edits"), 1);
+}
+
+#[test]
+fn test_checksum_ignores_surrounding_whitespace() {
+    let mut a = Migration::new("x");
+    a.up_sql = "CREATE TABLE x (id INT);".to_string();
+    let mut b = a.clone();
+    b.up_sql = "\nCREATE TABLE x (id INT);\n".to_string();
+    assert_eq!(a.checksum(), b.checksum());
+    assert_eq!(a.checksum().len(), 64);
+}
@@ -0,0 +1,90 @@
+use oxidite_db::{sqlx, Database, DbPool, Model, QueryBuilder};
+
+#[derive(Model, sqlx::FromRow, Debug)]
+struct Article {
+    id: i64,
+    title: String,
+    score: i64,
+}
+
+async fn setup() -> DbPool {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    db.execute("CREATE TABLE articles (id INTEGER PRIMARY KEY, title TEXT NOT NULL, score INTEGER NOT NULL)")
+        .await
+        .unwrap();
+    for i in 1..=25 {
+        // Scores repeat so the keyset needs the id tiebreaker
+        db.execute(&format!("INSERT INTO articles (id, title, score) VALUES ({}, 'a{}', {})", i, i, i % 5))
+            .await
+            .unwrap();
+    }
+    db
+}
+
+#[tokio::test]
+async fn test_offset_pagination() {
+    let db = setup().await;
+
+    let page = Article::paginate(&db, 2, 10).await.unwrap();
+    assert_eq!(page.data.len(), 10);
+    assert_eq!(page.data[0].id, 11);
+    assert_eq!(page.meta.total, 25);
+    assert_eq!(page.meta.last_page, 3);
+    assert_eq!(page.meta.next_page, Some(3));
+    assert_eq!(page.meta.prev_page, Some(1));
+
+    let last = Article::paginate(&db, 3, 10).await.unwrap();
+    assert_eq!(last.data.len(), 5);
+    assert_eq!(last.meta.next_page, None);
+
+    // A page number past any real offset is empty rather than an overflow
+    let beyond = Article::paginate(&db, u64::MAX, u64::MAX).await.unwrap();
+    assert!(beyond.data.is_empty());
+    assert_eq!(beyond.meta.total, 25);
+
+    let filtered = QueryBuilder::new("articles")
+        .where_eq("score", "0")
+        .order_by("id", "DESC")
+        .paginate::<Article>(&db, 1, 2)
+        .await
+        .unwrap();
+    assert_eq!(filtered.meta.total, 5);
+    assert_eq!(filtered.data.iter().map(|a| a.id).collect::<Vec<_>>(), vec![25, 20]);
+}
+
+#[tokio::test]
+async fn test_cursor_pagination_walks_every_row_once() {
+    let db = setup().await;
+
+    let mut seen = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = Article::cursor_paginate(&db, &["score DESC", "id"], cursor.as_deref(), 7)
+            .await
+            .unwrap();
+        seen.extend(page.data.iter().map(|a| (a.score, a.id)));
+        match page.meta.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+
+    let mut expected: Vec<(i64, i64)> = (1..=25).map(|i| (i % 5, i)).collect();
+    expected.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    assert_eq!(seen, expected);
+}
+
+#[tokio::test]
+async fn test_invalid_cursor_is_rejected() {
+    let db = setup().await;
+    assert!(Article::cursor_paginate(&db, &["id"], Some("garbage"), 5).await.is_err());
+}
+
+#[test]
+fn test_page_serializes_to_envelope() {
+    let page = oxidite_db::Page::new(vec!["a", "b"], 12, 1, 2);
+    let json = serde_json::to_value(&page).unwrap();
+    assert_eq!(json["data"], serde_json::json!(["a", "b"]));
+    assert_eq!(json["meta"]["last_page"], 6);
+    assert_eq!(json["meta"]["prev_page"], serde_json::Value::Null);
+}
@@ -0,0 +1,149 @@
+use oxidite_db::schema::ReferentialAction;
+use oxidite_db::{Database, DatabaseType, DbPool, Migration, MigrationError, MigrationManager, Schema, SchemaError};
+
+fn users() -> Schema {
+    Schema::create("users", |t| {
+        t.id();
+        t.string("email").unique();
+        t.string("name").nullable();
+        t.boolean("active").default(true);
+        t.timestamps();
+        t.soft_deletes();
+    })
+}
+
+fn posts() -> Schema {
+    Schema::create("posts", |t| {
+        t.id();
+        t.foreign_id("user_id").references("users", "id").on_delete(ReferentialAction::Cascade);
+        t.string_with_length("title", 120);
+        t.json("meta").nullable();
+        t.index(&["user_id", "title"]);
+    })
+}
+
+#[test]
+fn test_create_table_per_dialect() {
+    let sqlite = users().to_sql(DatabaseType::Sqlite).unwrap();
+    assert_eq!(
+        sqlite[0],
+        "CREATE TABLE users (\n    \
+         id INTEGER PRIMARY KEY AUTOINCREMENT,\n    \
+         email VARCHAR(255) NOT NULL,\n    \
+         name VARCHAR(255),\n    \
+         active BOOLEAN NOT NULL DEFAULT 1,\n    \
+         created_at INTEGER NOT NULL DEFAULT 0,\n    \
+         updated_at INTEGER NOT NULL DEFAULT 0,\n    \
+         deleted_at INTEGER\n)"
+    );
+    assert_eq!(sqlite[1], "CREATE UNIQUE INDEX users_email_unique ON users (email)");
+
+    let postgres = users().to_sql(DatabaseType::Postgres).unwrap();
+    assert!(postgres[0].contains("id BIGSERIAL PRIMARY KEY"));
+    assert!(postgres[0].contains("active BOOLEAN NOT NULL DEFAULT TRUE"));
+    assert!(postgres[0].contains("created_at BIGINT NOT NULL DEFAULT 0"));
+
+    let mysql = users().to_sql(DatabaseType::MySql).unwrap();
+    assert!(mysql[0].contains("id BIGINT AUTO_INCREMENT PRIMARY KEY"));
+}
+
+#[test]
+fn test_foreign_keys_and_indexes() {
+    let postgres = posts().to_sql(DatabaseType::Postgres).unwrap();
+    assert!(postgres[0].contains(
+        "CONSTRAINT posts_user_id_foreign FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE"
+    ));
+    assert!(postgres[0].contains("meta JSONB"));
+    assert_eq!(postgres[1], "CREATE INDEX posts_user_id_title_index ON posts (user_id, title)");
+
+    let mysql = posts().to_sql(DatabaseType::MySql).unwrap();
+    assert!(mysql[0].contains("meta JSON"));
+}
+
+#[test]
+fn test_alter_table() {
+    let alter = Schema::alter("users", |t| {
+        t.drop_index("users_email_unique");
+        t.rename_column("name", "full_name");
+        t.drop_column("active");
+        t.foreign_id("team_id").nullable().references("teams", "id");
+    });
+
+    assert_eq!(
+        alter.to_sql(DatabaseType::MySql).unwrap(),
+        vec![
+            "DROP INDEX users_email_unique ON users",
+            "ALTER TABLE users RENAME COLUMN name TO full_name",
+            "ALTER TABLE users DROP COLUMN active",
+            "ALTER TABLE users ADD COLUMN team_id BIGINT",
+            "ALTER TABLE users ADD CONSTRAINT users_team_id_foreign FOREIGN KEY (team_id) REFERENCES teams (id)",
+        ]
+    );
+
+    let sqlite = alter.to_sql(DatabaseType::Sqlite).unwrap();
+    assert_eq!(sqlite[0], "DROP INDEX users_email_unique");
+    assert_eq!(sqlite[3], "ALTER TABLE users ADD COLUMN team_id INTEGER REFERENCES teams (id)");
+    assert_eq!(sqlite.len(), 4);
+}
+
+#[tokio::test]
+async fn test_sqlite_rejects_a_foreign_key_on_an_existing_column() {
+    let alter = Schema::alter("posts", |t| {
+        t.foreign("user_id", "users", "id");
+    });
+
+    assert_eq!(alter.to_sql(DatabaseType::Postgres).unwrap().len(), 1);
+    assert_eq!(
+        alter.to_sql(DatabaseType::Sqlite),
+        Err(SchemaError::SqliteForeignKey { table: "posts".to_string(), column: "user_id".to_string() })
+    );
+
+    // The migration fails with the error instead of taking the process down
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let dir = std::env::temp_dir().join(format!("oxidite_schema_fk_{}", std::process::id()));
+    let mut manager = MigrationManager::new(&dir);
+    manager.register(Migration::schema("20240101120000_link_posts", vec![alter], Vec::new()));
+    assert!(matches!(manager.migrate(&db).await, Err(MigrationError::Schema(SchemaError::SqliteForeignKey { .. }))));
+}
+
+#[test]
+fn test_drop_and_rename() {
+    assert_eq!(Schema::drop("users").to_sql(DatabaseType::Sqlite).unwrap(), vec!["DROP TABLE users"]);
+    assert_eq!(Schema::drop_if_exists("users").to_sql(DatabaseType::Postgres).unwrap(), vec!["DROP TABLE IF EXISTS users"]);
+    assert_eq!(Schema::rename("users", "members").to_sql(DatabaseType::MySql).unwrap(), vec!["ALTER TABLE users RENAME TO members"]);
+}
+
+#[tokio::test]
+async fn test_registered_migration_runs_with_sql_files() {
+    let dir = std::env::temp_dir().join(format!("oxidite_schema_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    Migration {
+        version: "20240103000000_add_bio".to_string(),
+        name: "add_bio".to_string(),
+        up_sql: "ALTER TABLE users ADD COLUMN bio TEXT;".to_string(),
+        down_sql: "ALTER TABLE users DROP COLUMN bio;".to_string(),
+        ..Default::default()
+    }
+    .save(&dir)
+    .unwrap();
+
+    let mut manager = MigrationManager::new(&dir);
+    manager
+        .register(Migration::schema("20240101000000_create_users", vec![users()], vec![Schema::drop("users")]))
+        .register(Migration::schema("20240102000000_create_posts", vec![posts()], vec![Schema::drop("posts")]));
+
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let ran = manager.migrate(&db).await.unwrap();
+    assert_eq!(
+        ran,
+        vec!["20240101000000_create_users", "20240102000000_create_posts", "20240103000000_add_bio"]
+    );
+
+    db.execute("INSERT INTO users (email, bio) VALUES ('a@b.co', 'hi')").await.unwrap();
+    db.execute("INSERT INTO posts (user_id, title) VALUES (1, 'First')").await.unwrap();
+    // Unique index from `.unique()`
+    assert!(db.execute("INSERT INTO users (email) VALUES ('a@b.co')").await.is_err());
+
+    assert_eq!(manager.reset(&db).await.unwrap().len(), 3);
+    assert!(db.query_one("SELECT 1 FROM users").await.is_err());
+}
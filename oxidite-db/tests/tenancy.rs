@@ -0,0 +1,164 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use oxidite_db::tenancy::{self, with_tenant};
+use oxidite_db::{sqlx, Database, DbPool, Model, SqlValue, Tenant, TenantLayer, TenantResolver};
+use sha2::Sha256;
+use tower::{service_fn, Layer, ServiceExt};
+
+#[derive(Model, sqlx::FromRow, Debug, Clone)]
+#[model(tenant_scoped)]
+struct Project {
+    id: i64,
+    tenant_id: String,
+    name: String,
+}
+
+async fn setup() -> DbPool {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    db.execute("CREATE TABLE projects (id INTEGER PRIMARY KEY, tenant_id TEXT NOT NULL, name TEXT NOT NULL)")
+        .await
+        .unwrap();
+    db.execute(
+        "INSERT INTO projects (id, tenant_id, name) VALUES \
+         (1, 'acme', 'Rockets'), (2, 'acme', 'Anvils'), (3, 'globex', 'Domes')",
+    )
+    .await
+    .unwrap();
+    db
+}
+
+fn tenant(id: &str) -> Tenant {
+    Tenant::new(id).unwrap()
+}
+
+fn names(projects: &[Project]) -> Vec<&str> {
+    projects.iter().map(|p| p.name.as_str()).collect()
+}
+
+#[tokio::test]
+async fn test_reads_and_writes_are_limited_to_the_tenant() {
+    let db = setup().await;
+
+    with_tenant(tenant("acme"), async {
+        assert_eq!(names(&Project::all(&db).await.unwrap()), vec!["Rockets", "Anvils"]);
+        assert!(Project::find(&db, 3).await.unwrap().is_none());
+        assert_eq!(Project::paginate(&db, 1, 10).await.unwrap().meta.total, 2);
+
+        let mut project = Project { id: 0, tenant_id: "globex".into(), name: "Magnets".into() };
+        project.create(&db).await.unwrap();
+        assert_eq!(project.tenant_id, "acme", "inserts take the current tenant");
+
+        // A record of another tenant can't be changed by id
+        let mut foreign = Project { id: 3, tenant_id: "acme".into(), name: "Hijacked".into() };
+        foreign.update(&db).await.unwrap();
+        foreign.delete(&db).await.unwrap();
+    })
+    .await;
+
+    with_tenant(tenant("globex"), async {
+        assert_eq!(names(&Project::all(&db).await.unwrap()), vec!["Domes"]);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_set_based_operations_are_limited_to_the_tenant() {
+    let db = setup().await;
+
+    with_tenant(tenant("globex"), async {
+        let rows = [Project { id: 0, tenant_id: String::new(), name: "Lasers".into() }];
+        Project::insert_many(&db, &rows).await.unwrap();
+
+        let renamed = Project::update_where(&db, &[("name LIKE", "%".into())], &[("name", SqlValue::from("Renamed"))]).await.unwrap();
+        assert_eq!(renamed, 2);
+        assert_eq!(Project::delete_where(&db, &[("name", "Renamed".into())]).await.unwrap(), 2);
+    })
+    .await;
+
+    let rows = db.query("SELECT name FROM projects ORDER BY id").await.unwrap();
+    let stored: Vec<String> = rows.iter().map(|r| sqlx::Row::get(r, "name")).collect();
+    assert_eq!(stored, vec!["Rockets", "Anvils"]);
+}
+
+#[tokio::test]
+async fn test_upsert_leaves_other_tenants_rows_alone() {
+    let db = setup().await;
+    db.execute("CREATE UNIQUE INDEX projects_name ON projects (name)").await.unwrap();
+
+    // "Rockets" belongs to acme; a conflict on the shared key must not hand it to globex
+    with_tenant(tenant("globex"), async {
+        let rows = [Project { id: 0, tenant_id: String::new(), name: "Rockets".into() }];
+        Project::upsert(&db, &rows, &["name"], &["tenant_id"]).await.unwrap();
+    })
+    .await;
+
+    let row = db.query_one("SELECT tenant_id FROM projects WHERE name = 'Rockets'").await.unwrap().unwrap();
+    assert_eq!(sqlx::Row::get::<String, _>(&row, "tenant_id"), "acme");
+}
+
+#[tokio::test]
+async fn test_scoped_models_need_a_tenant() {
+    let db = setup().await;
+
+    assert!(Project::all(&db).await.is_err());
+    assert!(Project::find(&db, 1).await.is_err());
+    assert!(Project::delete_where(&db, &[]).await.is_err());
+    let mut project = Project { id: 0, tenant_id: "acme".into(), name: "Sneaky".into() };
+    assert!(project.create(&db).await.is_err());
+    assert!(Project::query().build().contains("1 = 0"));
+
+    assert_eq!(db.query("SELECT id FROM projects").await.unwrap().len(), 3);
+}
+
+fn request(header: &str, value: &str) -> http::Request<()> {
+    http::Request::builder().header(header, value).body(()).unwrap()
+}
+
+fn token(claims: &str, secret: &str) -> String {
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+    let payload = URL_SAFE_NO_PAD.encode(claims);
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.{}", header, payload).as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+    format!("Bearer {}.{}.{}", header, payload, signature)
+}
+
+#[test]
+fn test_resolvers() {
+    let subdomain = TenantResolver::subdomain("example.com");
+    assert_eq!(subdomain.resolve(&request("host", "acme.example.com:8080")), Some(tenant("acme")));
+    assert_eq!(subdomain.resolve(&request("host", "example.com")), None);
+    assert_eq!(subdomain.resolve(&request("host", "a.b.example.com")), None);
+    assert_eq!(subdomain.resolve(&request("host", "acme.example.org")), None);
+
+    let header = TenantResolver::header("x-tenant");
+    assert_eq!(header.resolve(&request("x-tenant", "globex")), Some(tenant("globex")));
+    assert_eq!(header.resolve(&request("x-tenant", "x'; DROP TABLE projects")), None);
+
+    let jwt = TenantResolver::jwt_claim("tenant", "secret");
+    let valid = token(r#"{"sub":"1","tenant":"acme","exp":4102444800}"#, "secret");
+    assert_eq!(jwt.resolve(&request("authorization", &valid)), Some(tenant("acme")));
+    let forged = token(r#"{"sub":"1","tenant":"acme"}"#, "guess");
+    assert_eq!(jwt.resolve(&request("authorization", &forged)), None);
+    let expired = token(r#"{"sub":"1","tenant":"acme","exp":1}"#, "secret");
+    assert_eq!(jwt.resolve(&request("authorization", &expired)), None);
+
+    assert_eq!(tenant("acme").schema(), "tenant_acme");
+    assert!(Tenant::new("").is_none());
+}
+
+#[tokio::test]
+async fn test_layer_runs_requests_as_their_tenant() {
+    let layer = TenantLayer::new(TenantResolver::subdomain("example.com")).or(TenantResolver::header("x-tenant"));
+    let service = layer.layer(service_fn(|_req: http::Request<()>| async {
+        Ok::<_, std::convert::Infallible>(tenancy::current().map(|t| t.id().to_string()))
+    }));
+
+    let by_host = service.clone().oneshot(request("host", "acme.example.com")).await.unwrap();
+    assert_eq!(by_host.as_deref(), Some("acme"));
+    let by_header = service.clone().oneshot(request("x-tenant", "globex")).await.unwrap();
+    assert_eq!(by_header.as_deref(), Some("globex"));
+    let unknown = service.oneshot(request("host", "localhost")).await.unwrap();
+    assert_eq!(unknown, None);
+}
@@ -0,0 +1,169 @@
+use oxidite_db::{Database, DatabaseType, DbPool, RetryPolicy, sqlx};
+use sqlx::Row;
+
+async fn setup() -> DbPool {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    db.execute("CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL)").await.unwrap();
+    db
+}
+
+async fn names(db: &DbPool) -> Vec<String> {
+    db.query("SELECT name FROM items ORDER BY id")
+        .await
+        .unwrap()
+        .iter()
+        .map(|row| row.get::<String, _>("name"))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_commit_on_ok() {
+    let db = setup().await;
+
+    let count = db
+        .transaction(|tx| async move {
+            tx.execute("INSERT INTO items (name) VALUES ('a')").await?;
+            tx.execute("INSERT INTO items (name) VALUES ('b')").await?;
+            Ok::<_, sqlx::Error>(2)
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(count, 2);
+    assert_eq!(names(&db).await, vec!["a", "b"]);
+}
+
+#[tokio::test]
+async fn test_rollback_on_err() {
+    let db = setup().await;
+
+    let result: Result<(), sqlx::Error> = db
+        .transaction(|tx| async move {
+            tx.execute("INSERT INTO items (name) VALUES ('a')").await?;
+            Err(sqlx::Error::RowNotFound)
+        })
+        .await;
+
+    assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
+    assert!(names(&db).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_rollback_on_panic() {
+    let db = setup().await;
+    let handle = db.clone();
+
+    let joined = tokio::spawn(async move {
+        let result: Result<(), sqlx::Error> = handle
+            .transaction(|tx| async move {
+                tx.execute("INSERT INTO items (name) VALUES ('a')").await?;
+                panic!("boom")
+            })
+            .await;
+        result
+    })
+    .await;
+
+    assert!(joined.unwrap_err().is_panic());
+    assert!(names(&db).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_nested_transactions_use_savepoints() {
+    let db = setup().await;
+
+    db.transaction(|tx| async move {
+        assert_eq!(tx.db_type(), DatabaseType::Sqlite);
+        tx.execute("INSERT INTO items (name) VALUES ('outer')").await?;
+
+        let inner: Result<(), sqlx::Error> = tx
+            .transaction(|sp| async move {
+                assert!(sp.is_nested());
+                sp.execute("INSERT INTO items (name) VALUES ('discarded')").await?;
+                Err(sqlx::Error::RowNotFound)
+            })
+            .await;
+        assert!(inner.is_err());
+
+        tx.transaction(|sp| async move {
+            sp.execute("INSERT INTO items (name) VALUES ('kept')").await?;
+            Ok::<_, sqlx::Error>(())
+        })
+        .await?;
+
+        Ok::<_, sqlx::Error>(())
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(names(&db).await, vec!["outer", "kept"]);
+}
+
+#[tokio::test]
+async fn test_retry_gives_up_on_non_retryable_errors() {
+    let db = setup().await;
+    let mut attempts = 0;
+
+    let result: oxidite_db::Result<()> = db
+        .transaction_with_retry(RetryPolicy::new(5), |_tx| {
+            attempts += 1;
+            async move { Err(sqlx::Error::RowNotFound) }
+        })
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(attempts, 1);
+}
+
+/// A driver error with a given SQLSTATE and message
+#[derive(Debug)]
+struct FakeError {
+    code: &'static str,
+    message: &'static str,
+}
+
+impl std::fmt::Display for FakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message)
+    }
+}
+
+impl std::error::Error for FakeError {}
+
+impl sqlx::error::DatabaseError for FakeError {
+    fn message(&self) -> &str {
+        self.message
+    }
+
+    fn code(&self) -> Option<std::borrow::Cow<'_, str>> {
+        Some(self.code.into())
+    }
+
+    fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        self
+    }
+
+    fn kind(&self) -> sqlx::error::ErrorKind {
+        sqlx::error::ErrorKind::Other
+    }
+}
+
+#[test]
+fn test_retryable_errors_are_matched_by_code() {
+    use oxidite_db::transaction::is_retryable;
+
+    let error = |code, message| sqlx::Error::from(FakeError { code, message });
+    assert!(is_retryable(&error("40001", "Deadlock found"), DatabaseType::MySql));
+    assert!(is_retryable(&error("40P01", "deadlock detected"), DatabaseType::Postgres));
+    // An error number in the message text isn't a lock error
+    assert!(!is_retryable(&error("23000", "Duplicate entry '1213' for key 'PRIMARY'"), DatabaseType::MySql));
+    assert!(!is_retryable(&error("HY000", "Out of range value 1205"), DatabaseType::MySql));
+}
@@ -1,4 +1,4 @@
-use oxidite_db::{Model, sqlx};
+use oxidite_db::{http_error, Database, DbPool, InvalidModel, Model, sqlx, ValidationErrors};
 
 #[derive(Model, sqlx::FromRow, Clone)]
 struct UserWithValidation {
@@ -29,7 +29,8 @@ fn test_email_validation_invalid() {
     
     let result = user.validate();
     assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Invalid email format"));
+    let errors = result.unwrap_err();
+    assert!(errors.get("email").unwrap().iter().any(|m| m.contains("Invalid email format")));
 }
 
 #[test]
@@ -53,3 +54,111 @@ fn test_email_validation_missing_domain() {
     
     assert!(user.validate().is_err());
 }
+
+fn not_reserved(username: &String) -> Result<(), String> {
+    if username == "admin" {
+        Err("Username is reserved".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+fn check_age_for_role(account: &Account) -> Result<(), ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+    if account.role == "admin" && account.age < 21 {
+        errors.add("age", "Admins must be at least 21");
+    }
+    errors.into_result()
+}
+
+#[derive(Model, sqlx::FromRow, Clone)]
+#[validate(custom = "check_age_for_role")]
+struct Account {
+    id: i64,
+    #[validate(required, length(min = 3, max = 16), custom = "not_reserved")]
+    username: String,
+    #[validate(range(min = 13, max = 120))]
+    age: i64,
+    #[validate(regex = "^[A-Z]{2}$")]
+    country: String,
+    #[validate(url)]
+    website: Option<String>,
+    #[validate(one_of("admin", "member"))]
+    role: String,
+    password: String,
+    #[validate(must_match = "password", length(min = 8, message = "Password is too short"))]
+    password_confirmation: String,
+}
+
+fn valid_account() -> Account {
+    Account {
+        id: 1,
+        username: "alice".to_string(),
+        age: 30,
+        country: "KE".to_string(),
+        website: None,
+        role: "member".to_string(),
+        password: "hunter2hunter2".to_string(),
+        password_confirmation: "hunter2hunter2".to_string(),
+    }
+}
+
+#[test]
+fn test_all_rules_pass() {
+    assert!(valid_account().validate().is_ok());
+}
+
+#[test]
+fn test_errors_are_aggregated() {
+    let account = Account {
+        username: "al".to_string(),
+        age: 7,
+        country: "kenya".to_string(),
+        website: Some("not a url".to_string()),
+        role: "owner".to_string(),
+        password_confirmation: "short".to_string(),
+        ..valid_account()
+    };
+
+    let errors = account.validate().unwrap_err();
+    assert_eq!(errors.len(), 6);
+    assert_eq!(errors.get("username").unwrap(), &["Length must be between 3 and 16".to_string()]);
+    assert!(errors.has("age"));
+    assert!(errors.has("country"));
+    assert!(errors.has("website"));
+    assert_eq!(errors.get("role").unwrap(), &["Value must be one of: admin, member".to_string()]);
+    assert_eq!(errors.get("password_confirmation").unwrap().len(), 2);
+    assert!(errors.get("password_confirmation").unwrap().contains(&"Password is too short".to_string()));
+}
+
+#[test]
+fn test_custom_field_and_struct_validators() {
+    let account = Account {
+        username: "admin".to_string(),
+        role: "admin".to_string(),
+        age: 18,
+        ..valid_account()
+    };
+
+    let errors = account.validate().unwrap_err();
+    assert_eq!(errors.get("username").unwrap(), &["Username is reserved".to_string()]);
+    assert_eq!(errors.get("age").unwrap(), &["Admins must be at least 21".to_string()]);
+}
+
+#[tokio::test]
+async fn test_save_reports_validation_errors_as_422() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    let create = format!("CREATE TABLE {} (id INTEGER PRIMARY KEY, username TEXT, email TEXT)", UserWithValidation::table_name());
+    db.execute(&create).await.unwrap();
+
+    let mut user = UserWithValidation { id: 0, username: "test".to_string(), email: "nope".to_string() };
+    let err = user.save(&db).await.unwrap_err();
+    let invalid = InvalidModel::find(&err).unwrap();
+    assert!(invalid.errors.has("email"));
+    // Nothing reached the database, so it is not a constraint violation
+    assert_eq!(err.as_database_error().unwrap().kind(), sqlx::error::ErrorKind::Other);
+
+    let err = http_error(err);
+    assert_eq!(err.status_code(), 422);
+    assert!(matches!(err, oxidite_core::Error::Validation(errors) if errors.has("email")));
+}
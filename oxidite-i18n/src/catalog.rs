@@ -0,0 +1,194 @@
+use crate::{I18nError, Locale, Result};
+use intl_pluralrules::{PluralCategory, PluralRuleType, PluralRules};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+use unic_langid::LanguageIdentifier;
+
+/// CLDR plural categories, the keys a plural message may use
+const PLURAL_FORMS: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+/// The messages of one locale, by dotted key
+///
+/// A catalog file nests tables, so `{"cart": {"title": "Cart"}}` defines
+/// `cart.title`. A table of plural forms (`one`, `few`, `other`, ...) with
+/// an `other` form is one plural message, picked by the `count` argument.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Catalog {
+    messages: HashMap<String, Message>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Text(String),
+    /// Forms by plural category; `zero` is also used for a count of 0 in
+    /// languages whose rules have no such category
+    Plural(BTreeMap<String, String>),
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_json(source: &str) -> Result<Self> {
+        let value = serde_json::from_str(source).map_err(|e| I18nError::InvalidCatalog(e.to_string()))?;
+        Self::from_value(value)
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self> {
+        let value = toml::from_str(source).map_err(|e| I18nError::InvalidCatalog(e.to_string()))?;
+        Self::from_value(value)
+    }
+
+    /// A catalog from nested objects of strings
+    pub fn from_value(value: Value) -> Result<Self> {
+        let Value::Object(entries) = value else {
+            return Err(I18nError::InvalidCatalog("a catalog must be a table of messages".to_string()));
+        };
+        let mut catalog = Self::new();
+        catalog.add_entries("", entries)?;
+        Ok(catalog)
+    }
+
+    fn add_entries(&mut self, prefix: &str, entries: Map<String, Value>) -> Result<()> {
+        for (key, value) in entries {
+            let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+            match value {
+                Value::String(text) => {
+                    self.messages.insert(key, Message::Text(text));
+                }
+                Value::Object(forms) if is_plural(&forms) => {
+                    let forms = forms
+                        .into_iter()
+                        .map(|(form, text)| (form, text.as_str().unwrap_or_default().to_string()))
+                        .collect();
+                    self.messages.insert(key, Message::Plural(forms));
+                }
+                Value::Object(entries) => self.add_entries(&key, entries)?,
+                _ => {
+                    return Err(I18nError::InvalidCatalog(format!(
+                        "{} must be text, plural forms or a table of messages",
+                        key
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Message> {
+        self.messages.get(key)
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, message: Message) {
+        self.messages.insert(key.into(), message);
+    }
+
+    /// Add the messages of `other`, with `prefix.` before their keys if `prefix` isn't empty
+    pub fn merge(&mut self, prefix: &str, other: Catalog) {
+        for (key, message) in other.messages {
+            let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+            self.messages.insert(key, message);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// Whether `forms` is a plural message rather than a table of messages
+fn is_plural(forms: &Map<String, Value>) -> bool {
+    forms.contains_key("other")
+        && forms.iter().all(|(form, text)| PLURAL_FORMS.contains(&form.as_str()) && text.is_string())
+}
+
+impl Message {
+    /// The text for `locale`, with `{name}` replaced by the argument `name`
+    pub fn format(&self, locale: &Locale, args: &Value) -> String {
+        let text = match self {
+            Message::Text(text) => text,
+            Message::Plural(forms) => {
+                let count = args.get("count").cloned().unwrap_or(Value::Null);
+                let form = if count.as_f64() == Some(0.0) && forms.contains_key("zero") {
+                    "zero"
+                } else {
+                    plural_form(locale, &count).filter(|form| forms.contains_key(*form)).unwrap_or("other")
+                };
+                // A message built by hand may lack `other`
+                forms.get(form).or_else(|| forms.get("other")).map_or("", String::as_str)
+            }
+        };
+        interpolate(text, args)
+    }
+}
+
+/// The CLDR plural category of `count` in the language of `locale`
+fn plural_form(locale: &Locale, count: &Value) -> Option<&'static str> {
+    let rules = plural_rules(locale)?;
+    let category = match count {
+        Value::Number(n) => rules.select(n.to_string().as_str()),
+        Value::String(s) => rules.select(s.as_str()),
+        _ => return None,
+    };
+    Some(match category.ok()? {
+        PluralCategory::ZERO => "zero",
+        PluralCategory::ONE => "one",
+        PluralCategory::TWO => "two",
+        PluralCategory::FEW => "few",
+        PluralCategory::MANY => "many",
+        PluralCategory::OTHER => "other",
+    })
+}
+
+/// The plural rules for `locale`, or else for its language, created once per process
+fn plural_rules(locale: &Locale) -> Option<Arc<PluralRules>> {
+    // Only identifiers with rules are added, so this stays as small as the CLDR data
+    static RULES: OnceLock<RwLock<HashMap<LanguageIdentifier, Arc<PluralRules>>>> = OnceLock::new();
+    let cache = RULES.get_or_init(Default::default);
+
+    for id in [locale.id(), locale.language_only().id()] {
+        if let Some(rules) = cache.read().unwrap_or_else(PoisonError::into_inner).get(id) {
+            return Some(rules.clone());
+        }
+        if let Ok(rules) = PluralRules::create(id.clone(), PluralRuleType::CARDINAL) {
+            let rules = Arc::new(rules);
+            cache.write().unwrap_or_else(PoisonError::into_inner).insert(id.clone(), rules.clone());
+            return Some(rules);
+        }
+    }
+    None
+}
+
+/// `text` with each `{name}` replaced by the argument `name`; unknown names
+/// are left as they are
+fn interpolate(text: &str, args: &Value) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let name = after.find('}').map(|end| &after[..end]);
+        match name.and_then(|name| Some((name, args.get(name.trim())?))) {
+            Some((name, value)) => {
+                match value {
+                    Value::String(s) => output.push_str(s),
+                    Value::Null => {}
+                    other => output.push_str(&other.to_string()),
+                }
+                rest = &after[name.len() + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
@@ -0,0 +1,49 @@
+use crate::I18n;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// Adds the [`I18n`] to every request's extensions, for the
+/// [`Locale`](crate::Locale) extractor
+#[derive(Clone, Debug)]
+pub struct I18nService<S> {
+    inner: S,
+    i18n: I18n,
+}
+
+impl<S, B> Service<http::Request<B>> for I18nService<S>
+where
+    S: Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        req.extensions_mut().insert(self.i18n.clone());
+        self.inner.call(req)
+    }
+}
+
+/// Layer for [`I18nService`]
+#[derive(Clone, Debug)]
+pub struct I18nLayer {
+    i18n: I18n,
+}
+
+impl I18nLayer {
+    pub fn new(i18n: I18n) -> Self {
+        Self { i18n }
+    }
+}
+
+impl<S> Layer<S> for I18nLayer {
+    type Service = I18nService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        I18nService { inner, i18n: self.i18n.clone() }
+    }
+}
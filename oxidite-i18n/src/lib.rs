@@ -0,0 +1,181 @@
+//! Translations for Oxidite apps
+//!
+//! An [`I18n`] holds a [`Catalog`] of messages per locale, loaded from a
+//! directory of JSON or TOML files:
+//!
+//! ```text
+//! locales/
+//!   en.json          {"cart": {"items": {"one": "{count} item", "other": "{count} items"}}}
+//!   de.toml          [cart.items] one = "{count} Artikel" ...
+//!   pt-BR/users.json keys under `users.`
+//! ```
+//!
+//! Wrap the router in [`I18nLayer`] and use the [`Locale`] extractor to find
+//! the locale of each request. Templates translate with `{% trans "key" %}`
+//! and `t("key")` once the engine has the catalogs through
+//! `TemplateEngine::set_i18n`.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+mod catalog;
+mod layer;
+mod locale;
+
+pub use catalog::{Catalog, Message};
+pub use layer::{I18nLayer, I18nService};
+pub use locale::{Locale, UserLocale};
+
+/// Translation catalogs by locale, cheap to clone
+#[derive(Debug, Clone)]
+pub struct I18n {
+    catalogs: Arc<HashMap<Locale, Catalog>>,
+    default_locale: Locale,
+    cookie_name: String,
+}
+
+impl I18n {
+    /// No catalogs yet; messages missing from a locale come from `default_locale`
+    pub fn new(default_locale: &str) -> Result<Self> {
+        Ok(Self {
+            catalogs: Arc::new(HashMap::new()),
+            default_locale: default_locale.parse()?,
+            cookie_name: "locale".to_string(),
+        })
+    }
+
+    /// The catalogs in `dir`, see [`I18n::load_dir`]
+    pub fn load(dir: impl AsRef<Path>, default_locale: &str) -> Result<Self> {
+        let mut i18n = Self::new(default_locale)?;
+        i18n.load_dir(dir)?;
+        Ok(i18n)
+    }
+
+    /// Load `<locale>.json` and `<locale>.toml` files, and the files in
+    /// `<locale>/` directories with their name before their keys
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if path.is_dir() {
+                for file in fs::read_dir(&path)? {
+                    let file = file?.path();
+                    if let (Some(catalog), Some(prefix)) = (read_catalog(&file)?, file.file_stem().and_then(|s| s.to_str())) {
+                        self.add_catalog(locale, prefix, catalog)?;
+                    }
+                }
+            } else if let Some(catalog) = read_catalog(&path)? {
+                self.add_catalog(locale, "", catalog)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add messages for `locale`, with `prefix.` before their keys if `prefix` isn't empty
+    pub fn add_catalog(&mut self, locale: &str, prefix: &str, catalog: Catalog) -> Result<()> {
+        let locale: Locale = locale.parse()?;
+        Arc::make_mut(&mut self.catalogs).entry(locale).or_default().merge(prefix, catalog);
+        Ok(())
+    }
+
+    /// Name of the cookie that picks the locale, `locale` by default
+    pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.cookie_name = name.into();
+        self
+    }
+
+    pub fn default_locale(&self) -> &Locale {
+        &self.default_locale
+    }
+
+    /// Locales with a catalog, sorted
+    pub fn locales(&self) -> Vec<&Locale> {
+        let mut locales: Vec<_> = self.catalogs.keys().collect();
+        locales.sort_by_key(|locale| locale.to_string());
+        locales
+    }
+
+    /// The message `key` in `locale`, with `args` (a map) filled in
+    ///
+    /// A key missing from `pt-BR` is looked up in `pt`, then in the default
+    /// locale. A key found nowhere is returned as it is, so it shows up on
+    /// the page.
+    pub fn translate(&self, locale: &Locale, key: &str, args: &Value) -> String {
+        [locale.clone(), locale.language_only(), self.default_locale.clone()]
+            .iter()
+            .find_map(|locale| Some(self.catalogs.get(locale)?.get(key)?.format(locale, args)))
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    /// The locale with a catalog that best serves `tag`: the same tag, else
+    /// one for the same language
+    pub fn supported(&self, tag: &str) -> Option<Locale> {
+        let locale = Locale::new(tag)?;
+        if self.catalogs.contains_key(&locale) {
+            return Some(locale);
+        }
+        let language = locale.language_only();
+        if self.catalogs.contains_key(&language) {
+            return Some(language);
+        }
+        self.locales().into_iter().find(|other| other.language() == locale.language()).cloned()
+    }
+
+    /// The best supported locale for an `Accept-Language` header
+    pub fn negotiate(&self, accept_language: &str) -> Option<Locale> {
+        locale::accept_language(accept_language).into_iter().find_map(|tag| self.supported(tag))
+    }
+
+    /// The locale of `req`: its [`UserLocale`], the locale cookie,
+    /// `Accept-Language` or the default, whichever is first to name a
+    /// supported locale
+    pub fn resolve<B>(&self, req: &http::Request<B>) -> Locale {
+        let header = |name| req.headers().get(name).and_then(|value| value.to_str().ok());
+        let user = req.extensions().get::<UserLocale>().and_then(|user| self.supported(&user.0));
+        let cookie = || {
+            header(http::header::COOKIE)?
+                .split(';')
+                .filter_map(|cookie| cookie.trim().split_once('='))
+                .find(|(name, _)| *name == self.cookie_name)
+                .and_then(|(_, value)| self.supported(value))
+        };
+        user.or_else(cookie)
+            .or_else(|| self.negotiate(header(http::header::ACCEPT_LANGUAGE)?))
+            .unwrap_or_else(|| self.default_locale.clone())
+    }
+}
+
+/// The catalog in `path`, or `None` if it isn't a JSON or TOML file
+fn read_catalog(path: &Path) -> Result<Option<Catalog>> {
+    let parse = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Catalog::from_json,
+        Some("toml") => Catalog::from_toml,
+        _ => return Ok(None),
+    };
+    let source = fs::read_to_string(path)?;
+    match parse(&source) {
+        Err(I18nError::InvalidCatalog(message)) => {
+            Err(I18nError::InvalidCatalog(format!("{}: {}", path.display(), message)))
+        }
+        result => result.map(Some),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum I18nError {
+    #[error("Invalid locale: {0}")]
+    InvalidLocale(String),
+
+    #[error("Invalid catalog: {0}")]
+    InvalidCatalog(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, I18nError>;
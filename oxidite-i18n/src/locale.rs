@@ -0,0 +1,156 @@
+use crate::I18n;
+use oxidite_core::extract::FromRequest;
+use oxidite_core::{Error, OxiditeRequest, Result};
+use pure_rust_locales::locale_match;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use unic_langid::LanguageIdentifier;
+
+/// A language tag such as `en` or `pt-BR`
+///
+/// As an extractor it is the locale of the request: the [`UserLocale`] an
+/// app sets after loading the user, else the locale cookie, else the best
+/// match for `Accept-Language`, else the default. Only locales with a
+/// catalog are picked.
+///
+/// ```ignore
+/// async fn cart(mut req: OxiditeRequest) -> Result<OxiditeResponse> {
+///     let locale = Locale::from_request(&mut req).await?;
+///     context.set("locale", &locale);
+///     // ...
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Locale(LanguageIdentifier);
+
+/// The locale saved in the signed-in user's settings
+///
+/// Insert it into the request extensions, for example in the middleware that
+/// loads the user, and the [`Locale`] extractor prefers it to the cookie and
+/// `Accept-Language`.
+#[derive(Debug, Clone)]
+pub struct UserLocale(pub String);
+
+/// Region used for number and date formats when a tag names only a language
+/// and the language's own country code isn't a locale, e.g. `en` is `en_US`
+const DEFAULT_REGIONS: &[(&str, &str)] = &[
+    ("ar", "SA"),
+    ("cs", "CZ"),
+    ("da", "DK"),
+    ("el", "GR"),
+    ("en", "US"),
+    ("fa", "IR"),
+    ("he", "IL"),
+    ("hi", "IN"),
+    ("ja", "JP"),
+    ("ko", "KR"),
+    ("nb", "NO"),
+    ("pt", "BR"),
+    ("sv", "SE"),
+    ("sw", "KE"),
+    ("uk", "UA"),
+    ("vi", "VN"),
+    ("zh", "CN"),
+];
+
+impl Locale {
+    /// Parse a tag; `pt_br` and `pt-BR` are the same locale
+    pub fn new(tag: &str) -> Option<Self> {
+        let id: LanguageIdentifier = tag.trim().parse().ok()?;
+        if id.language.is_empty() {
+            return None;
+        }
+        Some(Self(id))
+    }
+
+    /// The language alone, `pt` for `pt-BR`
+    pub fn language(&self) -> &str {
+        self.0.language.as_str()
+    }
+
+    pub(crate) fn language_only(&self) -> Self {
+        Self(LanguageIdentifier::from_parts(self.0.language, None, None, &[]))
+    }
+
+    pub(crate) fn id(&self) -> &LanguageIdentifier {
+        &self.0
+    }
+
+    /// The locale whose number and date conventions apply
+    pub fn time_locale(&self) -> chrono::Locale {
+        let language = self.language();
+        let region = self.0.region.map(|region| region.as_str().to_string());
+        let default_region = DEFAULT_REGIONS.iter().find(|(lang, _)| *lang == language).map(|(_, region)| region.to_string());
+        [region, default_region, Some(language.to_ascii_uppercase())]
+            .into_iter()
+            .flatten()
+            .find_map(|region| chrono::Locale::try_from(format!("{}_{}", language, region).as_str()).ok())
+            .unwrap_or(chrono::Locale::en_US)
+    }
+
+    /// The decimal point, `,` in `de`
+    pub fn decimal_point(&self) -> &'static str {
+        locale_match!(self.time_locale() => LC_NUMERIC::DECIMAL_POINT)
+    }
+
+    /// The separator between groups of thousands, `.` in `de`
+    pub fn thousands_separator(&self) -> &'static str {
+        locale_match!(self.time_locale() => LC_NUMERIC::THOUSANDS_SEP)
+    }
+
+    /// The usual date format as strftime, `%d.%m.%Y` in `de`
+    pub fn date_format(&self) -> &'static str {
+        locale_match!(self.time_locale() => LC_TIME::D_FMT)
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for Locale {
+    type Err = crate::I18nError;
+
+    fn from_str(tag: &str) -> std::result::Result<Self, Self::Err> {
+        Locale::new(tag).ok_or_else(|| crate::I18nError::InvalidLocale(tag.to_string()))
+    }
+}
+
+/// A locale is written as its tag, so templates can read it from the context
+impl Serialize for Locale {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromRequest for Locale {
+    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
+        let i18n = req
+            .extensions()
+            .get::<I18n>()
+            .cloned()
+            .ok_or_else(|| Error::Server("I18n not found in request extensions; add I18nLayer".to_string()))?;
+        Ok(i18n.resolve(req))
+    }
+}
+
+/// The tags of an `Accept-Language` header, best first
+pub(crate) fn accept_language(header: &str) -> Vec<&str> {
+    let mut tags: Vec<(&str, f32)> = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // Stable, so tags of equal quality keep the client's order
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+    tags.into_iter().map(|(tag, _)| tag).collect()
+}
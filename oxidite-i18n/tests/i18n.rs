@@ -0,0 +1,87 @@
+use oxidite_i18n::{Catalog, I18n, I18nError, Locale, Message, UserLocale};
+use serde_json::json;
+
+fn i18n() -> I18n {
+    I18n::load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/locales"), "en").unwrap()
+}
+
+fn locale(tag: &str) -> Locale {
+    Locale::new(tag).unwrap()
+}
+
+#[test]
+fn test_catalogs_load_from_json_and_toml() {
+    let i18n = i18n();
+    let locales: Vec<String> = i18n.locales().iter().map(|locale| locale.to_string()).collect();
+    assert_eq!(locales, ["de", "en", "pt-BR", "ru"]);
+
+    assert_eq!(i18n.translate(&locale("de"), "greeting", &json!({"name": "Ada"})), "Hallo, Ada!");
+    assert_eq!(i18n.translate(&locale("pt_br"), "users.welcome", &json!({"name": "Ada"})), "Bem-vindo, Ada");
+    // Missing keys fall back to the default locale, then to the key itself
+    assert_eq!(i18n.translate(&locale("de"), "cart.title", &json!({})), "Your cart");
+    assert_eq!(i18n.translate(&locale("de"), "cart.nope", &json!({})), "cart.nope");
+    // Unknown placeholders stay as they are
+    assert_eq!(i18n.translate(&locale("en"), "greeting", &json!({})), "Hello, {name}!");
+
+    let err = Catalog::from_json(r#"{"cart": {"items": 3}}"#).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid catalog: cart.items must be text, plural forms or a table of messages");
+    assert!(matches!(I18n::new("not a locale"), Err(I18nError::InvalidLocale(_))));
+}
+
+#[test]
+fn test_plurals_follow_the_language() {
+    let i18n = i18n();
+    let items = |tag: &str, count: serde_json::Value| i18n.translate(&locale(tag), "cart.items", &json!({ "count": count }));
+
+    assert_eq!(items("en", json!(0)), "Your cart is empty");
+    assert_eq!(items("en", json!(1)), "1 item");
+    assert_eq!(items("en", json!(2)), "2 items");
+    assert_eq!(items("de", json!(0)), "0 Artikel");
+    assert_eq!(items("ru", json!(1)), "1 товар");
+    assert_eq!(items("ru", json!(3)), "3 товара");
+    assert_eq!(items("ru", json!(5)), "5 товаров");
+    assert_eq!(items("ru", json!(21)), "21 товар");
+    assert_eq!(items("ru", json!(1.5)), "1.5 товара");
+
+    // Messages built in code need not have every form
+    let mut catalog = Catalog::default();
+    catalog.insert("apples", Message::Plural([("one".to_string(), "{count} apple".to_string())].into()));
+    assert_eq!(catalog.get("apples").unwrap().format(&locale("en"), &json!({"count": 1})), "1 apple");
+    assert_eq!(catalog.get("apples").unwrap().format(&locale("en"), &json!({"count": 2})), "");
+}
+
+#[test]
+fn test_locale_resolution() {
+    let i18n = i18n().cookie_name("lang");
+    let request = |headers: &[(&str, &str)]| {
+        let mut builder = http::Request::builder();
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap()
+    };
+
+    let accept = "fr-CH, fr;q=0.9, de-AT;q=0.8, en;q=0.7, *;q=0.5";
+    assert_eq!(i18n.negotiate(accept), Some(locale("de")));
+    assert_eq!(i18n.negotiate("pt-PT, en;q=0"), Some(locale("pt-BR")));
+    assert_eq!(i18n.negotiate("fr"), None);
+
+    assert_eq!(i18n.resolve(&request(&[])), locale("en"));
+    assert_eq!(i18n.resolve(&request(&[("accept-language", accept)])), locale("de"));
+    let cookie = request(&[("accept-language", accept), ("cookie", "theme=dark; lang=ru")]);
+    assert_eq!(i18n.resolve(&cookie), locale("ru"));
+
+    let mut user = cookie;
+    user.extensions_mut().insert(UserLocale("pt".to_string()));
+    assert_eq!(i18n.resolve(&user), locale("pt-BR"));
+}
+
+#[test]
+fn test_locale_formats() {
+    assert_eq!((locale("en").decimal_point(), locale("en").thousands_separator()), (".", ","));
+    assert_eq!((locale("de").decimal_point(), locale("de").thousands_separator()), (",", "."));
+    assert_eq!(locale("de-AT").date_format(), "%Y-%m-%d");
+    assert_eq!(locale("de").date_format(), "%d.%m.%Y");
+    assert_eq!(locale("en").date_format(), "%m/%d/%Y");
+    assert_eq!(serde_json::to_value(locale("pt_br")).unwrap(), json!("pt-BR"));
+}
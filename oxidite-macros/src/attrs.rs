@@ -0,0 +1,51 @@
+//! Parsing for struct-level `#[model(...)]` options
+
+use syn::Attribute;
+
+/// Options set with `#[model(...)]` on a `#[derive(Model)]` struct
+#[derive(Default)]
+pub struct ModelOptions {
+    /// Forward lifecycle hooks to the struct's `ModelHooks` impl
+    pub hooks: bool,
+    /// Named connection the model runs on
+    pub connection: Option<String>,
+    /// Integer column used for optimistic locking
+    pub version_column: Option<String>,
+    /// Record changes in the `audits` table
+    pub auditable: bool,
+    /// Limit rows to the current tenant through a `tenant_id` column
+    pub tenant_scoped: bool,
+}
+
+impl ModelOptions {
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut options = ModelOptions::default();
+
+        for attr in attrs.iter().filter(|a| a.path().is_ident("model")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("hooks") {
+                    options.hooks = true;
+                    Ok(())
+                } else if meta.path.is_ident("auditable") {
+                    options.auditable = true;
+                    Ok(())
+                } else if meta.path.is_ident("tenant_scoped") {
+                    options.tenant_scoped = true;
+                    Ok(())
+                } else if meta.path.is_ident("connection") {
+                    let name: syn::LitStr = meta.value()?.parse()?;
+                    options.connection = Some(name.value());
+                    Ok(())
+                } else if meta.path.is_ident("version_column") {
+                    let column: syn::LitStr = meta.value()?.parse()?;
+                    options.version_column = Some(column.value());
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown model option"))
+                }
+            })?;
+        }
+
+        Ok(options)
+    }
+}
@@ -1,11 +1,23 @@
 use proc_macro::TokenStream;
-use quote::{quote, ToTokens};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
 use syn::{parse_macro_input, DeriveInput, Data, Fields};
 
-#[proc_macro_derive(Model, attributes(validate))]
+mod attrs;
+mod template;
+mod validate;
+
+use attrs::ModelOptions;
+
+#[proc_macro_derive(Model, attributes(validate, model))]
 pub fn derive_model(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = input.ident;
+
+    let options = match ModelOptions::from_attrs(&input.attrs) {
+        Ok(options) => options,
+        Err(err) => return err.to_compile_error().into(),
+    };
     
     // Generate table name: snake_case + 's' (very naive pluralization for now)
     let table_name = name.to_string().to_lowercase() + "s";
@@ -22,6 +34,22 @@ pub fn derive_model(input: TokenStream) -> TokenStream {
         },
         _ => Vec::new(),
     };
+
+    // An `Original` field holds the change-tracking snapshot and isn't a column
+    let original_fields: Vec<_> = fields.iter().filter(|f| is_original(&f.ty)).collect();
+    if let Some(extra) = original_fields.get(1) {
+        return syn::Error::new_spanned(extra, "a model can only have one `Original` field")
+            .to_compile_error()
+            .into();
+    }
+    let original_ident = original_fields.first().map(|f| f.ident.clone().unwrap());
+    let has_original = original_ident.is_some();
+    let fields: Vec<_> = fields.into_iter().filter(|f| !is_original(&f.ty)).collect();
+    if options.auditable && !has_original {
+        return syn::Error::new(name.span(), "auditable models need an `oxidite_db::Original` field")
+            .to_compile_error()
+            .into();
+    }
     
     let field_names: Vec<_> = fields.iter().map(|f| f.ident.as_ref().unwrap()).collect();
     let field_names_str: Vec<_> = field_names.iter().map(|f| f.to_string()).collect();
@@ -33,6 +61,27 @@ pub fn derive_model(input: TokenStream) -> TokenStream {
     // Check for soft delete field
     let has_deleted_at = field_names_str.contains(&"deleted_at".to_string());
 
+    // Row-level tenancy: `tenant_id` is set from the current tenant and added to every WHERE
+    let tenant_scoped = options.tenant_scoped;
+    if tenant_scoped && !field_names_str.contains(&"tenant_id".to_string()) {
+        return syn::Error::new(name.span(), "tenant_scoped models need a `tenant_id` field")
+            .to_compile_error()
+            .into();
+    }
+    let (tenant_lookup, tenant_bind) = if tenant_scoped {
+        (
+            quote! { let tenant = oxidite_db::tenancy::require::<Self>()?; },
+            quote! { let query = query.bind(tenant.id().to_string()); },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+    let tenant_assign = if tenant_scoped {
+        quote! { self.tenant_id = tenant.id().to_string().into(); }
+    } else {
+        quote! {}
+    };
+
     // Filter out 'id' for create/update columns
     // Also filter out timestamps from bind list because we will handle them manually
     let non_id_fields: Vec<_> = fields.iter()
@@ -55,49 +104,201 @@ pub fn derive_model(input: TokenStream) -> TokenStream {
     let create_placeholders: Vec<_> = (1..=create_cols_list.len()).map(|i| format!("${}", i)).collect();
     let create_placeholders_str = create_placeholders.join(", ");
     let create_query = format!("INSERT INTO {} ({}) VALUES ({})", table_name, create_cols, create_placeholders_str);
+
+    // `Json<T>` columns are bound as text, which Postgres must cast to JSONB
+    let json_columns: Vec<String> = fields.iter()
+        .filter(|f| is_json(option_inner(&f.ty).unwrap_or(&f.ty)))
+        .map(|f| f.ident.as_ref().unwrap().to_string())
+        .collect();
+    let pg_placeholder = |column: &str, index: usize| {
+        let cast = if json_columns.iter().any(|c| c == column) { "::jsonb" } else { "" };
+        format!("${}{}", index, cast)
+    };
+    let create_placeholders_pg: Vec<_> = create_cols_list.iter().enumerate().map(|(i, c)| pg_placeholder(c, i + 1)).collect();
+    let create_query_pg = format!(
+        "INSERT INTO {} ({}) VALUES ({}) RETURNING id",
+        table_name, create_cols, create_placeholders_pg.join(", ")
+    );
     
+    // Optimistic locking: the version column is bumped in SQL, not bound from the struct
+    let version_ident = match &options.version_column {
+        Some(column) => match field_names.iter().find(|f| **f == column) {
+            Some(ident) => Some(*ident),
+            None => {
+                return syn::Error::new(name.span(), format!("version_column `{}` is not a field of {}", column, name))
+                    .to_compile_error()
+                    .into();
+            }
+        },
+        None => None,
+    };
+    let update_names: Vec<_> = non_id_names.iter().filter(|f| Some(**f) != version_ident).collect();
+
     // Update query generation
     let mut update_sets_list = Vec::new();
-    for (i, name) in non_id_names_str.iter().enumerate() {
+    let mut update_sets_pg = Vec::new();
+    for (i, name) in update_names.iter().enumerate() {
         update_sets_list.push(format!("{} = ${}", name, i + 1));
+        update_sets_pg.push(format!("{} = {}", name, pg_placeholder(&name.to_string(), i + 1)));
     }
     
-    let mut param_count = non_id_names_str.len();
+    let mut param_count = update_names.len();
     if has_updated_at {
         param_count += 1;
         update_sets_list.push(format!("updated_at = ${}", param_count));
     }
+    if let Some(version) = version_ident {
+        update_sets_list.push(format!("{} = {} + 1", version, version));
+    }
     
     let update_sets_str = update_sets_list.join(", ");
-    let update_where = format!("WHERE id = ${}", param_count + 1);
+    // Bound in this order: id, tenant, version
+    let mut update_conditions = vec![format!("id = ${}", param_count + 1)];
+    let mut where_count = param_count + 1;
+    if tenant_scoped {
+        where_count += 1;
+        update_conditions.push(format!("tenant_id = ${}", where_count));
+    }
+    if let Some(version) = version_ident {
+        update_conditions.push(format!("{} = ${}", version, where_count + 1));
+    }
+    let update_where = format!("WHERE {}", update_conditions.join(" AND "));
     let update_query = format!("UPDATE {} SET {} {}", table_name, update_sets_str, update_where);
+    update_sets_pg.extend(update_sets_list[update_names.len()..].iter().cloned());
+    let update_query_pg = format!("UPDATE {} SET {} {}", table_name, update_sets_pg.join(", "), update_where);
     
     // Delete query generation
-    let hard_delete_query = format!("DELETE FROM {} WHERE id = $1", table_name);
+    let tenant_where = |index: usize| if tenant_scoped { format!(" AND tenant_id = ${}", index) } else { String::new() };
+    let hard_delete_query = format!("DELETE FROM {} WHERE id = $1{}", table_name, tenant_where(2));
     
-    let delete_impl = if has_deleted_at {
-        let soft_delete_query = format!("UPDATE {} SET deleted_at = $1 WHERE id = $2", table_name);
+    // Lifecycle hooks run first, then registered observers
+    let before_delete_logic = quote! {
+        let db = &oxidite_db::connections::route::<Self, _>(db);
+        #tenant_lookup
+        <Self as oxidite_db::Model>::before_delete(self, db).await?;
+        let observers = oxidite_db::observers::observers_for::<Self>();
+        for observer in &observers {
+            observer.deleting(self).await?;
+        }
+    };
+    let audit_delete = if options.auditable {
         quote! {
-            async fn delete(&self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
+            let removed = oxidite_db::audit::removed(<Self as oxidite_db::Model>::attribute_values(self));
+            oxidite_db::audit::record::<Self>(db, oxidite_db::audit::AuditEvent::Deleted, &self.id, removed).await?;
+        }
+    } else {
+        quote! {}
+    };
+    let after_delete_logic = quote! {
+        <Self as oxidite_db::Model>::after_delete(self, db).await?;
+        for observer in &observers {
+            observer.deleted(self).await?;
+        }
+    };
+
+    let hooks_impl = if options.hooks {
+        quote! {
+            async fn before_create(&mut self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
+                <Self as oxidite_db::ModelHooks>::before_create(self, db).await
+            }
+
+            async fn after_create(&mut self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
+                <Self as oxidite_db::ModelHooks>::after_create(self, db).await
+            }
+
+            async fn before_update(&mut self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
+                <Self as oxidite_db::ModelHooks>::before_update(self, db).await
+            }
+
+            async fn after_update(&mut self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
+                <Self as oxidite_db::ModelHooks>::after_update(self, db).await
+            }
+
+            async fn before_delete(&self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
+                <Self as oxidite_db::ModelHooks>::before_delete(self, db).await
+            }
+
+            async fn after_delete(&self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
+                <Self as oxidite_db::ModelHooks>::after_delete(self, db).await
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let hard_delete = audited(
+        options.auditable,
+        quote! {
+            let query = oxidite_db::sqlx::query(#hard_delete_query)
+                .bind(&self.id);
+            #tenant_bind
+            oxidite_db::Database::execute_query(db, query).await?;
+        },
+        &audit_delete,
+        quote! {},
+        quote! {},
+    );
+
+    let delete_impl = if has_deleted_at {
+        let soft_delete_query = format!("UPDATE {} SET deleted_at = $1 WHERE id = $2{}", table_name, tenant_where(3));
+        let soft_delete = audited(
+            options.auditable,
+            quote! {
                 let now = oxidite_db::chrono::Utc::now().timestamp();
                 let query = oxidite_db::sqlx::query(#soft_delete_query)
                     .bind(now)
                     .bind(&self.id);
-                db.execute_query(query).await?;
+                #tenant_bind
+                oxidite_db::Database::execute_query(db, query).await?;
+            },
+            &audit_delete,
+            quote! {},
+            quote! {},
+        );
+        quote! {
+            async fn delete(&self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
+                #before_delete_logic
+                #soft_delete
+                #after_delete_logic
                 Ok(())
             }
         }
     } else {
         quote! {
             async fn delete(&self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
-                let query = oxidite_db::sqlx::query(#hard_delete_query)
-                    .bind(&self.id);
-                db.execute_query(query).await?;
+                #before_delete_logic
+                #hard_delete
+                #after_delete_logic
                 Ok(())
             }
         }
     };
 
+    let version_column = match &options.version_column {
+        Some(column) => quote! { Some(#column) },
+        None => quote! { None },
+    };
+
+    // A versioned update that matched no row lost a race with another writer
+    let version_check = match version_ident {
+        Some(version) => quote! {
+            let query = query.bind(&self.#version);
+            let affected = oxidite_db::Database::execute_query(db, query).await?;
+            if affected == 0 {
+                return Err(oxidite_db::StaleObject::new(Self::table_name(), &self.id, self.#version as i64).into());
+            }
+            self.#version += 1;
+        },
+        None => quote! {
+            oxidite_db::Database::execute_query(db, query).await?;
+        },
+    };
+
+    let connection_name = match &options.connection {
+        Some(name) => quote! { Some(#name) },
+        None => quote! { None },
+    };
+
     // Code generation parts for timestamps
     let created_at_logic = if has_created_at {
         quote! {
@@ -129,27 +330,240 @@ pub fn derive_model(input: TokenStream) -> TokenStream {
         quote! {}
     };
 
-    // Generate validation checks
-    let mut validation_checks = Vec::new();
-    for field in &fields {
-        let field_name = field.ident.as_ref().unwrap();
-        for attr in &field.attrs {
-            if attr.path().is_ident("validate") {
-                let attr_str = attr.to_token_stream().to_string();
-                if attr_str.contains("email") {
-                    validation_checks.push(quote! {
-                        {
-                            static EMAIL_REGEX: oxidite_db::once_cell::sync::Lazy<oxidite_db::regex::Regex> = 
-                                oxidite_db::once_cell::sync::Lazy::new(|| oxidite_db::regex::Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap());
-                            if !EMAIL_REGEX.is_match(&self.#field_name) {
-                                return Err(format!("Invalid email format for field {}", stringify!(#field_name)));
-                            }
-                        }
-                    });
+    // `create` reads the generated id back, with RETURNING where the database has it
+    let auditable = options.auditable;
+    let create_query_returning = format!("{} RETURNING id", create_query);
+    let audit_create = if auditable {
+        quote! {
+            let changes = <Self as oxidite_db::Model>::changes(self);
+            oxidite_db::audit::record::<Self>(db, oxidite_db::audit::AuditEvent::Created, &self.id, changes).await?;
+        }
+    } else {
+        quote! {}
+    };
+
+    let create_write = audited(
+        auditable,
+        quote! {
+            let sql = match oxidite_db::Database::db_type(db) {
+                oxidite_db::DatabaseType::Postgres => #create_query_pg,
+                db_type if db_type.supports_returning() => #create_query_returning,
+                _ => #create_query,
+            };
+            let query = oxidite_db::sqlx::query(sql);
+            #(
+                let query = query.bind(&self.#non_id_names);
+            )*
+            #created_at_logic
+            #updated_at_create_logic
+
+            if let Some(id) = oxidite_db::Database::execute_insert(db, query).await? {
+                self.id = id as _;
+            }
+        },
+        &audit_create,
+        quote! { let saved_id = self.id; },
+        quote! { self.id = saved_id; },
+    );
+
+    // With change tracking, `update` writes only the changed columns
+    let update_names_str: Vec<_> = update_names.iter().map(|f| f.to_string()).collect();
+    let updated_at_set = if has_updated_at {
+        quote! {
+            param += 1;
+            sets.push(format!("updated_at = ${}", param));
+        }
+    } else {
+        quote! {}
+    };
+    let tenant_condition = if tenant_scoped {
+        quote! {
+            param += 1;
+            conditions.push(format!("tenant_id = ${}", param));
+        }
+    } else {
+        quote! {}
+    };
+    let (version_set, version_condition) = match version_ident {
+        Some(version) => {
+            let set = format!("{} = {} + 1", version, version);
+            let column = version.to_string();
+            (
+                quote! { sets.push(#set.to_string()); },
+                quote! {
+                    param += 1;
+                    conditions.push(format!("{} = ${}", #column, param));
+                },
+            )
+        }
+        None => (quote! {}, quote! {}),
+    };
+    let (audit_update_capture, audit_update_record) = if auditable {
+        (
+            quote! { let changes = <Self as oxidite_db::Model>::changes(self); },
+            quote! {
+                oxidite_db::audit::record::<Self>(db, oxidite_db::audit::AuditEvent::Updated, &self.id, changes).await?;
+            },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+    let (version_save, version_restore) = match version_ident {
+        Some(version) => (
+            quote! { let saved_version = self.#version; },
+            quote! { self.#version = saved_version; },
+        ),
+        None => (quote! {}, quote! {}),
+    };
+    let update_write = audited(
+        auditable,
+        quote! {
+            let db_type = oxidite_db::Database::db_type(db);
+        let json_columns: &[&str] = &[#(#json_columns),*];
+        #[allow(unused_mut)]
+        let mut sets: Vec<String> = changed
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{} = ${}{}", c, i + 1, oxidite_db::json::cast(db_type, json_columns.contains(c))))
+            .collect();
+        let mut param = changed.len();
+        #updated_at_set
+        #version_set
+        param += 1;
+        #[allow(unused_mut)]
+        let mut conditions = vec![format!("id = ${}", param)];
+        #tenant_condition
+        #version_condition
+        let sql = format!("UPDATE {} SET {} WHERE {}", #table_name, sets.join(", "), conditions.join(" AND "));
+
+        let query = oxidite_db::sqlx::query(&sql);
+        #(
+            let query = if changed.contains(&#update_names_str) { query.bind(&self.#update_names) } else { query };
+        )*
+        #updated_at_update_logic
+
+        let query = query.bind(&self.id);
+        #tenant_bind
+            #version_check
+        },
+        &audit_update_record,
+        version_save,
+        version_restore,
+    );
+    let update_statement = if has_original {
+        quote! {
+            let columns: &[&str] = &[#(#update_names_str),*];
+            let changed = <Self as oxidite_db::Model>::changed_fields(self);
+            let changed: Vec<&str> = columns.iter().copied().filter(|c| changed.contains(c)).collect();
+            // Nothing to write: the hooks still run, but `updated_at` stays as it was
+            if !changed.is_empty() {
+                #audit_update_capture
+                #update_write
+            }
+        }
+    } else {
+        let update_sql = if json_columns.is_empty() {
+            quote! { #update_query }
+        } else {
+            quote! {
+                if oxidite_db::Database::db_type(db) == oxidite_db::DatabaseType::Postgres {
+                    #update_query_pg
+                } else {
+                    #update_query
+                }
+            }
+        };
+        quote! {
+            let query = oxidite_db::sqlx::query(#update_sql);
+            #(
+                let query = query.bind(&self.#update_names);
+            )*
+            #updated_at_update_logic
+            
+            let query = query.bind(&self.id);
+            #tenant_bind
+            #version_check
+        }
+    };
+
+    let tracking_impl = if let Some(original) = &original_ident {
+        let attribute_names: Vec<_> = field_names.iter().filter(|f| **f != "id").collect();
+        let attribute_names_str: Vec<_> = attribute_names.iter().map(|f| f.to_string()).collect();
+        quote! {
+            fn attribute_values(&self) -> Vec<(&'static str, oxidite_db::serde_json::Value)> {
+                vec![#(
+                    (#attribute_names_str, oxidite_db::serde_json::to_value(&self.#attribute_names).unwrap_or_default())
+                ),*]
+            }
+
+            fn original(&self) -> Option<&oxidite_db::Original> {
+                Some(&self.#original)
+            }
+
+            fn original_mut(&mut self) -> Option<&mut oxidite_db::Original> {
+                Some(&mut self.#original)
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Factories link related records by setting an integer foreign key column
+    let key_arms: Vec<_> = fields.iter().filter_map(|f| {
+        let ident = f.ident.as_ref().unwrap();
+        let column = ident.to_string();
+        if column == "id" {
+            return None;
+        }
+        match option_inner(&f.ty) {
+            Some(inner) if is_integer(inner) => Some(quote! { #column => self.#ident = Some(id as _), }),
+            None if is_integer(&f.ty) => Some(quote! { #column => self.#ident = id as _, }),
+            _ => None,
+        }
+    }).collect();
+    let foreign_key_impl = if key_arms.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            fn set_foreign_key(&mut self, column: &str, id: i64) -> bool {
+                match column {
+                    #(#key_arms)*
+                    _ => return false,
                 }
+                true
             }
         }
-    }
+    };
+
+    // Bulk inserts bind the same columns as `create`, with a shared timestamp
+    let insert_binds: Vec<_> = non_id_names.iter().map(|f| {
+        if tenant_scoped && *f == "tenant_id" {
+            quote! { let query = query.bind(oxidite_db::tenancy::current().map(|t| t.id().to_string())); }
+        } else {
+            quote! { let query = query.bind(&self.#f); }
+        }
+    }).collect();
+    let created_at_bind = if has_created_at { quote! { let query = query.bind(now); } } else { quote! {} };
+    let updated_at_bind = if has_updated_at { quote! { let query = query.bind(now); } } else { quote! {} };
+
+    // Column metadata for schema diffs: the Rust type with `Option` unwrapped
+    let field_meta: Vec<_> = fields.iter().map(|f| {
+        let name = f.ident.as_ref().unwrap().to_string();
+        let (ty, nullable) = match option_inner(&f.ty) {
+            Some(inner) => (inner, true),
+            None => (&f.ty, false),
+        };
+        let rust_type = quote!(#ty).to_string().replace(' ', "");
+        quote! {
+            oxidite_db::FieldMeta { name: #name, rust_type: #rust_type, nullable: #nullable }
+        }
+    }).collect();
+
+    // Generate validation checks
+    let validate_body = match validate::expand_validate_body(&fields, &input.attrs, &quote! { oxidite_db }) {
+        Ok(body) => body,
+        Err(err) => return err.to_compile_error().into(),
+    };
 
     let expanded = quote! {
         #[oxidite_db::async_trait]
@@ -161,50 +575,233 @@ pub fn derive_model(input: TokenStream) -> TokenStream {
             fn fields() -> &'static [&'static str] {
                 &[#(#field_names_str),*]
             }
+
+            fn field_meta() -> &'static [oxidite_db::FieldMeta] {
+                &[#(#field_meta),*]
+            }
+
+            #foreign_key_impl
             
             fn has_soft_delete() -> bool {
                 #has_deleted_at
             }
+
+            fn version_column() -> Option<&'static str> {
+                #version_column
+            }
+
+            fn connection_name() -> Option<&'static str> {
+                #connection_name
+            }
+
+            fn is_auditable() -> bool {
+                #auditable
+            }
+
+            fn is_tenant_scoped() -> bool {
+                #tenant_scoped
+            }
+
+            #tracking_impl
+
+            fn json_columns() -> &'static [&'static str] {
+                &[#(#json_columns),*]
+            }
+
+            fn insert_columns() -> &'static [&'static str] {
+                &[#(#create_cols_list),*]
+            }
+
+            fn bind_insert<'q>(
+                &'q self,
+                query: oxidite_db::sqlx::query::Query<'q, oxidite_db::sqlx::Any, oxidite_db::sqlx::any::AnyArguments<'q>>,
+                now: i64,
+            ) -> oxidite_db::sqlx::query::Query<'q, oxidite_db::sqlx::Any, oxidite_db::sqlx::any::AnyArguments<'q>> {
+                #(#insert_binds)*
+                #created_at_bind
+                #updated_at_bind
+                let _ = now;
+                query
+            }
             
             async fn create(&mut self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
-                let query = oxidite_db::sqlx::query(#create_query);
-                #(
-                    let query = query.bind(&self.#non_id_names);
-                )*
-                #created_at_logic
-                #updated_at_create_logic
-                
-                db.execute_query(query).await?;
+                let db = &oxidite_db::connections::route::<Self, _>(db);
+                #tenant_lookup
+                <Self as oxidite_db::Model>::before_create(self, db).await?;
+                let observers = oxidite_db::observers::observers_for::<Self>();
+                for observer in &observers {
+                    observer.creating(self).await?;
+                }
+
+                #tenant_assign
+                #create_write
+                <Self as oxidite_db::Model>::sync_original(self);
+
+                <Self as oxidite_db::Model>::after_create(self, db).await?;
+                for observer in &observers {
+                    observer.created(self).await?;
+                }
                 Ok(())
             }
             
             async fn update(&mut self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
-                let query = oxidite_db::sqlx::query(#update_query);
-                #(
-                    let query = query.bind(&self.#non_id_names);
-                )*
-                #updated_at_update_logic
-                
-                let query = query.bind(&self.id);
-                db.execute_query(query).await?;
+                let db = &oxidite_db::connections::route::<Self, _>(db);
+                #tenant_lookup
+                <Self as oxidite_db::Model>::before_update(self, db).await?;
+                let observers = oxidite_db::observers::observers_for::<Self>();
+                for observer in &observers {
+                    observer.updating(self).await?;
+                }
+
+                #update_statement
+                <Self as oxidite_db::Model>::sync_original(self);
+
+                <Self as oxidite_db::Model>::after_update(self, db).await?;
+                for observer in &observers {
+                    observer.updated(self).await?;
+                }
                 Ok(())
             }
             
             #delete_impl
             
             async fn force_delete(&self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
-                let query = oxidite_db::sqlx::query(#hard_delete_query)
-                    .bind(&self.id);
-                db.execute_query(query).await?;
+                #before_delete_logic
+                #hard_delete
+                #after_delete_logic
                 Ok(())
             }
+
+            #hooks_impl
             
-            fn validate(&self) -> std::result::Result<(), String> {
-                #(#validation_checks)*
-                Ok(())
+            fn validate(&self) -> std::result::Result<(), oxidite_db::validation::ValidationErrors> {
+                #validate_body
             }
         }
     };
     
     TokenStream::from(expanded)
 }
+
+/// Derive `oxidite_core::Validate` from `#[validate(...)]` field attributes
+///
+/// Used for request DTOs; models get the same rules through `#[derive(Model)]`.
+#[proc_macro_derive(Validate, attributes(validate))]
+pub fn derive_validate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => fields.named.iter().collect::<Vec<_>>(),
+            _ => Vec::new(),
+        },
+        _ => {
+            return syn::Error::new_spanned(&input.ident, "Validate can only be derived for structs")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let body = match validate::expand_validate_body(&fields, &input.attrs, &quote! { oxidite_core }) {
+        Ok(body) => body,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let expanded = quote! {
+        impl #impl_generics oxidite_core::validation::Validate for #name #ty_generics #where_clause {
+            fn validate(&self) -> std::result::Result<(), oxidite_core::validation::ValidationErrors> {
+                #body
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Derive a `render()` method and `IntoResponse` from a template checked at compile time
+///
+/// `#[template(path = "users/show.html")]` names a file under the crate's
+/// `templates/` directory; the struct's fields are the template's variables.
+#[proc_macro_derive(Template, attributes(template))]
+pub fn derive_template(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match template::expand(&input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// `write` followed by `audit`; for auditable models both run in one
+/// transaction (a savepoint when `db` already is one), so a failed audit
+/// insert undoes the write and `restore` puts back what `save` kept
+fn audited(auditable: bool, write: TokenStream2, audit: &TokenStream2, save: TokenStream2, restore: TokenStream2) -> TokenStream2 {
+    if !auditable {
+        return write;
+    }
+    let finish = if restore.is_empty() {
+        quote! { written?; }
+    } else {
+        quote! {
+            if let Err(err) = written {
+                #restore
+                return Err(err);
+            }
+        }
+    };
+    quote! {
+        #save
+        let tx = oxidite_db::Database::begin_transaction(db).await?;
+        let written: oxidite_db::Result<()> = oxidite_db::transaction::finish(tx.clone(), async {
+            let db = &tx;
+            #write
+            #audit
+            Ok(())
+        })
+        .await;
+        #finish
+    }
+}
+
+/// Whether `ty` is `Json<T>`, the JSON column wrapper
+fn is_json(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last().is_some_and(|segment| segment.ident == "Json"),
+        _ => false,
+    }
+}
+
+/// Whether `ty` is a primitive integer, which can hold a foreign key
+fn is_integer(ty: &syn::Type) -> bool {
+    const INTEGERS: &[&str] = &["i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize"];
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last().is_some_and(|segment| INTEGERS.iter().any(|i| segment.ident == i)),
+        _ => false,
+    }
+}
+
+fn is_original(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last().is_some_and(|segment| segment.ident == "Original"),
+        _ => false,
+    }
+}
+
+/// The `T` in `Option<T>`, if `ty` is an option
+fn option_inner(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,350 @@
+//! Code generation for `#[derive(Template)]`
+//!
+//! The template and everything it extends, includes or imports is read from
+//! the crate's `templates/` directory and parsed at compile time, so syntax
+//! errors, missing templates and variables that aren't fields of the struct
+//! fail the build. The sources are embedded with `include_str!`, which also
+//! makes cargo rebuild when a template changes.
+
+use oxidite_template_syntax::parser::{self, Macro, Parser, TemplateNode};
+use oxidite_template_syntax::{BinaryOp, Expr};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use syn::{Data, DeriveInput, Fields, LitStr};
+
+/// Directory under `CARGO_MANIFEST_DIR` that template paths are relative to
+const TEMPLATE_DIR: &str = "templates";
+
+struct Loaded {
+    path: PathBuf,
+    nodes: Vec<TemplateNode>,
+}
+
+pub fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().filter_map(|f| f.ident.as_ref()).collect::<Vec<_>>(),
+            Fields::Unit => Vec::new(),
+            Fields::Unnamed(_) => {
+                return Err(syn::Error::new_spanned(name, "Template needs a struct with named fields"));
+            }
+        },
+        _ => return Err(syn::Error::new_spanned(name, "Template can only be derived for structs")),
+    };
+    // `r#type` is `type` in the template
+    let keys: Vec<String> = fields.iter().map(|field| field.to_string().trim_start_matches("r#").to_string()).collect();
+
+    let path = template_path(input)?;
+    let root = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default()).join(TEMPLATE_DIR);
+    let mut templates = BTreeMap::new();
+    load(&root, &path.value(), None, &mut templates).map_err(|message| syn::Error::new(path.span(), message))?;
+
+    let known: HashSet<String> = keys.iter().cloned().collect();
+    let mut check = Check { templates: &templates, fields: &known, active: Vec::new(), problems: BTreeSet::new() };
+    check.template(&path.value(), Vec::new(), true);
+    let mut errors = check.problems.into_iter().map(|problem| {
+        let message = if problem.context {
+            format!("{} uses `{}`, which isn't a field of `{}`", problem.template, problem.variable, name)
+        } else {
+            format!("{} uses `{}`, which isn't passed to it", problem.template, problem.variable)
+        };
+        syn::Error::new(path.span(), message)
+    });
+    if let Some(mut error) = errors.next() {
+        error.extend(errors);
+        return Err(error);
+    }
+
+    let sources = templates.iter().map(|(name, loaded)| {
+        let file = loaded.path.to_string_lossy().into_owned();
+        quote! {
+            engine
+                .add_template(#name, include_str!(#file))
+                .expect("templates are parsed by #[derive(Template)]");
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Render the template with this value's fields as the context
+            pub fn render(&self) -> oxidite_template::Result<String> {
+                static ENGINE: std::sync::OnceLock<oxidite_template::TemplateEngine> = std::sync::OnceLock::new();
+                let engine = ENGINE.get_or_init(|| {
+                    let mut engine = oxidite_template::TemplateEngine::new();
+                    #(#sources)*
+                    engine
+                });
+                let mut context = oxidite_template::Context::new();
+                #(context.set(#keys, &self.#fields);)*
+                engine.render(#path, &context)
+            }
+        }
+
+        impl #impl_generics oxidite_template::IntoResponse for #name #ty_generics #where_clause {
+            fn into_response(self) -> oxidite_template::OxiditeResponse {
+                oxidite_template::html_response(self.render())
+            }
+        }
+    })
+}
+
+/// The `path` in `#[template(path = "...")]`
+fn template_path(input: &DeriveInput) -> syn::Result<LitStr> {
+    let mut path = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("template")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("path") {
+                path = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown template option"))
+            }
+        })?;
+    }
+    path.ok_or_else(|| syn::Error::new_spanned(&input.ident, "expected `#[template(path = \"...\")]`"))
+}
+
+/// Read and parse `name`, then every template it refers to
+fn load(root: &Path, name: &str, from: Option<(&str, &str)>, templates: &mut BTreeMap<String, Loaded>) -> Result<(), String> {
+    if templates.contains_key(name) {
+        return Ok(());
+    }
+    let path = root.join(name);
+    let source = std::fs::read_to_string(&path).map_err(|err| match from {
+        Some((verb, by)) => format!("{} {} missing template {}", by, verb, name),
+        None => format!("cannot read {}: {}", path.display(), err),
+    })?;
+    let nodes = Parser::new(&source).parse().map_err(|err| err.in_template(name).to_string())?;
+    // Canonical, so `include_str!` finds it from the generated code
+    let path = path.canonicalize().unwrap_or(path);
+    templates.insert(name.to_string(), Loaded { path, nodes });
+
+    let references: Vec<(&str, String)> = parser::references(&templates[name].nodes)
+        .into_iter()
+        .map(|(verb, template)| (verb, template.to_string()))
+        .collect();
+    for (verb, template) in references {
+        load(root, &template, Some((verb, name)), templates)?;
+    }
+    Ok(())
+}
+
+/// A variable that is neither a local nor in the context
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Problem {
+    template: String,
+    variable: String,
+    /// Whether the template sees the struct's fields, or only what an
+    /// `include ... only` passes it
+    context: bool,
+}
+
+/// Finds the variables a template reads that nothing defines
+struct Check<'a> {
+    templates: &'a BTreeMap<String, Loaded>,
+    fields: &'a HashSet<String>,
+    /// Templates being checked, so recursive includes stop
+    active: Vec<String>,
+    problems: BTreeSet<Problem>,
+}
+
+/// Where a node is: its template, the locals in scope and whether the
+/// struct's fields are visible
+struct Scope<'s> {
+    template: &'s str,
+    locals: Vec<String>,
+    context: bool,
+}
+
+impl Check<'_> {
+    fn template(&mut self, name: &str, locals: Vec<String>, context: bool) {
+        if self.active.iter().any(|active| active == name) {
+            return;
+        }
+        let templates = self.templates;
+        let Some(loaded) = templates.get(name) else {
+            return;
+        };
+        self.active.push(name.to_string());
+
+        let mut scope = Scope { template: name, locals, context };
+        let parent = loaded.nodes.iter().find_map(|node| match node {
+            TemplateNode::Extends(parent) => Some(parent),
+            _ => None,
+        });
+        match parent {
+            // A child only renders its blocks, inside the parent
+            Some(parent) => {
+                for node in &loaded.nodes {
+                    if let TemplateNode::Block { body, .. } = node {
+                        let mut block = Scope { template: name, locals: scope.locals.clone(), context };
+                        self.nodes(body, &mut block);
+                    } else if matches!(node, TemplateNode::Macro(_) | TemplateNode::Import { .. }) {
+                        self.nodes(std::slice::from_ref(node), &mut scope);
+                    }
+                }
+                self.template(parent, scope.locals, context);
+            }
+            None => self.nodes(&loaded.nodes, &mut scope),
+        }
+
+        self.active.pop();
+    }
+
+    fn nodes(&mut self, nodes: &[TemplateNode], scope: &mut Scope) {
+        for node in nodes {
+            match node {
+                TemplateNode::Text(_) | TemplateNode::Extends(_) => {}
+                TemplateNode::Variable(expr) => self.expr(expr, scope),
+                TemplateNode::If { branches, else_branch } => {
+                    for (condition, body) in branches {
+                        self.expr(condition, scope);
+                        let mark = scope.locals.len();
+                        defined(condition, &mut scope.locals);
+                        self.nodes(body, scope);
+                        scope.locals.truncate(mark);
+                    }
+                    self.nodes(else_branch.as_deref().unwrap_or_default(), scope);
+                }
+                TemplateNode::For { key, item, iterable, body, else_branch } => {
+                    self.expr(iterable, scope);
+                    let mark = scope.locals.len();
+                    scope.locals.extend(key.iter().cloned());
+                    scope.locals.extend([item.clone(), "loop".to_string()]);
+                    self.nodes(body, scope);
+                    scope.locals.truncate(mark);
+                    self.nodes(else_branch.as_deref().unwrap_or_default(), scope);
+                }
+                TemplateNode::Set { name, value } => {
+                    self.expr(value, scope);
+                    scope.locals.push(name.clone());
+                }
+                TemplateNode::With { assignments, body } => {
+                    let mark = scope.locals.len();
+                    for (name, value) in assignments {
+                        self.expr(value, scope);
+                        scope.locals.push(name.clone());
+                    }
+                    self.nodes(body, scope);
+                    scope.locals.truncate(mark);
+                }
+                TemplateNode::Block { body, .. } | TemplateNode::Autoescape { body, .. } => self.nodes(body, scope),
+                TemplateNode::Include { template, with, only } => {
+                    if let Some(with) = with {
+                        self.expr(with, scope);
+                    }
+                    // Only a literal map says which variables the include gets
+                    let passed = match with {
+                        None => Vec::new(),
+                        Some(Expr::Map(entries)) => entries.iter().map(|(key, _)| key.clone()).collect(),
+                        Some(_) => continue,
+                    };
+                    if *only {
+                        self.template(template, passed, false);
+                    } else {
+                        self.template(template, [scope.locals.clone(), passed].concat(), scope.context);
+                    }
+                }
+                TemplateNode::Macro(def) => self.macro_def(def, scope.template, scope.context),
+                TemplateNode::Import { template, .. } => {
+                    let templates = self.templates;
+                    if let Some(loaded) = templates.get(template) {
+                        for node in &loaded.nodes {
+                            if let TemplateNode::Macro(def) = node {
+                                self.macro_def(def, template, scope.context);
+                            }
+                        }
+                    }
+                }
+                TemplateNode::Trans { args, .. } => {
+                    for (_, value) in args {
+                        self.expr(value, scope);
+                    }
+                }
+                TemplateNode::Component { args, kwargs, slots, .. } => {
+                    for arg in args.iter().chain(kwargs.iter().map(|(_, value)| value)) {
+                        self.expr(arg, scope);
+                    }
+                    // Slot content renders where the component is used
+                    for (_, body) in slots {
+                        self.nodes(body, scope);
+                    }
+                }
+            }
+        }
+    }
+
+    /// A macro body sees its parameters and the context, not the caller's locals
+    fn macro_def(&mut self, def: &Macro, template: &str, context: bool) {
+        let mut scope = Scope { template, locals: def.params.iter().map(|(name, _)| name.clone()).collect(), context };
+        for (_, default) in &def.params {
+            if let Some(default) = default {
+                self.expr(default, &scope);
+            }
+        }
+        self.nodes(&def.body, &mut scope);
+    }
+
+    fn expr(&mut self, expr: &Expr, scope: &Scope) {
+        match expr {
+            Expr::Literal(_) => {}
+            Expr::Var(name) => {
+                let local = scope.locals.iter().any(|local| local == name);
+                let field = scope.context && self.fields.contains(name);
+                if !local && !field {
+                    self.problems.insert(Problem {
+                        template: scope.template.to_string(),
+                        variable: name.clone(),
+                        context: scope.context,
+                    });
+                }
+            }
+            Expr::List(items) => items.iter().for_each(|item| self.expr(item, scope)),
+            Expr::Map(entries) => entries.iter().for_each(|(_, value)| self.expr(value, scope)),
+            Expr::Attr(value, _) | Expr::Unary(_, value) => self.expr(value, scope),
+            Expr::Index(value, index) | Expr::Binary(_, value, index) => {
+                self.expr(value, scope);
+                self.expr(index, scope);
+            }
+            Expr::Call { args, kwargs, .. } => {
+                for arg in args.iter().chain(kwargs.iter().map(|(_, value)| value)) {
+                    self.expr(arg, scope);
+                }
+            }
+            // `default` and `is defined` are how a template handles a missing variable
+            Expr::Filter { value, name, args } => {
+                if name != "default" {
+                    self.expr(value, scope);
+                }
+                args.iter().for_each(|arg| self.expr(arg, scope));
+            }
+            Expr::Test { value, name, args, .. } => {
+                if name != "defined" && name != "undefined" {
+                    self.expr(value, scope);
+                }
+                args.iter().for_each(|arg| self.expr(arg, scope));
+            }
+        }
+    }
+}
+
+/// Variables that `condition` checks with `is defined`, which its branch may use
+fn defined(condition: &Expr, names: &mut Vec<String>) {
+    match condition {
+        Expr::Test { value, name, negated: false, .. } if name == "defined" => {
+            if let Expr::Var(var) = value.as_ref() {
+                names.push(var.clone());
+            }
+        }
+        Expr::Binary(BinaryOp::And, left, right) => {
+            defined(left, names);
+            defined(right, names);
+        }
+        _ => {}
+    }
+}
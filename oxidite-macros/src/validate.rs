@@ -0,0 +1,266 @@
+//! Code generation for `#[validate(...)]` rules
+//!
+//! Shared by `#[derive(Model)]` and `#[derive(Validate)]`; the caller passes the
+//! path of the crate that exposes the `validation` module (`oxidite_db` or
+//! `oxidite_core`).
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Field, Ident, Lit, LitStr, Token};
+
+/// A single parsed rule on a field
+enum Rule {
+    Email,
+    Url,
+    Required,
+    Length { min: Option<usize>, max: Option<usize> },
+    Range { min: Option<f64>, max: Option<f64> },
+    Regex(LitStr),
+    OneOf(Vec<String>),
+    Custom(syn::Path),
+    MustMatch(Ident),
+}
+
+struct FieldRule {
+    rule: Rule,
+    message: Option<LitStr>,
+}
+
+/// Parse the optional `(message = "...")` suffix accepted by flag rules
+fn parse_message_only(meta: &syn::meta::ParseNestedMeta) -> syn::Result<Option<LitStr>> {
+    let mut message = None;
+    if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|inner| {
+            if inner.path.is_ident("message") {
+                message = Some(inner.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(inner.error("expected `message`"))
+            }
+        })?;
+    }
+    Ok(message)
+}
+
+/// A `regex` pattern, compiled here so a bad one fails the build rather than the first request
+fn checked_pattern(pattern: LitStr) -> syn::Result<LitStr> {
+    match regex::Regex::new(&pattern.value()) {
+        Ok(_) => Ok(pattern),
+        Err(e) => Err(syn::Error::new_spanned(&pattern, format!("invalid regex: {}", e))),
+    }
+}
+
+fn lit_to_f64(lit: &Lit) -> syn::Result<f64> {
+    match lit {
+        Lit::Int(i) => i.base10_parse::<f64>(),
+        Lit::Float(f) => f.base10_parse::<f64>(),
+        _ => Err(syn::Error::new_spanned(lit, "expected a number")),
+    }
+}
+
+fn lit_to_literal_string(lit: &Lit) -> syn::Result<String> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        Lit::Int(i) => Ok(i.base10_digits().to_string()),
+        Lit::Char(c) => Ok(c.value().to_string()),
+        Lit::Bool(b) => Ok(b.value.to_string()),
+        _ => Err(syn::Error::new_spanned(lit, "expected a string, integer, char or bool literal")),
+    }
+}
+
+fn parse_field_rules(attrs: &[Attribute]) -> syn::Result<Vec<FieldRule>> {
+    let mut rules = Vec::new();
+
+    for attr in attrs.iter().filter(|a| a.path().is_ident("validate")) {
+        attr.parse_nested_meta(|meta| {
+            let path = &meta.path;
+
+            if path.is_ident("email") {
+                let message = parse_message_only(&meta)?;
+                rules.push(FieldRule { rule: Rule::Email, message });
+            } else if path.is_ident("url") {
+                let message = parse_message_only(&meta)?;
+                rules.push(FieldRule { rule: Rule::Url, message });
+            } else if path.is_ident("required") {
+                let message = parse_message_only(&meta)?;
+                rules.push(FieldRule { rule: Rule::Required, message });
+            } else if path.is_ident("length") {
+                let (mut min, mut max, mut message) = (None, None, None);
+                meta.parse_nested_meta(|inner| {
+                    if inner.path.is_ident("min") {
+                        min = Some(inner.value()?.parse::<syn::LitInt>()?.base10_parse()?);
+                    } else if inner.path.is_ident("max") {
+                        max = Some(inner.value()?.parse::<syn::LitInt>()?.base10_parse()?);
+                    } else if inner.path.is_ident("equal") {
+                        let n: usize = inner.value()?.parse::<syn::LitInt>()?.base10_parse()?;
+                        min = Some(n);
+                        max = Some(n);
+                    } else if inner.path.is_ident("message") {
+                        message = Some(inner.value()?.parse()?);
+                    } else {
+                        return Err(inner.error("expected `min`, `max`, `equal` or `message`"));
+                    }
+                    Ok(())
+                })?;
+                rules.push(FieldRule { rule: Rule::Length { min, max }, message });
+            } else if path.is_ident("range") {
+                let (mut min, mut max, mut message) = (None, None, None);
+                meta.parse_nested_meta(|inner| {
+                    if inner.path.is_ident("min") {
+                        min = Some(lit_to_f64(&inner.value()?.parse()?)?);
+                    } else if inner.path.is_ident("max") {
+                        max = Some(lit_to_f64(&inner.value()?.parse()?)?);
+                    } else if inner.path.is_ident("message") {
+                        message = Some(inner.value()?.parse()?);
+                    } else {
+                        return Err(inner.error("expected `min`, `max` or `message`"));
+                    }
+                    Ok(())
+                })?;
+                rules.push(FieldRule { rule: Rule::Range { min, max }, message });
+            } else if path.is_ident("regex") {
+                if meta.input.peek(Token![=]) {
+                    let pattern = checked_pattern(meta.value()?.parse()?)?;
+                    rules.push(FieldRule { rule: Rule::Regex(pattern), message: None });
+                } else {
+                    let (mut pattern, mut message) = (None, None);
+                    meta.parse_nested_meta(|inner| {
+                        if inner.path.is_ident("pattern") {
+                            pattern = Some(inner.value()?.parse::<LitStr>()?);
+                        } else if inner.path.is_ident("message") {
+                            message = Some(inner.value()?.parse()?);
+                        } else {
+                            return Err(inner.error("expected `pattern` or `message`"));
+                        }
+                        Ok(())
+                    })?;
+                    let pattern = checked_pattern(pattern.ok_or_else(|| meta.error("regex requires a `pattern`"))?)?;
+                    rules.push(FieldRule { rule: Rule::Regex(pattern), message });
+                }
+            } else if path.is_ident("one_of") {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                let lits = Punctuated::<Lit, Token![,]>::parse_terminated(&content)?;
+                let values = lits.iter().map(lit_to_literal_string).collect::<syn::Result<Vec<_>>>()?;
+                rules.push(FieldRule { rule: Rule::OneOf(values), message: None });
+            } else if path.is_ident("custom") {
+                let func: LitStr = meta.value()?.parse()?;
+                rules.push(FieldRule { rule: Rule::Custom(func.parse()?), message: None });
+            } else if path.is_ident("must_match") {
+                let other: LitStr = meta.value()?.parse()?;
+                rules.push(FieldRule { rule: Rule::MustMatch(other.parse()?), message: None });
+            } else {
+                return Err(meta.error(
+                    "unknown validation rule; expected one of `email`, `url`, `required`, `length`, `range`, `regex`, `one_of`, `custom`, `must_match`",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(rules)
+}
+
+/// Parse struct-level `#[validate(custom = "fn")]` validators
+fn parse_struct_validators(attrs: &[Attribute]) -> syn::Result<Vec<syn::Path>> {
+    let mut validators = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("validate")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("custom") {
+                let func: LitStr = meta.value()?.parse()?;
+                validators.push(func.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("only `custom = \"fn\"` is supported on the struct"))
+            }
+        })?;
+    }
+    Ok(validators)
+}
+
+fn opt_tokens<T: quote::ToTokens>(value: Option<T>) -> TokenStream2 {
+    match value {
+        Some(v) => quote! { Some(#v) },
+        None => quote! { None },
+    }
+}
+
+/// Generate the body of a `validate(&self)` method
+///
+/// The generated code collects every failure into a `ValidationErrors` map and
+/// returns it, rather than stopping at the first failing rule.
+pub fn expand_validate_body(
+    fields: &[&Field],
+    struct_attrs: &[Attribute],
+    krate: &TokenStream2,
+) -> syn::Result<TokenStream2> {
+    let rules_mod = quote! { #krate::validation::rules };
+    let mut checks = Vec::new();
+
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let name = ident.to_string();
+
+        for FieldRule { rule, message } in parse_field_rules(&field.attrs)? {
+            let check = match rule {
+                Rule::Email => quote! { #rules_mod::email(&self.#ident) },
+                Rule::Url => quote! { #rules_mod::url(&self.#ident) },
+                Rule::Required => quote! { #rules_mod::required(&self.#ident) },
+                Rule::Length { min, max } => {
+                    let (min, max) = (opt_tokens(min), opt_tokens(max));
+                    quote! { #rules_mod::length(&self.#ident, #min, #max) }
+                }
+                Rule::Range { min, max } => {
+                    let (min, max) = (opt_tokens(min), opt_tokens(max));
+                    quote! { #rules_mod::range(&self.#ident, #min, #max) }
+                }
+                Rule::Regex(pattern) => quote! {
+                    {
+                        static RE: std::sync::OnceLock<#krate::validation::regex::Regex> = std::sync::OnceLock::new();
+                        let re = RE.get_or_init(|| {
+                            #krate::validation::regex::Regex::new(#pattern)
+                                .expect("pattern checked when the derive expanded")
+                        });
+                        #rules_mod::pattern(&self.#ident, re)
+                    }
+                },
+                Rule::OneOf(values) => quote! { #rules_mod::one_of(&self.#ident, &[#(#values),*]) },
+                Rule::Custom(func) => quote! { #func(&self.#ident).err() },
+                Rule::MustMatch(other) => {
+                    let other_name = other.to_string();
+                    quote! { #rules_mod::must_match(&self.#ident, &self.#other, #other_name) }
+                }
+            };
+
+            let push = match message {
+                Some(message) => quote! {
+                    if (#check).is_some() {
+                        errors.add(#name, #message);
+                    }
+                },
+                None => quote! {
+                    if let Some(message) = #check {
+                        errors.add(#name, message);
+                    }
+                },
+            };
+            checks.push(push);
+        }
+    }
+
+    for func in parse_struct_validators(struct_attrs)? {
+        checks.push(quote! {
+            if let Err(more) = #func(self) {
+                errors.merge(more);
+            }
+        });
+    }
+
+    Ok(quote! {
+        #[allow(unused_mut)]
+        let mut errors = #krate::validation::ValidationErrors::new();
+        #(#checks)*
+        errors.into_result()
+    })
+}
@@ -0,0 +1,73 @@
+/// How `{{ }}` output is escaped
+///
+/// Chosen from the template name's extension; templates without a name are
+/// HTML. `{% autoescape %}` overrides it for part of a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escape {
+    /// `& < > " '` as HTML entities, safe in text and quoted attributes
+    Html,
+    /// `& < > " '` as XML entities
+    Xml,
+    /// Anything but letters, digits and ` ,._` as `\uXXXX`, for JavaScript strings
+    Js,
+    /// No escaping, for plain text
+    Text,
+}
+
+impl Escape {
+    /// The strategy for a template called `name`
+    pub fn for_template(name: &str) -> Self {
+        match name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).as_deref() {
+            Some("xml" | "svg" | "rss" | "atom") => Escape::Xml,
+            Some("js" | "mjs") => Escape::Js,
+            Some("txt" | "text" | "md") => Escape::Text,
+            _ => Escape::Html,
+        }
+    }
+
+    /// The strategy named in `{% autoescape "name" %}` or `escape("name")`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "html" => Some(Escape::Html),
+            "xml" => Some(Escape::Xml),
+            "js" => Some(Escape::Js),
+            "txt" | "text" => Some(Escape::Text),
+            _ => None,
+        }
+    }
+
+    pub fn escape(self, s: &str) -> String {
+        match self {
+            Escape::Html => html_escape(s),
+            Escape::Xml => s
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+                .replace('\'', "&apos;"),
+            Escape::Js => {
+                let mut escaped = String::with_capacity(s.len());
+                for c in s.chars() {
+                    if c.is_ascii_alphanumeric() || matches!(c, ' ' | ',' | '.' | '_') {
+                        escaped.push(c);
+                    } else {
+                        for unit in c.encode_utf16(&mut [0; 2]) {
+                            escaped.push_str(&format!("\\u{:04X}", unit));
+                        }
+                    }
+                }
+                escaped
+            }
+            Escape::Text => s.to_string(),
+        }
+    }
+}
+
+/// HTML escape for XSS protection
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
+}
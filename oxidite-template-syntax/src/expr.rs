@@ -0,0 +1,146 @@
+use serde_json::Value;
+use std::fmt;
+
+/// Expression inside `{{ }}` or a tag
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    List(Vec<Expr>),
+    Map(Vec<(String, Expr)>),
+    /// A variable from the context
+    Var(String),
+    /// `value.name`, also `items.0`
+    Attr(Box<Expr>, String),
+    /// `value[index]`
+    Index(Box<Expr>, Box<Expr>),
+    /// `name(args, key=value)`, where `name` is a function, a macro or an
+    /// imported macro such as `forms.field`
+    Call { name: String, args: Vec<Expr>, kwargs: Vec<(String, Expr)> },
+    /// `value | name(args)`
+    Filter { value: Box<Expr>, name: String, args: Vec<Expr> },
+    /// `value is name(args)` or `value is not name(args)`
+    Test { value: Box<Expr>, name: String, args: Vec<Expr>, negated: bool },
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    NotIn,
+    /// `~`, string concatenation
+    Concat,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Rem,
+    Pow,
+}
+
+impl BinaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Or => "or",
+            BinaryOp::And => "and",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::In => "in",
+            BinaryOp::NotIn => "not in",
+            BinaryOp::Concat => "~",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::FloorDiv => "//",
+            BinaryOp::Rem => "%",
+            BinaryOp::Pow => "**",
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter<'_>, items: &[Expr]| {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            Ok(())
+        };
+        match self {
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::List(items) => {
+                write!(f, "[")?;
+                list(f, items)?;
+                write!(f, "]")
+            }
+            Expr::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:?}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Attr(value, name) => write!(f, "{}.{}", value, name),
+            Expr::Index(value, index) => write!(f, "{}[{}]", value, index),
+            Expr::Call { name, args, kwargs } => {
+                write!(f, "{}(", name)?;
+                list(f, args)?;
+                for (i, (key, value)) in kwargs.iter().enumerate() {
+                    if i > 0 || !args.is_empty() {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}={}", key, value)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Filter { value, name, args } => {
+                write!(f, "{} | {}", value, name)?;
+                if !args.is_empty() {
+                    write!(f, "(")?;
+                    list(f, args)?;
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Expr::Test { value, name, args, negated } => {
+                write!(f, "({} is {}{}", value, if *negated { "not " } else { "" }, name)?;
+                if !args.is_empty() {
+                    write!(f, "(")?;
+                    list(f, args)?;
+                    write!(f, ")")?;
+                }
+                write!(f, ")")
+            }
+            Expr::Unary(UnaryOp::Not, value) => write!(f, "not {}", value),
+            Expr::Unary(UnaryOp::Neg, value) => write!(f, "-{}", value),
+            Expr::Binary(op, left, right) => write!(f, "({} {} {})", left, op.symbol(), right),
+        }
+    }
+}
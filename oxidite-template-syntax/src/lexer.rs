@@ -0,0 +1,195 @@
+use crate::parser::SyntaxError;
+
+/// Position of a token in the template source (1-based line and column)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Lexical token
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Text(String),
+    /// `{{`
+    VarStart,
+    /// `}}`
+    VarEnd,
+    /// `{%`
+    TagStart,
+    /// `%}`
+    TagEnd,
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Float(f64),
+    /// Operators and punctuation such as `.`, `|`, `==` or `(`
+    Punct(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+// Longest first, so `==` wins over `=`
+const PUNCTUATION: &[&str] = &[
+    "==", "!=", "<=", ">=", "//", "**", ".", "|", ",", ":", "(", ")", "[", "]", "{", "}", "=", "<", ">", "+", "-",
+    "*", "/", "%", "~",
+];
+
+/// Splits template source into text and the tokens inside `{{ }}` and `{% %}`
+pub struct Lexer<'a> {
+    source: &'a str,
+    pos: usize,
+    line: usize,
+    column: usize,
+    tokens: Vec<Token>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            pos: 0,
+            line: 1,
+            column: 1,
+            tokens: Vec::new(),
+        }
+    }
+
+    pub fn tokenize(mut self) -> std::result::Result<Vec<Token>, SyntaxError> {
+        while self.pos < self.source.len() {
+            let rest = &self.source[self.pos..];
+            if rest.starts_with("{{") {
+                self.lex_delimited("{{", "}}", TokenKind::VarStart, TokenKind::VarEnd)?;
+            } else if rest.starts_with("{%") {
+                self.lex_delimited("{%", "%}", TokenKind::TagStart, TokenKind::TagEnd)?;
+            } else {
+                self.lex_text();
+            }
+        }
+        Ok(self.tokens)
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            offset: self.pos,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn advance(&mut self, len: usize) {
+        for c in self.source[self.pos..self.pos + len].chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.pos += len;
+    }
+
+    fn push(&mut self, kind: TokenKind, span: Span) {
+        self.tokens.push(Token { kind, span });
+    }
+
+    fn lex_text(&mut self) {
+        let rest = &self.source[self.pos..];
+        let len = [rest.find("{{"), rest.find("{%")]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(rest.len());
+        let span = self.span();
+        self.push(TokenKind::Text(rest[..len].to_string()), span);
+        self.advance(len);
+    }
+
+    fn lex_delimited(&mut self, open: &str, close: &'static str, start: TokenKind, end: TokenKind) -> std::result::Result<(), SyntaxError> {
+        let opened = self.span();
+        self.push(start, opened);
+        self.advance(open.len());
+
+        loop {
+            let rest = &self.source[self.pos..];
+            let trimmed = rest.trim_start();
+            self.advance(rest.len() - trimmed.len());
+
+            let span = self.span();
+            let rest = &self.source[self.pos..];
+            if rest.is_empty() {
+                return Err(SyntaxError::new(format!("`{}` is never closed with `{}`", open, close), opened));
+            }
+            if rest.starts_with(close) {
+                self.push(end, span);
+                self.advance(close.len());
+                return Ok(());
+            }
+
+            let c = rest.chars().next().unwrap_or_default();
+            if c.is_ascii_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                self.push(TokenKind::Ident(rest[..len].to_string()), span);
+                self.advance(len);
+            } else if c.is_ascii_digit() {
+                self.lex_number(span)?;
+            } else if c == '"' || c == '\'' {
+                self.lex_string(c, span)?;
+            } else if let Some(punct) = PUNCTUATION.iter().find(|p| rest.starts_with(**p)) {
+                self.push(TokenKind::Punct(punct), span);
+                self.advance(punct.len());
+            } else {
+                return Err(SyntaxError::new(format!("unexpected character `{}`", c), span));
+            }
+        }
+    }
+
+    fn lex_number(&mut self, span: Span) -> std::result::Result<(), SyntaxError> {
+        let rest = &self.source[self.pos..];
+        let mut len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        // A fraction needs a digit after the dot, so `items.0.name` stays a path
+        let is_float = rest[len..].starts_with('.') && rest[len + 1..].starts_with(|c: char| c.is_ascii_digit());
+        if is_float {
+            len += 1 + rest[len + 1..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - len - 1);
+        }
+        let text = &rest[..len];
+        let kind = if is_float {
+            text.parse().map(TokenKind::Float).ok()
+        } else {
+            text.parse().map(TokenKind::Int).ok()
+        };
+        let kind = kind.ok_or_else(|| SyntaxError::new(format!("invalid number `{}`", text), span))?;
+        self.push(kind, span);
+        self.advance(len);
+        Ok(())
+    }
+
+    fn lex_string(&mut self, quote: char, span: Span) -> std::result::Result<(), SyntaxError> {
+        let mut value = String::new();
+        let mut chars = self.source[self.pos + 1..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                c if c == quote => {
+                    self.push(TokenKind::Str(value), span);
+                    self.advance(i + 2);
+                    return Ok(());
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, escaped)) => value.push(escaped),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        Err(SyntaxError::new("unterminated string", span))
+    }
+}
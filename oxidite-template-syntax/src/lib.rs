@@ -0,0 +1,13 @@
+//! Syntax of Oxidite templates: the lexer, the parser and the tree they produce
+//!
+//! This is its own crate so that `#[derive(Template)]` in oxidite-macros can
+//! parse templates at compile time. Use it through `oxidite-template`.
+
+pub mod escape;
+pub mod expr;
+pub mod lexer;
+pub mod parser;
+
+pub use escape::Escape;
+pub use expr::{BinaryOp, Expr, UnaryOp};
+pub use parser::{Macro, Parser, SyntaxError, TemplateNode};
@@ -0,0 +1,884 @@
+use crate::escape::Escape;
+use crate::expr::{BinaryOp, Expr, UnaryOp};
+use crate::lexer::{Lexer, Span, Token, TokenKind};
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+
+/// Template AST nodes
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateNode {
+    Text(String),
+    Variable(Expr),
+    /// `if`/`elif` conditions with their bodies, tried in order
+    If { branches: Vec<(Expr, Vec<TemplateNode>)>, else_branch: Option<Vec<TemplateNode>> },
+    /// `for item in iterable` or `for key, item in iterable`, with the
+    /// `else` body rendered when there is nothing to iterate
+    For { key: Option<String>, item: String, iterable: Expr, body: Vec<TemplateNode>, else_branch: Option<Vec<TemplateNode>> },
+    /// `set name = value`
+    Set { name: String, value: Expr },
+    /// `with name = value, ...`; locals set in the body end with it
+    With { assignments: Vec<(String, Expr)>, body: Vec<TemplateNode> },
+    Block { name: String, body: Vec<TemplateNode> },
+    Extends(String),
+    /// `include "name" with {...} only`; without `only` the included
+    /// template also sees the including one's variables
+    Include { template: String, with: Option<Expr>, only: bool },
+    Macro(Arc<Macro>),
+    /// `autoescape false`, `autoescape true` or `autoescape "js"` around a body
+    Autoescape { escape: Escape, body: Vec<TemplateNode> },
+    /// `import "name" as alias`
+    Import { template: String, alias: String },
+    /// `component name(args)` with the content for the macro's slots; the
+    /// content outside `{% slot %}` tags fills the `default` slot
+    Component { name: String, args: Vec<Expr>, kwargs: Vec<(String, Expr)>, slots: Vec<(String, Vec<TemplateNode>)> },
+    /// `trans "key" name = value, ...`, the message for the current locale
+    Trans { key: String, args: Vec<(String, Expr)> },
+}
+
+/// `{% macro name(param, param=default) %}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Macro {
+    pub name: String,
+    pub params: Vec<(String, Option<Expr>)>,
+    pub body: Vec<TemplateNode>,
+}
+
+/// A template syntax error, pointing at the offending tag
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    /// 1-based line of the error
+    pub line: usize,
+    /// 1-based column of the error
+    pub column: usize,
+    /// Name of the template, when it was added to an engine
+    pub template: Option<String>,
+    /// The source line with a caret under the error
+    pub snippet: String,
+}
+
+impl SyntaxError {
+    pub(crate) fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            line: span.line,
+            column: span.column,
+            template: None,
+            snippet: String::new(),
+        }
+    }
+
+    fn with_source(mut self, source: &str) -> Self {
+        let text = source.lines().nth(self.line - 1).unwrap_or_default();
+        let gutter = self.line.to_string().len();
+        let pad = text.chars().take(self.column - 1).map(|c| if c == '\t' { '\t' } else { ' ' });
+        self.snippet = format!(
+            "{} | {}\n{} | {}^",
+            self.line,
+            text,
+            " ".repeat(gutter),
+            pad.collect::<String>()
+        );
+        self
+    }
+
+    /// Report the error against the template called `name`
+    pub fn in_template(mut self, name: &str) -> Self {
+        self.template = Some(name.to_string());
+        self
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.template {
+            Some(name) => write!(f, "{}:{}:{}: {}", name, self.line, self.column, self.message)?,
+            None => write!(f, "line {}, column {}: {}", self.line, self.column, self.message)?,
+        }
+        if !self.snippet.is_empty() {
+            write!(f, "\n{}", self.snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Templates that `nodes` extend, include or import, each with the verb for error messages
+pub fn references(nodes: &[TemplateNode]) -> Vec<(&'static str, &str)> {
+    let mut references = Vec::new();
+    collect_references(nodes, &mut references);
+    references
+}
+
+fn collect_references<'t>(nodes: &'t [TemplateNode], references: &mut Vec<(&'static str, &'t str)>) {
+    for node in nodes {
+        match node {
+            TemplateNode::Extends(parent) => references.push(("extends", parent)),
+            TemplateNode::Include { template, .. } => references.push(("includes", template)),
+            TemplateNode::Import { template, .. } => references.push(("imports", template)),
+            TemplateNode::If { branches, else_branch } => {
+                for (_, body) in branches {
+                    collect_references(body, references);
+                }
+                collect_references(else_branch.as_deref().unwrap_or_default(), references);
+            }
+            TemplateNode::For { body, else_branch, .. } => {
+                collect_references(body, references);
+                collect_references(else_branch.as_deref().unwrap_or_default(), references);
+            }
+            TemplateNode::With { body, .. }
+            | TemplateNode::Block { body, .. }
+            | TemplateNode::Autoescape { body, .. } => collect_references(body, references),
+            TemplateNode::Macro(def) => collect_references(&def.body, references),
+            TemplateNode::Component { slots, .. } => {
+                for (_, body) in slots {
+                    collect_references(body, references);
+                }
+            }
+            TemplateNode::Text(_) | TemplateNode::Variable(_) | TemplateNode::Set { .. } | TemplateNode::Trans { .. } => {}
+        }
+    }
+}
+
+type ParseResult<T> = std::result::Result<T, SyntaxError>;
+
+/// Name and position of the tag that ended a run of nodes
+type Closing = Option<(String, Span)>;
+
+/// Tags that close or split an enclosing block
+const END_TAGS: &[&str] = &["elif", "else", "endif", "endfor", "endblock", "endwith", "endmacro", "endcomponent", "endslot", "endautoescape"];
+
+/// Identifiers that can't name a variable
+const KEYWORDS: &[&str] = &["and", "or", "not", "in", "is"];
+
+/// Template parser
+pub struct Parser {
+    source: String,
+}
+
+impl Parser {
+    pub fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+        }
+    }
+
+    pub fn parse(&self) -> Result<Vec<TemplateNode>, SyntaxError> {
+        self.parse_tokens().map_err(|err| err.with_source(&self.source))
+    }
+
+    fn parse_tokens(&self) -> ParseResult<Vec<TemplateNode>> {
+        let tokens = Lexer::new(&self.source).tokenize()?;
+        let end = tokens.last().map(|t| t.span).unwrap_or(Span { offset: 0, line: 1, column: 1 });
+        let mut stream = TokenStream { tokens, pos: 0, end };
+
+        let (nodes, closing) = stream.parse_until(&[])?;
+        match closing {
+            None => Ok(nodes),
+            Some((tag, span)) => Err(SyntaxError::new(format!("unexpected `{{% {} %}}`", tag), span)),
+        }
+    }
+}
+
+struct TokenStream {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Where the source ends, for errors about missing tokens
+    end: Span,
+}
+
+impl TokenStream {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    fn span(&self) -> Span {
+        self.tokens.get(self.pos).map(|t| t.span).unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn describe(kind: Option<&TokenKind>) -> String {
+        match kind {
+            None => "end of template".to_string(),
+            Some(TokenKind::Text(_)) => "text".to_string(),
+            Some(TokenKind::VarStart) => "`{{`".to_string(),
+            Some(TokenKind::VarEnd) => "`}}`".to_string(),
+            Some(TokenKind::TagStart) => "`{%`".to_string(),
+            Some(TokenKind::TagEnd) => "`%}`".to_string(),
+            Some(TokenKind::Ident(name)) => format!("`{}`", name),
+            Some(TokenKind::Str(s)) => format!("\"{}\"", s),
+            Some(TokenKind::Int(n)) => format!("`{}`", n),
+            Some(TokenKind::Float(n)) => format!("`{}`", n),
+            Some(TokenKind::Punct(p)) => format!("`{}`", p),
+        }
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> ParseResult<T> {
+        Err(SyntaxError::new(
+            format!("expected {}, found {}", expected, Self::describe(self.peek())),
+            self.span(),
+        ))
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> ParseResult<()> {
+        if self.peek() == Some(&kind) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.unexpected(expected)
+        }
+    }
+
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(TokenKind::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn ident(&mut self, expected: &str) -> ParseResult<String> {
+        match self.peek() {
+            Some(TokenKind::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.unexpected(expected),
+        }
+    }
+
+    fn string(&mut self, expected: &str) -> ParseResult<String> {
+        match self.peek() {
+            Some(TokenKind::Str(value)) => {
+                let value = value.clone();
+                self.pos += 1;
+                Ok(value)
+            }
+            _ => self.unexpected(expected),
+        }
+    }
+
+    fn end_tag(&mut self) -> ParseResult<()> {
+        self.expect(TokenKind::TagEnd, "`%}`")
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(TokenKind::Ident(name)) if name == keyword)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Comma-separated expressions up to `close`, which is consumed
+    fn expr_list(&mut self, close: &'static str) -> ParseResult<Vec<Expr>> {
+        let mut items = Vec::new();
+        while !self.eat_punct(close) {
+            items.push(self.expr()?);
+            if !self.eat_punct(",") {
+                self.expect(TokenKind::Punct(close), &format!("`,` or `{}`", close))?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// Arguments of a call to `name`, after the `(`
+    fn call(&mut self, name: String) -> ParseResult<Expr> {
+        let mut args = Vec::new();
+        let mut kwargs = Vec::new();
+        while !self.eat_punct(")") {
+            let is_keyword_arg = matches!(self.peek(), Some(TokenKind::Ident(_)))
+                && matches!(self.tokens.get(self.pos + 1).map(|t| &t.kind), Some(TokenKind::Punct("=")));
+            if is_keyword_arg {
+                let (key, value) = self.assignment()?;
+                kwargs.push((key, value));
+            } else if kwargs.is_empty() {
+                args.push(self.expr()?);
+            } else {
+                return self.unexpected("a keyword argument");
+            }
+            if !self.eat_punct(",") {
+                self.expect(TokenKind::Punct(")"), "`,` or `)`")?;
+                break;
+            }
+        }
+        Ok(Expr::Call { name, args, kwargs })
+    }
+
+    /// An expression, loosest binding first: `or`, `and`, `not`, comparisons,
+    /// `in` and `is` tests, `~`, `+ -`, `* / // %`, unary `-`, `**`, then `.attr`,
+    /// `[index]` and `| filter`
+    fn expr(&mut self) -> ParseResult<Expr> {
+        let mut left = self.and_expr()?;
+        while self.eat_keyword("or") {
+            left = Expr::Binary(BinaryOp::Or, Box::new(left), Box::new(self.and_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> ParseResult<Expr> {
+        let mut left = self.not_expr()?;
+        while self.eat_keyword("and") {
+            left = Expr::Binary(BinaryOp::And, Box::new(left), Box::new(self.not_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn not_expr(&mut self) -> ParseResult<Expr> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Unary(UnaryOp::Not, Box::new(self.not_expr()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> ParseResult<Expr> {
+        let mut left = self.concat()?;
+        loop {
+            if self.eat_keyword("is") {
+                let negated = self.eat_keyword("not");
+                let name = self.ident("a test name after `is`")?;
+                let args = if self.eat_punct("(") { self.expr_list(")")? } else { Vec::new() };
+                left = Expr::Test { value: Box::new(left), name, args, negated };
+                continue;
+            }
+            let op = match self.peek() {
+                Some(TokenKind::Punct("==")) => BinaryOp::Eq,
+                Some(TokenKind::Punct("!=")) => BinaryOp::Ne,
+                Some(TokenKind::Punct("<")) => BinaryOp::Lt,
+                Some(TokenKind::Punct("<=")) => BinaryOp::Le,
+                Some(TokenKind::Punct(">")) => BinaryOp::Gt,
+                Some(TokenKind::Punct(">=")) => BinaryOp::Ge,
+                Some(TokenKind::Ident(name)) if name == "in" => BinaryOp::In,
+                Some(TokenKind::Ident(name))
+                    if name == "not"
+                        && matches!(self.tokens.get(self.pos + 1).map(|t| &t.kind), Some(TokenKind::Ident(next)) if next == "in") =>
+                {
+                    self.pos += 1;
+                    BinaryOp::NotIn
+                }
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.concat()?));
+        }
+    }
+
+    fn concat(&mut self) -> ParseResult<Expr> {
+        let mut left = self.additive()?;
+        while self.eat_punct("~") {
+            left = Expr::Binary(BinaryOp::Concat, Box::new(left), Box::new(self.additive()?));
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> ParseResult<Expr> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(TokenKind::Punct("+")) => BinaryOp::Add,
+                Some(TokenKind::Punct("-")) => BinaryOp::Sub,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> ParseResult<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(TokenKind::Punct("*")) => BinaryOp::Mul,
+                Some(TokenKind::Punct("/")) => BinaryOp::Div,
+                Some(TokenKind::Punct("//")) => BinaryOp::FloorDiv,
+                Some(TokenKind::Punct("%")) => BinaryOp::Rem,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> ParseResult<Expr> {
+        if self.eat_punct("-") {
+            return Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.unary()?)));
+        }
+        let base = self.postfix()?;
+        if self.eat_punct("**") {
+            // Right associative, and binds tighter than a unary minus on its left
+            return Ok(Expr::Binary(BinaryOp::Pow, Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn postfix(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat_punct(".") {
+                let name = match self.peek() {
+                    Some(TokenKind::Ident(name)) => name.clone(),
+                    Some(TokenKind::Int(index)) => index.to_string(),
+                    _ => return self.unexpected("a field name after `.`"),
+                };
+                self.pos += 1;
+                expr = match expr {
+                    // `forms.field(...)` calls a macro imported as `forms`
+                    Expr::Var(namespace) if self.eat_punct("(") => self.call(format!("{}.{}", namespace, name))?,
+                    expr => Expr::Attr(Box::new(expr), name),
+                };
+            } else if self.eat_punct("[") {
+                let index = self.expr()?;
+                self.expect(TokenKind::Punct("]"), "`]`")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else if self.eat_punct("|") {
+                let name = self.ident("a filter name")?;
+                let args = if self.eat_punct("(") { self.expr_list(")")? } else { Vec::new() };
+                expr = Expr::Filter { value: Box::new(expr), name, args };
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr> {
+        let literal = match self.peek() {
+            Some(TokenKind::Int(n)) => Some(Value::from(*n)),
+            Some(TokenKind::Float(n)) => Some(Value::from(*n)),
+            Some(TokenKind::Str(s)) => Some(Value::String(s.clone())),
+            Some(TokenKind::Ident(name)) => match name.as_str() {
+                "true" | "True" => Some(Value::Bool(true)),
+                "false" | "False" => Some(Value::Bool(false)),
+                "none" | "None" => Some(Value::Null),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(value) = literal {
+            self.pos += 1;
+            return Ok(Expr::Literal(value));
+        }
+
+        if self.eat_punct("(") {
+            let expr = self.expr()?;
+            self.expect(TokenKind::Punct(")"), "`)`")?;
+            return Ok(expr);
+        }
+        if self.eat_punct("[") {
+            return Ok(Expr::List(self.expr_list("]")?));
+        }
+        if self.eat_punct("{") {
+            let mut entries = Vec::new();
+            while !self.eat_punct("}") {
+                let key = match self.peek() {
+                    Some(TokenKind::Str(key)) | Some(TokenKind::Ident(key)) => key.clone(),
+                    _ => return self.unexpected("a key"),
+                };
+                self.pos += 1;
+                self.expect(TokenKind::Punct(":"), "`:`")?;
+                entries.push((key, self.expr()?));
+                if !self.eat_punct(",") {
+                    self.expect(TokenKind::Punct("}"), "`,` or `}`")?;
+                    break;
+                }
+            }
+            return Ok(Expr::Map(entries));
+        }
+
+        match self.peek() {
+            Some(TokenKind::Ident(name)) if !KEYWORDS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.pos += 1;
+                if self.eat_punct("(") {
+                    self.call(name)
+                } else {
+                    Ok(Expr::Var(name))
+                }
+            }
+            _ => self.unexpected("an expression"),
+        }
+    }
+
+    /// Nodes up to one of `end_tags` (or the end of the template), along with
+    /// the closing tag's name and position; the closing tag's `%}` is not consumed
+    fn parse_until(&mut self, end_tags: &[&str]) -> ParseResult<(Vec<TemplateNode>, Closing)> {
+        let mut nodes = Vec::new();
+        while let Some(token) = self.next() {
+            match token.kind {
+                TokenKind::Text(text) => nodes.push(TemplateNode::Text(text)),
+                TokenKind::VarStart => nodes.push(self.parse_variable()?),
+                TokenKind::TagStart => {
+                    let name = self.ident("a tag name")?;
+                    if end_tags.contains(&name.as_str()) || END_TAGS.contains(&name.as_str()) {
+                        return Ok((nodes, Some((name, token.span))));
+                    }
+                    nodes.push(self.parse_tag(&name, token.span)?);
+                }
+                _ => {
+                    self.pos -= 1;
+                    return self.unexpected("text or a tag");
+                }
+            }
+        }
+        Ok((nodes, None))
+    }
+
+    /// Body of a block tag opened at `opened`, which must close with `end`
+    fn parse_body(&mut self, tag: &str, opened: Span, ends: &[&str]) -> ParseResult<(Vec<TemplateNode>, String)> {
+        let (body, closing) = self.parse_until(ends)?;
+        match closing {
+            Some((name, _)) if ends.contains(&name.as_str()) => Ok((body, name)),
+            Some((name, span)) => Err(SyntaxError::new(
+                format!("unexpected `{{% {} %}}` inside `{{% {} %}}`", name, tag),
+                span,
+            )),
+            None => Err(SyntaxError::new(
+                format!("`{{% {} %}}` is never closed with `{{% {} %}}`", tag, ends[ends.len() - 1]),
+                opened,
+            )),
+        }
+    }
+
+    /// A name to assign to, which can't be a keyword
+    fn variable_name(&mut self, expected: &str) -> ParseResult<String> {
+        match self.peek() {
+            Some(TokenKind::Ident(name)) if !KEYWORDS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.unexpected(expected),
+        }
+    }
+
+    /// `name = expr` in `set` and `with`
+    fn assignment(&mut self) -> ParseResult<(String, Expr)> {
+        let name = self.variable_name("a variable name")?;
+        self.expect(TokenKind::Punct("="), "`=`")?;
+        Ok((name, self.expr()?))
+    }
+
+    /// `name = expr, ...` up to the end of the tag, in `with` and `trans`
+    fn assignments(&mut self) -> ParseResult<Vec<(String, Expr)>> {
+        let mut assignments = Vec::new();
+        while !matches!(self.peek(), Some(TokenKind::TagEnd)) {
+            if !assignments.is_empty() {
+                self.expect(TokenKind::Punct(","), "`,` or `%}`")?;
+            }
+            assignments.push(self.assignment()?);
+        }
+        Ok(assignments)
+    }
+
+    fn parse_variable(&mut self) -> ParseResult<TemplateNode> {
+        let expr = self.expr()?;
+        self.expect(TokenKind::VarEnd, "`}}`")?;
+        Ok(TemplateNode::Variable(expr))
+    }
+
+    fn parse_tag(&mut self, name: &str, opened: Span) -> ParseResult<TemplateNode> {
+        match name {
+            "if" => {
+                let mut branches = Vec::new();
+                let mut else_branch = None;
+                let mut condition = self.expr()?;
+                loop {
+                    self.end_tag()?;
+                    let (body, closing) = self.parse_body("if", opened, &["elif", "else", "endif"])?;
+                    branches.push((condition, body));
+                    match closing.as_str() {
+                        "elif" => condition = self.expr()?,
+                        "else" => {
+                            self.end_tag()?;
+                            let (body, _) = self.parse_body("if", opened, &["endif"])?;
+                            else_branch = Some(body);
+                            self.end_tag()?;
+                            break;
+                        }
+                        _ => {
+                            self.end_tag()?;
+                            break;
+                        }
+                    }
+                }
+                Ok(TemplateNode::If { branches, else_branch })
+            }
+            "for" => {
+                let mut key = None;
+                let mut item = self.variable_name("a loop variable")?;
+                if self.eat_punct(",") {
+                    key = Some(item);
+                    item = self.variable_name("a second loop variable")?;
+                }
+                if !self.eat_keyword("in") {
+                    return self.unexpected("`in`");
+                }
+                let iterable = self.expr()?;
+                self.end_tag()?;
+                let (body, closing) = self.parse_body("for", opened, &["else", "endfor"])?;
+                self.end_tag()?;
+                let else_branch = if closing == "else" {
+                    let (body, _) = self.parse_body("for", opened, &["endfor"])?;
+                    self.end_tag()?;
+                    Some(body)
+                } else {
+                    None
+                };
+                Ok(TemplateNode::For { key, item, iterable, body, else_branch })
+            }
+            "set" => {
+                let (name, value) = self.assignment()?;
+                self.end_tag()?;
+                Ok(TemplateNode::Set { name, value })
+            }
+            "with" => {
+                let assignments = self.assignments()?;
+                self.end_tag()?;
+                let (body, _) = self.parse_body("with", opened, &["endwith"])?;
+                self.end_tag()?;
+                Ok(TemplateNode::With { assignments, body })
+            }
+            "block" => {
+                let name = self.ident("a block name")?;
+                self.end_tag()?;
+                let (body, _) = self.parse_body("block", opened, &["endblock"])?;
+                // `{% endblock name %}` must name the block it closes
+                if let Some(TokenKind::Ident(closing)) = self.peek() {
+                    if *closing != name {
+                        return Err(SyntaxError::new(
+                            format!("`{{% endblock {} %}}` closes block `{}`", closing, name),
+                            self.span(),
+                        ));
+                    }
+                    self.pos += 1;
+                }
+                self.end_tag()?;
+                Ok(TemplateNode::Block { name, body })
+            }
+            "extends" => {
+                let parent = self.string("a template name in quotes")?;
+                self.end_tag()?;
+                Ok(TemplateNode::Extends(parent))
+            }
+            "include" => {
+                let template = self.string("a template name in quotes")?;
+                let with = if self.eat_keyword("with") { Some(self.expr()?) } else { None };
+                let only = self.eat_keyword("only");
+                self.end_tag()?;
+                Ok(TemplateNode::Include { template, with, only })
+            }
+            "macro" => {
+                let name = self.variable_name("a macro name")?;
+                self.expect(TokenKind::Punct("("), "`(`")?;
+                let mut params: Vec<(String, Option<Expr>)> = Vec::new();
+                while !self.eat_punct(")") {
+                    let param = self.variable_name("a parameter name")?;
+                    let default = if self.eat_punct("=") {
+                        Some(self.expr()?)
+                    } else if params.iter().any(|(_, default)| default.is_some()) {
+                        return self.unexpected("`=` and a default, as earlier parameters have one");
+                    } else {
+                        None
+                    };
+                    params.push((param, default));
+                    if !self.eat_punct(",") {
+                        self.expect(TokenKind::Punct(")"), "`,` or `)`")?;
+                        break;
+                    }
+                }
+                self.end_tag()?;
+                let (body, _) = self.parse_body("macro", opened, &["endmacro"])?;
+                self.end_tag()?;
+                Ok(TemplateNode::Macro(Arc::new(Macro { name, params, body })))
+            }
+            "autoescape" => {
+                let escape = match self.peek() {
+                    Some(TokenKind::Ident(flag)) if flag == "true" => Some(Escape::Html),
+                    Some(TokenKind::Ident(flag)) if flag == "false" => Some(Escape::Text),
+                    Some(TokenKind::Str(name)) => Escape::from_name(name),
+                    _ => None,
+                };
+                let Some(escape) = escape else {
+                    return self.unexpected("`true`, `false` or one of \"html\", \"xml\", \"js\" and \"txt\"");
+                };
+                self.pos += 1;
+                self.end_tag()?;
+                let (body, _) = self.parse_body("autoescape", opened, &["endautoescape"])?;
+                self.end_tag()?;
+                Ok(TemplateNode::Autoescape { escape, body })
+            }
+            "import" => {
+                let template = self.string("a template name in quotes")?;
+                if !self.eat_keyword("as") {
+                    return self.unexpected("`as`");
+                }
+                let alias = self.variable_name("a name to import as")?;
+                self.end_tag()?;
+                Ok(TemplateNode::Import { template, alias })
+            }
+            "trans" => {
+                let key = self.string("a message key in quotes")?;
+                let args = self.assignments()?;
+                self.end_tag()?;
+                Ok(TemplateNode::Trans { key, args })
+            }
+            "component" => {
+                let call_span = self.span();
+                let (name, args, kwargs) = match self.expr()? {
+                    Expr::Call { name, args, kwargs } => (name, args, kwargs),
+                    Expr::Var(name) => (name, Vec::new(), Vec::new()),
+                    _ => return Err(SyntaxError::new("expected a macro to call", call_span)),
+                };
+                self.end_tag()?;
+
+                let mut default = Vec::new();
+                let mut slots = Vec::new();
+                loop {
+                    let (nodes, closing) = self.parse_body("component", opened, &["slot", "endcomponent"])?;
+                    default.extend(nodes);
+                    if closing == "endcomponent" {
+                        self.end_tag()?;
+                        break;
+                    }
+                    let slot = self.variable_name("a slot name")?;
+                    self.end_tag()?;
+                    let (body, _) = self.parse_body("slot", opened, &["endslot"])?;
+                    self.end_tag()?;
+                    slots.push((slot, body));
+                }
+                slots.insert(0, ("default".to_string(), default));
+                Ok(TemplateNode::Component { name, args, kwargs, slots })
+            }
+            _ => Err(SyntaxError::new(format!("unknown tag `{}`", name), opened)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Vec<TemplateNode> {
+        Parser::new(source).parse().unwrap()
+    }
+
+    fn error(source: &str) -> SyntaxError {
+        Parser::new(source).parse().unwrap_err()
+    }
+
+    #[test]
+    fn test_nested_blocks_close_at_their_own_end_tag() {
+        let nodes = parse("{%if a%}{% if b %}B{% else %}b{% endif %}A{%   endif   %}!");
+        let TemplateNode::If { branches, else_branch } = &nodes[0] else {
+            panic!("expected an if, got {:?}", nodes);
+        };
+        assert!(else_branch.is_none());
+        let then_branch = &branches[0].1;
+        assert!(matches!(&then_branch[0], TemplateNode::If { else_branch: Some(_), .. }));
+        assert_eq!(then_branch[1], TemplateNode::Text("A".to_string()));
+        assert_eq!(nodes[1], TemplateNode::Text("!".to_string()));
+
+        let nodes = parse("{% for row in rows %}{% for cell in row.cells %}{{ cell | upper | trim }}{% endfor %}{% endfor %}");
+        let TemplateNode::For { body, .. } = &nodes[0] else {
+            panic!("expected a for, got {:?}", nodes);
+        };
+        assert_eq!(
+            body[0],
+            TemplateNode::For {
+                key: None,
+                item: "cell".to_string(),
+                iterable: Expr::Attr(Box::new(Expr::Var("row".to_string())), "cells".to_string()),
+                body: vec![TemplateNode::Variable(Expr::Filter {
+                    value: Box::new(Expr::Filter {
+                        value: Box::new(Expr::Var("cell".to_string())),
+                        name: "upper".to_string(),
+                        args: vec![],
+                    }),
+                    name: "trim".to_string(),
+                    args: vec![],
+                })],
+                else_branch: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_expression_precedence() {
+        let expr = |source: &str| match parse(source).remove(0) {
+            TemplateNode::Variable(expr) => expr.to_string(),
+            node => panic!("expected a variable, got {:?}", node),
+        };
+
+        assert_eq!(expr("{{ a or b and not c == 1 }}"), "(a or (b and not (c == 1)))");
+        assert_eq!(expr("{{ 1 + 2 * 3 - -4 ** 2 }}"), "((1 + (2 * 3)) - -(4 ** 2))");
+        assert_eq!(expr("{{ x not in [1, 2.5] ~ 'a' }}"), "(x not in ([1, 2.5] ~ \"a\"))");
+        assert_eq!(expr("{{ items[0].name | truncate(80) | upper }}"), "items[0].name | truncate(80) | upper");
+        assert_eq!(expr("{{ max(a, {'b': none}) }}"), "max(a, {\"b\": null})");
+        assert_eq!(expr("{{ not a is defined and b is not divisibleby(3) }}"), "(not (a is defined) and (b is not divisibleby(3)))");
+
+        let TemplateNode::If { branches, else_branch } = &parse("{% if a %}A{% elif b %}B{% elif c %}C{% else %}D{% endif %}")[0] else {
+            panic!("expected an if");
+        };
+        assert_eq!(branches.len(), 3);
+        assert!(else_branch.is_some());
+
+        assert_eq!(
+            parse("{% trans \"cart.items\" count = items | length, name = user.name %}")[0],
+            TemplateNode::Trans {
+                key: "cart.items".to_string(),
+                args: vec![
+                    ("count".to_string(), Expr::Filter { value: Box::new(Expr::Var("items".to_string())), name: "length".to_string(), args: vec![] }),
+                    ("name".to_string(), Expr::Attr(Box::new(Expr::Var("user".to_string())), "name".to_string())),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_errors_point_at_the_offending_tag() {
+        let err = error("<p>\n  {% if user %}\n  hi\n");
+        assert_eq!((err.line, err.column), (2, 3));
+        assert_eq!(err.message, "`{% if %}` is never closed with `{% endif %}`");
+        assert_eq!(err.snippet, "2 |   {% if user %}\n  |   ^");
+
+        let err = error("{% for x in xs %}{% endif %}");
+        assert_eq!((err.line, err.column, err.message.as_str()), (1, 18, "unexpected `{% endif %}` inside `{% for %}`"));
+
+        let err = error("a {% frobnicate %}");
+        assert_eq!((err.column, err.message.as_str()), (3, "unknown tag `frobnicate`"));
+
+        let err = error("{{ user. }}");
+        assert_eq!((err.column, err.message.as_str()), (10, "expected a field name after `.`, found `}}`"));
+
+        assert_eq!(error("{% endfor %}").message, "unexpected `{% endfor %}`");
+        assert_eq!(error("{% for k, in map %}{% endfor %}").message, "expected a second loop variable, found `in`");
+        assert_eq!(error("{% with a = 1 b = 2 %}{% endwith %}").message, "expected `,` or `%}`, found `b`");
+        assert_eq!(error("{% set in = 1 %}").message, "expected a variable name, found `in`");
+        assert_eq!(error("{% macro f(a=1, b) %}{% endmacro %}").message, "expected `=` and a default, as earlier parameters have one, found `)`");
+        assert_eq!(error("{{ f(a=1, 2) }}").message, "expected a keyword argument, found `2`");
+        assert_eq!(error("{% component 'card' %}{% endcomponent %}").message, "expected a macro to call");
+        assert_eq!(error("{% component card %}{% slot footer %}").message, "`{% slot %}` is never closed with `{% endslot %}`");
+        assert_eq!(
+            error("{% autoescape \"css\" %}{% endautoescape %}").message,
+            "expected `true`, `false` or one of \"html\", \"xml\", \"js\" and \"txt\", found \"css\""
+        );
+        assert_eq!(error("{{ a + }}").message, "expected an expression, found `}}`");
+        assert_eq!(error("{{ f(a b) }}").message, "expected `,` or `)`, found `b`");
+        assert_eq!(error("{% if a %}{% else %}{% elif b %}{% endif %}").message, "unexpected `{% elif %}` inside `{% if %}`");
+        assert_eq!(error("{{ name").message, "`{{` is never closed with `}}`");
+        assert_eq!(error("{% block a %}{% endblock b %}").message, "`{% endblock b %}` closes block `a`");
+    }
+}
@@ -0,0 +1,162 @@
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+
+/// Key of the single-entry map a [`SafeString`] serializes to, which is how
+/// the renderer tells trusted markup from plain strings
+///
+/// It is random for each process, so a map from request data or a database
+/// column can't pass itself off as markup. Output never contains it.
+fn safe_marker() -> &'static str {
+    static MARKER: OnceLock<String> = OnceLock::new();
+    MARKER.get_or_init(|| {
+        let random = || RandomState::new().build_hasher().finish();
+        format!("$oxidite_safe_{:016x}{:016x}", random(), random())
+    })
+}
+
+thread_local! {
+    /// Set while a value is serialized into a template context, the only
+    /// place a [`SafeString`] is marked as markup
+    static MARKING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// `value` serialized for a template context, with [`SafeString`]s marked
+pub(crate) fn to_context_value<T: Serialize>(value: T) -> serde_json::Result<Value> {
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            MARKING.with(|marking| marking.set(self.0));
+        }
+    }
+
+    let _restore = Restore(MARKING.with(|marking| marking.replace(true)));
+    serde_json::to_value(value)
+}
+
+pub use oxidite_template_syntax::escape::Escape;
+
+/// A string output as-is, such as HTML rendered from markdown on the server
+///
+/// Use it for trusted content only; anything from users must be escaped.
+/// It serializes as a plain string, except into a template [`Context`](crate::Context).
+///
+/// ```
+/// use oxidite_template::{Context, SafeString, Template};
+///
+/// let mut ctx = Context::new();
+/// ctx.set("body", SafeString::from("<p>Hello</p>"));
+/// assert_eq!(Template::new("{{ body }}").unwrap().render(&ctx).unwrap(), "<p>Hello</p>");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SafeString(String);
+
+impl SafeString {
+    pub fn new(markup: impl Into<String>) -> Self {
+        Self(markup.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl From<String> for SafeString {
+    fn from(markup: String) -> Self {
+        Self(markup)
+    }
+}
+
+impl From<&str> for SafeString {
+    fn from(markup: &str) -> Self {
+        Self(markup.to_string())
+    }
+}
+
+impl fmt::Display for SafeString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for SafeString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !MARKING.with(Cell::get) {
+            return serializer.serialize_str(&self.0);
+        }
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(safe_marker(), &self.0)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for SafeString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Plain(String),
+            Marked(HashMap<String, String>),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Plain(markup) => Ok(Self(markup)),
+            Repr::Marked(mut map) => map
+                .remove(safe_marker())
+                .map(Self)
+                .ok_or_else(|| serde::de::Error::custom("expected a string")),
+        }
+    }
+}
+
+/// `markup` as a value the renderer outputs without escaping
+pub(crate) fn mark_safe(markup: String) -> Value {
+    let mut map = Map::new();
+    map.insert(safe_marker().to_string(), Value::String(markup));
+    Value::Object(map)
+}
+
+/// The markup in a [`SafeString`] value
+pub(crate) fn safe_str(value: &Value) -> Option<&str> {
+    match value {
+        Value::Object(map) if map.len() == 1 => map.get(safe_marker())?.as_str(),
+        _ => None,
+    }
+}
+
+/// `value` with every [`SafeString`] in it replaced by its markup, for
+/// writing it out as data without revealing the marker
+pub(crate) fn unmark_all(value: &Value) -> Cow<'_, Value> {
+    fn contains_safe(value: &Value) -> bool {
+        safe_str(value).is_some()
+            || match value {
+                Value::Array(items) => items.iter().any(contains_safe),
+                Value::Object(map) => map.values().any(contains_safe),
+                _ => false,
+            }
+    }
+
+    if !contains_safe(value) {
+        return Cow::Borrowed(value);
+    }
+    Cow::Owned(match (safe_str(value), value) {
+        (Some(markup), _) => Value::String(markup.to_string()),
+        (None, Value::Array(items)) => Value::Array(items.iter().map(|item| unmark_all(item).into_owned()).collect()),
+        (None, Value::Object(map)) => {
+            Value::Object(map.iter().map(|(key, item)| (key.clone(), unmark_all(item).into_owned())).collect())
+        }
+        (None, other) => other.clone(),
+    })
+}
@@ -0,0 +1,247 @@
+use crate::escape;
+use crate::{Result, TemplateError};
+use serde_json::{Number, Value};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+pub use oxidite_template_syntax::expr::{BinaryOp, Expr, UnaryOp};
+
+/// Whether a value counts as true in `if` and `and`/`or`
+pub fn is_truthy(value: &Value) -> bool {
+    if let Some(markup) = escape::safe_str(value) {
+        return !markup.is_empty();
+    }
+    match value {
+        Value::Bool(b) => *b,
+        Value::Null => false,
+        Value::String(s) => !s.is_empty(),
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+/// How a value is written into the output
+pub fn to_output(value: &Value) -> String {
+    if let Some(markup) = escape::safe_str(value) {
+        return markup.to_string();
+    }
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => String::new(),
+        _ => serde_json::to_string(&escape::unmark_all(value)).unwrap_or_default(),
+    }
+}
+
+/// Name of a value's type, for error messages
+pub fn type_name(value: &Value) -> &'static str {
+    if escape::safe_str(value).is_some() {
+        return "string";
+    }
+    match value {
+        Value::Null => "none",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "list",
+        Value::Object(_) => "map",
+    }
+}
+
+/// A safe string as the plain string it holds, for operators and comparisons
+pub(crate) fn unmark(value: &Value) -> Cow<'_, Value> {
+    match escape::safe_str(value) {
+        Some(markup) => Cow::Owned(Value::String(markup.to_string())),
+        None => Cow::Borrowed(value),
+    }
+}
+
+/// `value.name` or `value[index]`; `None` when it doesn't exist
+pub fn get_item<'v>(value: &'v Value, index: &Value) -> Option<&'v Value> {
+    match (value, index) {
+        (Value::Object(map), Value::String(key)) => map.get(key),
+        (Value::Array(items), Value::String(key)) => key.parse().ok().and_then(|i: usize| items.get(i)),
+        (Value::Array(items), Value::Number(n)) => {
+            let i = n.as_i64()?;
+            // Negative indexes count from the end
+            let i = if i < 0 { items.len() as i64 + i } else { i };
+            usize::try_from(i).ok().and_then(|i| items.get(i))
+        }
+        _ => None,
+    }
+}
+
+/// Values compare equal across integer and float numbers
+pub fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => left == right,
+    }
+}
+
+/// Order of two numbers, strings or bools; `None` for anything else
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn contains(container: &Value, item: &Value) -> Result<bool> {
+    match (container, item) {
+        (Value::String(haystack), Value::String(needle)) => Ok(haystack.contains(needle.as_str())),
+        (Value::Array(items), item) => Ok(items.iter().any(|i| values_equal(i, item))),
+        (Value::Object(map), Value::String(key)) => Ok(map.contains_key(key)),
+        (container, item) => Err(TemplateError::RenderError(format!(
+            "cannot check whether a {} is in a {}",
+            type_name(item),
+            type_name(container)
+        ))),
+    }
+}
+
+/// `value is name(args)` for every test except `defined` and `undefined`,
+/// which the renderer answers since they need to know about undefined values
+pub fn test(name: &str, value: &Value, args: &[Value]) -> Result<bool> {
+    let value = &*unmark(value);
+    let integer = || {
+        value
+            .as_i64()
+            .ok_or_else(|| TemplateError::RenderError(format!("`is {}` expects an integer, found a {}", name, type_name(value))))
+    };
+    Ok(match name {
+        "none" => value.is_null(),
+        "empty" => match value {
+            Value::Null => true,
+            Value::String(s) => s.is_empty(),
+            Value::Array(items) => items.is_empty(),
+            Value::Object(map) => map.is_empty(),
+            _ => false,
+        },
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "iterable" => matches!(value, Value::Array(_) | Value::Object(_)),
+        "mapping" => value.is_object(),
+        "even" => integer()? % 2 == 0,
+        "odd" => integer()? % 2 != 0,
+        "divisibleby" => {
+            let divisor = args
+                .first()
+                .and_then(Value::as_i64)
+                .ok_or_else(|| TemplateError::RenderError("`is divisibleby` expects an integer argument".to_string()))?;
+            if divisor == 0 {
+                return Err(TemplateError::RenderError("division by zero".to_string()));
+            }
+            // Only `i64::MIN % -1` overflows, and it divides evenly
+            integer()?.checked_rem(divisor).unwrap_or(0) == 0
+        }
+        _ => return Err(TemplateError::RenderError(format!("Test not found: {}", name))),
+    })
+}
+
+fn int(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        Value::from(value as i64)
+    } else {
+        Number::from_f64(value).map(Value::Number).unwrap_or(Value::Null)
+    }
+}
+
+fn float(value: f64) -> Value {
+    Number::from_f64(value).map(Value::Number).unwrap_or(Value::Null)
+}
+
+/// `-value`
+pub fn negate(value: &Value) -> Result<Value> {
+    match &*unmark(value) {
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Ok(i.checked_neg().map(Value::from).unwrap_or_else(|| float(-(i as f64)))),
+            None => Ok(float(-n.as_f64().unwrap_or_default())),
+        },
+        other => Err(TemplateError::RenderError(format!("cannot negate a {}", type_name(other)))),
+    }
+}
+
+/// `left op right` for every operator except the short-circuiting `and`/`or`
+pub fn binary(op: BinaryOp, left: &Value, right: &Value) -> Result<Value> {
+    let (left, right) = (&*unmark(left), &*unmark(right));
+    let mismatch = || {
+        TemplateError::RenderError(format!(
+            "cannot apply `{}` to a {} and a {}",
+            op.symbol(),
+            type_name(left),
+            type_name(right)
+        ))
+    };
+
+    match op {
+        BinaryOp::Eq => return Ok(Value::Bool(values_equal(left, right))),
+        BinaryOp::Ne => return Ok(Value::Bool(!values_equal(left, right))),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = compare(left, right).ok_or_else(mismatch)?;
+            let result = match op {
+                BinaryOp::Lt => ordering.is_lt(),
+                BinaryOp::Le => ordering.is_le(),
+                BinaryOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            };
+            return Ok(Value::Bool(result));
+        }
+        BinaryOp::In => return contains(right, left).map(Value::Bool),
+        BinaryOp::NotIn => return contains(right, left).map(|found| Value::Bool(!found)),
+        BinaryOp::Concat => return Ok(Value::String(to_output(left) + &to_output(right))),
+        _ => {}
+    }
+
+    match (op, left, right) {
+        (BinaryOp::Add, Value::String(a), Value::String(b)) => return Ok(Value::String(format!("{}{}", a, b))),
+        (BinaryOp::Add, Value::Array(a), Value::Array(b)) => {
+            return Ok(Value::Array(a.iter().chain(b).cloned().collect()));
+        }
+        _ => {}
+    }
+
+    let (Value::Number(a), Value::Number(b)) = (left, right) else {
+        return Err(mismatch());
+    };
+
+    // Integers stay integers unless the result doesn't fit
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        let result = match op {
+            BinaryOp::Add => a.checked_add(b),
+            BinaryOp::Sub => a.checked_sub(b),
+            BinaryOp::Mul => a.checked_mul(b),
+            BinaryOp::FloorDiv | BinaryOp::Rem if b == 0 => {
+                return Err(TemplateError::RenderError("division by zero".to_string()));
+            }
+            BinaryOp::FloorDiv => a.checked_div_euclid(b),
+            // Only `i64::MIN % -1` overflows, and it divides evenly
+            BinaryOp::Rem => Some(a.checked_rem_euclid(b).unwrap_or(0)),
+            BinaryOp::Pow => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+            _ => None,
+        };
+        if let Some(result) = result {
+            return Ok(Value::from(result));
+        }
+    }
+
+    let (a, b) = (a.as_f64().unwrap_or_default(), b.as_f64().unwrap_or_default());
+    match op {
+        BinaryOp::Div | BinaryOp::FloorDiv | BinaryOp::Rem if b == 0.0 => {
+            Err(TemplateError::RenderError("division by zero".to_string()))
+        }
+        BinaryOp::Add => Ok(float(a + b)),
+        BinaryOp::Sub => Ok(float(a - b)),
+        BinaryOp::Mul => Ok(float(a * b)),
+        BinaryOp::Div => Ok(int(a / b)),
+        BinaryOp::FloorDiv => Ok(int((a / b).floor())),
+        BinaryOp::Rem => Ok(float(a.rem_euclid(b))),
+        BinaryOp::Pow => Ok(float(a.powf(b))),
+        _ => Err(mismatch()),
+    }
+}
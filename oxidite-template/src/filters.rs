@@ -1,42 +1,92 @@
+use crate::escape;
+use crate::expr;
 use crate::{TemplateError, Result};
+use oxidite_i18n::Locale;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use pulldown_cmark::{CowStr, Event, Options, Tag};
+use serde_json::Value;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
 
-/// Built-in template filters
+/// A filter, given the value before the `|` and the arguments of `name(args)`
+pub type Filter = Arc<dyn Fn(&Value, &[Value]) -> Result<Value> + Send + Sync>;
+
+/// A built-in filter that follows the locale being rendered in
+type Localized = fn(&Value, &[Value], Option<&Locale>) -> Result<Value>;
+
+/// Filters applied with `value | name(args)`
+///
+/// `safe`, `escape` and `json` depend on where the output goes, so the
+/// renderer handles them and they can't be replaced.
+#[derive(Clone)]
 pub struct Filters {
-    filters: HashMap<String, fn(&str) -> String>,
+    filters: HashMap<String, Filter>,
+    /// Built-ins that format for the locale, until they are replaced
+    localized: HashMap<String, Localized>,
 }
 
 impl Filters {
     pub fn new() -> Self {
-        let mut filters = HashMap::new();
-        
+        let mut filters = Self { filters: HashMap::new(), localized: HashMap::new() };
+
         // Register built-in filters
-        filters.insert("uppercase".to_string(), uppercase as fn(&str) -> String);
-        filters.insert("lowercase".to_string(), lowercase as fn(&str) -> String);
-        filters.insert("upper".to_string(), uppercase as fn(&str) -> String); // Alias
-        filters.insert("lower".to_string(), lowercase as fn(&str) -> String); // Alias
-        filters.insert("capitalize".to_string(), capitalize as fn(&str) -> String);
-        filters.insert("trim".to_string(), trim as fn(&str) -> String);
-        filters.insert("length".to_string(), length as fn(&str) -> String);
-        filters.insert("reverse".to_string(), reverse as fn(&str) -> String);
-        filters.insert("truncate".to_string(), truncate_default as fn(&str) -> String);
-        filters.insert("slugify".to_string(), slugify as fn(&str) -> String);
-        filters.insert("title".to_string(), title_case as fn(&str) -> String);
-        filters.insert("default".to_string(), default_value as fn(&str) -> String);
-
-        Self { filters }
-    }
-
-    pub fn apply(&self, name: &str, input: &str) -> Result<String> {
-        if let Some(filter_fn) = self.filters.get(name) {
-            Ok(filter_fn(input))
-        } else {
-            Err(TemplateError::FilterNotFound(name.to_string()))
+        filters.register("uppercase", text("uppercase", uppercase));
+        filters.register("lowercase", text("lowercase", lowercase));
+        filters.register("upper", text("upper", uppercase)); // Alias
+        filters.register("lower", text("lower", lowercase)); // Alias
+        filters.register("capitalize", text("capitalize", capitalize));
+        filters.register("trim", text("trim", trim));
+        filters.register("slugify", text("slugify", slugify));
+        filters.register("title", text("title", title_case));
+        filters.register("length", length);
+        filters.register("reverse", reverse);
+        filters.register("truncate", truncate);
+        filters.register("default", default_value);
+        filters.register("date", |input: &Value, args: &[Value]| date(input, args, None));
+        filters.register("number_format", |input: &Value, args: &[Value]| number_format(input, args, None));
+        filters.register("join", join);
+        filters.register("first", |input: &Value, _: &[Value]| end("first", input, <[Value]>::first, |s| s.chars().next()));
+        filters.register("last", |input: &Value, _: &[Value]| end("last", input, <[Value]>::last, |s| s.chars().last()));
+        filters.register("sort", sort);
+        filters.register("map", map);
+        filters.register("markdown", markdown);
+        filters.localized.insert("date".to_string(), date);
+        filters.localized.insert("number_format".to_string(), number_format);
+
+        filters
+    }
+
+    pub fn apply(&self, name: &str, input: &Value, args: &[Value]) -> Result<Value> {
+        match self.filters.get(name) {
+            Some(filter) => filter(input, args),
+            None => Err(TemplateError::FilterNotFound(name.to_string())),
         }
     }
 
-    pub fn register(&mut self, name: String, filter: fn(&str) -> String) {
-        self.filters.insert(name, filter);
+    /// Apply `name` for a page in `locale`; `date` and `number_format`
+    /// use its month names, date format and separators
+    pub fn apply_in(&self, name: &str, input: &Value, args: &[Value], locale: &Locale) -> Result<Value> {
+        match self.localized.get(name) {
+            Some(filter) => filter(input, args, Some(locale)),
+            None => self.apply(name, input, args),
+        }
+    }
+
+    /// Add or replace a filter
+    ///
+    /// Filters are closures, so they can hold state such as a locale and
+    /// return an error for input they can't handle.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        filter: impl Fn(&Value, &[Value]) -> Result<Value> + Send + Sync + 'static,
+    ) {
+        let name = name.into();
+        self.localized.remove(&name);
+        self.filters.insert(name, Arc::new(filter));
     }
 }
 
@@ -46,6 +96,32 @@ impl Default for Filters {
     }
 }
 
+/// A filter working on the value as text, without arguments
+fn text(name: &'static str, filter: fn(&str) -> String) -> impl Fn(&Value, &[Value]) -> Result<Value> {
+    move |input, args| {
+        if !args.is_empty() {
+            return Err(TemplateError::RenderError(format!("Filter {} takes no arguments", name)));
+        }
+        Ok(Value::String(filter(&expr::to_output(input))))
+    }
+}
+
+fn str_arg<'v>(args: &'v [Value], index: usize, default: &'v str) -> &'v str {
+    args.get(index).and_then(Value::as_str).unwrap_or(default)
+}
+
+fn expected(filter: &str, what: &str, input: &Value) -> TemplateError {
+    TemplateError::RenderError(format!("{} expects {}, found a {}", filter, what, expr::type_name(input)))
+}
+
+/// A list's items, or `None` for anything else
+fn list(input: &Value) -> Option<&[Value]> {
+    match input {
+        Value::Array(items) => Some(items),
+        _ => None,
+    }
+}
+
 // Built-in filter functions
 
 fn uppercase(s: &str) -> String {
@@ -68,20 +144,36 @@ fn trim(s: &str) -> String {
     s.trim().to_string()
 }
 
-fn length(s: &str) -> String {
-    s.len().to_string()
+/// Items in a list or map, characters in a string
+fn length(input: &Value, _: &[Value]) -> Result<Value> {
+    if let Some(markup) = escape::safe_str(input) {
+        return Ok(Value::from(markup.chars().count()));
+    }
+    match input {
+        Value::String(s) => Ok(Value::from(s.chars().count())),
+        Value::Array(items) => Ok(Value::from(items.len())),
+        Value::Object(map) => Ok(Value::from(map.len())),
+        Value::Null => Ok(Value::from(0)),
+        other => Err(expected("length", "a string, list or map", other)),
+    }
 }
 
-fn reverse(s: &str) -> String {
-    s.chars().rev().collect()
+fn reverse(input: &Value, _: &[Value]) -> Result<Value> {
+    match list(input) {
+        Some(items) => Ok(Value::Array(items.iter().rev().cloned().collect())),
+        None => Ok(Value::String(expr::to_output(input).chars().rev().collect())),
+    }
 }
 
-fn truncate_default(s: &str) -> String {
-    // Default truncate to 100 chars
-    if s.len() > 100 {
-        format!("{}...", &s[..97])
+fn truncate(input: &Value, args: &[Value]) -> Result<Value> {
+    // `truncate(length)`, 100 chars by default, counting the "..."
+    let s = expr::to_output(input);
+    let length = args.first().and_then(Value::as_u64).unwrap_or(100) as usize;
+    if s.chars().count() > length {
+        let kept: String = s.chars().take(length.saturating_sub(3)).collect();
+        Ok(Value::String(format!("{}...", kept)))
     } else {
-        s.to_string()
+        Ok(Value::String(s))
     }
 }
 
@@ -118,10 +210,189 @@ fn title_case(s: &str) -> String {
         .join(" ")
 }
 
-fn default_value(s: &str) -> String {
-    if s.trim().is_empty() {
-        "N/A".to_string()
+/// The argument (or "N/A") for `none`, undefined values and blank strings
+fn default_value(input: &Value, args: &[Value]) -> Result<Value> {
+    let missing = match &*expr::unmark(input) {
+        Value::Null => true,
+        Value::String(s) => s.trim().is_empty(),
+        _ => false,
+    };
+    if missing {
+        Ok(args.first().cloned().unwrap_or_else(|| Value::String("N/A".to_string())))
     } else {
-        s.to_string()
+        Ok(input.clone())
+    }
+}
+
+/// An RFC 3339 datetime, `YYYY-MM-DD HH:MM:SS` or `YYYY-MM-DD` (taken as
+/// UTC), or a Unix timestamp
+fn parse_datetime(input: &Value) -> Option<DateTime<FixedOffset>> {
+    if let Value::Number(n) = input {
+        return DateTime::from_timestamp(n.as_i64()?, 0).map(|datetime| datetime.fixed_offset());
+    }
+    let s = expr::to_output(input);
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(&s) {
+        return Some(datetime);
+    }
+    let naive = NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S%.f"))
+        .or_else(|_| NaiveDate::parse_from_str(&s, "%Y-%m-%d").map(|date| date.and_time(NaiveTime::MIN)))
+        .ok()?;
+    Some(naive.and_utc().fixed_offset())
+}
+
+/// `date(format)` with a strftime format, by default `%Y-%m-%d` or the
+/// locale's date format
+fn date(input: &Value, args: &[Value], locale: Option<&Locale>) -> Result<Value> {
+    let datetime = parse_datetime(input).ok_or_else(|| {
+        TemplateError::RenderError(format!("date expects a date or a timestamp, found {}", input))
+    })?;
+    let format = str_arg(args, 0, locale.map_or("%Y-%m-%d", Locale::date_format));
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    let invalid = || TemplateError::RenderError(format!("Invalid date format: {}", format));
+    if items.contains(&Item::Error) {
+        return Err(invalid());
+    }
+
+    let mut output = String::new();
+    match locale {
+        Some(locale) => write!(output, "{}", datetime.format_localized_with_items(items.iter(), locale.time_locale())),
+        None => write!(output, "{}", datetime.format_with_items(items.iter())),
+    }
+    .map_err(|_| invalid())?;
+    Ok(Value::String(output))
+}
+
+/// `number_format(decimals, point, separator)`, by default `1,234` or with
+/// the locale's separators
+fn number_format(input: &Value, args: &[Value], locale: Option<&Locale>) -> Result<Value> {
+    let number = match &*expr::unmark(input) {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| expected("number_format", "a number", input))?;
+    let decimals = args.first().and_then(Value::as_u64).unwrap_or(0) as usize;
+    let (point, separator) = match locale {
+        Some(locale) => (locale.decimal_point(), locale.thousands_separator()),
+        None => (".", ","),
+    };
+    let (point, separator) = (str_arg(args, 1, point), str_arg(args, 2, separator));
+
+    let digits = format!("{:.*}", decimals, number.abs());
+    let (whole, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+    let mut output = String::new();
+    // No sign when the number rounds to zero
+    if number < 0.0 && digits.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        output.push('-');
+    }
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            output.push_str(separator);
+        }
+        output.push(digit);
+    }
+    if !fraction.is_empty() {
+        output.push_str(point);
+        output.push_str(fraction);
+    }
+    Ok(Value::String(output))
+}
+
+/// `join(separator)`, the items of a list as text
+fn join(input: &Value, args: &[Value]) -> Result<Value> {
+    let input = &*expr::unmark(input);
+    let items = list(input).ok_or_else(|| expected("join", "a list", input))?;
+    let separator = str_arg(args, 0, "");
+    let parts: Vec<String> = items.iter().map(expr::to_output).collect();
+    Ok(Value::String(parts.join(separator)))
+}
+
+/// `first` or `last`: an item of a list or a character of a string
+fn end(
+    name: &str,
+    input: &Value,
+    item: fn(&[Value]) -> Option<&Value>,
+    char: fn(&str) -> Option<char>,
+) -> Result<Value> {
+    match &*expr::unmark(input) {
+        Value::Array(items) => Ok(item(items).cloned().unwrap_or(Value::Null)),
+        Value::String(s) => Ok(char(s).map(|c| Value::String(c.to_string())).unwrap_or(Value::Null)),
+        other => Err(expected(name, "a list or a string", other)),
+    }
+}
+
+/// `item[key]`, or the item itself without a key
+fn field<'v>(item: &'v Value, key: Option<&Value>) -> &'v Value {
+    match key {
+        Some(key) => expr::get_item(item, key).unwrap_or(&Value::Null),
+        None => item,
+    }
+}
+
+/// `sort`, or `sort("field")` for a list of maps
+fn sort(input: &Value, args: &[Value]) -> Result<Value> {
+    let input = &*expr::unmark(input);
+    let mut items = list(input).ok_or_else(|| expected("sort", "a list", input))?.to_vec();
+    let key = args.first();
+    let mut mismatch = None;
+    items.sort_by(|a, b| {
+        let (a, b) = (field(a, key), field(b, key));
+        expr::compare(a, b).unwrap_or_else(|| {
+            mismatch.get_or_insert((expr::type_name(a), expr::type_name(b)));
+            Ordering::Equal
+        })
+    });
+    match mismatch {
+        Some((a, b)) => Err(TemplateError::RenderError(format!("sort cannot compare a {} and a {}", a, b))),
+        None => Ok(Value::Array(items)),
+    }
+}
+
+/// `map("field")`, that field of every item
+fn map(input: &Value, args: &[Value]) -> Result<Value> {
+    let input = &*expr::unmark(input);
+    let items = list(input).ok_or_else(|| expected("map", "a list", input))?;
+    let key = args
+        .first()
+        .ok_or_else(|| TemplateError::RenderError("map expects the name of a field".to_string()))?;
+    Ok(Value::Array(items.iter().map(|item| field(item, Some(key)).clone()).collect()))
+}
+
+/// Markdown as HTML, which is output without escaping
+///
+/// HTML in the source is escaped and `javascript:` style links are dropped,
+/// so user-written markdown can't inject markup.
+fn markdown(input: &Value, _: &[Value]) -> Result<Value> {
+    let source = expr::to_output(input);
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let events = pulldown_cmark::Parser::new_ext(&source, options).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link { link_type, dest_url, title, id }) => Event::Start(Tag::Link {
+            link_type,
+            dest_url: safe_url(dest_url),
+            title,
+            id,
+        }),
+        Event::Start(Tag::Image { link_type, dest_url, title, id }) => Event::Start(Tag::Image {
+            link_type,
+            dest_url: safe_url(dest_url),
+            title,
+            id,
+        }),
+        event => event,
+    });
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    Ok(escape::mark_safe(html))
+}
+
+/// `url` if it is relative or uses a scheme that can't run script
+fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+    let scheme = url.split_once(':').map(|(scheme, _)| scheme).filter(|scheme| !scheme.contains(['/', '?', '#']));
+    match scheme.map(str::to_ascii_lowercase).as_deref() {
+        None | Some("http" | "https" | "mailto") => url,
+        Some(_) => CowStr::Borrowed("#"),
     }
 }
@@ -0,0 +1,196 @@
+use crate::expr;
+use crate::{Result, TemplateError};
+use chrono::{SecondsFormat, Utc};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// A function taking its evaluated arguments
+pub type Function = Arc<dyn Fn(&[Value]) -> Result<Value> + Send + Sync>;
+
+/// Functions callable from template expressions, such as `range(1, 10)`
+#[derive(Clone)]
+pub struct Functions {
+    functions: HashMap<String, Function>,
+}
+
+impl Functions {
+    pub fn new() -> Self {
+        let mut functions = Self { functions: HashMap::new() };
+
+        // Register built-in functions
+        functions.register("range", range);
+        functions.register("len", len);
+        functions.register("min", min);
+        functions.register("max", max);
+        functions.register("now", now);
+        functions.register("asset", asset("/static"));
+        functions.register("url_for", url_for(HashMap::new()));
+
+        functions
+    }
+
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Value> {
+        if let Some(function) = self.functions.get(name) {
+            function(args)
+        } else {
+            Err(TemplateError::RenderError(format!("Function not found: {}", name)))
+        }
+    }
+
+    /// Add or replace a function
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        function: impl Fn(&[Value]) -> Result<Value> + Send + Sync + 'static,
+    ) {
+        self.functions.insert(name.into(), Arc::new(function));
+    }
+}
+
+impl Default for Functions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn int_arg(name: &str, args: &[Value], index: usize) -> Result<i64> {
+    args.get(index)
+        .and_then(Value::as_i64)
+        .ok_or_else(|| TemplateError::RenderError(format!("{}() expects an integer as argument {}", name, index + 1)))
+}
+
+// Built-in functions
+
+/// Most values `range()` will produce, so a huge bound can't exhaust memory
+const MAX_RANGE_LEN: usize = 100_000;
+
+/// `range(end)`, `range(start, end)` or `range(start, end, step)`, end exclusive
+fn range(args: &[Value]) -> Result<Value> {
+    let (start, end, step) = match args.len() {
+        1 => (0, int_arg("range", args, 0)?, 1),
+        2 => (int_arg("range", args, 0)?, int_arg("range", args, 1)?, 1),
+        3 => (int_arg("range", args, 0)?, int_arg("range", args, 1)?, int_arg("range", args, 2)?),
+        _ => return Err(TemplateError::RenderError("range() takes 1 to 3 arguments".to_string())),
+    };
+    if step == 0 {
+        return Err(TemplateError::RenderError("range() step must not be zero".to_string()));
+    }
+
+    // Count in i128 so neither the span nor the last step can overflow
+    let (start, end, step) = (start as i128, end as i128, step as i128);
+    let count = if step > 0 { (end - start + step - 1) / step } else { (start - end - step - 1) / -step }.max(0);
+    if count > MAX_RANGE_LEN as i128 {
+        return Err(TemplateError::RenderError(format!(
+            "range() would produce {} values; the limit is {}",
+            count, MAX_RANGE_LEN
+        )));
+    }
+    Ok(Value::Array((0..count).map(|i| Value::from((start + i * step) as i64)).collect()))
+}
+
+fn len(args: &[Value]) -> Result<Value> {
+    match args {
+        [Value::String(s)] => Ok(Value::from(s.chars().count())),
+        [Value::Array(items)] => Ok(Value::from(items.len())),
+        [Value::Object(map)] => Ok(Value::from(map.len())),
+        _ => Err(TemplateError::RenderError("len() expects a string, list or map".to_string())),
+    }
+}
+
+fn extreme(name: &str, args: &[Value], keep: fn(f64, f64) -> bool) -> Result<Value> {
+    // A single list argument means the items of that list
+    let items = match args {
+        [Value::Array(items)] => items.as_slice(),
+        items => items,
+    };
+    let mut best: Option<&Value> = None;
+    for item in items {
+        let number = item
+            .as_f64()
+            .ok_or_else(|| TemplateError::RenderError(format!("{}() expects numbers", name)))?;
+        if best.and_then(Value::as_f64).is_none_or(|current| keep(number, current)) {
+            best = Some(item);
+        }
+    }
+    Ok(best.cloned().unwrap_or(Value::Null))
+}
+
+fn min(args: &[Value]) -> Result<Value> {
+    extreme("min", args, |number, current| number < current)
+}
+
+fn max(args: &[Value]) -> Result<Value> {
+    extreme("max", args, |number, current| number > current)
+}
+
+/// `now()`, the current UTC time in RFC 3339 form, for the `date` filter
+fn now(_: &[Value]) -> Result<Value> {
+    Ok(Value::String(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)))
+}
+
+/// `asset(path)`, the URL of a static file under `base_url`
+pub(crate) fn asset(base_url: &str) -> impl Fn(&[Value]) -> Result<Value> {
+    let base_url = base_url.trim_end_matches('/').to_string();
+    move |args| match args {
+        [Value::String(path)] => Ok(Value::String(format!("{}/{}", base_url, path.trim_start_matches('/')))),
+        _ => Err(TemplateError::RenderError("asset() expects a path".to_string())),
+    }
+}
+
+/// `url_for(name, params)`, the path of a named route such as `/posts/:id`
+///
+/// Parameters that aren't in the path go in the query string.
+pub(crate) fn url_for(routes: HashMap<String, String>) -> impl Fn(&[Value]) -> Result<Value> {
+    move |args| {
+        let (name, params) = match args {
+            [Value::String(name)] => (name, None),
+            [Value::String(name), Value::Object(params)] => (name, Some(params)),
+            _ => return Err(TemplateError::RenderError("url_for() expects a route name and a map of parameters".to_string())),
+        };
+        let path = routes
+            .get(name)
+            .ok_or_else(|| TemplateError::RenderError(format!("url_for(): no route named {}", name)))?;
+
+        let mut used = HashSet::new();
+        let mut segments = Vec::new();
+        for segment in path.split('/') {
+            match segment.strip_prefix(':') {
+                Some(param) => {
+                    let value = params.and_then(|params| params.get(param)).ok_or_else(|| {
+                        TemplateError::RenderError(format!("url_for(\"{}\") needs a value for {}", name, param))
+                    })?;
+                    used.insert(param);
+                    segments.push(percent_encode(&expr::to_output(value)));
+                }
+                None => segments.push(segment.to_string()),
+            }
+        }
+
+        let mut url = segments.join("/");
+        let query: Vec<String> = params
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| !used.contains(key.as_str()))
+            .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(&expr::to_output(value))))
+            .collect();
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(&query.join("&"));
+        }
+        Ok(Value::String(url))
+    }
+}
+
+/// Everything but unreserved URL characters as `%XX`
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
@@ -0,0 +1,75 @@
+use crate::{Result, TemplateError};
+use serde_json::Value;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use tokio::runtime::Handle;
+
+type Load = Pin<Box<dyn Future<Output = std::result::Result<Value, String>> + Send>>;
+
+/// A context value loaded the first time a template reads it
+///
+/// Clones of a context share it, so the value is loaded at most once.
+pub(crate) struct Lazy {
+    load: Mutex<Option<Load>>,
+    /// The value, or why loading it failed
+    value: OnceLock<std::result::Result<Value, String>>,
+}
+
+impl Lazy {
+    pub(crate) fn new<T, E>(load: impl Future<Output = std::result::Result<T, E>> + Send + 'static) -> Self
+    where
+        T: serde::Serialize,
+        E: fmt::Display,
+    {
+        let load = async move {
+            let value = load.await.map_err(|e| e.to_string())?;
+            crate::escape::to_context_value(value).map_err(|e| e.to_string())
+        };
+        Self {
+            load: Mutex::new(Some(Box::pin(load))),
+            value: OnceLock::new(),
+        }
+    }
+
+    /// The loaded value; loads it by blocking on `runtime` the first time
+    pub(crate) fn get(&self, name: &str, runtime: Option<&Handle>) -> Result<&Value> {
+        let value = match self.value.get() {
+            Some(value) => value,
+            None => {
+                // Holding the lock while loading makes other renders wait for this load
+                let mut load = self.load.lock().unwrap_or_else(PoisonError::into_inner);
+                match (self.value.get(), runtime) {
+                    (Some(value), _) => value,
+                    (None, Some(runtime)) => {
+                        let value = match load.take() {
+                            Some(future) => runtime.block_on(future),
+                            None => Err("a previous load was interrupted".to_string()),
+                        };
+                        self.value.get_or_init(|| value)
+                    }
+                    (None, None) => {
+                        return Err(TemplateError::RenderError(format!(
+                            "`{}` loads asynchronously; render it with render_async() or render_stream()",
+                            name
+                        )));
+                    }
+                }
+            }
+        };
+        value
+            .as_ref()
+            .map_err(|e| TemplateError::RenderError(format!("Failed to load `{}`: {}", name, e)))
+    }
+}
+
+impl fmt::Debug for Lazy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value.get() {
+            Some(Ok(value)) => f.debug_tuple("Lazy").field(value).finish(),
+            Some(Err(e)) => f.debug_tuple("Lazy").field(&format_args!("failed: {}", e)).finish(),
+            None => f.write_str("Lazy(<not loaded>)"),
+        }
+    }
+}
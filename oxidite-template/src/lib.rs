@@ -1,37 +1,87 @@
+use lazy::Lazy;
+use oxidite_i18n::I18n;
+use stream::IoWriter;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::io;
 use std::path::Path;
 use std::fs;
+use std::sync::Arc;
 
-pub mod parser;
+pub mod escape;
+pub mod expr;
+pub mod functions;
+mod lazy;
+pub mod reload;
 pub mod renderer;
+mod scope;
+pub mod stream;
 pub mod filters;
 pub mod static_files;
 
-pub use parser::{Parser, TemplateNode};
+pub use oxidite_template_syntax::{lexer, parser};
+pub use parser::{Macro, Parser, SyntaxError, TemplateNode};
+pub use reload::Templates;
 pub use renderer::Renderer;
-pub use filters::Filters;
+pub use filters::{Filter, Filters};
+pub use functions::{Function, Functions};
+pub use expr::Expr;
+pub use escape::{Escape, SafeString};
 pub use static_files::{StaticFiles, serve_static};
+pub use stream::RenderStream;
+pub use oxidite_core::{IntoResponse, OxiditeResponse};
+pub use oxidite_macros::Template;
 
 /// Template context for variable interpolation
 #[derive(Debug, Clone)]
 pub struct Context {
     data: HashMap<String, Value>,
+    /// Values loaded when a template first reads them
+    lazy: HashMap<String, Arc<Lazy>>,
 }
 
 impl Context {
     pub fn new() -> Self {
         Self {
             data: HashMap::new(),
+            lazy: HashMap::new(),
         }
     }
 
     pub fn set<T: serde::Serialize>(&mut self, key: impl Into<String>, value: T) {
-        if let Ok(json_value) = serde_json::to_value(value) {
-            self.data.insert(key.into(), json_value);
+        if let Ok(json_value) = escape::to_context_value(value) {
+            let key = key.into();
+            self.lazy.remove(&key);
+            self.data.insert(key, json_value);
         }
     }
 
+    /// Set `key` to the result of `load`, which only runs if a template reads `key`
+    ///
+    /// Lazy values are loaded while rendering with [`Templates::render_async`]
+    /// or [`Templates::render_stream`]; other renders fail on one. A failed
+    /// load is a render error.
+    ///
+    /// ```ignore
+    /// let db = db.clone();
+    /// context.set_lazy("orders", async move { Order::recent(&db, 500).await });
+    /// let page = templates.render_stream("reports/orders.html", context);
+    /// ```
+    pub fn set_lazy<T, E>(
+        &mut self,
+        key: impl Into<String>,
+        load: impl Future<Output = std::result::Result<T, E>> + Send + 'static,
+    ) where
+        T: serde::Serialize,
+        E: fmt::Display,
+    {
+        let key = key.into();
+        self.data.remove(&key);
+        self.lazy.insert(key, Arc::new(Lazy::new(load)));
+    }
+
     pub fn get(&self, key: &str) -> Option<&Value> {
         // Support dotted notation: user.name
         let parts: Vec<&str> = key.split('.').collect();
@@ -44,6 +94,10 @@ impl Context {
         Some(current)
     }
 
+    pub(crate) fn lazy(&self, key: &str) -> Option<&Lazy> {
+        self.lazy.get(key).map(|lazy| &**lazy)
+    }
+
     pub fn from_json(json: Value) -> Self {
         let mut context = Self::new();
         if let Value::Object(map) = json {
@@ -62,20 +116,87 @@ impl Default for Context {
 }
 
 /// Template engine to manage multiple templates
+#[derive(Clone)]
 pub struct TemplateEngine {
     templates: HashMap<String, Template>,
+    filters: Filters,
+    functions: Functions,
+    /// Named routes for `url_for()`
+    routes: HashMap<String, String>,
+    /// Catalogs for `{% trans %}` and `t()`
+    i18n: Option<I18n>,
 }
 
 impl TemplateEngine {
     pub fn new() -> Self {
         Self {
             templates: HashMap::new(),
+            filters: Filters::new(),
+            functions: Functions::new(),
+            routes: HashMap::new(),
+            i18n: None,
         }
     }
 
+    /// Add or replace a filter for every template rendered by this engine
+    ///
+    /// ```
+    /// use oxidite_template::{Context, TemplateEngine};
+    /// use serde_json::Value;
+    ///
+    /// let mut engine = TemplateEngine::new();
+    /// let currency = "KES".to_string();
+    /// engine.register_filter("price", move |value: &Value, _: &[Value]| {
+    ///     Ok(Value::String(format!("{} {}", currency, value)))
+    /// });
+    /// engine.add_template("cart.html", "{{ total | price }}").unwrap();
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.set("total", 1200);
+    /// assert_eq!(engine.render("cart.html", &ctx).unwrap(), "KES 1200");
+    /// ```
+    pub fn register_filter(
+        &mut self,
+        name: impl Into<String>,
+        filter: impl Fn(&Value, &[Value]) -> Result<Value> + Send + Sync + 'static,
+    ) {
+        self.filters.register(name, filter);
+    }
+
+    /// Add or replace a function callable from every template
+    pub fn register_function(
+        &mut self,
+        name: impl Into<String>,
+        function: impl Fn(&[Value]) -> Result<Value> + Send + Sync + 'static,
+    ) {
+        self.functions.register(name, function);
+    }
+
+    /// Name a route for `url_for()`, with parameters written as in the router: `/posts/:id`
+    pub fn add_route(&mut self, name: impl Into<String>, path: impl Into<String>) {
+        self.routes.insert(name.into(), path.into());
+        self.functions.register("url_for", functions::url_for(self.routes.clone()));
+    }
+
+    /// Where `asset()` points, `/static` by default
+    pub fn set_asset_url(&mut self, base_url: &str) {
+        self.functions.register("asset", functions::asset(base_url));
+    }
+
+    /// Translate `{% trans %}` and `t()` with these catalogs
+    ///
+    /// Templates render in the locale named by `locale` in the context,
+    /// usually the request's [`Locale`](oxidite_i18n::Locale), or else the
+    /// catalogs' default. The `date` and `number_format` filters follow the
+    /// same locale.
+    pub fn set_i18n(&mut self, i18n: I18n) {
+        self.i18n = Some(i18n);
+    }
+
     pub fn add_template(&mut self, name: impl Into<String>, source: impl Into<String>) -> Result<()> {
-        let template = Template::new(source)?;
-        self.templates.insert(name.into(), template);
+        let name = name.into();
+        let template = Template::named(name.clone(), source)?;
+        self.templates.insert(name, template);
         Ok(())
     }
 
@@ -83,12 +204,141 @@ impl TemplateEngine {
         self.templates.get(name)
     }
 
+    pub fn remove_template(&mut self, name: &str) -> Option<Template> {
+        self.templates.remove(name)
+    }
+
+    /// Check that every template's parent, includes and imports exist and
+    /// that no template ends up extending, including or importing itself
+    ///
+    /// Run it once all templates are added, so a typo fails at startup
+    /// rather than when the page is first rendered.
+    pub fn validate(&self) -> Result<()> {
+        let mut names: Vec<&String> = self.templates.keys().collect();
+        names.sort();
+
+        let mut problems = Vec::new();
+        for &name in &names {
+            let template = &self.templates[name];
+            for (tag, reference) in template.references() {
+                if !self.templates.contains_key(reference) {
+                    problems.push(format!("{} {} missing template {}", name, tag, reference));
+                }
+            }
+
+            let mut chain = vec![name.as_str()];
+            let mut current = template;
+            while let Some(parent) = current.parent() {
+                let seen = chain.contains(&parent);
+                chain.push(parent);
+                if parent == name {
+                    problems.push(format!("{} extends itself: {}", name, chain.join(" -> ")));
+                    break;
+                }
+                // A cycle further up is reported for the templates in it
+                if seen {
+                    break;
+                }
+                match self.templates.get(parent) {
+                    Some(next) => current = next,
+                    None => break,
+                }
+            }
+        }
+
+        // Includes and imports that lead back to the template
+        for name in names {
+            if let Some((tag, chain)) = self.reference_cycle(name) {
+                problems.push(format!("{} {} itself: {}", name, tag, chain.join(" -> ")));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(TemplateError::Invalid(problems))
+        }
+    }
+
+    /// The shortest chain of references from `name` back to itself with an
+    /// include or import in it, and the first such tag; `extends`-only cycles
+    /// are reported separately
+    fn reference_cycle<'t>(&'t self, name: &'t str) -> Option<(&'static str, Vec<&'t str>)> {
+        let mut seen: HashSet<(&str, bool)> = HashSet::new();
+        let mut pending = VecDeque::from([(name, None, vec![name])]);
+        while let Some((current, tag, chain)) = pending.pop_front() {
+            let Some(template) = self.templates.get(current) else {
+                continue;
+            };
+            for (reference_tag, reference) in template.references() {
+                let tag = tag.or((reference_tag != "extends").then_some(reference_tag));
+                let mut chain = chain.clone();
+                chain.push(reference);
+                if reference == name {
+                    if let Some(tag) = tag {
+                        return Some((tag, chain));
+                    }
+                } else if seen.insert((reference, tag.is_some())) {
+                    pending.push_back((reference, tag, chain));
+                }
+            }
+        }
+        None
+    }
+
+    /// Templates that extend, include or import `name`, directly or through
+    /// other templates
+    pub fn dependents(&self, name: &str) -> Vec<String> {
+        let mut found: HashSet<&str> = HashSet::new();
+        let mut pending = vec![name];
+        while let Some(target) = pending.pop() {
+            for (candidate, template) in &self.templates {
+                let uses_target = template.references().iter().any(|(_, reference)| *reference == target);
+                if uses_target && candidate != name && found.insert(candidate) {
+                    pending.push(candidate);
+                }
+            }
+        }
+
+        let mut dependents: Vec<String> = found.into_iter().map(String::from).collect();
+        dependents.sort();
+        dependents
+    }
+
     pub fn render(&self, name: &str, context: &Context) -> Result<String> {
+        let mut output = String::new();
+        self.render_to(name, context, &mut output)?;
+        Ok(output)
+    }
+
+    /// Render into `out` as the page renders, rather than into one string
+    pub fn render_to(&self, name: &str, context: &Context, out: &mut impl fmt::Write) -> Result<()> {
+        self.render_with(name, context, out, None)
+    }
+
+    /// Render into `writer`, such as a file; wrap unbuffered writers in a `BufWriter`
+    pub fn render_to_writer(&self, name: &str, context: &Context, writer: impl io::Write) -> Result<()> {
+        let mut writer = IoWriter::new(writer);
+        let result = self.render_to(name, context, &mut writer);
+        writer.finish(result)
+    }
+
+    /// Render with lazy context values loaded on `runtime`, if given
+    pub(crate) fn render_with(
+        &self,
+        name: &str,
+        context: &Context,
+        out: &mut dyn fmt::Write,
+        runtime: Option<tokio::runtime::Handle>,
+    ) -> Result<()> {
         let template = self.get_template(name)
             .ok_or_else(|| TemplateError::RenderError(format!("Template not found: {}", name)))?;
-        
+
         let mut renderer = Renderer::new(context, Some(self));
-        renderer.render(template)
+        if let Some(runtime) = runtime {
+            renderer = renderer.runtime(runtime);
+        }
+        renderer.render_to(template, out)
     }
     
     /// Load all templates from a directory (recursive)
@@ -114,23 +364,13 @@ impl TemplateEngine {
             if path.is_dir() {
                 // Recursively load templates from subdirectories
                 self.load_dir_recursive(base_dir, &path, count)?;
-            } else if path.is_file() {
-                if let Some(ext) = path.extension() {
-                    if ext == "html" || ext == "htm" {
-                        let content = fs::read_to_string(&path)
-                            .map_err(|e| TemplateError::RenderError(format!("Failed to read file: {}", e)))?;
-                        
-                        // Get relative path from base_dir to preserve directory structure
-                        let relative_path = path.strip_prefix(base_dir)
-                            .map_err(|e| TemplateError::RenderError(e.to_string()))?;
-                        
-                        let name = relative_path.to_str()
-                            .ok_or_else(|| TemplateError::RenderError("Invalid filename".to_string()))?;
-                        
-                        self.add_template(name, content)?;
-                        *count += 1;
-                    }
-                }
+            } else if path.is_file() && is_template_file(&path) {
+                let content = fs::read_to_string(&path)
+                    .map_err(|e| TemplateError::RenderError(format!("Failed to read file: {}", e)))?;
+
+                let name = template_name(base_dir, &path)?;
+                self.add_template(name, content)?;
+                *count += 1;
             }
         }
         
@@ -138,6 +378,23 @@ impl TemplateEngine {
     }
 }
 
+/// Whether `load_dir` picks up the file at `path`
+pub(crate) fn is_template_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ["html", "htm", "xml", "txt", "js"].iter().any(|known| ext == *known))
+}
+
+/// The name of the template at `path`: its path relative to `base_dir`,
+/// which keeps the directory structure
+pub(crate) fn template_name(base_dir: &Path, path: &Path) -> Result<String> {
+    let relative_path = path.strip_prefix(base_dir)
+        .map_err(|e| TemplateError::RenderError(e.to_string()))?;
+
+    relative_path.to_str()
+        .map(String::from)
+        .ok_or_else(|| TemplateError::RenderError("Invalid filename".to_string()))
+}
+
 impl Default for TemplateEngine {
     fn default() -> Self {
         Self::new()
@@ -147,8 +404,10 @@ impl Default for TemplateEngine {
 /// Template
 #[derive(Debug, Clone)]
 pub struct Template {
+    name: Option<String>,
     source: String,
     parsed: Vec<TemplateNode>,
+    escape: Escape,
 }
 
 impl Template {
@@ -157,20 +416,67 @@ impl Template {
         let parser = Parser::new(&source);
         let parsed = parser.parse()?;
 
-        Ok(Self { source, parsed })
+        Ok(Self { name: None, source, parsed, escape: Escape::Html })
+    }
+
+    /// Parse a template whose syntax errors are reported against `name`
+    pub fn named(name: impl Into<String>, source: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        let mut template = Self::new(source).map_err(|err| match err {
+            TemplateError::ParseError(err) => TemplateError::ParseError(err.in_template(&name)),
+            err => err,
+        })?;
+        template.escape = Escape::for_template(&name);
+        template.name = Some(name);
+        Ok(template)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// How output is escaped, from the extension of the template's name
+    pub fn escape(&self) -> Escape {
+        self.escape
     }
 
     pub fn render(&self, context: &Context) -> Result<String> {
         let mut renderer = Renderer::new(context, None);
         renderer.render(self)
     }
+
+    /// Render into `out` as the template renders, rather than into one string
+    pub fn render_to(&self, context: &Context, out: &mut impl fmt::Write) -> Result<()> {
+        let mut renderer = Renderer::new(context, None);
+        renderer.render_to(self, out)
+    }
+
+    /// Render into `writer`; wrap unbuffered writers in a `BufWriter`
+    pub fn render_to_writer(&self, context: &Context, writer: impl io::Write) -> Result<()> {
+        let mut writer = IoWriter::new(writer);
+        let result = self.render_to(context, &mut writer);
+        writer.finish(result)
+    }
+
+    /// The template named by a top-level `{% extends %}`
+    fn parent(&self) -> Option<&str> {
+        self.parsed.iter().find_map(|node| match node {
+            TemplateNode::Extends(parent) => Some(parent.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Templates this one extends, includes or imports, each with the verb for error messages
+    fn references(&self) -> Vec<(&'static str, &str)> {
+        parser::references(&self.parsed)
+    }
 }
 
 /// Template errors
 #[derive(Debug, thiserror::Error)]
 pub enum TemplateError {
     #[error("Parse error: {0}")]
-    ParseError(String),
+    ParseError(#[from] SyntaxError),
 
     #[error("Render error: {0}")]
     RenderError(String),
@@ -180,10 +486,35 @@ pub enum TemplateError {
 
     #[error("Filter not found: {0}")]
     FilterNotFound(String),
+
+    /// Problems found by [`TemplateEngine::validate`]
+    #[error("Invalid templates: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+
+    /// The output couldn't be written, e.g. the client went away
+    #[error("Write error: {0}")]
+    WriteError(#[from] io::Error),
+}
+
+/// A `fmt::Write` sink that refused output
+impl From<fmt::Error> for TemplateError {
+    fn from(_: fmt::Error) -> Self {
+        TemplateError::WriteError(io::Error::other("the output refused a write"))
+    }
 }
 
 pub type Result<T> = std::result::Result<T, TemplateError>;
 
+/// A rendered page as an HTML response; a render error becomes a 500
+///
+/// Used by the `IntoResponse` impl of `#[derive(Template)]`.
+pub fn html_response(rendered: Result<String>) -> OxiditeResponse {
+    match rendered {
+        Ok(html) => oxidite_core::response::html(html),
+        Err(err) => oxidite_core::Error::Server(err.to_string()).into_response(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,157 @@
+use crate::{is_template_file, template_name, Context, RenderStream, Result, SyntaxError, TemplateEngine, TemplateError};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+use tokio::runtime::Handle;
+
+/// A [`TemplateEngine`] loaded from a directory, shared between handlers
+///
+/// [`Templates::load`] is for production: every template is parsed and
+/// validated once at startup. [`Templates::watch`] is for development: the
+/// directory is watched, and a changed file is parsed again along with every
+/// template that extends, includes or imports it, so the next render shows
+/// the change without restarting the server.
+#[derive(Clone)]
+pub struct Templates {
+    state: Arc<RwLock<State>>,
+    /// Watching stops when the last clone is dropped
+    _watcher: Option<Arc<RecommendedWatcher>>,
+}
+
+struct State {
+    /// Renders take a snapshot, so a reload never waits for a slow render;
+    /// one still in flight keeps the engine it started with
+    engine: Arc<TemplateEngine>,
+    dir: PathBuf,
+    /// Files whose latest version doesn't parse; the last good version stays loaded
+    broken: HashMap<String, SyntaxError>,
+}
+
+impl Templates {
+    /// Load every template in `dir` into `engine` and check that each parent,
+    /// include and import exists
+    pub fn load(mut engine: TemplateEngine, dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        engine.load_dir(&dir)?;
+        engine.validate()?;
+        Ok(Self {
+            state: Arc::new(RwLock::new(State { engine: Arc::new(engine), dir, broken: HashMap::new() })),
+            _watcher: None,
+        })
+    }
+
+    /// Load every template in `dir` into `engine` and reload them as files change
+    pub fn watch(mut engine: TemplateEngine, dir: impl AsRef<Path>) -> Result<Self> {
+        // Events name files by their full path, which must start with `dir`
+        let dir = dir.as_ref().canonicalize()
+            .map_err(|e| TemplateError::RenderError(format!("Failed to read directory: {}", e)))?;
+        engine.load_dir(&dir)?;
+        let state = Arc::new(RwLock::new(State { engine: Arc::new(engine), dir: dir.clone(), broken: HashMap::new() }));
+
+        let watched = Arc::clone(&state);
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                if !matches!(event.kind, EventKind::Access(_)) {
+                    watched.write().unwrap_or_else(PoisonError::into_inner).reload(&event.paths);
+                }
+            }
+        })
+        .map_err(watch_error)?;
+        watcher.watch(&dir, RecursiveMode::Recursive).map_err(watch_error)?;
+
+        Ok(Self { state, _watcher: Some(Arc::new(watcher)) })
+    }
+
+    pub fn render(&self, name: &str, context: &Context) -> Result<String> {
+        let mut output = String::new();
+        self.render_with(name, context, &mut output, None)?;
+        Ok(output)
+    }
+
+    /// Render on a blocking thread, loading lazy context values as the
+    /// template reads them
+    pub async fn render_async(&self, name: &str, context: Context) -> Result<String> {
+        let templates = self.clone();
+        let template = name.to_string();
+        let runtime = Handle::current();
+        tokio::task::spawn_blocking(move || {
+            let mut output = String::new();
+            templates.render_with(&template, &context, &mut output, Some(runtime))?;
+            Ok(output)
+        })
+        .await
+        .map_err(|e| TemplateError::RenderError(format!("Rendering {} failed: {}", name, e)))?
+    }
+
+    /// Render on a blocking thread and stream the page a chunk at a time,
+    /// loading lazy context values as the template reads them
+    ///
+    /// The browser gets the top of a long page while the rest still renders.
+    /// Must be called within a Tokio runtime.
+    pub fn render_stream(&self, name: &str, context: Context) -> RenderStream {
+        let templates = self.clone();
+        let name = name.to_string();
+        RenderStream::spawn(move |out, runtime| templates.render_with(&name, &context, out, Some(runtime)))
+    }
+
+    fn render_with(&self, name: &str, context: &Context, out: &mut dyn fmt::Write, runtime: Option<Handle>) -> Result<()> {
+        let engine = {
+            let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+
+            // A file that stopped parsing shows its error on every page using it
+            for (file, error) in &state.broken {
+                if file == name || state.engine.dependents(file).iter().any(|dependent| dependent == name) {
+                    return Err(TemplateError::ParseError(error.clone()));
+                }
+            }
+            Arc::clone(&state.engine)
+        };
+
+        // Streaming may block on the client, so render without holding the lock
+        engine.render_with(name, context, out, runtime)
+    }
+}
+
+impl State {
+    /// Parse the templates at `paths` again, and every template using them
+    fn reload(&mut self, paths: &[PathBuf]) {
+        let changed: BTreeSet<String> = paths
+            .iter()
+            .filter(|path| is_template_file(path))
+            .filter_map(|path| template_name(&self.dir, path).ok())
+            .collect();
+        let dependents: BTreeSet<String> = changed.iter().flat_map(|name| self.engine.dependents(name)).collect();
+        // Copies the engine only while a render still holds the old one
+        let engine = Arc::make_mut(&mut self.engine);
+
+        for name in changed.iter().chain(&dependents) {
+            let path = self.dir.join(name);
+            match fs::read_to_string(&path) {
+                Ok(source) => match engine.add_template(name.as_str(), source) {
+                    Ok(()) => {
+                        self.broken.remove(name);
+                    }
+                    Err(TemplateError::ParseError(err)) => {
+                        self.broken.insert(name.clone(), err);
+                    }
+                    Err(_) => {}
+                },
+                // Dependents may have been added in code rather than loaded
+                // from the directory, so only the changed files can be gone
+                Err(_) if changed.contains(name) && !path.exists() => {
+                    engine.remove_template(name);
+                    self.broken.remove(name);
+                }
+                // Still being written; the next event reads it again
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+fn watch_error(err: notify::Error) -> TemplateError {
+    TemplateError::RenderError(format!("Failed to watch templates: {}", err))
+}
@@ -1,203 +1,704 @@
-use crate::{Context, TemplateNode, TemplateError, Result, filters::Filters, TemplateEngine, Template};
-use serde_json::Value;
+use crate::{Context, TemplateNode, TemplateError, Result, filters::Filters, functions::Functions, TemplateEngine, Template};
+use crate::escape::{self, Escape};
+use crate::expr::{self, BinaryOp, Expr, UnaryOp};
+use crate::parser::Macro;
+use crate::scope::Scopes;
+use oxidite_i18n::Locale;
+use serde_json::{json, Map, Value};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, OnceLock};
+use tokio::runtime::Handle;
+
+/// Bodies of a block from the most derived template to the base one
+type BlockLevels = Vec<Arc<Vec<TemplateNode>>>;
+
+/// How deep includes, macro calls, `super()` and `extends` may nest, so a
+/// cycle ends in an error instead of a stack overflow
+pub const MAX_DEPTH: usize = 32;
 
 /// Template renderer
 pub struct Renderer<'a> {
     context: &'a Context,
-    filters: Filters,
+    scopes: Scopes,
+    filters: &'a Filters,
+    functions: &'a Functions,
     engine: Option<&'a TemplateEngine>,
-    blocks: HashMap<String, Vec<TemplateNode>>,
+    blocks: HashMap<String, BlockLevels>,
+    /// The overridden block being rendered and its level, for `super()`
+    current_block: Option<(BlockLevels, usize)>,
+    macros: HashMap<String, Arc<Macro>>,
+    /// Templates imported with `{% import "name" as alias %}`, by alias
+    namespaces: HashMap<String, &'a Template>,
+    /// Rendered slot content when rendering a component
+    slots: HashMap<String, String>,
+    escape: Escape,
+    /// Runtime that lazy context values are loaded on, see [`Renderer::runtime`]
+    runtime: Option<Handle>,
+    /// Templates and macros this render is nested in, see [`MAX_DEPTH`]
+    depth: usize,
+}
+
+/// Built-in filters and functions, for templates rendered without an engine
+fn builtins() -> &'static (Filters, Functions) {
+    static BUILTINS: OnceLock<(Filters, Functions)> = OnceLock::new();
+    BUILTINS.get_or_init(|| (Filters::new(), Functions::new()))
 }
 
 impl<'a> Renderer<'a> {
     pub fn new(context: &'a Context, engine: Option<&'a TemplateEngine>) -> Self {
+        let (filters, functions) = match engine {
+            Some(engine) => (&engine.filters, &engine.functions),
+            None => {
+                let (filters, functions) = builtins();
+                (filters, functions)
+            }
+        };
         Self {
             context,
-            filters: Filters::new(),
+            scopes: Scopes::new(),
+            filters,
+            functions,
             engine,
             blocks: HashMap::new(),
+            current_block: None,
+            macros: HashMap::new(),
+            namespaces: HashMap::new(),
+            slots: HashMap::new(),
+            escape: Escape::Html,
+            runtime: None,
+            depth: 0,
+        }
+    }
+
+    /// Load lazy context values by blocking on `runtime` when a template reads them
+    ///
+    /// Only render from a thread that may block, such as a `spawn_blocking`
+    /// task. Without a runtime a lazy value that hasn't been loaded is an
+    /// error.
+    pub fn runtime(mut self, runtime: Handle) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// A renderer for an include, macro or parent block, sharing the filters and functions
+    fn sub_renderer<'b>(&self, context: &'b Context) -> Result<Renderer<'b>>
+    where
+        'a: 'b,
+    {
+        Ok(Renderer {
+            context,
+            scopes: Scopes::new(),
+            filters: self.filters,
+            functions: self.functions,
+            engine: self.engine,
+            blocks: HashMap::new(),
+            current_block: None,
+            macros: HashMap::new(),
+            namespaces: HashMap::new(),
+            slots: HashMap::new(),
+            escape: self.escape,
+            runtime: self.runtime.clone(),
+            depth: self.nested_depth()?,
+        })
+    }
+
+    /// The depth one level further in, or an error past [`MAX_DEPTH`]
+    fn nested_depth(&self) -> Result<usize> {
+        if self.depth >= MAX_DEPTH {
+            return Err(TemplateError::RenderError(format!(
+                "Templates nested more than {} deep; look for an include, macro or extends cycle",
+                MAX_DEPTH
+            )));
         }
+        Ok(self.depth + 1)
     }
 
+    /// Render `template`, escaping output the way its name calls for
     pub fn render(&mut self, template: &Template) -> Result<String> {
-        // Check for Extends (ignoring leading whitespace)
-        let extends_node = template.parsed.iter().find(|node| {
-            match node {
-                TemplateNode::Text(t) => !t.trim().is_empty(),
-                _ => true,
-            }
+        let mut output = String::new();
+        self.render_to(template, &mut output)?;
+        Ok(output)
+    }
+
+    /// Render `template` into `out` as it goes, rather than into one string
+    pub fn render_to(&mut self, template: &Template, out: &mut dyn Write) -> Result<()> {
+        self.escape = template.escape();
+        self.render_template(template, out)
+    }
+
+    fn render_template(&mut self, template: &Template, out: &mut dyn Write) -> Result<()> {
+        self.register(&template.parsed)?;
+
+        let extends = template.parsed.iter().find_map(|node| match node {
+            TemplateNode::Extends(parent_name) => Some(parent_name),
+            _ => None,
         });
 
-        if let Some(TemplateNode::Extends(parent_name)) = extends_node {
+        if let Some(parent_name) = extends {
             // Collect blocks from current template (child)
             // We only collect top-level blocks in the child template
             for node in &template.parsed {
                 if let TemplateNode::Block { name, body } = node {
-                    // Only insert if not already present (child overrides parent, but we are going up)
-                    // Wait, we start at child. Child blocks should override everything.
-                    // So we insert. But if we are in a chain C -> B -> A.
-                    // We render C. C extends B. We collect C blocks. Recurse to B.
-                    // B extends A. We collect B blocks. If B defines "content" and C defined "content", C wins.
-                    // So we use entry().or_insert().
-                    self.blocks.entry(name.clone()).or_insert(body.clone());
+                    // We start at the most derived template and go up the chain, so
+                    // each block's first level is the override that wins and
+                    // `super()` moves towards the base template.
+                    self.blocks.entry(name.clone()).or_default().push(Arc::new(body.clone()));
                 }
             }
 
-            if let Some(engine) = self.engine {
-                let parent = engine.get_template(parent_name)
-                    .ok_or_else(|| TemplateError::RenderError(format!("Parent template not found: {}", parent_name)))?;
-                return self.render(parent);
-            } else {
-                return Err(TemplateError::RenderError("Extends used without TemplateEngine".to_string()));
-            }
+            let parent = self.engine_template(parent_name, "Extends", "Parent")?;
+            self.depth = self.nested_depth()?;
+            return self.render_template(parent, out);
         }
 
-        self.render_nodes(&template.parsed)
+        self.render_nodes(&template.parsed, out)
     }
 
-    fn render_nodes(&mut self, nodes: &[TemplateNode]) -> Result<String> {
-        let mut output = String::new();
+    /// A template from the engine, for `extends`, `include` or `import`
+    fn engine_template(&self, name: &str, tag: &str, role: &str) -> Result<&'a Template> {
+        let engine = self.engine
+            .ok_or_else(|| TemplateError::RenderError(format!("{} used without TemplateEngine", tag)))?;
+        engine.get_template(name)
+            .ok_or_else(|| TemplateError::RenderError(format!("{} template not found: {}", role, name)))
+    }
 
+    /// Make the macros defined and imported in `nodes` callable
+    fn register(&mut self, nodes: &[TemplateNode]) -> Result<()> {
+        for node in nodes {
+            match node {
+                TemplateNode::Macro(def) => {
+                    self.macros.insert(def.name.clone(), def.clone());
+                }
+                TemplateNode::Import { template, alias } => {
+                    let template = self.engine_template(template, "Import", "Imported")?;
+                    self.namespaces.insert(alias.clone(), template);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn render_nodes(&mut self, nodes: &[TemplateNode], out: &mut dyn Write) -> Result<()> {
         for node in nodes {
             match node {
                 TemplateNode::Text(text) => {
-                    output.push_str(text);
+                    out.write_str(text)?;
+                }
+                TemplateNode::Variable(expr) => {
+                    let value = self.render_variable(expr)?;
+                    out.write_str(&value)?;
                 }
-                TemplateNode::Variable { name, filters } => {
-                    let value = self.render_variable(name, filters)?;
-                    output.push_str(&value);
+                TemplateNode::If { branches, else_branch } => {
+                    self.render_if(branches, else_branch, out)?;
                 }
-                TemplateNode::If { condition, then_branch, else_branch } => {
-                    let value = self.render_if(condition, then_branch, else_branch)?;
-                    output.push_str(&value);
+                TemplateNode::For { key, item, iterable, body, else_branch } => {
+                    self.render_for(key.as_deref(), item, iterable, body, else_branch, out)?;
                 }
-                TemplateNode::For { item, iterable, body } => {
-                    let value = self.render_for(item, iterable, body)?;
-                    output.push_str(&value);
+                TemplateNode::Set { name, value } => {
+                    let value = self.value(value)?.into_owned();
+                    self.scopes.set(name, value);
+                }
+                TemplateNode::With { assignments, body } => {
+                    self.scoped(|renderer| {
+                        for (name, value) in assignments {
+                            let value = renderer.value_or_none(value)?.into_owned();
+                            renderer.scopes.set(name, value);
+                        }
+                        renderer.render_nodes(body, out)
+                    })?;
                 }
                 TemplateNode::Block { name, body } => {
                     // If block is overridden, use that, else use default body
-                    if let Some(override_body) = self.blocks.get(name).cloned() {
-                        // We need to render the override body
-                        let nodes = override_body; 
-                        output.push_str(&self.render_nodes(&nodes)?);
+                    if let Some(overrides) = self.blocks.get(name) {
+                        let mut levels = overrides.clone();
+                        levels.push(Arc::new(body.clone()));
+                        self.render_block(levels, 0, out)?;
                     } else {
-                        output.push_str(&self.render_nodes(body)?);
+                        let outer = self.current_block.take();
+                        let result = self.render_nodes(body, out);
+                        self.current_block = outer;
+                        result?;
                     }
                 }
                 TemplateNode::Extends(_) => {
-                    // Should not happen inside render_nodes (only at top level)
-                    // But if it does, ignore or error?
-                    // Ignore for now.
-                }
-                TemplateNode::Include(template_name) => {
-                    if let Some(engine) = self.engine {
-                        let template = engine.get_template(template_name)
-                            .ok_or_else(|| TemplateError::RenderError(format!("Included template not found: {}", template_name)))?;
-                        
-                        // Includes are rendered in-place with current context
-                        // They do NOT inherit blocks (usually).
-                        // So we create a new renderer for the include, but share context/engine.
-                        // But we don't pass `self.blocks`?
-                        // Correct, includes are isolated from inheritance chain usually.
-                        let mut sub_renderer = Renderer::new(self.context, self.engine);
-                        output.push_str(&sub_renderer.render(template)?);
+                    // Handled by `render` before any node is rendered
+                }
+                TemplateNode::Include { template, with, only } => {
+                    let template = self.engine_template(template, "Include", "Included")?;
+                    let extra = match with {
+                        Some(with) => match self.value(with)?.into_owned() {
+                            Value::Object(map) => map,
+                            other => {
+                                return Err(TemplateError::RenderError(format!(
+                                    "include expects a map after `with`, found a {}",
+                                    expr::type_name(&other)
+                                )));
+                            }
+                        },
+                        None => Map::new(),
+                    };
+
+                    // Includes are rendered in-place and do NOT inherit blocks.
+                    if *only {
+                        let context = Context::from_json(Value::Object(extra));
+                        self.sub_renderer(&context)?.render_to(template, out)?;
                     } else {
-                         return Err(TemplateError::RenderError("Include used without TemplateEngine".to_string()));
+                        // Locals such as the loop variable stay visible.
+                        let mut sub_renderer = self.sub_renderer(self.context)?;
+                        sub_renderer.scopes = self.scopes.flatten();
+                        for (name, value) in extra {
+                            sub_renderer.scopes.set(name, value);
+                        }
+                        sub_renderer.render_to(template, out)?;
+                    }
+                }
+                TemplateNode::Autoescape { escape, body } => {
+                    let outer = std::mem::replace(&mut self.escape, *escape);
+                    let result = self.render_nodes(body, out);
+                    self.escape = outer;
+                    result?;
+                }
+                TemplateNode::Macro(_) | TemplateNode::Import { .. } => {
+                    self.register(std::slice::from_ref(node))?;
+                }
+                TemplateNode::Component { name, args, kwargs, slots } => {
+                    // Slot content renders here, with the caller's variables
+                    let mut rendered = HashMap::new();
+                    for (slot, body) in slots {
+                        let mut content = String::new();
+                        self.render_nodes(body, &mut content)?;
+                        let content = if content.trim().is_empty() { String::new() } else { content };
+                        rendered.insert(slot.clone(), content);
                     }
+                    out.write_str(&self.call_macro(name, args, kwargs, rendered)?)?;
+                }
+                TemplateNode::Trans { key, args } => {
+                    let mut named = Map::new();
+                    for (name, value) in args {
+                        named.insert(name.clone(), self.value_or_none(value)?.into_owned());
+                    }
+                    let text = self.translate(key, named)?;
+                    out.write_str(&self.escape.escape(&text))?;
                 }
             }
         }
 
+        Ok(())
+    }
+
+    /// Render level `depth` of an overridden block
+    fn render_block(&mut self, levels: BlockLevels, depth: usize, out: &mut dyn Write) -> Result<()> {
+        let body = levels[depth].clone();
+        let outer = self.current_block.replace((levels, depth));
+        let result = self.render_nodes(&body, out);
+        self.current_block = outer;
+        result
+    }
+
+    /// `super()`: the next level of the block being rendered
+    fn render_super(&self) -> Result<String> {
+        let (levels, depth) = self
+            .current_block
+            .as_ref()
+            .filter(|(levels, depth)| depth + 1 < levels.len())
+            .ok_or_else(|| TemplateError::RenderError("super() used outside a block that overrides another".to_string()))?;
+
+        let mut renderer = self.sub_renderer(self.context)?;
+        renderer.scopes = self.scopes.flatten();
+        renderer.blocks = self.blocks.clone();
+        renderer.macros = self.macros.clone();
+        renderer.namespaces = self.namespaces.clone();
+        let mut output = String::new();
+        renderer.render_block(levels.clone(), depth + 1, &mut output)?;
         Ok(output)
     }
 
-    fn render_variable(&self, name: &str, filter_names: &[String]) -> Result<String> {
-        let value = self.context.get(name)
-            .ok_or_else(|| TemplateError::VariableNotFound(name.to_string()))?;
+    /// The macro `name` refers to, and the template it was imported from
+    fn find_macro(&self, name: &str) -> Option<(Arc<Macro>, Option<&'a Template>)> {
+        match name.split_once('.') {
+            Some((namespace, name)) => {
+                let template = *self.namespaces.get(namespace)?;
+                let def = template.parsed.iter().find_map(|node| match node {
+                    TemplateNode::Macro(def) if def.name == name => Some(def.clone()),
+                    _ => None,
+                })?;
+                Some((def, Some(template)))
+            }
+            None => self.macros.get(name).map(|def| (def.clone(), None)),
+        }
+    }
+
+    fn call_macro(&self, name: &str, args: &[Expr], kwargs: &[(String, Expr)], slots: HashMap<String, String>) -> Result<String> {
+        let (def, imported_from) = self.find_macro(name)
+            .ok_or_else(|| TemplateError::RenderError(format!("Macro not found: {}", name)))?;
+        if args.len() > def.params.len() {
+            return Err(TemplateError::RenderError(format!(
+                "{}() takes at most {} arguments, got {}",
+                name,
+                def.params.len(),
+                args.len()
+            )));
+        }
 
-        let mut result = self.value_to_string(value);
+        // The body sees its arguments and the context, not the caller's locals
+        let mut renderer = self.sub_renderer(self.context)?;
+        match imported_from {
+            Some(template) => renderer.register(&template.parsed)?,
+            None => {
+                renderer.macros = self.macros.clone();
+                renderer.namespaces = self.namespaces.clone();
+            }
+        }
+        renderer.slots = slots;
 
-        // Apply filters
-        for filter_name in filter_names {
-            result = self.filters.apply(filter_name, &result)?;
+        let mut named = HashMap::new();
+        for (key, value) in kwargs {
+            if !def.params.iter().any(|(param, _)| param == key) {
+                return Err(TemplateError::RenderError(format!("{}() has no parameter {}", name, key)));
+            }
+            named.insert(key.as_str(), self.value_or_none(value)?.into_owned());
         }
 
-        // Auto-escape HTML
-        result = html_escape(&result);
+        // Arguments are evaluated by the caller, defaults in the macro
+        let mut args = args.iter();
+        for (param, default) in &def.params {
+            let value = match (args.next(), named.remove(param.as_str()), default) {
+                (Some(arg), _, _) => self.value_or_none(arg)?.into_owned(),
+                (None, Some(value), _) => value,
+                (None, None, Some(default)) => renderer.value_or_none(default)?.into_owned(),
+                (None, None, None) => continue,
+            };
+            renderer.scopes.set(param, value);
+        }
 
-        Ok(result)
+        let mut output = String::new();
+        renderer.render_nodes(&def.body, &mut output)?;
+        Ok(output)
     }
 
-    fn render_if(&mut self, condition: &str, then_branch: &[TemplateNode], else_branch: &Option<Vec<TemplateNode>>) -> Result<String> {
-        // Evaluate condition (simple truthy check)
-        let is_truthy = self.evaluate_condition(condition);
+    fn render_variable(&self, expr: &Expr) -> Result<String> {
+        let value = self.value(expr)?;
+        Ok(self.escaped(&value))
+    }
 
-        if is_truthy {
-            self.render_nodes(then_branch)
-        } else if let Some(else_nodes) = else_branch {
-            self.render_nodes(else_nodes)
-        } else {
-            Ok(String::new())
+    /// `value` as output: escaped, unless it is a safe string
+    fn escaped(&self, value: &Value) -> String {
+        match escape::safe_str(value) {
+            Some(markup) => markup.to_string(),
+            None => self.escape.escape(&expr::to_output(value)),
         }
     }
 
-    fn render_for(&mut self, item: &str, iterable: &str, body: &[TemplateNode]) -> Result<String> {
-        let array = self.context.get(iterable)
-            .ok_or_else(|| TemplateError::VariableNotFound(iterable.to_string()))?;
+    fn apply_filter(&self, name: &str, input: &Value, args: &[Value]) -> Result<Value> {
+        match name {
+            "safe" => Ok(escape::mark_safe(expr::to_output(input))),
+            "escape" | "e" => {
+                if escape::safe_str(input).is_some() {
+                    return Ok(input.clone());
+                }
+                let strategy = match args.first() {
+                    Some(Value::String(strategy)) => Escape::from_name(strategy)
+                        .ok_or_else(|| TemplateError::RenderError(format!("Unknown escape strategy: {}", strategy)))?,
+                    _ if self.escape == Escape::Text => Escape::Html,
+                    _ => self.escape,
+                };
+                Ok(escape::mark_safe(strategy.escape(&expr::to_output(input))))
+            }
+            "json" => {
+                let json = match escape::safe_str(input) {
+                    Some(markup) => serde_json::to_string(markup),
+                    None => serde_json::to_string(&escape::unmark_all(input)),
+                }
+                .map_err(|e| TemplateError::RenderError(e.to_string()))?;
+                // Nothing in the output can end a `<script>` element or a single-quoted attribute
+                let json = json
+                    .replace('<', "\\u003c")
+                    .replace('>', "\\u003e")
+                    .replace('&', "\\u0026")
+                    .replace('\'', "\\u0027")
+                    .replace('\u{2028}', "\\u2028")
+                    .replace('\u{2029}', "\\u2029");
+                Ok(escape::mark_safe(json))
+            }
+            _ => match self.locale() {
+                Some(locale) => self.filters.apply_in(name, input, args, &locale),
+                None => self.filters.apply(name, input, args),
+            },
+        }
+    }
 
-        let mut output = String::new();
+    /// The locale to render in: `locale` in the context, else the default of
+    /// the engine's catalogs
+    fn locale(&self) -> Option<Locale> {
+        self.lookup("locale")
+            .and_then(Value::as_str)
+            .and_then(Locale::new)
+            .or_else(|| Some(self.engine?.i18n.as_ref()?.default_locale().clone()))
+    }
 
-        if let Value::Array(items) = array {
-            for item_value in items {
-                // Create new context with loop variable
-                let mut loop_context = self.context.clone();
-                loop_context.data.insert(item.to_string(), item_value.clone());
+    /// `{% trans %}` and `t()`: message `key` in the current locale
+    fn translate(&self, key: &str, args: Map<String, Value>) -> Result<String> {
+        let i18n = self
+            .engine
+            .and_then(|engine| engine.i18n.as_ref())
+            .ok_or_else(|| TemplateError::RenderError("Translating needs a TemplateEngine with set_i18n()".to_string()))?;
+        let locale = self.locale().unwrap_or_else(|| i18n.default_locale().clone());
+        Ok(i18n.translate(&locale, key, &Value::Object(args)))
+    }
 
-                let mut renderer = Renderer::new(&loop_context, self.engine);
-                // Pass blocks to loop renderer?
-                // Loops are inside the template, so they should have access to blocks?
-                // Yes, if I use a block inside a loop?
-                renderer.blocks = self.blocks.clone();
-                output.push_str(&renderer.render_nodes(body)?);
+    fn render_if(
+        &mut self,
+        branches: &[(Expr, Vec<TemplateNode>)],
+        else_branch: &Option<Vec<TemplateNode>>,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        for (condition, body) in branches {
+            if self.evaluate_condition(condition)? {
+                return self.render_nodes(body, out);
             }
         }
 
-        Ok(output)
+        match else_branch {
+            Some(else_nodes) => self.render_nodes(else_nodes, out),
+            None => Ok(()),
+        }
     }
 
-    fn evaluate_condition(&self, condition: &str) -> bool {
-        if let Some(value) = self.context.get(condition) {
-            match value {
-                Value::Bool(b) => *b,
-                Value::Null => false,
-                Value::String(s) => !s.is_empty(),
-                Value::Number(_) => true,
-                Value::Array(a) => !a.is_empty(),
-                Value::Object(o) => !o.is_empty(),
+    fn render_for(
+        &mut self,
+        key: Option<&str>,
+        item: &str,
+        iterable: &Expr,
+        body: &[TemplateNode],
+        else_branch: &Option<Vec<TemplateNode>>,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        // Pairs of the key (when destructuring) and the item
+        // Markup iterates like the string it is, not as the map holding it
+        let value = self.value(iterable)?;
+        let entries: Vec<(Option<Value>, Value)> = match expr::unmark(&value).into_owned() {
+            Value::Array(items) if key.is_some() => items
+                .into_iter()
+                .map(|pair| match pair {
+                    Value::Array(mut pair) if pair.len() == 2 => {
+                        let value = pair.pop().unwrap_or_default();
+                        Ok((pair.pop(), value))
+                    }
+                    other => Err(TemplateError::RenderError(format!(
+                        "cannot unpack a {} into two loop variables",
+                        expr::type_name(&other)
+                    ))),
+                })
+                .collect::<Result<_>>()?,
+            Value::Array(items) => items.into_iter().map(|value| (None, value)).collect(),
+            Value::Object(map) if key.is_some() => map.into_iter().map(|(k, v)| (Some(Value::String(k)), v)).collect(),
+            // A single loop variable goes over a map's keys
+            Value::Object(map) => map.into_iter().map(|(k, _)| (None, Value::String(k))).collect(),
+            Value::Null => Vec::new(),
+            other => {
+                return Err(TemplateError::RenderError(format!("cannot iterate over a {}", expr::type_name(&other))));
             }
-        } else {
-            false
+        };
+
+        if entries.is_empty() {
+            return match else_branch {
+                Some(else_nodes) => self.render_nodes(else_nodes, out),
+                None => Ok(()),
+            };
+        }
+
+        let length = entries.len();
+        for (index, (key_value, value)) in entries.into_iter().enumerate() {
+            // A fresh frame per iteration, so `set` inside the body doesn't carry over
+            self.scoped(|renderer| {
+                if let (Some(key), Some(key_value)) = (key, key_value) {
+                    renderer.scopes.set(key, key_value);
+                }
+                renderer.scopes.set(item, value);
+                renderer.scopes.set(
+                    "loop",
+                    json!({
+                        "index": index + 1,
+                        "index0": index,
+                        "first": index == 0,
+                        "last": index + 1 == length,
+                        "length": length,
+                    }),
+                );
+                renderer.render_nodes(body, out)
+            })?;
         }
+
+        Ok(())
+    }
+
+    /// Run `f` in a new scope frame, which is dropped afterwards even on error
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.scopes.push();
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// A local, or else a context variable
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        self.scopes.get(name).or_else(|| self.context.get(name))
     }
 
-    fn value_to_string(&self, value: &Value) -> String {
-        match value {
-            Value::String(s) => s.clone(),
-            Value::Number(n) => n.to_string(),
-            Value::Bool(b) => b.to_string(),
-            Value::Null => String::new(),
-            _ => serde_json::to_string(value).unwrap_or_default(),
+    /// A local or context variable, loading it first if it is lazy
+    fn variable(&self, name: &str) -> Result<Option<&Value>> {
+        if let Some(value) = self.lookup(name) {
+            return Ok(Some(value));
         }
+        match self.context.lazy(name) {
+            Some(lazy) => lazy.get(name, self.runtime.as_ref()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Undefined variables are false rather than an error
+    fn evaluate_condition(&self, condition: &Expr) -> Result<bool> {
+        Ok(self.eval(condition)?.is_some_and(|value| expr::is_truthy(&value)))
+    }
+
+    /// The value of `expr`, which must be defined
+    fn value(&self, expr: &Expr) -> Result<Cow<'_, Value>> {
+        self.eval(expr)?.ok_or_else(|| {
+            // Name the undefined variable, not the filters applied to it
+            let mut undefined = expr;
+            while let Expr::Filter { value, .. } = undefined {
+                undefined = value;
+            }
+            TemplateError::VariableNotFound(undefined.to_string())
+        })
+    }
+
+    /// The value of `expr`, with undefined values as `none`
+    fn value_or_none(&self, expr: &Expr) -> Result<Cow<'_, Value>> {
+        Ok(self.eval(expr)?.unwrap_or(Cow::Owned(Value::Null)))
+    }
+
+    fn values(&self, exprs: &[Expr]) -> Result<Vec<Value>> {
+        exprs.iter().map(|expr| Ok(self.value_or_none(expr)?.into_owned())).collect()
+    }
+
+    /// Evaluate `expr`; `None` when it refers to a variable or field that doesn't exist
+    ///
+    /// Variables and their fields are borrowed, so reading `user.name` doesn't
+    /// copy `user`.
+    fn eval(&self, expr: &Expr) -> Result<Option<Cow<'_, Value>>> {
+        let value = match expr {
+            Expr::Literal(value) => value.clone(),
+            Expr::List(items) => Value::Array(self.values(items)?),
+            Expr::Map(entries) => Value::Object(
+                entries
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), self.value_or_none(value)?.into_owned())))
+                    .collect::<Result<Map<_, _>>>()?,
+            ),
+            Expr::Var(name) => return Ok(self.variable(name)?.map(Cow::Borrowed)),
+            Expr::Attr(value, name) => {
+                let index = Value::String(name.clone());
+                return Ok(self.eval(value)?.and_then(|value| get_item(value, &index)));
+            }
+            Expr::Index(value, index) => {
+                let index = self.value(index)?;
+                return Ok(self.eval(value)?.and_then(|value| get_item(value, &index)));
+            }
+            // Macros, slots and `super()` give markup that was escaped as it rendered
+            Expr::Call { name, args, kwargs } => match name.as_str() {
+                "super" => escape::mark_safe(self.render_super()?),
+                "slot" => {
+                    let args = self.values(args)?;
+                    let slot = args.first().and_then(Value::as_str).unwrap_or("default");
+                    escape::mark_safe(self.slots.get(slot).cloned().unwrap_or_default())
+                }
+                _ if self.find_macro(name).is_some() => {
+                    escape::mark_safe(self.call_macro(name, args, kwargs, HashMap::new())?)
+                }
+                // `t("key", {"name": value})` or `t("key", name=value)`
+                "t" => {
+                    let mut values = self.values(args)?.into_iter();
+                    let Some(Value::String(key)) = values.next() else {
+                        return Err(TemplateError::RenderError("t() expects a message key".to_string()));
+                    };
+                    let mut named = match values.next() {
+                        Some(Value::Object(map)) => map,
+                        None => Map::new(),
+                        Some(other) => {
+                            return Err(TemplateError::RenderError(format!(
+                                "t() expects a map of arguments, found a {}",
+                                expr::type_name(&other)
+                            )));
+                        }
+                    };
+                    for (name, value) in kwargs {
+                        named.insert(name.clone(), self.value_or_none(value)?.into_owned());
+                    }
+                    Value::String(self.translate(&key, named)?)
+                }
+                _ if !kwargs.is_empty() => {
+                    return Err(TemplateError::RenderError(format!("{}() takes no keyword arguments", name)));
+                }
+                // The token belongs to the request, so it comes from the context
+                "csrf_token" if args.is_empty() => self.lookup("csrf_token").cloned().ok_or_else(|| {
+                    TemplateError::RenderError("csrf_token() needs `csrf_token` in the context".to_string())
+                })?,
+                _ => self.functions.call(name, &self.values(args)?)?,
+            },
+            Expr::Filter { value, name, args } => {
+                // `default` is the one filter that accepts an undefined value
+                let input = match self.eval(value)? {
+                    Some(value) => value,
+                    None if name == "default" => Cow::Owned(Value::Null),
+                    None => return Ok(None),
+                };
+                self.apply_filter(name, &input, &self.values(args)?)?
+            }
+            Expr::Test { value, name, args, negated } => {
+                let passed = match name.as_str() {
+                    "defined" => self.eval(value)?.is_some(),
+                    "undefined" => self.eval(value)?.is_none(),
+                    _ => expr::test(name, &*self.value_or_none(value)?, &self.values(args)?)?,
+                };
+                Value::Bool(passed != *negated)
+            }
+            Expr::Unary(UnaryOp::Not, value) => Value::Bool(!self.evaluate_condition(value)?),
+            Expr::Unary(UnaryOp::Neg, value) => expr::negate(&*self.value(value)?)?,
+            Expr::Binary(BinaryOp::And, left, right) => {
+                let left = self.value_or_none(left)?;
+                if expr::is_truthy(&left) { self.value_or_none(right)?.into_owned() } else { left.into_owned() }
+            }
+            Expr::Binary(BinaryOp::Or, left, right) => {
+                let left = self.value_or_none(left)?;
+                if expr::is_truthy(&left) { left.into_owned() } else { self.value_or_none(right)?.into_owned() }
+            }
+            // Joining markup escapes the other side, so the result stays safe
+            Expr::Binary(BinaryOp::Concat, left, right) => {
+                let (left, right) = (self.value(left)?, self.value(right)?);
+                if escape::safe_str(&left).is_some() || escape::safe_str(&right).is_some() {
+                    escape::mark_safe(self.escaped(&left) + &self.escaped(&right))
+                } else {
+                    expr::binary(BinaryOp::Concat, &left, &right)?
+                }
+            }
+            // Comparing with an undefined variable is false rather than an error
+            Expr::Binary(op @ (BinaryOp::Eq | BinaryOp::Ne), left, right) => {
+                expr::binary(*op, &*self.value_or_none(left)?, &*self.value_or_none(right)?)?
+            }
+            Expr::Binary(op, left, right) => expr::binary(*op, &*self.value(left)?, &*self.value(right)?)?,
+        };
+        Ok(Some(Cow::Owned(value)))
     }
 }
 
-/// HTML escape for XSS protection
-fn html_escape(s: &str) -> String {
-    s.replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-        .replace('"', "&quot;")
-        .replace('\'', "&#x27;")
+/// `value[index]`, still borrowed if `value` was
+fn get_item<'v>(value: Cow<'v, Value>, index: &Value) -> Option<Cow<'v, Value>> {
+    match value {
+        Cow::Borrowed(value) => expr::get_item(value, index).map(Cow::Borrowed),
+        Cow::Owned(value) => expr::get_item(&value, index).cloned().map(Cow::Owned),
+    }
 }
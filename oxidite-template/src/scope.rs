@@ -0,0 +1,57 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Local variables from `for`, `set` and `with`, layered over the render context
+///
+/// Each loop or `with` pushes a frame that is popped when it ends, so locals
+/// never leak out and the context itself is never copied.
+#[derive(Debug, Clone)]
+pub struct Scopes {
+    frames: Vec<HashMap<String, Value>>,
+}
+
+impl Scopes {
+    pub fn new() -> Self {
+        Self {
+            frames: vec![HashMap::new()],
+        }
+    }
+
+    pub fn push(&mut self) {
+        self.frames.push(HashMap::new());
+    }
+
+    pub fn pop(&mut self) {
+        // The template's own frame always stays
+        if self.frames.len() > 1 {
+            self.frames.pop();
+        }
+    }
+
+    /// Set `name` in the innermost frame
+    pub fn set(&mut self, name: impl Into<String>, value: Value) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.insert(name.into(), value);
+        }
+    }
+
+    /// The innermost value of `name`
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.frames.iter().rev().find_map(|frame| frame.get(name))
+    }
+
+    /// Every visible local in a single frame, for rendering an include
+    pub fn flatten(&self) -> Self {
+        let mut frame = HashMap::new();
+        for (name, value) in self.frames.iter().flatten() {
+            frame.insert(name.clone(), value.clone());
+        }
+        Self { frames: vec![frame] }
+    }
+}
+
+impl Default for Scopes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,131 @@
+//! Rendering into writers and response streams
+//!
+//! A page can be written out as it renders instead of being built in one
+//! string: [`TemplateEngine::render_to`](crate::TemplateEngine::render_to)
+//! writes into any [`fmt::Write`], `render_to_writer` into an [`io::Write`],
+//! and [`Templates::render_stream`](crate::Templates::render_stream) yields
+//! chunks a handler can return as a streaming response.
+
+use crate::{Result, TemplateError};
+use bytes::Bytes;
+use futures_util::Stream;
+use http::header::{HeaderValue, CONTENT_TYPE};
+use oxidite_core::{BoxBody, IntoResponse, OxiditeResponse};
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+
+/// Output is sent once this much has been rendered
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Chunks of a page rendered on a blocking thread
+///
+/// A handler can return it as an HTML response sent a chunk at a time, or
+/// wrap it with [`BoxBody::from_stream`] for another content type. Dropping
+/// the stream, as when the client goes away, stops the render at its next
+/// chunk. A render error ends the stream with an `Err`, after the chunks
+/// already sent.
+pub struct RenderStream {
+    chunks: mpsc::Receiver<Result<Bytes>>,
+}
+
+impl RenderStream {
+    /// Run `render` on a blocking thread of the current runtime, sending its
+    /// output as it goes
+    pub(crate) fn spawn<F>(render: F) -> Self
+    where
+        F: FnOnce(&mut dyn fmt::Write, Handle) -> Result<()> + Send + 'static,
+    {
+        let (sender, chunks) = mpsc::channel(4);
+        let runtime = Handle::current();
+        tokio::task::spawn_blocking(move || {
+            let mut writer = ChunkWriter { sender, buffer: String::new() };
+            let result = render(&mut writer, runtime).and_then(|()| writer.send());
+            if let Err(err) = result {
+                // Nobody is listening if the stream was dropped
+                let _ = writer.sender.blocking_send(Err(err));
+            }
+        });
+        Self { chunks }
+    }
+}
+
+impl Stream for RenderStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.chunks.poll_recv(cx)
+    }
+}
+
+/// The page as `text/html`; a render error after the first chunk aborts the
+/// response, since its status has already been sent
+impl IntoResponse for RenderStream {
+    fn into_response(self) -> OxiditeResponse {
+        let mut response = OxiditeResponse::new(BoxBody::from_stream(self));
+        response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        response
+    }
+}
+
+/// Buffers output and sends it to a [`RenderStream`] a chunk at a time
+struct ChunkWriter {
+    sender: mpsc::Sender<Result<Bytes>>,
+    buffer: String,
+}
+
+impl ChunkWriter {
+    fn send(&mut self) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::take(&mut self.buffer));
+        self.sender
+            .blocking_send(Ok(chunk))
+            .map_err(|_| TemplateError::WriteError(io::ErrorKind::BrokenPipe.into()))
+    }
+}
+
+impl fmt::Write for ChunkWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.buffer.push_str(s);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send().map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lets the renderer write into an [`io::Write`], keeping the error it returns
+pub(crate) struct IoWriter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> IoWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self { inner, error: None }
+    }
+
+    /// The result of a render into this writer; a write error is reported
+    /// rather than the formatting error it caused
+    pub(crate) fn finish(mut self, result: Result<()>) -> Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(TemplateError::WriteError(error));
+        }
+        result?;
+        self.inner.flush().map_err(TemplateError::WriteError)
+    }
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
+    }
+}
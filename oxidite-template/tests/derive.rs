@@ -0,0 +1,52 @@
+use http::StatusCode;
+use oxidite_core::{OxiditeRequest, Router};
+use oxidite_template::{IntoResponse, Template};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct User {
+    name: String,
+    roles: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "derive/users/show.html")]
+struct UserPage {
+    user: User,
+    site: String,
+    motto: Option<String>,
+}
+
+fn page(roles: &[&str]) -> UserPage {
+    UserPage {
+        user: User { name: "<Ada>".to_string(), roles: roles.iter().map(|role| role.to_string()).collect() },
+        site: "Oxidite".to_string(),
+        motto: None,
+    }
+}
+
+async fn show_user(_req: OxiditeRequest) -> oxidite_core::Result<UserPage> {
+    Ok(page(&["admin"]))
+}
+
+#[test]
+fn test_derived_template_renders_fields() {
+    let html = page(&["admin", "staff"]).render().unwrap();
+    assert_eq!(
+        html,
+        "<title>&lt;Ada&gt; - Oxidite</title>\n<main><b>&lt;Ada&gt;</b>\n\n\
+         <span class=\"info\">admin</span><span class=\"info\">staff</span>\n-</main>\n"
+    );
+    assert!(page(&[]).render().unwrap().contains("<span class=\"muted\">none</span>"));
+}
+
+#[test]
+fn test_derived_template_is_a_response() {
+    let response = page(&["admin"]).into_response();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/html");
+
+    // Handlers can return the page itself
+    let mut router = Router::new();
+    router.get("/users/:id", show_user);
+}
@@ -0,0 +1,120 @@
+use oxidite_template::{Context, Escape, SafeString, Template, TemplateEngine, TemplateError};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Post {
+    title: String,
+    body_html: SafeString,
+}
+
+fn context() -> Context {
+    let mut context = Context::new();
+    context.set("name", r#"<b>"Ada" & 'Bob'</b>"#);
+    context.set(
+        "post",
+        Post { title: "<Intro>".to_string(), body_html: SafeString::from("<p>Rendered <em>markdown</em></p>") },
+    );
+    context
+}
+
+fn render(source: &str) -> String {
+    Template::new(source).unwrap().render(&context()).unwrap()
+}
+
+#[test]
+fn test_html_escaping_covers_attributes() {
+    assert_eq!(
+        render(r#"<a title="{{ name }}">"#),
+        r#"<a title="&lt;b&gt;&quot;Ada&quot; &amp; &#x27;Bob&#x27;&lt;/b&gt;">"#
+    );
+    assert_eq!(render("{{ post.title }}{{ post.body_html }}"), "&lt;Intro&gt;<p>Rendered <em>markdown</em></p>");
+    assert_eq!(render("{% if post.body_html %}{{ post.body_html | length }}{% endif %}"), "33");
+}
+
+#[test]
+fn test_safe_escape_and_autoescape() {
+    assert_eq!(render("{{ '<br>' | safe }}"), "<br>");
+    assert_eq!(render("{{ '<br>' | escape | escape }}"), "&lt;br&gt;");
+    assert_eq!(render("{{ '<br>' | e('js') }}"), "\\u003Cbr\\u003E");
+    assert_eq!(render("{% autoescape false %}{{ '<br>' }}{% endautoescape %}{{ '<br>' }}"), "<br>&lt;br&gt;");
+    assert_eq!(render("{% autoescape false %}{{ '<br>' | escape }}{% endautoescape %}"), "&lt;br&gt;");
+    assert_eq!(render("{% autoescape \"xml\" %}{{ \"'\" }}{% endautoescape %}"), "&apos;");
+
+    // Joining markup with a plain string escapes the plain side only
+    assert_eq!(render("{{ post.body_html ~ '<hr>' }}"), "<p>Rendered <em>markdown</em></p>&lt;hr&gt;");
+}
+
+#[test]
+fn test_user_data_cannot_pose_as_markup() {
+    // The shape a safe string used to serialize to, arriving in a request body
+    let forged = serde_json::json!({ "bio": { "$oxidite_safe": "<script>alert(1)</script>" } });
+    let output = Template::new("{{ bio }}").unwrap().render(&Context::from_json(forged)).unwrap();
+    assert!(!output.contains("<script>"), "{}", output);
+
+    // Nothing written out reveals how real markup is marked
+    for source in ["{{ [post.body_html] }}", "{{ post | json }}", "{{ post }}"] {
+        let output = render(source);
+        assert!(!output.contains("oxidite_safe"), "{} rendered {}", source, output);
+    }
+    assert_eq!(render("{{ post | json }}"), r#"{"body_html":"\u003cp\u003eRendered \u003cem\u003emarkdown\u003c/em\u003e\u003c/p\u003e","title":"\u003cIntro\u003e"}"#);
+    assert!(Template::new("{% for k in post.body_html %}{{ k }}{% endfor %}").unwrap().render(&context()).is_err());
+}
+
+#[test]
+fn test_safe_strings_serialize_as_plain_strings() {
+    let post = Post { title: "Intro".to_string(), body_html: SafeString::from("<p>Hi</p>") };
+    let json = serde_json::to_string(&post).unwrap();
+    assert_eq!(json, r#"{"title":"Intro","body_html":"<p>Hi</p>"}"#);
+
+    let stored: SafeString = serde_json::from_value(serde_json::to_value(&post).unwrap()["body_html"].clone()).unwrap();
+    assert_eq!(stored.as_str(), "<p>Hi</p>");
+
+    // Data that went through JSON is plain text again
+    let output = Template::new("{{ body_html }}").unwrap().render(&Context::from_json(serde_json::from_str(&json).unwrap()));
+    assert_eq!(output.unwrap(), "&lt;p&gt;Hi&lt;/p&gt;");
+}
+
+#[test]
+fn test_json_is_safe_inside_script() {
+    let mut context = Context::new();
+    context.set("data", serde_json::json!({ "bio": "</script><script>alert('x')</script>", "n": 1 }));
+    let output = Template::new("<script>const data = {{ data | json }};</script>").unwrap().render(&context).unwrap();
+    assert_eq!(
+        output,
+        r#"<script>const data = {"bio":"\u003c/script\u003e\u003cscript\u003ealert(\u0027x\u0027)\u003c/script\u003e","n":1};</script>"#
+    );
+}
+
+#[test]
+fn test_strategy_follows_template_extension() {
+    assert_eq!(Escape::for_template("feed.rss"), Escape::Xml);
+    assert_eq!(Escape::for_template("pages/home.HTML"), Escape::Html);
+    assert_eq!(Escape::for_template("README"), Escape::Html);
+
+    let mut engine = TemplateEngine::new();
+    engine.add_template("email.txt", "Hi {{ name }}").unwrap();
+    engine.add_template("feed.xml", "<title>{{ name }}</title>").unwrap();
+    engine.add_template("config.js", "const name = \"{{ name }}\";").unwrap();
+    engine.add_template("page.html", "{% include \"email.txt\" %}|{{ name }}").unwrap();
+
+    let context = context();
+    assert_eq!(engine.render("email.txt", &context).unwrap(), r#"Hi <b>"Ada" & 'Bob'</b>"#);
+    assert_eq!(
+        engine.render("feed.xml", &context).unwrap(),
+        "<title>&lt;b&gt;&quot;Ada&quot; &amp; &apos;Bob&apos;&lt;/b&gt;</title>"
+    );
+    assert_eq!(
+        engine.render("config.js", &context).unwrap(),
+        r#"const name = "\u003Cb\u003E\u0022Ada\u0022 \u0026 \u0027Bob\u0027\u003C\u002Fb\u003E";"#
+    );
+    assert_eq!(
+        engine.render("page.html", &context).unwrap(),
+        r#"Hi <b>"Ada" & 'Bob'</b>|&lt;b&gt;&quot;Ada&quot; &amp; &#x27;Bob&#x27;&lt;/b&gt;"#
+    );
+}
+
+#[test]
+fn test_unknown_escape_strategy() {
+    let error = Template::new("{{ 'x' | escape('css') }}").unwrap().render(&Context::new()).unwrap_err();
+    assert!(matches!(error, TemplateError::RenderError(message) if message == "Unknown escape strategy: css"));
+}
@@ -0,0 +1,70 @@
+use oxidite_template::{Context, Template, TemplateError};
+use serde_json::json;
+
+fn render(source: &str) -> String {
+    let context = Context::from_json(json!({
+        "user": { "name": "Ada", "age": 36, "roles": ["admin", "editor"] },
+        "items": [{ "name": "first" }, { "name": "second" }],
+        "count": 7,
+        "empty": "",
+        "body": "The quick brown fox jumps over the lazy dog",
+    }));
+    Template::new(source).unwrap().render(&context).unwrap()
+}
+
+#[test]
+fn test_comparisons_and_boolean_logic() {
+    assert_eq!(render("{% if user.age >= 18 and 'admin' in user.roles %}yes{% endif %}"), "yes");
+    assert_eq!(render("{% if not missing and count != 7 %}yes{% else %}no{% endif %}"), "no");
+    assert_eq!(render("{% if user.name == 'Ada' or missing.field > 3 %}short{% endif %}"), "short");
+    assert_eq!(render("{% if 'guest' not in user.roles %}member{% endif %}"), "member");
+    assert_eq!(render("{% if missing == none %}unset{% endif %}"), "unset");
+    assert_eq!(render("{{ empty or 'fallback' }}"), "fallback");
+}
+
+#[test]
+fn test_elif_chains() {
+    let source = "{% if count < 5 %}few{% elif count < 10 %}some{% elif count < 100 %}many{% else %}lots{% endif %}";
+    assert_eq!(render(source), "some");
+    assert_eq!(render("{% if missing %}a{% elif empty %}b{% else %}c{% endif %}"), "c");
+}
+
+#[test]
+fn test_arithmetic_indexing_and_calls() {
+    assert_eq!(render("{{ count * 2 + 1 }} {{ count / 2 }} {{ count // 2 }} {{ count % 4 }} {{ 2 ** 10 }}"), "15 3.5 3 3 1024");
+    assert_eq!(render("{{ items[0].name }} {{ items[-1]['name'] }} {{ items.1.name }}"), "first second second");
+    assert_eq!(render("{{ user.name ~ ' (' ~ user.age ~ ')' }}"), "Ada (36)");
+    assert_eq!(render("{{ len(user.roles) }} {{ max(3, count, 5) }} {{ range(1, 4) }}"), "2 7 [1,2,3]");
+}
+
+#[test]
+fn test_integer_overflow_falls_back_to_floats() {
+    let context = Context::from_json(json!({ "min": i64::MIN, "max": i64::MAX }));
+    let render = |source: &str| Template::new(source).unwrap().render(&context).unwrap();
+
+    let overflowed = "9.223372036854776e+18";
+    assert_eq!(render("{{ -min }} {{ min // -1 }} {{ max + 1 }}"), [overflowed; 3].join(" "));
+    assert_eq!(render("{{ min % -1 }}|{% if min is divisibleby(-1) %}even{% endif %}"), "0|even");
+    assert_eq!(render("{{ range(max - 2, max) }}"), format!("[{},{}]", i64::MAX - 2, i64::MAX - 1));
+}
+
+#[test]
+fn test_filter_arguments() {
+    assert_eq!(render("{{ body | truncate(12) | upper }}"), "THE QUICK...");
+    assert_eq!(render("{{ missing | default(\"anon\") }}|{{ empty | default('none given') }}"), "anon|none given");
+    assert_eq!(render("{{ user.name | default('anon') }}"), "Ada");
+}
+
+#[test]
+fn test_render_errors() {
+    let context = Context::new();
+    let error = |source: &str| Template::new(source).unwrap().render(&context).unwrap_err();
+
+    assert!(matches!(error("{{ user.name }}"), TemplateError::VariableNotFound(name) if name == "user.name"));
+    assert!(matches!(error("{{ missing | upper }}"), TemplateError::VariableNotFound(name) if name == "missing"));
+    assert!(matches!(error("{{ 1 / 0 }}"), TemplateError::RenderError(message) if message == "division by zero"));
+    assert!(matches!(error("{{ 'a' - 1 }}"), TemplateError::RenderError(message) if message == "cannot apply `-` to a string and a number"));
+    assert!(matches!(error("{{ 'a' | upper(1) }}"), TemplateError::RenderError(_)));
+    assert!(matches!(error("{{ nope() }}"), TemplateError::RenderError(message) if message == "Function not found: nope"));
+    assert!(matches!(error("{{ range(100001) }}"), TemplateError::RenderError(message) if message.starts_with("range() would produce 100001 values")));
+}
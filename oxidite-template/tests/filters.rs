@@ -0,0 +1,114 @@
+use oxidite_template::{Context, Template, TemplateEngine, TemplateError};
+use serde_json::{json, Value};
+
+fn context() -> Context {
+    Context::from_json(json!({
+        "posts": [
+            { "title": "Zebras", "views": 1200, "published": "2024-03-09T14:05:00Z" },
+            { "title": "Apples", "views": 87, "published": "2023-12-25" },
+        ],
+        "tags": ["rust", "web"],
+        "price": 1234567.891,
+        "body": "# Hi\n\nSay <b>hi</b> to [me](javascript:alert(1)) and [you](/you) **now**\n\n<script>x</script>",
+        "nothing": [],
+        "csrf_token": "t0k3n",
+    }))
+}
+
+fn render(source: &str) -> String {
+    Template::new(source).unwrap().render(&context()).unwrap()
+}
+
+fn render_err(source: &str) -> String {
+    match Template::new(source).unwrap().render(&context()) {
+        Err(TemplateError::RenderError(message)) => message,
+        other => panic!("expected a render error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_filters_work_on_values() {
+    assert_eq!(render("{{ posts | length }} {{ tags | length }} {{ 'héllo' | length }}"), "2 2 5");
+    assert_eq!(render("{{ tags | join(', ') }} {{ tags | first }} {{ tags | last | upper }}"), "rust, web rust WEB");
+    assert_eq!(render("{{ posts | map('title') | join }}"), "ZebrasApples");
+    assert_eq!(render("{{ posts | sort('views') | map('title') | join(' ') }}"), "Apples Zebras");
+    assert_eq!(render("{{ [3, 1, 2] | sort | reverse | join }}"), "321");
+    assert_eq!(render("{{ missing | default('none') }} {{ nothing | first | default }}"), "none N/A");
+    assert_eq!(render_err("{{ [1, 'a'] | sort }}"), "sort cannot compare a string and a number");
+    assert_eq!(render_err("{{ 3 | join }}"), "join expects a list, found a number");
+
+    // Markup from `safe` is a string to the other filters
+    assert_eq!(render("{{ '<b>' | safe | first }}{{ '<b>' | safe | last }}"), "&lt;&gt;");
+    assert_eq!(render("{{ '' | safe | default('none') }} {{ '1234' | safe | number_format }}"), "none 1,234");
+    assert_eq!(render_err("{{ 'ab' | safe | sort }}"), "sort expects a list, found a string");
+    assert_eq!(render_err("{{ 'ab' | safe | map('x') }}"), "map expects a list, found a string");
+}
+
+#[test]
+fn test_date_and_number_format() {
+    assert_eq!(render("{{ posts.0.published | date('%d %b %Y %H:%M') }}"), "09 Mar 2024 14:05");
+    assert_eq!(render("{{ posts.1.published | date }} {{ 0 | date('%Y') }}"), "2023-12-25 1970");
+    assert!(render("{{ now() | date('%Y') }}").parse::<u32>().is_ok_and(|year| year >= 2024));
+    assert_eq!(render_err("{{ 'soon' | date }}"), "date expects a date or a timestamp, found \"soon\"");
+    assert_eq!(render_err("{{ 0 | date('%Q') }}"), "Invalid date format: %Q");
+
+    assert_eq!(render("{{ price | number_format }} {{ price | number_format(2) }}"), "1,234,568 1,234,567.89");
+    assert_eq!(render("{{ (-1234.5) | number_format(1, ',', '.') }} {{ (-0.001) | number_format }}"), "-1.234,5 0");
+}
+
+#[test]
+fn test_markdown_escapes_html() {
+    assert_eq!(
+        render("{{ body | markdown }}"),
+        "<h1>Hi</h1>\n<p>Say &lt;b&gt;hi&lt;/b&gt; to <a href=\"#\">me</a> and <a href=\"/you\">you</a> <strong>now</strong></p>\n\
+         &lt;script&gt;x&lt;/script&gt;"
+    );
+}
+
+#[test]
+fn test_is_tests() {
+    assert_eq!(render("{% if missing is defined %}a{% elif posts is defined %}b{% endif %}"), "b");
+    assert_eq!(render("{% if missing is not defined and nothing is empty and missing is empty %}ok{% endif %}"), "ok");
+    assert_eq!(render("{% for n in range(1, 5) %}{% if n is even %}{{ n }}{% endif %}{% endfor %}"), "24");
+    assert_eq!(render("{{ 9 is divisibleby(3) }} {{ tags is iterable }} {{ tags is not string }}"), "true true true");
+    assert_eq!(render_err("{{ tags is shiny }}"), "Test not found: shiny");
+}
+
+#[test]
+fn test_engine_filters_and_functions() {
+    let mut engine = TemplateEngine::new();
+    let symbol = "KES".to_string();
+    engine.register_filter("price", move |value: &Value, args: &[Value]| {
+        let amount = value
+            .as_f64()
+            .ok_or_else(|| TemplateError::RenderError(format!("price expects a number, found {}", value)))?;
+        let decimals = args.first().and_then(Value::as_u64).unwrap_or(0) as usize;
+        Ok(Value::String(format!("{} {:.*}", symbol, decimals, amount)))
+    });
+    engine.register_function("greet", |args: &[Value]| Ok(json!(format!("Hi {}", args[0].as_str().unwrap_or("you")))));
+    engine.add_route("post", "/posts/:slug");
+    engine.set_asset_url("https://cdn.example.com/assets/");
+    engine
+        .add_template(
+            "page.html",
+            "{{ 12.5 | price(2) }} {{ greet('Ada') }} {{ url_for('post', {'slug': 'a b', 'page': 2}) }} {{ asset('/app.css') }}",
+        )
+        .unwrap();
+    engine.add_template("bad.html", "{{ 'x' | price }}").unwrap();
+    engine.add_template("form.html", "<input value=\"{{ csrf_token() }}\">").unwrap();
+
+    assert_eq!(
+        engine.render("page.html", &context()).unwrap(),
+        "KES 12.50 Hi Ada /posts/a%20b?page=2 https://cdn.example.com/assets/app.css"
+    );
+    assert!(matches!(
+        engine.render("bad.html", &context()),
+        Err(TemplateError::RenderError(message)) if message == "price expects a number, found \"x\""
+    ));
+    assert_eq!(engine.render("form.html", &context()).unwrap(), "<input value=\"t0k3n\">");
+    assert!(engine.render("form.html", &Context::new()).is_err());
+
+    // Without an engine the built-ins are still there
+    assert_eq!(render("{{ asset('app.css') }}"), "/static/app.css");
+    assert_eq!(render_err("{{ url_for('post') }}"), "url_for(): no route named post");
+}
@@ -0,0 +1,66 @@
+use oxidite_i18n::{Catalog, I18n};
+use oxidite_template::{Context, Template, TemplateEngine, TemplateError};
+use serde_json::{json, Value};
+
+fn engine() -> TemplateEngine {
+    let mut i18n = I18n::new("en").unwrap();
+    let en = r#"{"hello": "Hello, {name}!", "cart": {"items": {"one": "{count} item", "other": "{count} items"}}}"#;
+    i18n.add_catalog("en", "", Catalog::from_json(en).unwrap()).unwrap();
+    i18n.add_catalog("de", "", Catalog::from_toml("hello = \"Hallo, {name}!\"").unwrap()).unwrap();
+
+    let mut engine = TemplateEngine::new();
+    engine.set_i18n(i18n);
+    engine
+        .add_template(
+            "cart.html",
+            "{% trans \"hello\" name = user %} {{ t('cart.items', count=items | length) }} {{ t('hello', {'name': 'Bo'}) }}",
+        )
+        .unwrap();
+    engine.add_template("formats.html", "{{ day | date('%A %d %B %Y') }} {{ day | date }} {{ total | number_format(2) }}").unwrap();
+    engine
+}
+
+fn context(locale: Option<&str>) -> Context {
+    let mut context = Context::from_json(json!({
+        "user": "<Ada>",
+        "items": [1, 2, 3],
+        "day": "2024-03-09",
+        "total": 1234567.891,
+    }));
+    if let Some(locale) = locale {
+        context.set("locale", locale);
+    }
+    context
+}
+
+#[test]
+fn test_trans_tag_and_t_function() {
+    let engine = engine();
+    assert_eq!(engine.render("cart.html", &context(None)).unwrap(), "Hello, &lt;Ada&gt;! 3 items Hello, Bo!");
+    // German has no `cart.items`, so it comes from the default locale
+    assert_eq!(engine.render("cart.html", &context(Some("de-AT"))).unwrap(), "Hallo, &lt;Ada&gt;! 3 items Hallo, Bo!");
+
+    let err = Template::new("{% trans \"hello\" %}").unwrap().render(&Context::new()).unwrap_err();
+    assert!(matches!(err, TemplateError::RenderError(message) if message == "Translating needs a TemplateEngine with set_i18n()"));
+}
+
+#[test]
+fn test_date_and_number_filters_follow_the_locale() {
+    let mut engine = engine();
+    assert_eq!(
+        engine.render("formats.html", &context(None)).unwrap(),
+        "Saturday 09 March 2024 03/09/2024 1,234,567.89"
+    );
+    assert_eq!(
+        engine.render("formats.html", &context(Some("de"))).unwrap(),
+        "Samstag 09 März 2024 09.03.2024 1.234.567,89"
+    );
+
+    // Without catalogs or a locale the filters keep their defaults
+    let plain = Template::new("{{ day | date }} {{ total | number_format(1) }}").unwrap();
+    assert_eq!(plain.render(&context(None)).unwrap(), "2024-03-09 1,234,567.9");
+
+    // A replaced filter is used as it is
+    engine.register_filter("number_format", |value: &Value, _: &[Value]| Ok(json!(format!("#{}", value))));
+    assert!(engine.render("formats.html", &context(Some("de"))).unwrap().ends_with("#1234567.891"));
+}
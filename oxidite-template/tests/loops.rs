@@ -0,0 +1,70 @@
+use oxidite_template::{Context, Template, TemplateEngine, TemplateError};
+use serde_json::json;
+
+fn render(source: &str) -> String {
+    let context = Context::from_json(json!({
+        "fruits": ["apple", "banana", "cherry"],
+        "prices": { "apple": 3, "banana": 1 },
+        "pairs": [["a", 1], ["b", 2]],
+        "nothing": [],
+        "title": "Fruits",
+    }));
+    Template::new(source).unwrap().render(&context).unwrap()
+}
+
+#[test]
+fn test_loop_object() {
+    let source = "{% for fruit in fruits %}{{ loop.index }}/{{ loop.length }}:{{ fruit }}\
+                  {% if loop.first %}(first){% endif %}{% if not loop.last %}, {% endif %}{% endfor %}";
+    assert_eq!(render(source), "1/3:apple(first), 2/3:banana, 3/3:cherry");
+
+    // An inner loop shadows the outer `loop` only until it ends
+    let source = "{% for a in range(2) %}{% for b in range(2) %}{{ loop.index0 }}{% endfor %}{{ loop.index }};{% endfor %}";
+    assert_eq!(render(source), "011;012;");
+}
+
+#[test]
+fn test_destructuring_ranges_and_else() {
+    assert_eq!(render("{% for name, price in prices %}{{ name }}={{ price }} {% endfor %}"), "apple=3 banana=1 ");
+    assert_eq!(render("{% for name in prices %}{{ name }} {% endfor %}"), "apple banana ");
+    assert_eq!(render("{% for key, value in pairs %}{{ key }}{{ value }}{% endfor %}"), "a1b2");
+    assert_eq!(render("{% for i in range(10, 0, -3) %}{{ i }} {% endfor %}"), "10 7 4 1 ");
+    assert_eq!(render("{% for x in nothing %}{{ x }}{% else %}empty{% endfor %}"), "empty");
+    assert_eq!(render("{% for x in none %}{{ x }}{% else %}empty{% endfor %}"), "empty");
+}
+
+#[test]
+fn test_set_and_with_scopes() {
+    assert_eq!(render("{% set total = 0 %}{% set total = total + prices.apple %}{{ total }}"), "3");
+    assert_eq!(
+        render("{% with heading = title | upper, n = len(fruits) %}{{ heading }} ({{ n }}){% endwith %}"),
+        "FRUITS (3)"
+    );
+    // Locals end with the loop or `with` that set them, and shadow the context inside it
+    assert_eq!(render("{% for f in fruits %}{% set title = f %}{% endfor %}{{ title }}"), "Fruits");
+    assert_eq!(render("{% with title = 'Inner' %}{{ title }}{% endwith %} {{ title }}"), "Inner Fruits");
+    assert_eq!(render("{% for f in fruits %}{% set last = f %}{% endfor %}{{ last | default('unset') }}"), "unset");
+}
+
+#[test]
+fn test_includes_see_loop_variables() {
+    let mut engine = TemplateEngine::new();
+    engine.add_template("item.html", "<li>{{ loop.index }}. {{ fruit }}</li>").unwrap();
+    engine.add_template("list.html", "{% for fruit in fruits %}{% include \"item.html\" %}{% endfor %}").unwrap();
+
+    let mut context = Context::new();
+    context.set("fruits", vec!["apple", "banana"]);
+    assert_eq!(engine.render("list.html", &context).unwrap(), "<li>1. apple</li><li>2. banana</li>");
+}
+
+#[test]
+fn test_loop_errors() {
+    let error = |source: &str| Template::new(source).unwrap().render(&Context::new()).unwrap_err();
+
+    assert!(matches!(error("{% for x in missing %}{% endfor %}"), TemplateError::VariableNotFound(name) if name == "missing"));
+    assert!(matches!(error("{% for x in 5 %}{% endfor %}"), TemplateError::RenderError(message) if message == "cannot iterate over a number"));
+    assert!(matches!(
+        error("{% for a, b in [1, 2] %}{% endfor %}"),
+        TemplateError::RenderError(message) if message == "cannot unpack a number into two loop variables"
+    ));
+}
@@ -0,0 +1,138 @@
+use oxidite_template::{Context, TemplateEngine, TemplateError};
+
+fn engine() -> TemplateEngine {
+    let mut engine = TemplateEngine::new();
+    engine
+        .add_template(
+            "forms.html",
+            r#"{% macro label(text) %}<label>{{ text }}</label>{% endmacro %}
+{% macro field(name, text, type="text", id=name ~ "-input") %}{{ label(text) }}<input type="{{ type }}" name="{{ name }}" id="{{ id }}">{% endmacro %}"#,
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "ui.html",
+            r#"{% macro card(title) %}<div class="card"><h2>{{ title }}</h2>{{ slot() }}{% if slot("footer") %}<footer>{{ slot("footer") }}</footer>{% endif %}</div>{% endmacro %}"#,
+        )
+        .unwrap();
+    engine
+}
+
+fn render(engine: &mut TemplateEngine, source: &str, context: &Context) -> String {
+    engine.add_template("page.html", source).unwrap();
+    engine.render("page.html", context).unwrap()
+}
+
+#[test]
+fn test_macros_with_defaults_and_imports() {
+    let mut engine = engine();
+    let mut context = Context::new();
+    context.set("user", "<ada>");
+
+    let local = r#"{% macro greet(name, greeting="Hello") %}{{ greeting }}, {{ name }}!{% endmacro %}{{ greet(user) }} {{ greet("Bob", greeting="Hi") }}"#;
+    assert_eq!(render(&mut engine, local, &context), "Hello, &lt;ada&gt;! Hi, Bob!");
+
+    let imported = r#"{% import "forms.html" as forms %}{{ forms.field("email", "Email", type="email") }}"#;
+    assert_eq!(
+        render(&mut engine, imported, &context),
+        r#"<label>Email</label><input type="email" name="email" id="email-input">"#
+    );
+}
+
+#[test]
+fn test_components_fill_named_slots() {
+    let mut engine = engine();
+    let mut context = Context::new();
+    context.set("items", vec!["a", "b"]);
+
+    let source = r#"{% import "ui.html" as ui %}{% component ui.card("Items") %}{% for item in items %}<p>{{ item }}</p>{% endfor %}{% slot footer %}{{ len(items) }} items{% endslot %}{% endcomponent %}"#;
+    assert_eq!(
+        render(&mut engine, source, &context),
+        r#"<div class="card"><h2>Items</h2><p>a</p><p>b</p><footer>2 items</footer></div>"#
+    );
+
+    let source = r#"{% import "ui.html" as ui %}{% component ui.card(title="Empty") %}{% endcomponent %}"#;
+    assert_eq!(render(&mut engine, source, &context), r#"<div class="card"><h2>Empty</h2></div>"#);
+}
+
+#[test]
+fn test_include_with_explicit_context() {
+    let mut engine = engine();
+    engine.add_template("greeting.html", "{{ greeting | default('Hello') }} {{ name | default('nobody') }}").unwrap();
+    let mut context = Context::new();
+    context.set("name", "Ada");
+
+    assert_eq!(render(&mut engine, r#"{% include "greeting.html" with {"greeting": "Hi"} %}"#, &context), "Hi Ada");
+    assert_eq!(render(&mut engine, r#"{% include "greeting.html" with {"greeting": "Hi"} only %}"#, &context), "Hi nobody");
+    assert_eq!(render(&mut engine, r#"{% include "greeting.html" only %}"#, &context), "Hello nobody");
+}
+
+#[test]
+fn test_super_renders_the_parent_block() {
+    let mut engine = TemplateEngine::new();
+    engine.add_template("base.html", "<title>{% block title %}Site{% endblock %}</title>").unwrap();
+    engine
+        .add_template("section.html", r#"{% extends "base.html" %}{% block title %}Docs | {{ super() }}{% endblock %}"#)
+        .unwrap();
+    engine
+        .add_template("page.html", r#"{% extends "section.html" %}{% block title %}Intro | {{ super() }}{% endblock %}"#)
+        .unwrap();
+
+    engine.add_template("lonely.html", "{% block a %}{{ super() }}{% endblock %}").unwrap();
+
+    let context = Context::new();
+    assert_eq!(engine.render("page.html", &context).unwrap(), "<title>Intro | Docs | Site</title>");
+    assert!(matches!(
+        engine.render("lonely.html", &context),
+        Err(TemplateError::RenderError(message)) if message == "super() used outside a block that overrides another"
+    ));
+}
+
+#[test]
+fn test_macro_errors() {
+    let mut engine = engine();
+    let context = Context::new();
+    let mut error = |source: &str| {
+        engine.add_template("page.html", source).unwrap();
+        engine.render("page.html", &context).unwrap_err()
+    };
+
+    assert!(matches!(
+        error(r#"{% import "forms.html" as forms %}{{ forms.field("a", "b", "c", "d", "e") }}"#),
+        TemplateError::RenderError(message) if message == "forms.field() takes at most 4 arguments, got 5"
+    ));
+    assert!(matches!(
+        error(r#"{% import "forms.html" as forms %}{{ forms.field("a", size=3) }}"#),
+        TemplateError::RenderError(message) if message == "forms.field() has no parameter size"
+    ));
+    assert!(matches!(
+        error(r#"{% import "missing.html" as forms %}"#),
+        TemplateError::RenderError(message) if message == "Imported template not found: missing.html"
+    ));
+    assert!(matches!(
+        error(r#"{% component card("x") %}{% endcomponent %}"#),
+        TemplateError::RenderError(message) if message == "Macro not found: card"
+    ));
+}
+
+#[test]
+fn test_runaway_recursion_is_an_error() {
+    let mut engine = engine();
+    let context = Context::new();
+    let nested = |message: &str| message.starts_with("Templates nested more than 32 deep");
+
+    engine.add_template("self.html", r#"x{% include "self.html" %}"#).unwrap();
+    assert!(matches!(engine.render("self.html", &context), Err(TemplateError::RenderError(m)) if nested(&m)));
+
+    engine.add_template("loop.html", "{% macro down(n) %}{{ down(n - 1) }}{% endmacro %}{{ down(1) }}").unwrap();
+    assert!(matches!(engine.render("loop.html", &context), Err(TemplateError::RenderError(m)) if nested(&m)));
+
+    // Added without validate(), so only the render catches the cycle
+    engine.add_template("a.html", r#"{% extends "b.html" %}"#).unwrap();
+    engine.add_template("b.html", r#"{% extends "a.html" %}"#).unwrap();
+    assert!(matches!(engine.render("a.html", &context), Err(TemplateError::RenderError(m)) if nested(&m)));
+
+    // Recursion that ends renders normally
+    engine.add_template("count.html", "{% macro down(n) %}{{ n }}{% if n > 0 %}{{ down(n - 1) }}{% endif %}{% endmacro %}{{ down(3) }}").unwrap();
+    assert_eq!(engine.render("count.html", &context).unwrap(), "3210");
+}
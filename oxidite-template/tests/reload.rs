@@ -0,0 +1,132 @@
+use oxidite_template::{Context, TemplateEngine, TemplateError, Templates};
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// An empty directory for one test's templates
+fn template_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("oxidite-template-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("partials")).unwrap();
+    dir
+}
+
+/// Wait for the watcher to pick up a change
+fn eventually(templates: &Templates, name: &str, check: impl Fn(&Result<String, TemplateError>) -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let result = templates.render(name, &Context::new());
+        if check(&result) {
+            return;
+        }
+        assert!(Instant::now() < deadline, "{} never changed, last rendered {:?}", name, result);
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn test_validate_reports_missing_templates_and_cycles() {
+    let mut engine = TemplateEngine::new();
+    engine.add_template("page.html", "{% extends \"base.html\" %}{% block main %}{% include \"nav.html\" %}{% endblock %}").unwrap();
+    engine.add_template("forms.html", "{% if x %}{% import \"macros.html\" as m %}{% endif %}").unwrap();
+    engine.add_template("a.html", "{% extends \"b.html\" %}").unwrap();
+    engine.add_template("b.html", "{% extends \"a.html\" %}").unwrap();
+    engine.add_template("c.html", "{% extends \"a.html\" %}").unwrap();
+    engine.add_template("x.html", "{% for row in rows %}{% include \"y.html\" %}{% endfor %}").unwrap();
+    engine.add_template("y.html", "{% import \"x.html\" as x %}").unwrap();
+
+    match engine.validate() {
+        Err(TemplateError::Invalid(problems)) => assert_eq!(
+            problems,
+            [
+                "a.html extends itself: a.html -> b.html -> a.html",
+                "b.html extends itself: b.html -> a.html -> b.html",
+                "forms.html imports missing template macros.html",
+                "page.html extends missing template base.html",
+                "page.html includes missing template nav.html",
+                "x.html includes itself: x.html -> y.html -> x.html",
+                "y.html imports itself: y.html -> x.html -> y.html",
+            ]
+        ),
+        other => panic!("expected invalid templates, got {:?}", other),
+    }
+
+    engine.add_template("b.html", "B").unwrap();
+    engine.add_template("base.html", "{% block main %}{% endblock %}").unwrap();
+    engine.add_template("nav.html", "nav").unwrap();
+    engine.add_template("macros.html", "").unwrap();
+    engine.add_template("y.html", "").unwrap();
+    engine.validate().unwrap();
+    assert_eq!(engine.dependents("b.html"), ["a.html", "c.html"]);
+}
+
+#[test]
+fn test_load_fails_at_startup_for_a_missing_parent() {
+    let dir = template_dir("load");
+    fs::write(dir.join("page.html"), "{% extends \"layout.html\" %}").unwrap();
+
+    let err = Templates::load(TemplateEngine::new(), &dir).err().unwrap();
+    assert_eq!(err.to_string(), "Invalid templates: page.html extends missing template layout.html");
+
+    fs::write(dir.join("layout.html"), "layout").unwrap();
+    let templates = Templates::load(TemplateEngine::new(), &dir).unwrap();
+    assert_eq!(templates.render("page.html", &Context::new()).unwrap(), "layout");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_watch_reloads_changed_files_and_their_dependents() {
+    let dir = template_dir("watch");
+    fs::write(dir.join("base.html"), "<main>{% block body %}{% endblock %}</main>").unwrap();
+    fs::write(dir.join("partials/nav.html"), "nav").unwrap();
+    fs::write(
+        dir.join("page.html"),
+        "{% extends \"base.html\" %}{% block body %}{% include \"partials/nav.html\" %} page{% endblock %}",
+    )
+    .unwrap();
+
+    let templates = Templates::watch(TemplateEngine::new(), &dir).unwrap();
+    assert_eq!(templates.render("page.html", &Context::new()).unwrap(), "<main>nav page</main>");
+
+    fs::write(dir.join("partials/nav.html"), "menu").unwrap();
+    eventually(&templates, "page.html", |result| result.as_deref().ok() == Some("<main>menu page</main>"));
+
+    // A file that stops parsing shows its error on the pages that use it
+    fs::write(dir.join("base.html"), "<main>{% block body %}</main>").unwrap();
+    eventually(&templates, "page.html", |result| matches!(result, Err(TemplateError::ParseError(_))));
+    fs::write(dir.join("base.html"), "<body>{% block body %}{% endblock %}</body>").unwrap();
+    eventually(&templates, "page.html", |result| result.as_deref().ok() == Some("<body>menu page</body>"));
+
+    fs::write(dir.join("about.html"), "about").unwrap();
+    eventually(&templates, "about.html", |result| result.as_deref().ok() == Some("about"));
+    fs::remove_file(dir.join("about.html")).unwrap();
+    eventually(&templates, "about.html", |result| result.is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_a_stalled_stream_does_not_hold_up_reloads() {
+    let dir = template_dir("stalled");
+    fs::write(dir.join("big.html"), "{% for i in range(0, 100000) %}row {{ i }}\n{% endfor %}").unwrap();
+    fs::write(dir.join("page.html"), "old").unwrap();
+    let templates = Templates::watch(TemplateEngine::new(), &dir).unwrap();
+
+    // Nobody reads the stream, so its render blocks once the channel is full
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let stream = runtime.block_on(async { templates.render_stream("big.html", Context::new()) });
+    thread::sleep(Duration::from_millis(100));
+
+    fs::write(dir.join("page.html"), "new").unwrap();
+    eventually(&templates, "page.html", |result| result.as_deref().ok() == Some("new"));
+
+    drop(stream);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_templates_can_be_shared_between_threads() {
+    fn assert_shareable<T: Send + Sync + Clone + 'static>() {}
+    assert_shareable::<Templates>();
+}
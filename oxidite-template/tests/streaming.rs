@@ -0,0 +1,172 @@
+use futures_util::StreamExt;
+use oxidite_core::{OxiditeRequest, Router, Server};
+use oxidite_template::{Context, TemplateEngine, TemplateError, Templates};
+use serde_json::json;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn engine() -> TemplateEngine {
+    let mut engine = TemplateEngine::new();
+    engine.add_template("base.html", "<main>{% block main %}{% endblock %}</main>").unwrap();
+    engine
+        .add_template(
+            "report.html",
+            "{% extends \"base.html\" %}{% block main %}{% for row in rows %}<p>{{ row }}</p>{% endfor %}{% endblock %}",
+        )
+        .unwrap();
+    engine
+}
+
+fn templates(test: &str) -> Templates {
+    let dir = std::env::temp_dir().join(format!("oxidite-template-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    Templates::load(engine(), &dir).unwrap()
+}
+
+/// A writer that takes `limit` bytes and then fails
+struct Full {
+    written: Vec<u8>,
+    limit: usize,
+}
+
+impl io::Write for Full {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written.len() + buf.len() > self.limit {
+            return Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"));
+        }
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_render_to_writers() {
+    let engine = engine();
+    let context = Context::from_json(json!({"rows": ["a", "<b>"]}));
+    let expected = "<main><p>a</p><p>&lt;b&gt;</p></main>";
+
+    let mut output = String::new();
+    engine.render_to("report.html", &context, &mut output).unwrap();
+    assert_eq!(output, expected);
+
+    let mut bytes = Vec::new();
+    engine.render_to_writer("report.html", &context, &mut bytes).unwrap();
+    assert_eq!(String::from_utf8(bytes).unwrap(), expected);
+
+    // The writer's own error is reported, and what fit was already written
+    let mut full = Full { written: Vec::new(), limit: 12 };
+    let err = engine.render_to_writer("report.html", &context, &mut full).unwrap_err();
+    assert!(matches!(err, TemplateError::WriteError(e) if e.kind() == io::ErrorKind::StorageFull));
+    assert_eq!(full.written, b"<main><p>a");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lazy_values_load_once_when_read() {
+    let templates = templates("lazy");
+    let loads = Arc::new(AtomicUsize::new(0));
+    let mut context = Context::new();
+    let counter = Arc::clone(&loads);
+    context.set_lazy("rows", async move {
+        counter.fetch_add(1, Ordering::SeqCst);
+        tokio::task::yield_now().await;
+        Ok::<_, String>(vec![1, 2])
+    });
+    context.set_lazy("unused", async { Err::<(), _>("never loaded") });
+
+    // A synchronous render can't wait for the value
+    let err = templates.render("report.html", &context).unwrap_err();
+    assert!(err.to_string().contains("`rows` loads asynchronously"), "{}", err);
+
+    let page = templates.render_async("report.html", context.clone()).await.unwrap();
+    assert_eq!(page, "<main><p>1</p><p>2</p></main>");
+    templates.render_async("report.html", context.clone()).await.unwrap();
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    // Loaded values are there for synchronous renders of the same context too
+    assert_eq!(templates.render("report.html", &context).unwrap(), page);
+
+    let mut failing = Context::new();
+    failing.set_lazy("rows", async { Err::<Vec<u8>, _>("connection refused") });
+    let err = templates.render_async("report.html", failing).await.unwrap_err();
+    assert_eq!(err.to_string(), "Render error: Failed to load `rows`: connection refused");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_render_stream_sends_chunks() {
+    let templates = templates("stream");
+    let rows: Vec<String> = (0..2000).map(|i| format!("row {}", i)).collect();
+    let mut context = Context::new();
+    context.set_lazy("rows", async move { Ok::<_, String>(rows) });
+
+    let chunks: Vec<_> = templates.render_stream("report.html", context).collect().await;
+    assert!(chunks.len() > 1, "sent {} chunk", chunks.len());
+    let page: Vec<u8> = chunks.into_iter().flat_map(|chunk| chunk.unwrap()).collect();
+    let page = String::from_utf8(page).unwrap();
+    assert!(page.starts_with("<main><p>row 0</p>") && page.ends_with("<p>row 1999</p></main>"));
+
+    // An error ends the stream after what was already sent
+    let chunks: Vec<_> = templates.render_stream("missing.html", Context::new()).collect().await;
+    assert!(matches!(&chunks[..], [Err(TemplateError::RenderError(message))] if message == "Template not found: missing.html"));
+}
+
+/// The chunks of a `Transfer-Encoding: chunked` body
+fn dechunk(mut body: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    loop {
+        let (size, rest) = body.split_once("\r\n").unwrap();
+        let size = usize::from_str_radix(size, 16).unwrap();
+        if size == 0 {
+            return chunks;
+        }
+        chunks.push(&rest[..size]);
+        body = &rest[size + 2..];
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_render_stream_is_a_streaming_response() {
+    let templates = templates("server");
+    let mut router = Router::new();
+    router.get("/report", move |_req: OxiditeRequest| {
+        let templates = templates.clone();
+        async move {
+            let mut context = Context::new();
+            context.set("rows", (0..2000).map(|i| format!("row {}", i)).collect::<Vec<_>>());
+            Ok(templates.render_stream("report.html", context))
+        }
+    });
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    tokio::spawn(Server::new(router).listen(addr));
+
+    let mut stream = loop {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => break stream,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    stream
+        .write_all(b"GET /report HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let head = head.to_ascii_lowercase();
+    assert!(head.starts_with("http/1.1 200 ok"), "{}", head);
+    assert!(head.contains("content-type: text/html") && head.contains("transfer-encoding: chunked"), "{}", head);
+    let chunks = dechunk(body);
+    assert!(chunks.len() > 1, "sent {} chunk", chunks.len());
+    let page = chunks.concat();
+    assert!(page.starts_with("<main><p>row 0</p>") && page.ends_with("<p>row 1999</p></main>"));
+}
@@ -41,6 +41,7 @@
 //! - **Templates**: Server-side rendering
 //! - **Email**: SMTP support
 //! - **File Storage**: Local and S3 backends
+//! - **Translations**: JSON/TOML catalogs, plurals and locale negotiation
 
 // Re-export core types
 pub use oxidite_core::*;
@@ -77,6 +78,9 @@ pub use oxidite_security as security;
 #[cfg(feature = "utils")]
 pub use oxidite_utils as utils;
 
+#[cfg(feature = "i18n")]
+pub use oxidite_i18n as i18n;
+
 /// Prelude module for common imports
 pub mod prelude {
     pub use oxidite_core::{